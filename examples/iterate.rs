@@ -19,8 +19,10 @@ fn format_value(value: sysctl::CtlValue) -> String {
         sysctl::CtlValue::Struct(_) => "(opaque struct)".to_owned(),
         sysctl::CtlValue::Node(_) => "(node)".to_owned(),
         sysctl::CtlValue::String(s) => s.to_owned(),
+        sysctl::CtlValue::LongArray(a) => format!("{:?}", a),
+        sysctl::CtlValue::UlongArray(a) => format!("{:?}", a),
         #[cfg(not(target_os = "macos"))]
-        sysctl::CtlValue::Temperature(t) => format!("{} °C", t.celsius()),
+        sysctl::CtlValue::Temperature(t) => t.to_string(),
     }
 }
 
@@ -0,0 +1,128 @@
+extern crate sysctl;
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+use sysctl::{Ctl, CtlIter, CtlType, CtlValue};
+
+const HELP: &str = "commands:
+  get <name>                      print the value of a sysctl
+  set <name> <value>              set the value of a sysctl
+  ls [node]                       list sysctls below node (or all)
+  describe <name>                 print type, flags and description
+  watch <name> [secs] [count]     print the value every secs seconds
+  help                            show this help
+  quit                            leave the shell";
+
+fn parse_value(ctl: &Ctl, s: &str) -> Result<CtlValue, String> {
+    let err = |e: std::num::ParseIntError| e.to_string();
+    let value_type = ctl.value_type().map_err(|e| e.to_string())?;
+    Ok(match value_type {
+        CtlType::Int => CtlValue::Int(s.parse().map_err(err)?),
+        CtlType::Uint => CtlValue::Uint(s.parse().map_err(err)?),
+        CtlType::Long => CtlValue::Long(s.parse().map_err(err)?),
+        CtlType::Ulong => CtlValue::Ulong(s.parse().map_err(err)?),
+        CtlType::S64 => CtlValue::S64(s.parse().map_err(err)?),
+        CtlType::U64 => CtlValue::U64(s.parse().map_err(err)?),
+        CtlType::U8 => CtlValue::U8(s.parse().map_err(err)?),
+        CtlType::U16 => CtlValue::U16(s.parse().map_err(err)?),
+        CtlType::U32 => CtlValue::U32(s.parse().map_err(err)?),
+        CtlType::S8 => CtlValue::S8(s.parse().map_err(err)?),
+        CtlType::S16 => CtlValue::S16(s.parse().map_err(err)?),
+        CtlType::S32 => CtlValue::S32(s.parse().map_err(err)?),
+        CtlType::String => CtlValue::String(s.to_owned()),
+        t => return Err(format!("cannot set values of type {:?}", t)),
+    })
+}
+
+fn print_ctl(ctl: &Ctl) {
+    let name = match ctl.name() {
+        Ok(n) => n,
+        Err(e) => return println!("error: {}", e),
+    };
+    match ctl.value() {
+        Ok(CtlValue::Node(_)) => {}
+        Ok(CtlValue::Struct(_)) => println!("{}: (opaque struct)", name),
+        Ok(v) => println!("{}: {}", name, v),
+        Err(e) => println!("{}: error: {}", name, e),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn describe(ctl: &Ctl) {
+    println!("type: {:?}", ctl.type_flags());
+    println!("description: {:?}", ctl.description());
+}
+
+#[cfg(target_os = "macos")]
+fn describe(ctl: &Ctl) {
+    // sysctl descriptions are not available on macos.
+    println!("type: {:?}", ctl.type_flags());
+}
+
+fn run(words: &[&str]) -> Result<(), String> {
+    let ctl = |name: &str| Ctl::new(name).map_err(|e| format!("{}: {}", name, e));
+
+    match *words {
+        ["get", name] => print_ctl(&ctl(name)?),
+        // Values may contain spaces, e.g. for kern.hostname
+        ["set", name, ref value @ ..] if !value.is_empty() => {
+            let ctl = ctl(name)?;
+            let value = parse_value(&ctl, &value.join(" "))?;
+            let new = ctl.set_value(value).map_err(|e| e.to_string())?;
+            println!("{}: {}", name, new);
+        }
+        ["ls"] => CtlIter::root().filter_map(Result::ok).for_each(|c| print_ctl(&c)),
+        ["ls", node] => ctl(node)?
+            .into_iter()
+            .filter_map(Result::ok)
+            .for_each(|c| print_ctl(&c)),
+        ["describe", name] => describe(&ctl(name)?),
+        ["watch", name] => watch(&ctl(name)?, 1, None),
+        ["watch", name, secs] => watch(&ctl(name)?, parse(secs)?, None),
+        ["watch", name, secs, count] => watch(&ctl(name)?, parse(secs)?, Some(parse(count)?)),
+        ["help"] => println!("{}", HELP),
+        _ => return Err("unknown command, try 'help'".to_owned()),
+    }
+    Ok(())
+}
+
+fn parse(s: &str) -> Result<u64, String> {
+    s.parse().map_err(|e| format!("{}: {}", s, e))
+}
+
+fn watch(ctl: &Ctl, secs: u64, count: Option<u64>) {
+    let mut n = 0;
+    while count.iter().all(|&c| n < c) {
+        if n > 0 {
+            thread::sleep(Duration::from_secs(secs));
+        }
+        print_ctl(ctl);
+        n += 1;
+    }
+}
+
+fn main() {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("sysctl> ");
+        io::stdout().flush().expect("could not flush stdout");
+
+        let line = match lines.next() {
+            Some(Ok(l)) => l,
+            _ => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            None => continue,
+            Some(&"quit") | Some(&"exit") => break,
+            _ => {}
+        }
+        if let Err(e) = run(&words) {
+            println!("error: {}", e);
+        }
+    }
+}
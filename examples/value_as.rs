@@ -14,6 +14,7 @@ struct ClockInfo {
     stathz: c_int, /* statistics clock frequency */
     profhz: c_int, /* profiling clock frequency */
 }
+unsafe impl sysctl::SysctlPod for ClockInfo {}
 
 // Copied from /usr/include/sys/resource.h
 #[repr(C)]
@@ -21,6 +22,7 @@ struct LoadAvg {
     ldavg: [u32; 3],
     fscale: u64,
 }
+unsafe impl sysctl::SysctlPod for LoadAvg {}
 impl fmt::Debug for LoadAvg {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = self.fscale as f32;
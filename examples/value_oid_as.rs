@@ -1,3 +1,6 @@
+// Linux has no struct sysctls, ClockInfo is unused there
+#![cfg_attr(target_os = "linux", allow(dead_code))]
+
 extern crate libc;
 extern crate sysctl;
 
@@ -13,10 +16,11 @@ struct ClockInfo {
     stathz: c_int, /* statistics clock frequency */
     profhz: c_int, /* profiling clock frequency */
 }
+unsafe impl sysctl::SysctlPod for ClockInfo {}
 #[cfg(not(target_os = "linux"))] //no CTL_KERN or KERN_CLOCKRATE on x86_64 linux
 fn main() {
     let oid: Vec<i32> = vec![libc::CTL_KERN, libc::KERN_CLOCKRATE];
-    let val: Box<ClockInfo> = sysctl::Ctl { oid }.value_as().expect("could not get value");
+    let val: Box<ClockInfo> = sysctl::Ctl::from_oid(oid).value_as().expect("could not get value");
     println!("{:?}", val);
 }
 #[cfg(target_os = "linux")]
@@ -0,0 +1,176 @@
+//! Battery, AC adapter and thermal zone status from `hw.acpi`. Only
+//! available on FreeBSD.
+//!
+//! The sysctls only exist if the acpi(4) drivers for the hardware are
+//! attached, otherwise the functions fail with `ENOENT`.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::acpi;
+//!
+//! fn main() {
+//!     if let (Ok(Some(life)), Ok(time)) = (acpi::battery_life(), acpi::battery_time()) {
+//!         println!("battery at {}%, {:?} left", life, time);
+//!     }
+//!     for zone in acpi::thermal_zones().unwrap_or_default() {
+//!         println!("{}: {}", zone.name, zone.temperature);
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use super::{value, Ctl, CtlValue, SysctlError, Temperature};
+
+/// Whether the system runs on external power, from `hw.acpi.acline`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AcLine {
+    Offline,
+    Online,
+}
+
+/// An ACPI thermal zone, `hw.acpi.thermal.<name>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermalZone {
+    /// The name of the zone, e.g. `tz0`.
+    pub name: String,
+    pub temperature: Temperature,
+    /// The temperature at which the system shuts down, from `_CRT`, if
+    /// the firmware sets one.
+    pub critical: Option<Temperature>,
+    /// The temperature at which the CPUs are throttled, from `_PSV`, if
+    /// the firmware sets one.
+    pub passive: Option<Temperature>,
+}
+
+/// Return the remaining capacity of all batteries together, in percent.
+/// `None` if it is not known, e.g. without batteries.
+pub fn battery_life() -> Result<Option<u8>, SysctlError> {
+    Ok(percent(integer("hw.acpi.battery.life")?))
+}
+
+/// Return the estimated time until the batteries are empty. `None` if it
+/// is not known, e.g. while charging.
+pub fn battery_time() -> Result<Option<Duration>, SysctlError> {
+    Ok(minutes(integer("hw.acpi.battery.time")?))
+}
+
+/// Return whether the AC adapter is connected.
+pub fn ac_line() -> Result<AcLine, SysctlError> {
+    match integer("hw.acpi.acline")? {
+        0 => Ok(AcLine::Offline),
+        _ => Ok(AcLine::Online),
+    }
+}
+
+/// Return the thermal zones, sorted by name. Zones whose temperature can
+/// not be read are left out.
+pub fn thermal_zones() -> Result<Vec<ThermalZone>, SysctlError> {
+    let mut zones = vec![];
+
+    for ctl in Ctl::new("hw.acpi.thermal")? {
+        let ctl = ctl?;
+        let name = ctl.name()?;
+        let zone = match thermal_zone_name(&name) {
+            Some(zone) => zone,
+            None => continue,
+        };
+        let temperature = match ctl.value() {
+            Ok(CtlValue::Temperature(t)) => t,
+            _ => continue,
+        };
+        let prefix = format!("hw.acpi.thermal.{}.", zone);
+        zones.push(ThermalZone {
+            name: zone.to_owned(),
+            temperature,
+            critical: threshold(&format!("{}_CRT", prefix)),
+            passive: threshold(&format!("{}_PSV", prefix)),
+        });
+    }
+
+    zones.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(zones)
+}
+
+// The zone of a name like hw.acpi.thermal.tz0.temperature.
+fn thermal_zone_name(name: &str) -> Option<&str> {
+    let zone = name
+        .strip_prefix("hw.acpi.thermal.")?
+        .strip_suffix(".temperature")?;
+    if zone.is_empty() || zone.contains('.') {
+        return None;
+    }
+    Some(zone)
+}
+
+// A trip point of a thermal zone, -1 if not set by the firmware.
+fn threshold(name: &str) -> Option<Temperature> {
+    match value(name) {
+        Ok(CtlValue::Temperature(t)) if t.raw() >= 0 => Some(t),
+        _ => None,
+    }
+}
+
+// The battery life, -1 if unknown.
+fn percent(life: i64) -> Option<u8> {
+    match life {
+        0..=100 => Some(life as u8),
+        _ => None,
+    }
+}
+
+// The battery time in minutes, -1 if unknown.
+fn minutes(time: i64) -> Option<Duration> {
+    match time {
+        t if t >= 0 => Some(Duration::from_secs(t as u64 * 60)),
+        _ => None,
+    }
+}
+
+fn integer(name: &str) -> Result<i64, SysctlError> {
+    value(name)?.as_int().ok_or(SysctlError::ExtractionError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acpi_conversions() {
+        assert_eq!(percent(87), Some(87));
+        assert_eq!(percent(-1), None);
+        assert_eq!(percent(101), None);
+        assert_eq!(minutes(90), Some(Duration::from_secs(5400)));
+        assert_eq!(minutes(0), Some(Duration::from_secs(0)));
+        assert_eq!(minutes(-1), None);
+
+        assert_eq!(
+            thermal_zone_name("hw.acpi.thermal.tz0.temperature"),
+            Some("tz0")
+        );
+        assert_eq!(thermal_zone_name("hw.acpi.thermal.tz0._CRT"), None);
+        assert_eq!(thermal_zone_name("hw.acpi.thermal.min_runtime"), None);
+        assert_eq!(thermal_zone_name("hw.acpi.thermal..temperature"), None);
+    }
+
+    #[test]
+    fn acpi_status() {
+        // Virtual machines have no batteries, and may have no ACPI at all
+        let absent = |e: &SysctlError| match *e {
+            SysctlError::IoError(ref e) => e.raw_os_error() == Some(libc::ENOENT),
+            _ => false,
+        };
+        match battery_life() {
+            Ok(Some(life)) => assert!(life <= 100),
+            Ok(None) => {}
+            Err(e) => assert!(absent(&e), "{:?}", e),
+        }
+        if let Err(e) = ac_line() {
+            assert!(absent(&e), "{:?}", e);
+        }
+        if let Ok(zones) = thermal_zones() {
+            assert!(zones.iter().all(|z| z.temperature.kelvin() > 0.0));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Helpers for security audits of the sysctl tree.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     for entry in sysctl::audit::anybody_writable().expect("could not walk tree") {
+//!         println!("{}: {:?}", entry.name, entry.value);
+//!     }
+//! }
+//! ```
+
+use super::{Ctl, CtlIter, CtlValue, SysctlError, CTLFLAG_ANYBODY, CTLFLAG_WR};
+
+/// A sysctl found by an audit, with its current value.
+#[derive(Debug)]
+pub struct AuditEntry {
+    pub ctl: Ctl,
+    pub name: String,
+    /// The value, or why it could not be read.
+    pub value: Result<CtlValue, SysctlError>,
+    /// `None` where descriptions are not available.
+    pub description: Option<String>,
+}
+
+/// List all writable sysctls with `CTLFLAG_ANYBODY` set, i.e. those any
+/// user may change.
+pub fn anybody_writable() -> Result<Vec<AuditEntry>, SysctlError> {
+    let mut entries = vec![];
+
+    for ctl in CtlIter::root() {
+        let ctl = ctl?;
+        let info = ctl.info()?;
+        if info.flags & CTLFLAG_ANYBODY == 0 || info.flags & CTLFLAG_WR == 0 {
+            continue;
+        }
+
+        entries.push(AuditEntry {
+            name: ctl.name()?,
+            value: ctl.value(),
+            description: ctl.description().ok(),
+            ctl,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::CtlFlags;
+    use super::*;
+
+    #[test]
+    fn audit_anybody_writable() {
+        let entries = anybody_writable().expect("could not walk tree");
+        for entry in entries {
+            let flags = entry.ctl.flags().unwrap();
+            assert!(flags.contains(CtlFlags::ANYBODY | CtlFlags::WR));
+        }
+    }
+}
@@ -0,0 +1,846 @@
+//! Pluggable access to the sysctl tree, so code using sysctls can be
+//! tested without a real kernel.
+//!
+//! `SysctlBackend` has the primitive operations on OIDs; decoding,
+//! encoding and the access checks are shared by all backends. Code
+//! written against the trait runs on the `KernelBackend` in production
+//! and on a `MockBackend` populated with fake sysctls in tests.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::backend::{MockBackend, SysctlBackend};
+//! use sysctl::{CtlValue, SysctlError};
+//!
+//! // Raise the connection backlog to at least 1024.
+//! fn raise_somaxconn<B: SysctlBackend>(backend: &B) -> Result<(), SysctlError> {
+//!     if let CtlValue::Int(n) = backend.value("kern.ipc.somaxconn")? {
+//!         if n < 1024 {
+//!             backend.set_value("kern.ipc.somaxconn", CtlValue::Int(1024))?;
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//!
+//! fn main() {
+//!     let mut mock = MockBackend::new();
+//!     mock.insert("kern.ipc.somaxconn", CtlValue::Int(128));
+//!     raise_somaxconn(&mock).unwrap();
+//!     assert_eq!(mock.value("kern.ipc.somaxconn").unwrap(), CtlValue::Int(1024));
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::Bound;
+#[cfg(not(target_os = "linux"))]
+use std::ptr;
+use std::str;
+use std::sync::{Mutex, MutexGuard};
+
+use libc::c_int;
+
+#[cfg(not(target_os = "macos"))]
+use std::convert::TryFrom;
+
+#[cfg(target_os = "linux")]
+use super::linux;
+#[cfg(not(target_os = "macos"))]
+use super::Temperature;
+use super::{check_readable, decode_value, encode_value, name2oid, next_oid, oidfmt};
+#[cfg(not(target_os = "linux"))]
+use super::{fetch_oid, meta};
+use super::{snapshot::Snapshot, value_from_str};
+use super::{CtlInfo, CtlType, CtlValue, SysctlError, CTLFLAG_RD, CTLFLAG_RW};
+
+/// The primitive operations on the sysctl tree.
+pub trait SysctlBackend {
+    /// Resolve a dotted name to its OID.
+    fn name2oid(&self, name: &str) -> Result<Vec<c_int>, SysctlError>;
+
+    /// The type, format and flags of the sysctl.
+    fn oidfmt(&self, oid: &[c_int]) -> Result<CtlInfo, SysctlError>;
+
+    /// The raw value, strings including their trailing NUL.
+    fn read(&self, oid: &[c_int]) -> Result<Vec<u8>, SysctlError>;
+
+    /// Set the raw value, strings without a trailing NUL.
+    fn write(&self, oid: &[c_int], value: &[u8]) -> Result<(), SysctlError>;
+
+    /// The leaf following the OID in the tree, `None` at the end. An
+    /// empty OID returns the first leaf.
+    fn next(&self, oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError>;
+
+    /// Read the value of the sysctl with the name.
+    fn value(&self, name: &str) -> Result<CtlValue, SysctlError> {
+        let oid = self.name2oid(name)?;
+        self.value_oid(&oid)
+    }
+
+    /// Read the value of the sysctl with the OID.
+    fn value_oid(&self, oid: &[c_int]) -> Result<CtlValue, SysctlError> {
+        let info = self.oidfmt(oid)?;
+        check_readable(&info)?;
+        let mut val = self.read(oid)?;
+        // Numbers are decoded from fixed-size buffers
+        let min_size = info.ctl_type().min_type_size();
+        if val.len() < min_size {
+            val.resize(min_size, 0);
+        }
+        decode_value(&info, val)
+    }
+
+    /// Set the value of the sysctl with the name, returning the new value.
+    fn set_value(&self, name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        let oid = self.name2oid(name)?;
+        let info = self.oidfmt(&oid)?;
+        let bytes = encode_value(&info, value)?;
+        self.write(&oid, &bytes)?;
+        self.value_oid(&oid)
+    }
+}
+
+/// The sysctls of the running kernel, as used by `Ctl` and the free
+/// functions.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct KernelBackend;
+
+impl SysctlBackend for KernelBackend {
+    fn name2oid(&self, name: &str) -> Result<Vec<c_int>, SysctlError> {
+        name2oid(name)
+    }
+
+    fn oidfmt(&self, oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+        oidfmt(oid)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read(&self, oid: &[c_int]) -> Result<Vec<u8>, SysctlError> {
+        fetch_oid(oid, 0)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read(&self, oid: &[c_int]) -> Result<Vec<u8>, SysctlError> {
+        linux::read_oid(oid)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn write(&self, oid: &[c_int], value: &[u8]) -> Result<(), SysctlError> {
+        linux::write_oid(oid, value)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn write(&self, oid: &[c_int], value: &[u8]) -> Result<(), SysctlError> {
+        let mut mib = oid.to_vec();
+        let mut len = 0;
+        if unsafe { meta::raw(&mut mib, ptr::null_mut(), &mut len, value) } < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    fn next(&self, oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+        next_oid(&oid.to_vec())
+    }
+}
+
+/// An in-memory sysctl tree for tests.
+///
+/// Sysctls are added with `insert()`, which creates the nodes above them
+/// and numbers OIDs in the order they were added, starting at 1. Sysctls
+/// are readable and writable unless inserted with other flags through
+/// `insert_info()`. Unknown names and OIDs fail with `ENOENT`, like they
+/// do in the kernel.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    tree: Mutex<MockTree>,
+}
+
+#[derive(Debug, Default)]
+struct MockTree {
+    oids: BTreeMap<String, Vec<c_int>>,
+    entries: BTreeMap<Vec<c_int>, MockEntry>,
+    // The number of children of each node
+    children: BTreeMap<Vec<c_int>, c_int>,
+}
+
+#[derive(Debug)]
+struct MockEntry {
+    info: CtlInfo,
+    bytes: Vec<u8>,
+    // Nodes created for the sysctls below them are not listed by next()
+    leaf: bool,
+}
+
+impl MockBackend {
+    /// An empty tree.
+    pub fn new() -> Self {
+        MockBackend::default()
+    }
+
+    /// Add a readable and writable sysctl, or replace the value of an
+    /// existing one.
+    pub fn insert(&mut self, name: &str, value: CtlValue) {
+        let fmt = mock_fmt(&value);
+        let info = CtlInfo::new(mock_type(&value), &fmt, CTLFLAG_RW);
+        self.insert_info(name, info, value);
+    }
+
+    /// Add a sysctl with the given type, format and flags, or replace an
+    /// existing one. The value is stored as is, so it should match the
+    /// type.
+    pub fn insert_info(&mut self, name: &str, info: CtlInfo, value: CtlValue) {
+        let bytes = raw_bytes(&info, &value);
+        let tree = self.tree.get_mut().unwrap_or_else(|e| e.into_inner());
+        let oid = tree.oid_or_insert(name);
+        tree.entries.insert(
+            oid,
+            MockEntry {
+                info,
+                bytes,
+                leaf: true,
+            },
+        );
+    }
+
+    /// Remove a sysctl, and everything below it for a node.
+    pub fn remove(&mut self, name: &str) {
+        let tree = self.tree.get_mut().unwrap_or_else(|e| e.into_inner());
+        let oid = match tree.oids.remove(name) {
+            Some(oid) => oid,
+            None => return,
+        };
+        let prefix = format!("{}.", name);
+        tree.oids.retain(|n, _| !n.starts_with(&prefix));
+        tree.entries.retain(|o, _| !o.starts_with(&oid));
+    }
+
+    fn tree(&self) -> MutexGuard<'_, MockTree> {
+        self.tree.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MockTree {
+    // The OID of the name, adding it and the nodes above it if missing.
+    fn oid_or_insert(&mut self, name: &str) -> Vec<c_int> {
+        if let Some(oid) = self.oids.get(name) {
+            return oid.clone();
+        }
+
+        let mut oid = vec![];
+        let mut prefix = String::new();
+        for component in name.split('.') {
+            if !prefix.is_empty() {
+                prefix.push('.');
+            }
+            prefix.push_str(component);
+            if let Some(existing) = self.oids.get(&prefix) {
+                oid = existing.clone();
+                continue;
+            }
+
+            let n = self.children.entry(oid.clone()).or_insert(0);
+            *n += 1;
+            oid.push(*n);
+            self.oids.insert(prefix.clone(), oid.clone());
+            self.entries.insert(
+                oid.clone(),
+                MockEntry {
+                    info: CtlInfo::new(CtlType::Node, "N", CTLFLAG_RD),
+                    bytes: vec![],
+                    leaf: false,
+                },
+            );
+        }
+        oid
+    }
+}
+
+impl SysctlBackend for MockBackend {
+    fn name2oid(&self, name: &str) -> Result<Vec<c_int>, SysctlError> {
+        self.tree().oids.get(name).cloned().ok_or_else(enoent)
+    }
+
+    fn oidfmt(&self, oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+        match self.tree().entries.get(oid) {
+            Some(entry) => Ok(entry.info.clone()),
+            None => Err(enoent()),
+        }
+    }
+
+    fn read(&self, oid: &[c_int]) -> Result<Vec<u8>, SysctlError> {
+        match self.tree().entries.get(oid) {
+            Some(entry) => Ok(entry.bytes.clone()),
+            None => Err(enoent()),
+        }
+    }
+
+    fn write(&self, oid: &[c_int], value: &[u8]) -> Result<(), SysctlError> {
+        let mut tree = self.tree();
+        let entry = tree.entries.get_mut(oid).ok_or_else(enoent)?;
+        entry.bytes = value.to_vec();
+        // The kernel terminates strings
+        if entry.info.ctl_type() == CtlType::String {
+            entry.bytes.push(0);
+        }
+        Ok(())
+    }
+
+    fn next(&self, oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+        let tree = self.tree();
+        let next = tree
+            .entries
+            .range((Bound::Excluded(oid.to_vec()), Bound::Unbounded))
+            .find(|&(_, entry)| entry.leaf)
+            .map(|(o, _)| o.clone());
+        Ok(next)
+    }
+}
+
+/// A `MockBackend` populated from a dump of a real system, e.g. to test
+/// code for FreeBSD against the sysctls of a FreeBSD machine on another
+/// system.
+///
+/// Dumps are the output of `sysctl -ao`, optionally with `-t` for the
+/// exact types:
+///
+/// ```text
+/// kern.ostype: FreeBSD
+/// kern.maxfiles: integer: 64000
+/// dev.cpu.0.temperature: 45.0C
+/// kern.clockrate: Format:S,clockinfo Length:20 Dump:0xe8030000e8030000...
+/// ```
+///
+/// Without `-t`, numbers become an `Int` if they fit and a `Long` or
+/// `Ulong` otherwise, temperatures an `IK` integer and anything else a
+/// `String`. `sysctl -o` only dumps the first 16 bytes of opaque values,
+/// unless `-x` is given, the rest is filled with zeros up to the length
+/// shown. Lines that do not start with a name continue the value of the
+/// line before, like the contents of `kern.msgbuf`.
+///
+/// Snapshots keep the exact types and can be loaded with
+/// `from_snapshot()`, e.g. after deserializing them from JSON with the
+/// `serde` feature.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::backend::{FixtureBackend, SysctlBackend};
+/// use sysctl::CtlValue;
+///
+/// fn main() {
+///     let dump = "kern.ostype: FreeBSD\nkern.osreldate: 1300139\n";
+///     let fixture = FixtureBackend::from_reader(dump.as_bytes()).unwrap();
+///     assert_eq!(fixture.value("kern.osreldate").unwrap(), CtlValue::Int(1300139));
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct FixtureBackend {
+    mock: MockBackend,
+}
+
+impl FixtureBackend {
+    /// Parse the output of `sysctl -ao` or `sysctl -aot`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<FixtureBackend, SysctlError> {
+        let mut mock = MockBackend::new();
+        // The line number, name and value text of the entry being read
+        let mut entry: Option<(usize, String, String)> = None;
+
+        for (n, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line.map_err(SysctlError::IoError)?;
+            if let Some((name, value)) = entry_start(&line) {
+                if let Some((line, name, value)) = entry.take() {
+                    insert_dumped(&mut mock, line, &name, &value)?;
+                }
+                entry = Some((n + 1, name.to_owned(), value.to_owned()));
+                continue;
+            }
+
+            match entry {
+                Some((_, _, ref mut value)) => {
+                    value.push('\n');
+                    value.push_str(&line);
+                }
+                None if line.trim().is_empty() => {}
+                None => {
+                    return Err(SysctlError::ParseError {
+                        line: n + 1,
+                        reason: format!("expected 'name: value', got {:?}", line),
+                    })
+                }
+            }
+        }
+        if let Some((line, name, value)) = entry {
+            insert_dumped(&mut mock, line, &name, &value)?;
+        }
+
+        Ok(FixtureBackend { mock })
+    }
+
+    /// Load the values of a snapshot.
+    pub fn from_snapshot(snapshot: &Snapshot) -> FixtureBackend {
+        let mut mock = MockBackend::new();
+        for (name, value) in &snapshot.values {
+            mock.insert(name, value.clone());
+        }
+        FixtureBackend { mock }
+    }
+
+    /// The underlying tree, e.g. for adding sysctls missing from the dump.
+    pub fn mock_mut(&mut self) -> &mut MockBackend {
+        &mut self.mock
+    }
+}
+
+impl SysctlBackend for FixtureBackend {
+    fn name2oid(&self, name: &str) -> Result<Vec<c_int>, SysctlError> {
+        self.mock.name2oid(name)
+    }
+
+    fn oidfmt(&self, oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+        self.mock.oidfmt(oid)
+    }
+
+    fn read(&self, oid: &[c_int]) -> Result<Vec<u8>, SysctlError> {
+        self.mock.read(oid)
+    }
+
+    fn write(&self, oid: &[c_int], value: &[u8]) -> Result<(), SysctlError> {
+        self.mock.write(oid, value)
+    }
+
+    fn next(&self, oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+        self.mock.next(oid)
+    }
+}
+
+// Split a line like "kern.ostype: FreeBSD" into name and value, if it
+// starts with a name.
+fn entry_start(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = match line.find(": ") {
+        Some(i) => (&line[..i], &line[i + 2..]),
+        None if line.ends_with(':') => (&line[..line.len() - 1], ""),
+        None => return None,
+    };
+    if name.starts_with('.') || !name.contains('.') || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((name, value))
+}
+
+// Add an entry of a dump to the tree.
+fn insert_dumped(
+    mock: &mut MockBackend,
+    line: usize,
+    name: &str,
+    text: &str,
+) -> Result<(), SysctlError> {
+    // sysctl -t puts the type before the value
+    let typed = match text.find(": ") {
+        Some(i) => dumped_type(&text[..i]).map(|t| (t, &text[i + 2..])),
+        None => text
+            .strip_suffix(':')
+            .and_then(dumped_type)
+            .map(|t| (t, "")),
+    };
+    let (ctl_type, text) = match typed {
+        Some((t, text)) => (Some(t), text),
+        None => (None, text),
+    };
+
+    if let Some((fmt, bytes)) = parse_opaque(text) {
+        let (ctl_type, value) = match ctl_type {
+            Some(CtlType::Node) => (CtlType::Node, CtlValue::Node(bytes)),
+            _ => (CtlType::Struct, CtlValue::Struct(bytes)),
+        };
+        mock.insert_info(name, CtlInfo::new(ctl_type, fmt, CTLFLAG_RW), value);
+        return Ok(());
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let temperature = match ctl_type {
+            Some(CtlType::String) => None,
+            _ => parse_temperature(text),
+        };
+        if let Some(t) = temperature {
+            mock.insert(name, CtlValue::Temperature(t));
+            return Ok(());
+        }
+    }
+
+    let value = match ctl_type {
+        Some(t) => {
+            value_from_str(t, text).map_err(|reason| SysctlError::ParseError { line, reason })?
+        }
+        None => infer_value(text),
+    };
+    mock.insert(name, value);
+    Ok(())
+}
+
+// The type as named by sysctl -t, or by type_flags_string().
+fn dumped_type(name: &str) -> Option<CtlType> {
+    let ctl_type = match name {
+        "node" => CtlType::Node,
+        "integer" | "int" => CtlType::Int,
+        "string" => CtlType::String,
+        "int64_t" => CtlType::S64,
+        "opaque" => CtlType::Struct,
+        "unsigned integer" | "uint" => CtlType::Uint,
+        "long integer" | "long" => CtlType::Long,
+        "unsigned long" | "ulong" => CtlType::Ulong,
+        "uint64_t" => CtlType::U64,
+        "uint8_t" => CtlType::U8,
+        "uint16_t" => CtlType::U16,
+        "int8_t" => CtlType::S8,
+        "int16_t" => CtlType::S16,
+        "int32_t" => CtlType::S32,
+        "uint32_t" => CtlType::U32,
+        _ => return None,
+    };
+    Some(ctl_type)
+}
+
+// The narrowest of Int, Long and Ulong holding a number, or a string.
+fn infer_value(text: &str) -> CtlValue {
+    if let Ok(v) = text.parse() {
+        CtlValue::Int(v)
+    } else if let Ok(v) = text.parse() {
+        CtlValue::Long(v)
+    } else if let Ok(v) = text.parse() {
+        CtlValue::Ulong(v)
+    } else {
+        CtlValue::String(text.to_owned())
+    }
+}
+
+// Parse "Format:S,clockinfo Length:20 Dump:0xe803...", as printed by
+// sysctl -o, into the format and the bytes.
+fn parse_opaque(text: &str) -> Option<(&str, Vec<u8>)> {
+    let rest = text.strip_prefix("Format:")?;
+    let i = rest.find(" Length:")?;
+    let (fmt, rest) = (&rest[..i], &rest[i + " Length:".len()..]);
+    let i = rest.find(" Dump:0x")?;
+    let len: usize = rest[..i].parse().ok()?;
+
+    // Truncated dumps end in "..."
+    let hex: Vec<u8> = rest[i + " Dump:0x".len()..]
+        .bytes()
+        .take_while(u8::is_ascii_hexdigit)
+        .collect();
+    let mut bytes = hex
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if bytes.len() < len {
+        bytes.resize(len, 0);
+    }
+    Some((fmt, bytes))
+}
+
+// Parse a temperature as printed by sysctl, e.g. "45.0C", keeping the
+// precision.
+#[cfg(not(target_os = "macos"))]
+fn parse_temperature(text: &str) -> Option<Temperature> {
+    let number = text.strip_suffix('C')?;
+    let precision = match number.find('.') {
+        Some(i) => number.len() - i - 1,
+        None => 0,
+    };
+    let digits = number.replacen('.', "", 1);
+    if precision > 9 || digits.is_empty() || digits == "-" {
+        return None;
+    }
+    let celsius: i128 = digits.parse().ok()?;
+
+    // Kelvin is 273.15 more, round the raw value half away from zero
+    let kelvin = celsius * 100 + 27315 * 10i128.pow(precision as u32);
+    let raw = (kelvin + kelvin.signum() * 50) / 100;
+    Some(Temperature::from_raw(
+        i64::try_from(raw).ok()?,
+        precision as u32,
+    ))
+}
+
+fn enoent() -> SysctlError {
+    SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT))
+}
+
+// The type the kernel reports for a value: temperatures are integers as
+// wide as `as_bytes()` encodes them.
+fn mock_type(value: &CtlValue) -> CtlType {
+    match *value {
+        #[cfg(not(target_os = "macos"))]
+        CtlValue::Temperature(t) if i32::try_from(t.raw()).is_err() => CtlType::S64,
+        #[cfg(not(target_os = "macos"))]
+        CtlValue::Temperature(_) => CtlType::Int,
+        ref value => CtlType::from(value),
+    }
+}
+
+// The format string the kernel reports for a value.
+fn mock_fmt(value: &CtlValue) -> String {
+    let fmt = match *value {
+        CtlValue::None => "",
+        CtlValue::Node(_) => "N",
+        CtlValue::Int(_) | CtlValue::S32(_) => "I",
+        CtlValue::Uint(_) | CtlValue::U32(_) => "IU",
+        CtlValue::Long(_) | CtlValue::LongArray(_) => "L",
+        CtlValue::Ulong(_) | CtlValue::UlongArray(_) => "LU",
+        CtlValue::S64(_) => "Q",
+        CtlValue::U64(_) => "QU",
+        CtlValue::S8(_) => "C",
+        CtlValue::U8(_) => "CU",
+        CtlValue::S16(_) => "S",
+        CtlValue::U16(_) => "SU",
+        CtlValue::String(_) => "A",
+        CtlValue::Struct(_) => "S",
+        // deciKelvin is the default
+        #[cfg(not(target_os = "macos"))]
+        CtlValue::Temperature(t) if t.precision() == 1 => "IK",
+        #[cfg(not(target_os = "macos"))]
+        CtlValue::Temperature(t) => return format!("IK{}", t.precision()),
+    };
+    fmt.to_owned()
+}
+
+// The bytes the kernel returns for the value.
+fn raw_bytes(info: &CtlInfo, value: &CtlValue) -> Vec<u8> {
+    let mut bytes = value.as_bytes().into_owned();
+    if info.ctl_type() == CtlType::String {
+        bytes.push(0);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use CTLFLAG_WR;
+
+    #[test]
+    fn backend_mock_tree() {
+        let mut mock = MockBackend::new();
+        mock.insert("kern.ostype", CtlValue::String("FreeBSD".into()));
+        mock.insert("kern.maxfiles", CtlValue::Int(1000));
+        mock.insert("vm.stats.vm.v_page_count", CtlValue::Uint(4096));
+
+        assert_eq!(mock.name2oid("kern").unwrap(), vec![1]);
+        assert_eq!(mock.name2oid("kern.maxfiles").unwrap(), vec![1, 2]);
+        assert_eq!(
+            mock.name2oid("vm.stats.vm.v_page_count").unwrap(),
+            vec![2, 1, 1, 1]
+        );
+        assert_eq!(mock.oidfmt(&[1, 2]).unwrap().fmt(), "I");
+        assert_eq!(mock.oidfmt(&[1]).unwrap().ctl_type(), CtlType::Node);
+
+        let mut leaves = vec![];
+        let mut oid = vec![];
+        while let Some(next) = mock.next(&oid).unwrap() {
+            leaves.push(next.clone());
+            oid = next;
+        }
+        assert_eq!(leaves, vec![vec![1, 1], vec![1, 2], vec![2, 1, 1, 1]]);
+
+        mock.remove("kern");
+        assert!(mock.name2oid("kern.ostype").is_err());
+        assert_eq!(mock.next(&[]).unwrap(), Some(vec![2, 1, 1, 1]));
+    }
+
+    #[test]
+    fn backend_mock_values() {
+        let mut mock = MockBackend::new();
+        mock.insert("kern.hostname", CtlValue::String("build".into()));
+        mock.insert("kern.maxfiles", CtlValue::Int(1000));
+        mock.insert_info(
+            "kern.osrevision",
+            CtlInfo::new(CtlType::Int, "I", CTLFLAG_RD),
+            CtlValue::Int(199506),
+        );
+        mock.insert_info(
+            "kern.secret",
+            CtlInfo::new(CtlType::Int, "I", CTLFLAG_WR),
+            CtlValue::Int(1),
+        );
+
+        assert_eq!(
+            mock.value("kern.hostname").unwrap(),
+            CtlValue::String("build".into())
+        );
+        assert_eq!(
+            mock.set_value("kern.hostname", CtlValue::String("test".into()))
+                .unwrap(),
+            CtlValue::String("test".into())
+        );
+        assert_eq!(
+            mock.set_value("kern.maxfiles", CtlValue::Int(2000))
+                .unwrap(),
+            CtlValue::Int(2000)
+        );
+
+        match mock.set_value("kern.osrevision", CtlValue::Int(1)) {
+            Err(SysctlError::NoWriteAccess) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match mock.set_value("kern.maxfiles", CtlValue::Uint(1)) {
+            Err(SysctlError::TypeMismatch { .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match mock.value("kern.secret") {
+            Err(SysctlError::WriteOnly) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match mock.value("kern.nonexistent") {
+            Err(SysctlError::IoError(e)) => assert_eq!(e.raw_os_error(), Some(libc::ENOENT)),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn backend_mock_temperature() {
+        use Temperature;
+
+        let t = Temperature::from_raw(3330, 1);
+        let mut mock = MockBackend::new();
+        mock.insert("dev.cpu.0.temperature", CtlValue::Temperature(t));
+        assert_eq!(mock.oidfmt(&[1, 1, 1, 1]).unwrap().fmt(), "IK");
+        assert_eq!(
+            mock.value("dev.cpu.0.temperature").unwrap(),
+            CtlValue::Temperature(t)
+        );
+    }
+
+    #[test]
+    fn backend_fixture() {
+        let dump = "kern.ostype: FreeBSD\n\
+                    kern.version: FreeBSD 13.0-RELEASE #0: Fri Apr  9 04:24:09 UTC 2021\n\
+                    \x20   root@releng1.nyi.freebsd.org:/usr/obj/usr/src/amd64.amd64/sys/GENERIC\n\
+                    \n\
+                    kern.maxfiles: integer: 64000\n\
+                    hw.physmem: 8589934592\n\
+                    kern.hostuuid: string:\n\
+                    hw.pagesizes: unsigned long: 4096\n\
+                    kern.cp_time: long: 10 0 20 5 100\n\
+                    kern.clockrate: Format:S,clockinfo Length:20 Dump:0xe8030000e8030000...\n\
+                    kern.osreldate: 1300139\n";
+        let fixture = FixtureBackend::from_reader(dump.as_bytes()).unwrap();
+
+        assert_eq!(
+            fixture.value("kern.ostype").unwrap(),
+            CtlValue::String("FreeBSD".into())
+        );
+        match fixture.value("kern.version").unwrap() {
+            CtlValue::String(s) => assert!(s.ends_with("/sys/GENERIC\n")),
+            v => panic!("unexpected value {:?}", v),
+        }
+        assert_eq!(
+            fixture.value("kern.maxfiles").unwrap(),
+            CtlValue::Int(64000)
+        );
+        assert_eq!(
+            fixture.value("hw.physmem").unwrap(),
+            CtlValue::Long(8589934592)
+        );
+        assert_eq!(
+            fixture.value("kern.hostuuid").unwrap(),
+            CtlValue::String("".into())
+        );
+        assert_eq!(
+            fixture.value("hw.pagesizes").unwrap(),
+            CtlValue::Ulong(4096)
+        );
+        assert_eq!(
+            fixture.value("kern.cp_time").unwrap(),
+            CtlValue::LongArray(vec![10, 0, 20, 5, 100])
+        );
+
+        let oid = fixture.name2oid("kern.clockrate").unwrap();
+        assert_eq!(
+            fixture.oidfmt(&oid).unwrap().struct_name(),
+            Some("clockinfo")
+        );
+        let mut clockrate = vec![0xe8, 3, 0, 0, 0xe8, 3, 0, 0];
+        clockrate.resize(20, 0);
+        assert_eq!(
+            fixture.value_oid(&oid).unwrap(),
+            CtlValue::Struct(clockrate)
+        );
+
+        // OIDs follow the order of the dump
+        let first = fixture.next(&[]).unwrap().unwrap();
+        assert_eq!(first, fixture.name2oid("kern.ostype").unwrap());
+
+        match FixtureBackend::from_reader("junk\nkern.ostype: FreeBSD".as_bytes()) {
+            Err(SysctlError::ParseError { line: 1, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match FixtureBackend::from_reader("kern.maxfiles: integer: many".as_bytes()) {
+            Err(SysctlError::ParseError { line: 1, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    #[cfg(not(target_os = "macos"))]
+    fn backend_parse_temperature() {
+        assert_eq!(
+            parse_temperature("45.0C"),
+            Some(Temperature::from_raw(3182, 1))
+        );
+        assert_eq!(
+            parse_temperature("45.000C"),
+            Some(Temperature::from_raw(318150, 3))
+        );
+        assert_eq!(
+            parse_temperature("-10C"),
+            Some(Temperature::from_raw(263, 0))
+        );
+        assert_eq!(parse_temperature("45.0"), None);
+        assert_eq!(parse_temperature("C"), None);
+        assert_eq!(parse_temperature("ABC"), None);
+    }
+
+    #[test]
+    fn backend_fixture_snapshot() {
+        use std::time::SystemTime;
+
+        let mut snapshot = Snapshot {
+            taken: SystemTime::now(),
+            values: BTreeMap::new(),
+        };
+        snapshot
+            .values
+            .insert("kern.proc.all".into(), CtlValue::Node(vec![1, 2]));
+        snapshot
+            .values
+            .insert("vm.stats.vm.v_page_count".into(), CtlValue::U32(4096));
+        let fixture = FixtureBackend::from_snapshot(&snapshot);
+
+        assert_eq!(
+            fixture.value("vm.stats.vm.v_page_count").unwrap(),
+            CtlValue::U32(4096)
+        );
+        // Nodes with a value are listed, unlike the nodes above them
+        let first = fixture.next(&[]).unwrap().unwrap();
+        assert_eq!(first, fixture.name2oid("kern.proc.all").unwrap());
+        assert_eq!(
+            fixture.value_oid(&first).unwrap(),
+            CtlValue::Node(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn backend_kernel() {
+        #[cfg(not(target_os = "linux"))]
+        let name = "kern.ostype";
+        #[cfg(target_os = "linux")]
+        let name = "kernel.ostype";
+        let oid = KernelBackend.name2oid(name).unwrap();
+        assert_eq!(
+            KernelBackend.value_oid(&oid).unwrap(),
+            ::value(name).unwrap()
+        );
+    }
+}
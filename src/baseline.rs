@@ -0,0 +1,309 @@
+//! Compare the live system against a baseline of expected values.
+//!
+//! A baseline is a list of sysctl names together with the value they are
+//! expected to have, either exactly or as an inclusive numeric range.
+//! Evaluating a baseline reads every listed sysctl and produces a
+//! pass/warn/fail result for each of them.
+//!
+//! Baselines can be built in code or parsed from text:
+//!
+//! ```text
+//! # exact value
+//! kern.ipc.somaxconn = 4096
+//! # inclusive range, either bound may be left out
+//! kern.maxfiles = 10000..
+//! # advisory expectations only warn when they do not hold
+//! warn kern.maxproc = ..20000
+//! ```
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::baseline::Baseline;
+//!
+//! fn main() {
+//!     let baseline: Baseline = "kern.osrevision = 199506\nwarn kern.maxproc = 1000.."
+//!         .parse()
+//!         .expect("could not parse baseline");
+//!     for result in baseline.evaluate() {
+//!         println!("{}: {}", result.name, result.status);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::conf::{lines, split_line};
+use super::{numeric, value, CtlValue, SysctlError};
+
+/// The value a sysctl is expected to have.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    /// The formatted value must equal this string.
+    Exact(String),
+    /// The value must be numeric and lie within these inclusive bounds.
+    Range { min: Option<i128>, max: Option<i128> },
+}
+
+impl Expected {
+    /// Check if a value satisfies this expectation.
+    pub fn matches(&self, val: &CtlValue) -> bool {
+        match *self {
+            Expected::Exact(ref s) => val.to_string().trim() == s.trim(),
+            Expected::Range { min, max } => match numeric(val) {
+                Some(n) => min.iter().all(|&m| n >= m) && max.iter().all(|&m| n <= m),
+                None => false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expected::Exact(ref s) => write!(f, "{}", s),
+            Expected::Range { min, max } => {
+                if let Some(min) = min {
+                    write!(f, "{}", min)?;
+                }
+                write!(f, "..")?;
+                if let Some(max) = max {
+                    write!(f, "{}", max)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for Expected {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parts: Vec<&str> = s.splitn(2, "..").collect();
+        if parts.len() == 1 {
+            return Ok(Expected::Exact(s.to_owned()));
+        }
+
+        let bound = |b: &str| -> Result<Option<i128>, String> {
+            match b.trim() {
+                "" => Ok(None),
+                b => b
+                    .parse::<i128>()
+                    .map(Some)
+                    .map_err(|e| format!("invalid range bound {:?}: {}", b, e)),
+            }
+        };
+        let min = bound(parts[0])?;
+        let max = bound(parts[1])?;
+        if min.is_none() && max.is_none() {
+            return Err("range needs at least one bound".to_owned());
+        }
+        Ok(Expected::Range { min, max })
+    }
+}
+
+/// How a mismatch of an expectation is reported.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    /// Mismatches are reported as `Status::Fail`.
+    Required,
+    /// Mismatches are reported as `Status::Warn`.
+    Advisory,
+}
+
+/// Outcome of checking a single sysctl.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Status::Pass => write!(f, "pass"),
+            Status::Warn => write!(f, "warn"),
+            Status::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// A single entry of a baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectation {
+    pub name: String,
+    pub expected: Expected,
+    pub severity: Severity,
+}
+
+/// The result of evaluating one expectation against the live system.
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: String,
+    pub expected: Expected,
+    /// The value read from the system, or the error encountered reading it.
+    pub actual: Result<CtlValue, SysctlError>,
+    pub status: Status,
+}
+
+/// A set of expected sysctl values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Baseline {
+    entries: Vec<Expectation>,
+}
+
+impl Baseline {
+    /// Create an empty baseline.
+    pub fn new() -> Self {
+        Baseline::default()
+    }
+
+    /// Add an expectation to the baseline.
+    pub fn expect(mut self, name: &str, expected: Expected, severity: Severity) -> Self {
+        self.entries.push(Expectation {
+            name: name.to_owned(),
+            expected,
+            severity,
+        });
+        self
+    }
+
+    /// The expectations of this baseline, in order.
+    pub fn entries(&self) -> &[Expectation] {
+        &self.entries
+    }
+
+    /// Read every sysctl in the baseline and check it against its
+    /// expectation. Sysctls that cannot be read are treated as mismatches.
+    pub fn evaluate(&self) -> Vec<CheckResult> {
+        self.entries
+            .iter()
+            .map(|e| {
+                let actual = value(&e.name);
+                let pass = match actual {
+                    Ok(ref v) => e.expected.matches(v),
+                    Err(_) => false,
+                };
+                CheckResult {
+                    name: e.name.clone(),
+                    expected: e.expected.clone(),
+                    actual,
+                    status: status(pass, e.severity),
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Baseline {
+    type Err = SysctlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut baseline = Baseline::new();
+
+        for (n, line) in lines(s) {
+            let err = |reason: String| SysctlError::ParseError { line: n, reason };
+
+            let (line, severity) = match line.strip_prefix("warn ") {
+                Some(rest) => (rest.trim_start(), Severity::Advisory),
+                None => (line, Severity::Required),
+            };
+
+            let (name, expected) = split_line(line).map_err(&err)?;
+            let expected = expected.parse::<Expected>().map_err(&err)?;
+
+            baseline = baseline.expect(name, expected, severity);
+        }
+
+        Ok(baseline)
+    }
+}
+
+fn status(pass: bool, severity: Severity) -> Status {
+    match (pass, severity) {
+        (true, _) => Status::Pass,
+        (false, Severity::Advisory) => Status::Warn,
+        (false, Severity::Required) => Status::Fail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baseline_parse() {
+        let baseline: Baseline = "# comment\n\
+                                  kern.ipc.somaxconn = 4096\n\
+                                  \n\
+                                  kern.maxfiles = 10000..\n\
+                                  warn kern.maxproc = ..20000\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            baseline.entries(),
+            &[
+                Expectation {
+                    name: "kern.ipc.somaxconn".into(),
+                    expected: Expected::Exact("4096".into()),
+                    severity: Severity::Required,
+                },
+                Expectation {
+                    name: "kern.maxfiles".into(),
+                    expected: Expected::Range {
+                        min: Some(10000),
+                        max: None,
+                    },
+                    severity: Severity::Required,
+                },
+                Expectation {
+                    name: "kern.maxproc".into(),
+                    expected: Expected::Range {
+                        min: None,
+                        max: Some(20000),
+                    },
+                    severity: Severity::Advisory,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn baseline_parse_errors() {
+        match "kern.maxfiles 10".parse::<Baseline>() {
+            Err(SysctlError::ParseError { line: 1, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match "\nkern.maxfiles = a..b".parse::<Baseline>() {
+            Err(SysctlError::ParseError { line: 2, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!("kern.maxfiles = ..".parse::<Baseline>().is_err());
+    }
+
+    #[test]
+    fn baseline_matches() {
+        let range = Expected::Range {
+            min: Some(10),
+            max: Some(20),
+        };
+        assert!(range.matches(&CtlValue::Int(10)));
+        assert!(range.matches(&CtlValue::U64(20)));
+        assert!(!range.matches(&CtlValue::Long(21)));
+        assert!(!range.matches(&CtlValue::String("15".into())));
+
+        let exact = Expected::Exact("FreeBSD".into());
+        assert!(exact.matches(&CtlValue::String("FreeBSD".into())));
+        assert!(!exact.matches(&CtlValue::String("Linux".into())));
+
+        assert_eq!(status(false, Severity::Advisory), Status::Warn);
+        assert_eq!(status(false, Severity::Required), Status::Fail);
+        assert_eq!(status(true, Severity::Required), Status::Pass);
+        assert_eq!(range.to_string(), "10..20");
+    }
+}
@@ -0,0 +1,296 @@
+//! Reading and setting many sysctls at once.
+//!
+//! A failure for one sysctl does not stop the others. If any of them
+//! failed, the `BatchError` lists every failure together with the
+//! results of the sysctls that succeeded.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     match sysctl::batch::get(&["kern.ostype", "kern.osrelease"]) {
+//!         Ok(values) => println!("{:?}", values),
+//!         Err(e) => {
+//!             for (name, error) in &e.failures {
+//!                 println!("{}: {}", name, error);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Settings that depend on each other can be applied as a
+//! `CtlTransaction` instead, which restores the sysctls already set when
+//! one of them fails:
+//!
+//! ```no_run
+//! extern crate sysctl;
+//! use sysctl::batch::CtlTransaction;
+//! use sysctl::CtlValue;
+//!
+//! fn main() {
+//!     let result = CtlTransaction::new()
+//!         .set("net.inet.tcp.sendbuf_max", CtlValue::Int(16777216))
+//!         .set("net.inet.tcp.sendspace", CtlValue::Int(262144))
+//!         .apply();
+//!     if let Err(e) = result {
+//!         println!("{}", e);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+
+use failure::Fail;
+
+use super::{set_value, value, CtlValue, SysctlError};
+
+/// The failures of a batch operation, and the results of the sysctls
+/// that succeeded.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Names and values of the sysctls that succeeded, in request order.
+    pub values: Vec<(String, CtlValue)>,
+    /// Names and errors of the sysctls that failed, in request order.
+    pub failures: Vec<(String, SysctlError)>,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} sysctls failed",
+            self.failures.len(),
+            self.failures.len() + self.values.len()
+        )?;
+        for (name, e) in &self.failures {
+            write!(f, "\n{}: {}", name, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fail for BatchError {}
+
+/// Read the values of all named sysctls.
+pub fn get<S: AsRef<str>>(names: &[S]) -> Result<Vec<(String, CtlValue)>, BatchError> {
+    collect(
+        names
+            .iter()
+            .map(|name| (name.as_ref().to_owned(), value(name.as_ref()))),
+    )
+}
+
+/// Set all sysctls to their new values, returning the values after
+/// setting them.
+pub fn set<I>(settings: I) -> Result<Vec<(String, CtlValue)>, BatchError>
+where
+    I: IntoIterator<Item = (String, CtlValue)>,
+{
+    collect(settings.into_iter().map(|(name, val)| {
+        let result = set_value(&name, val);
+        (name, result)
+    }))
+}
+
+fn collect<I>(results: I) -> Result<Vec<(String, CtlValue)>, BatchError>
+where
+    I: Iterator<Item = (String, Result<CtlValue, SysctlError>)>,
+{
+    let mut values = vec![];
+    let mut failures = vec![];
+
+    for (name, result) in results {
+        match result {
+            Ok(v) => values.push((name, v)),
+            Err(e) => failures.push((name, e)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(values)
+    } else {
+        Err(BatchError { values, failures })
+    }
+}
+
+/// Writes to several sysctls that are undone if one of them fails.
+///
+/// The writes are applied in order. Before each write the current value
+/// is recorded, and if a write fails the sysctls already written are set
+/// back to their recorded values in reverse order. Other processes can
+/// still see the intermediate values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CtlTransaction {
+    writes: Vec<(String, CtlValue)>,
+}
+
+impl CtlTransaction {
+    pub fn new() -> Self {
+        CtlTransaction::default()
+    }
+
+    /// Stage a write of the value to the named sysctl.
+    pub fn set<S: Into<String>>(mut self, name: S, value: CtlValue) -> Self {
+        self.writes.push((name.into(), value));
+        self
+    }
+
+    /// Apply all staged writes, returning the values after setting them.
+    pub fn apply(self) -> Result<Vec<(String, CtlValue)>, TransactionError> {
+        apply_with(self.writes, value, set_value)
+    }
+}
+
+fn apply_with<G, S>(
+    writes: Vec<(String, CtlValue)>,
+    get: G,
+    mut set: S,
+) -> Result<Vec<(String, CtlValue)>, TransactionError>
+where
+    G: Fn(&str) -> Result<CtlValue, SysctlError>,
+    S: FnMut(&str, CtlValue) -> Result<CtlValue, SysctlError>,
+{
+    let mut applied: Vec<(String, CtlValue)> = vec![];
+    let mut values = vec![];
+
+    for (name, val) in writes {
+        let result = get(&name).and_then(|old| {
+            let new = set(&name, val)?;
+            applied.push((name.clone(), old));
+            Ok(new)
+        });
+        match result {
+            Ok(new) => values.push((name, new)),
+            Err(error) => {
+                let rollback_failures = applied
+                    .into_iter()
+                    .rev()
+                    .filter_map(|(name, old)| set(&name, old).err().map(|e| (name, e)))
+                    .collect();
+                return Err(TransactionError {
+                    name,
+                    error,
+                    rollback_failures,
+                });
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// A failed `CtlTransaction`.
+#[derive(Debug)]
+pub struct TransactionError {
+    /// The name of the sysctl that could not be set.
+    pub name: String,
+    pub error: SysctlError,
+    /// Names and errors of the sysctls that could not be set back to
+    /// their previous values. Empty if the rollback succeeded.
+    pub rollback_failures: Vec<(String, SysctlError)>,
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.error)?;
+        if !self.rollback_failures.is_empty() {
+            write!(
+                f,
+                "\nrollback failed for {} sysctls",
+                self.rollback_failures.len()
+            )?;
+        }
+        for (name, e) in &self.rollback_failures {
+            write!(f, "\n{}: {}", name, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fail for TransactionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_collect() {
+        let results = vec![
+            ("a".to_owned(), Ok(CtlValue::Int(1))),
+            ("b".to_owned(), Err(SysctlError::NoReadAccess)),
+            ("c".to_owned(), Ok(CtlValue::Int(3))),
+            ("d".to_owned(), Err(SysctlError::WriteOnly)),
+        ];
+        let e = collect(results.into_iter()).unwrap_err();
+        let names: Vec<&str> = e.values.iter().map(|v| v.0.as_str()).collect();
+        assert_eq!(names, vec!["a", "c"]);
+        let names: Vec<&str> = e.failures.iter().map(|v| v.0.as_str()).collect();
+        assert_eq!(names, vec!["b", "d"]);
+        assert!(e.to_string().starts_with("2 of 4 sysctls failed\n"));
+
+        let ok = vec![("a".to_owned(), Ok(CtlValue::Int(1)))];
+        assert_eq!(collect(ok.into_iter()).unwrap(), vec![("a".to_owned(), CtlValue::Int(1))]);
+    }
+
+    #[test]
+    fn batch_get_nonexistent() {
+        let e = get(&["nonexistent.a", "nonexistent.b"]).unwrap_err();
+        assert!(e.values.is_empty());
+        assert_eq!(e.failures.len(), 2);
+    }
+
+    #[test]
+    fn batch_transaction_rollback() {
+        use std::cell::RefCell;
+        use std::collections::HashMap;
+
+        let state = RefCell::new(HashMap::new());
+        state.borrow_mut().insert("a", 1);
+        state.borrow_mut().insert("b", 2);
+        state.borrow_mut().insert("c", 3);
+        let get = |name: &str| match state.borrow().get(name) {
+            Some(&v) => Ok(CtlValue::Int(v)),
+            None => Err(SysctlError::NoReadAccess),
+        };
+        // c is read-only
+        let set = |name: &str, val: CtlValue| match (name, val) {
+            ("c", _) => Err(SysctlError::NoWriteAccess),
+            (_, CtlValue::Int(v)) => {
+                *state.borrow_mut().get_mut(name).unwrap() = v;
+                Ok(CtlValue::Int(v))
+            }
+            _ => Err(SysctlError::ExtractionError),
+        };
+
+        let writes = vec![
+            ("a".to_owned(), CtlValue::Int(10)),
+            ("b".to_owned(), CtlValue::Int(20)),
+            ("c".to_owned(), CtlValue::Int(30)),
+        ];
+        let e = apply_with(writes, get, set).unwrap_err();
+        assert_eq!(e.name, "c");
+        assert!(e.rollback_failures.is_empty());
+        assert_eq!(state.borrow()["a"], 1);
+        assert_eq!(state.borrow()["b"], 2);
+
+        let writes = vec![
+            ("a".to_owned(), CtlValue::Int(10)),
+            ("b".to_owned(), CtlValue::Int(20)),
+        ];
+        let values = apply_with(writes, get, set).unwrap();
+        assert_eq!(values[1], ("b".to_owned(), CtlValue::Int(20)));
+        assert_eq!(state.borrow()["a"], 10);
+    }
+
+    #[test]
+    fn batch_transaction_nonexistent() {
+        let e = CtlTransaction::new()
+            .set("nonexistent.a", CtlValue::Int(1))
+            .apply()
+            .unwrap_err();
+        assert_eq!(e.name, "nonexistent.a");
+        assert!(e.rollback_failures.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! Writing the whole tree to a file for attaching to support tickets.
+//!
+//! A support bundle has the type, flags, value and description of every
+//! readable sysctl, as plain text that can be read on any system. Values
+//! that identify the host can be redacted, see `HOST_IDENTIFIERS`.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! use std::fs::File;
+//! use sysctl::bundle::{support_bundle, HOST_IDENTIFIERS};
+//!
+//! fn main() {
+//!     let out = File::create("sysctl-bundle.txt").expect("could not create file");
+//!     support_bundle(out, HOST_IDENTIFIERS).expect("could not write bundle");
+//! }
+//! ```
+
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{under, CtlIter, CtlValue, SysctlError, CTLFLAG_RD, CTLFLAG_SKIP};
+
+/// Sysctls that identify the host or its owner, to pass to
+/// `support_bundle()`.
+pub const HOST_IDENTIFIERS: &[&str] = &[
+    "kern.hostname",
+    "kern.domainname",
+    "kern.hostuuid",
+    "kern.hostid",
+    "kernel.hostname",
+    "kernel.domainname",
+    "kernel.random.boot_id",
+    "kernel.random.uuid",
+];
+
+/// Write every readable sysctl with its type, flags, value and description
+/// to `out`, one per line and separated by tabs. The values of sysctls at
+/// or below the names in `redact`, like `HOST_IDENTIFIERS`, are replaced
+/// by `<redacted>`, and opaque values by their size. Sysctls flagged
+/// `CTLFLAG_SKIP`, or that can not be read, are left out. Returns the
+/// number of sysctls written.
+///
+/// The output is plain text. To compress it, pass a compressing writer,
+/// such as a `GzEncoder` of the flate2 crate.
+pub fn support_bundle<W: Write>(mut out: W, redact: &[&str]) -> Result<usize, SysctlError> {
+    let taken = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    writeln!(
+        out,
+        "# sysctl support bundle, {} seconds after the epoch",
+        taken
+    )
+    .and_then(|_| writeln!(out, "# name\ttype\tflags\tvalue\tdescription"))
+    .map_err(SysctlError::IoError)?;
+
+    let mut count = 0;
+    for ctl in CtlIter::root() {
+        let ctl = ctl?;
+        let flags = ctl.flags()?.bits();
+        if flags & CTLFLAG_RD == 0 || flags & CTLFLAG_SKIP != 0 {
+            continue;
+        }
+        // Skip what vanished or needs privileges
+        let (name, value) = match (ctl.name(), ctl.value()) {
+            (Ok(name), Ok(value)) => (name, value),
+            _ => continue,
+        };
+        let value = if redact.iter().any(|r| under(&name, r)) {
+            "<redacted>".to_owned()
+        } else {
+            match value {
+                CtlValue::Node(ref b) | CtlValue::Struct(ref b) => format!("<{} bytes>", b.len()),
+                ref v => v.to_string(),
+            }
+        };
+        // Debug quotes and escapes tabs and newlines
+        writeln!(
+            out,
+            "{}\t{:?}\t{:#x}\t{:?}\t{:?}",
+            name,
+            ctl.value_type()?,
+            flags,
+            value,
+            ctl.description().unwrap_or_default()
+        )
+        .map_err(SysctlError::IoError)?;
+        count += 1;
+    }
+
+    out.flush().map_err(SysctlError::IoError)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_redact() {
+        let mut out = vec![];
+        let count = support_bundle(&mut out, HOST_IDENTIFIERS).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), count + 2);
+
+        #[cfg(not(target_os = "linux"))]
+        let (hostname, ostype) = ("kern.hostname", "kern.ostype");
+        #[cfg(target_os = "linux")]
+        let (hostname, ostype) = ("kernel.hostname", "kernel.ostype");
+        let line = |name: &str| {
+            out.lines()
+                .find(|l| l.starts_with(&format!("{}\t", name)))
+                .unwrap()
+                .split('\t')
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(line(hostname)[3], "\"<redacted>\"");
+        assert_eq!(line(ostype)[1], "String");
+        assert_ne!(line(ostype)[3], "\"<redacted>\"");
+    }
+}
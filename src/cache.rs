@@ -0,0 +1,326 @@
+//! A process-wide cache of resolved sysctls.
+//!
+//! `Ctl::new()` translates the name to an OID, which takes a syscall
+//! before the value can be read. Programs that look sysctls up by name on
+//! latency-critical paths can resolve them once at startup with
+//! `warm_cache()`. It returns a `CtlCache`, a snapshot of the cache whose
+//! lookups take no lock and do not allocate. `current()` takes a new
+//! snapshot, e.g. after more sysctls were added.
+//!
+//! `cached()` looks a sysctl up in the cache itself, resolving and adding
+//! it on its first lookup. It takes a shared lock for a moment and clones
+//! the `Ctl`, which is cheap but not free under contention.
+//!
+//! Changes never modify a snapshot that is in use, they replace the
+//! cache with an updated copy instead. Dropped entries live on in the
+//! snapshots taken before.
+//!
+//! The OIDs of dynamic sysctls, e.g. those of a device, change when the
+//! device is detached and attached again. Entries can be dropped with
+//! `invalidate()` or `clear()`, or all of them resolved again with
+//! `refresh()`. Long-running programs can leave that to a
+//! `CacheRefresher` thread, and take a new snapshot now and then.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # const PREFIX: &str = "kernel";
+//! # #[cfg(target_os = "linux")]
+//! # const OSTYPE: &str = "kernel.ostype";
+//! # #[cfg(not(target_os = "linux"))]
+//! # const PREFIX: &str = "kern";
+//! # #[cfg(not(target_os = "linux"))]
+//! # const OSTYPE: &str = "kern.ostype";
+//! fn main() {
+//!     let cache = sysctl::warm_cache(&[PREFIX]).expect("could not resolve sysctls");
+//!     let ctl = cache.get(OSTYPE).expect("not cached");
+//!     println!("{:?}", ctl.value());
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::{Ctl, CtlIter, CtlType, SysctlError};
+
+static CACHE: RwLock<Option<Arc<HashMap<String, Ctl>>>> = RwLock::new(None);
+
+/// A snapshot of the cache, see the module documentation. Cloning it is
+/// cheap, clones share the entries.
+#[derive(Debug, Clone, Default)]
+pub struct CtlCache {
+    ctls: Arc<HashMap<String, Ctl>>,
+}
+
+impl CtlCache {
+    /// The sysctl of the given name, if it was cached when the snapshot
+    /// was taken.
+    pub fn get(&self, name: &str) -> Option<&Ctl> {
+        self.ctls.get(name)
+    }
+
+    /// The number of cached sysctls.
+    pub fn len(&self) -> usize {
+        self.ctls.len()
+    }
+
+    /// Whether no sysctls were cached.
+    pub fn is_empty(&self) -> bool {
+        self.ctls.is_empty()
+    }
+
+    /// The names of the cached sysctls, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ctls.keys().map(String::as_str)
+    }
+}
+
+/// Resolve the sysctls below each of the prefixes, or the prefix alone if
+/// it is a leaf, and add them to the cache. An empty prefix selects the
+/// whole tree. Returns a snapshot of the cache with them.
+pub fn warm_cache(prefixes: &[&str]) -> Result<CtlCache, SysctlError> {
+    let mut resolved = vec![];
+    for prefix in prefixes {
+        let ctls = if prefix.is_empty() {
+            CtlIter::root()
+        } else {
+            let ctl = Ctl::new(prefix)?;
+            if ctl.value_type()? != CtlType::Node {
+                resolved.push((prefix.to_string(), ctl));
+                continue;
+            }
+            CtlIter::below(ctl)
+        };
+        for ctl in ctls {
+            let ctl = ctl?;
+            resolved.push((ctl.name()?, ctl));
+        }
+    }
+
+    // Resolve everything first, so the lock is only held for the inserts
+    Ok(update(|ctls| ctls.extend(resolved)))
+}
+
+/// Take a snapshot of the cache as it is now.
+pub fn current() -> CtlCache {
+    let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+    CtlCache {
+        ctls: cache.clone().unwrap_or_default(),
+    }
+}
+
+/// Return the cached sysctl, resolving and caching it if it is not.
+pub fn cached(name: &str) -> Result<Ctl, SysctlError> {
+    if let Some(ctl) = current().get(name) {
+        return Ok(ctl.clone());
+    }
+
+    let ctl = Ctl::new(name)?;
+    update(|ctls| {
+        ctls.insert(name.to_owned(), ctl.clone());
+    });
+    Ok(ctl)
+}
+
+/// Drop a sysctl from the cache, e.g. after its device was detached.
+pub fn invalidate(name: &str) {
+    update(|ctls| {
+        ctls.remove(name);
+    });
+}
+
+/// Drop every cached sysctl.
+pub fn clear() {
+    *CACHE.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Resolve every cached sysctl again. Those whose OID changed are
+/// updated, those that no longer resolve are dropped. Returns the number
+/// of sysctls dropped.
+///
+/// Sysctls dropped from the cache while they are resolved stay dropped.
+pub fn refresh() -> usize {
+    // Resolve without holding the lock, lookups go on meanwhile
+    let resolved: Vec<(String, Option<Ctl>)> = current()
+        .names()
+        .map(|name| (name.to_owned(), Ctl::new(name).ok()))
+        .collect();
+
+    let mut cache = CACHE.write().unwrap_or_else(|e| e.into_inner());
+    // Cleared meanwhile
+    let ctls = match *cache {
+        Some(ref mut ctls) => ctls,
+        None => return 0,
+    };
+    // Only entries still cached that changed, so that an unchanged cache
+    // is not copied
+    let changed: Vec<(String, Option<Ctl>)> = resolved
+        .into_iter()
+        .filter(|(name, ctl)| match ctls.get(name) {
+            Some(cached) => ctl.as_ref() != Some(cached),
+            None => false,
+        })
+        .collect();
+    if changed.is_empty() {
+        return 0;
+    }
+
+    let ctls = Arc::make_mut(ctls);
+    let mut dropped = 0;
+    for (name, ctl) in changed {
+        match ctl {
+            Some(ctl) => {
+                ctls.insert(name, ctl);
+            }
+            None => {
+                ctls.remove(&name);
+                dropped += 1;
+            }
+        }
+    }
+    dropped
+}
+
+/// Refreshes the cache on a background thread at a fixed interval, see
+/// `refresh()`. The thread runs until the refresher is stopped or
+/// dropped.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::time::Duration;
+/// use sysctl::cache::CacheRefresher;
+///
+/// fn main() {
+///     sysctl::warm_cache(&["dev"]).expect("could not resolve sysctls");
+///     let refresher = CacheRefresher::spawn(Duration::from_secs(30));
+///     // ...
+///     refresher.stop();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CacheRefresher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CacheRefresher {
+    /// Start refreshing the cache every `interval`.
+    pub fn spawn(interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            // Anything but a timeout means the refresher asked to stop
+            while stopped.recv_timeout(interval) == Err(RecvTimeoutError::Timeout) {
+                refresh();
+            }
+        });
+        CacheRefresher {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stop refreshing and wait for the thread to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for CacheRefresher {
+    fn drop(&mut self) {
+        // Closing the channel wakes the thread up
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Replace the cache with a changed copy. Snapshots of the old one keep
+// it, the copy is only made if there are any. Returns a snapshot of the
+// new cache.
+fn update<F>(change: F) -> CtlCache
+where
+    F: FnOnce(&mut HashMap<String, Ctl>),
+{
+    let mut cache = CACHE.write().unwrap_or_else(|e| e.into_inner());
+    let ctls = cache.get_or_insert_with(Default::default);
+    change(Arc::make_mut(ctls));
+    CtlCache { ctls: ctls.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    const PREFIX: &str = "kernel";
+    #[cfg(target_os = "linux")]
+    const OSTYPE: &str = "kernel.ostype";
+    #[cfg(not(target_os = "linux"))]
+    const PREFIX: &str = "kern";
+    #[cfg(not(target_os = "linux"))]
+    const OSTYPE: &str = "kern.ostype";
+
+    // The cache is shared by the whole process, so one test covers it
+    #[test]
+    fn cache_warm_and_lookup() {
+        let cache = warm_cache(&[PREFIX]).unwrap();
+        assert!(cache.len() > 1);
+        assert_eq!(cache.get(OSTYPE), Some(&Ctl::new(OSTYPE).unwrap()));
+        assert!(cache.names().any(|name| name == OSTYPE));
+        assert_eq!(cached(OSTYPE).unwrap(), Ctl::new(OSTYPE).unwrap());
+
+        assert!(warm_cache(&[OSTYPE]).unwrap().get(OSTYPE).is_some());
+        invalidate(OSTYPE);
+        // Snapshots taken before keep their entries
+        assert!(cache.get(OSTYPE).is_some());
+        assert!(current().get(OSTYPE).is_none());
+        assert_eq!(cached(OSTYPE).unwrap(), Ctl::new(OSTYPE).unwrap());
+        assert!(current().get(OSTYPE).is_some());
+        assert!(cached("nonexistent.sysctl").is_err());
+
+        // Stale entries are dropped or resolved again, the others left
+        let stale = Ctl::from_oid;
+        update(|ctls| {
+            ctls.insert("nonexistent.sysctl".into(), stale(vec![]));
+            ctls.insert(OSTYPE.into(), stale(vec![0x7fff_ffff]));
+        });
+        let before = current();
+        assert_eq!(refresh(), 1);
+        assert!(current().get("nonexistent.sysctl").is_none());
+        assert_eq!(current().get(OSTYPE), Some(&Ctl::new(OSTYPE).unwrap()));
+        assert_eq!(before.get(OSTYPE), Some(&stale(vec![0x7fff_ffff])));
+        assert_eq!(refresh(), 0);
+
+        // The refresher thread does the same
+        update(|ctls| {
+            ctls.insert("nonexistent.sysctl".into(), stale(vec![]));
+            ctls.insert(OSTYPE.into(), stale(vec![0x7fff_ffff]));
+        });
+        let refresher = CacheRefresher::spawn(Duration::from_millis(1));
+        let mut refreshed = false;
+        for _ in 0..5000 {
+            let now = current();
+            if now.get("nonexistent.sysctl").is_none()
+                && now.get(OSTYPE) == Some(&Ctl::new(OSTYPE).unwrap())
+            {
+                refreshed = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        refresher.stop();
+        assert!(refreshed, "the refresher did not update the cache");
+
+        clear();
+        assert!(current().is_empty());
+        assert!(!cache.is_empty());
+        // Nothing comes back into a cleared cache
+        assert_eq!(refresh(), 0);
+        assert!(current().is_empty());
+    }
+}
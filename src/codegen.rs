@@ -0,0 +1,257 @@
+//! Generate typed Rust accessors from a schema.
+//!
+//! `generate()` turns the entries of a `Schema` below a prefix into the
+//! source of a Rust module. For every readable sysctl it contains a
+//! constant holding the name and a getter returning the value as its
+//! native Rust type; writable sysctls also get a setter. The generated
+//! code only depends on this crate, so it can be produced once from a
+//! schema dump and checked into a project.
+//!
+//! Identifiers are derived from the sysctl name relative to the prefix,
+//! so `kern.ipc.somaxconn` below `kern.ipc` becomes `SOMAXCONN`,
+//! `somaxconn()` and `set_somaxconn()`. Sysctls whose type has no plain
+//! Rust equivalent (nodes, opaque structs) and names that map to an
+//! identifier already in use are skipped.
+//!
+//! Temperatures (format `IK`) get getters returning a `Temperature`.
+//! Longs the schema marks as arrays, like `kern.cp_time`, get a getter
+//! returning a `Vec` and no setter. Only the schema is used, so the code
+//! is the same whatever system it is generated on.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let schema = sysctl::schema().expect("could not read schema");
+//!     println!("{}", sysctl::codegen::generate(&schema, "kern.ipc"));
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::schema::{Schema, SchemaEntry};
+use super::{CtlType, CTLFLAG_RD, CTLFLAG_WR};
+
+const KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+/// Generate the source of a Rust module with accessors for all sysctls
+/// at or below `prefix`.
+pub fn generate(schema: &Schema, prefix: &str) -> String {
+    let mut out = String::new();
+    let mut seen = HashSet::new();
+
+    writeln!(out, "// Generated by sysctl::codegen for {:?}. Do not edit.", prefix).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use sysctl::{{self, CtlValue, SysctlError}};").unwrap();
+
+    for entry in &schema.entries {
+        let rel = match relative(&entry.name, prefix) {
+            Some(r) => r,
+            None => continue,
+        };
+        let (variant, ty) = match rust_type(entry.ctl_type, &entry.fmt, entry.array) {
+            Some(t) => t,
+            None => continue,
+        };
+        if entry.flags & CTLFLAG_RD == 0 {
+            continue;
+        }
+        let ident = identifier(rel);
+        if !seen.insert(ident.clone()) {
+            continue;
+        }
+        write_entry(&mut out, entry, &ident, variant, ty);
+    }
+
+    out
+}
+
+fn write_entry(out: &mut String, entry: &SchemaEntry, ident: &str, variant: &str, ty: &str) {
+    let konst = ident.to_uppercase();
+
+    writeln!(out).unwrap();
+    if let Some(ref d) = entry.description {
+        for line in d.lines() {
+            writeln!(out, "/// {}", line).unwrap();
+        }
+    }
+    writeln!(out, "pub const {}: &str = {:?};", konst, entry.name).unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "/// Read `{}`.", entry.name).unwrap();
+    writeln!(out, "pub fn {}() -> Result<{}, SysctlError> {{", ident, ty).unwrap();
+    writeln!(out, "    match sysctl::value({})? {{", konst).unwrap();
+    writeln!(out, "        CtlValue::{}(v) => Ok(v),", variant).unwrap();
+    writeln!(out, "        _ => Err(SysctlError::ExtractionError),").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    // Arrays cannot be written
+    if entry.flags & CTLFLAG_WR == 0 || variant.ends_with("Array") {
+        return;
+    }
+
+    writeln!(out).unwrap();
+    writeln!(out, "/// Set `{}`, returning the new value.", entry.name).unwrap();
+    writeln!(out, "pub fn set_{}(v: {}) -> Result<{}, SysctlError> {{", ident, ty, ty).unwrap();
+    writeln!(
+        out,
+        "    match sysctl::set_value({}, CtlValue::{}(v))? {{",
+        konst, variant
+    )
+    .unwrap();
+    writeln!(out, "        CtlValue::{}(v) => Ok(v),", variant).unwrap();
+    writeln!(out, "        _ => Err(SysctlError::ExtractionError),").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+// The part of name below prefix, or the last component if name is the
+// prefix itself.
+fn relative<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches('.');
+    if prefix.is_empty() {
+        return Some(name);
+    }
+    if name == prefix {
+        return name.rsplit('.').next();
+    }
+    if name.starts_with(prefix) && name[prefix.len()..].starts_with('.') {
+        return Some(&name[prefix.len() + 1..]);
+    }
+    None
+}
+
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+// The CtlValue variant and Rust type holding values of a sysctl type.
+fn rust_type(ctl_type: CtlType, fmt: &str, array: bool) -> Option<(&'static str, &'static str)> {
+    match ctl_type {
+        CtlType::Int if fmt.starts_with("IK") => Some(("Temperature", "sysctl::Temperature")),
+        CtlType::Long if array => Some(("LongArray", "Vec<i64>")),
+        CtlType::Ulong if array => Some(("UlongArray", "Vec<u64>")),
+        CtlType::Int => Some(("Int", "i32")),
+        CtlType::String => Some(("String", "String")),
+        CtlType::S64 => Some(("S64", "i64")),
+        CtlType::Uint => Some(("Uint", "u32")),
+        CtlType::Long => Some(("Long", "i64")),
+        CtlType::Ulong => Some(("Ulong", "u64")),
+        CtlType::U64 => Some(("U64", "u64")),
+        CtlType::U8 => Some(("U8", "u8")),
+        CtlType::U16 => Some(("U16", "u16")),
+        CtlType::S8 => Some(("S8", "i8")),
+        CtlType::S16 => Some(("S16", "i16")),
+        CtlType::S32 => Some(("S32", "i32")),
+        CtlType::U32 => Some(("U32", "u32")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{CTLFLAG_RD, CTLFLAG_RW};
+    use super::*;
+
+    fn entry(name: &str, ctl_type: CtlType, flags: u32) -> SchemaEntry {
+        SchemaEntry {
+            name: name.to_owned(),
+            oid: vec![],
+            ctl_type,
+            fmt: String::new(),
+            flags,
+            description: Some(format!("{} description", name)),
+            label: None,
+            array: false,
+        }
+    }
+
+    #[test]
+    fn codegen_identifiers() {
+        assert_eq!(relative("kern.ipc.somaxconn", "kern.ipc"), Some("somaxconn"));
+        assert_eq!(relative("kern.ipc.somaxconn", "kern.ipc."), Some("somaxconn"));
+        assert_eq!(relative("kern.ipc", "kern.ipc"), Some("ipc"));
+        assert_eq!(relative("kern.ipcx", "kern.ipc"), None);
+        assert_eq!(identifier("cpu.0.%desc"), "cpu_0__desc");
+        assert_eq!(identifier("0.freq"), "_0_freq");
+        assert_eq!(identifier("type"), "type_");
+    }
+
+    #[test]
+    fn codegen_rust_type() {
+        assert_eq!(rust_type(CtlType::Int, "I", false), Some(("Int", "i32")));
+        assert_eq!(
+            rust_type(CtlType::Int, "IK3", false),
+            Some(("Temperature", "sysctl::Temperature"))
+        );
+        assert_eq!(rust_type(CtlType::Long, "L", false), Some(("Long", "i64")));
+        assert_eq!(
+            rust_type(CtlType::Long, "L", true),
+            Some(("LongArray", "Vec<i64>"))
+        );
+        assert_eq!(
+            rust_type(CtlType::Ulong, "LU", true),
+            Some(("UlongArray", "Vec<u64>"))
+        );
+        assert_eq!(rust_type(CtlType::Struct, "S,foo", false), None);
+    }
+
+    #[test]
+    fn codegen_generate() {
+        let schema = Schema {
+            entries: vec![
+                entry("kern.ipc", CtlType::Node, CTLFLAG_RD),
+                entry("kern.ipc.somaxconn", CtlType::Uint, CTLFLAG_RW),
+                SchemaEntry {
+                    fmt: "IK".to_owned(),
+                    description: Some("Temperature\nin deciKelvin".to_owned()),
+                    ..entry("kern.ipc.temp", CtlType::Int, CTLFLAG_RW)
+                },
+                entry("kern.ipc.maxpipekva", CtlType::Long, CTLFLAG_RD),
+                SchemaEntry {
+                    array: true,
+                    ..entry("kern.ipc.times", CtlType::Long, CTLFLAG_RW)
+                },
+                entry("kern.ipc.umtx", CtlType::Struct, CTLFLAG_RD),
+                entry("kern.maxfiles", CtlType::Int, CTLFLAG_RW),
+            ],
+        };
+        let src = generate(&schema, "kern.ipc");
+
+        assert!(src.contains("/// kern.ipc.somaxconn description\n"));
+        assert!(src.contains("pub const SOMAXCONN: &str = \"kern.ipc.somaxconn\";\n"));
+        assert!(src.contains("pub fn somaxconn() -> Result<u32, SysctlError> {\n"));
+        assert!(src.contains("pub fn set_somaxconn(v: u32) -> Result<u32, SysctlError> {\n"));
+        assert!(src.contains("pub fn maxpipekva() -> Result<i64, SysctlError> {\n"));
+        assert!(src.contains("/// Temperature\n/// in deciKelvin\n"));
+        assert!(src.contains("pub fn temp() -> Result<sysctl::Temperature, SysctlError> {\n"));
+        assert!(!src.contains("set_maxpipekva"));
+        assert!(src.contains("pub fn times() -> Result<Vec<i64>, SysctlError> {\n"));
+        assert!(!src.contains("set_times"));
+        assert!(!src.contains("umtx"));
+        assert!(!src.contains("maxfiles"));
+    }
+}
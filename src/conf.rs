@@ -0,0 +1,299 @@
+//! Reading and applying sysctl.conf(5) files.
+//!
+//! Each line of a configuration sets one sysctl:
+//!
+//! ```text
+//! # comments start with '#', or ';' at the start of a line
+//! ; as in Linux sysctl.conf
+//! kern.ipc.somaxconn=4096
+//! kern.hostname = "build host"  # values may be quoted
+//! # a leading '-' ignores failures, e.g. for sysctls of unloaded modules
+//! -net.inet.tcp.cc.algorithm=htcp
+//! # Linux also accepts '/' as separator
+//! net/ipv4/ip_forward = 1
+//! ```
+//!
+//! Applying a configuration sets every sysctl in order, like `sysctl -f`,
+//! and reports the outcome for each line instead of stopping at the first
+//! failure.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! use sysctl::conf::Conf;
+//!
+//! fn main() {
+//!     let conf = Conf::load("/etc/sysctl.conf").expect("could not read config");
+//!     let report = conf.apply();
+//!     for result in report.failures() {
+//!         println!("{}", result);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{set_value, CtlValue, SysctlError};
+#[cfg(not(target_os = "linux"))]
+use super::{value_from_str, Ctl};
+
+/// A `name=value` line of a configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfEntry {
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The dotted name, e.g. `kern.ipc.somaxconn`.
+    pub name: String,
+    /// The value with quotes removed.
+    pub value: String,
+    /// Set by a leading `-`, failures to apply the line are not reported.
+    pub ignore_errors: bool,
+}
+
+impl ConfEntry {
+    /// The value converted to the type of the sysctl.
+    #[cfg(not(target_os = "linux"))]
+    pub fn typed_value(&self) -> Result<CtlValue, SysctlError> {
+        let ctl_type = Ctl::new(&self.name)?.value_type()?;
+        value_from_str(ctl_type, &self.value).map_err(SysctlError::InvalidValue)
+    }
+
+    /// The value converted to the type of the sysctl. Values in
+    /// `/proc/sys` are text and parsed by the kernel.
+    #[cfg(target_os = "linux")]
+    pub fn typed_value(&self) -> Result<CtlValue, SysctlError> {
+        Ok(CtlValue::String(self.value.clone()))
+    }
+
+    /// Set the sysctl to the value, returning the new value.
+    pub fn apply(&self) -> Result<CtlValue, SysctlError> {
+        set_value(&self.name, self.typed_value()?)
+    }
+}
+
+/// A configuration in sysctl.conf(5) syntax.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conf {
+    pub entries: Vec<ConfEntry>,
+}
+
+impl Conf {
+    /// Read and parse a configuration file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Conf, SysctlError> {
+        fs::read_to_string(path)
+            .map_err(SysctlError::IoError)?
+            .parse()
+    }
+
+    /// Set every sysctl of the configuration, in order.
+    pub fn apply(&self) -> ApplyReport {
+        ApplyReport {
+            results: self
+                .entries
+                .iter()
+                .map(|entry| ApplyResult {
+                    entry: entry.clone(),
+                    result: entry.apply(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Conf {
+    type Err = SysctlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = vec![];
+
+        for (n, line) in lines(s) {
+            let err = |reason: String| SysctlError::ParseError { line: n, reason };
+
+            let (line, ignore_errors) = match line.strip_prefix('-') {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+
+            let (name, value) = split_line(line).map_err(&err)?;
+            let value = parse_value(value).map_err(&err)?;
+
+            entries.push(ConfEntry {
+                line: n,
+                name: dotted(name),
+                value,
+                ignore_errors,
+            });
+        }
+
+        Ok(Conf { entries })
+    }
+}
+
+// The lines of s that are neither blank nor comments, trimmed and
+// numbered from 1. Baselines and policies share the syntax.
+pub(crate) fn lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+}
+
+// Split a line into the name and the unparsed value.
+pub(crate) fn split_line(line: &str) -> Result<(&str, &str), String> {
+    let mut kv = line.splitn(2, '=');
+    let name = kv.next().unwrap_or("").trim();
+    let value = kv
+        .next()
+        .ok_or_else(|| format!("expected 'name=value', got {:?}", line))?;
+    if name.is_empty() {
+        return Err("missing sysctl name".to_owned());
+    }
+    Ok((name, value))
+}
+
+// Remove quotes and a trailing comment from a value.
+pub(crate) fn parse_value(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    let quote = match s.chars().next() {
+        Some(q) if q == '"' || q == '\'' => q,
+        _ => {
+            let end = s.find('#').unwrap_or(s.len());
+            return Ok(s[..end].trim_end().to_owned());
+        }
+    };
+
+    let rest = &s[1..];
+    let end = rest
+        .find(quote)
+        .ok_or_else(|| format!("unterminated quote in {}", s))?;
+    let trailing = rest[end + 1..].trim_start();
+    if !trailing.is_empty() && !trailing.starts_with('#') {
+        return Err(format!("unexpected {:?} after quoted value", trailing));
+    }
+    Ok(rest[..end].to_owned())
+}
+
+// Names written with '/' as separator, like net/ipv4/ip_forward, use '.'
+// within components, as in net/ipv4/conf/eth0.100/forwarding.
+pub(crate) fn dotted(name: &str) -> String {
+    if !name.contains('/') {
+        return name.to_owned();
+    }
+    name.chars()
+        .map(|c| match c {
+            '/' => '.',
+            '.' => '/',
+            c => c,
+        })
+        .collect()
+}
+
+/// The outcome of applying one line of a configuration.
+#[derive(Debug)]
+pub struct ApplyResult {
+    pub entry: ConfEntry,
+    /// The new value, or why it could not be set.
+    pub result: Result<CtlValue, SysctlError>,
+}
+
+impl fmt::Display for ApplyResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.result {
+            Ok(ref v) => write!(f, "line {}: {} = {}", self.entry.line, self.entry.name, v),
+            Err(ref e) => write!(f, "line {}: {}: {}", self.entry.line, self.entry.name, e),
+        }
+    }
+}
+
+/// The outcome of applying a configuration, see `Conf::apply()`.
+#[derive(Debug)]
+pub struct ApplyReport {
+    /// The outcome of every line, in order.
+    pub results: Vec<ApplyResult>,
+}
+
+impl ApplyReport {
+    /// The lines that failed, except those marked to ignore errors.
+    pub fn failures(&self) -> impl Iterator<Item = &ApplyResult> {
+        self.results
+            .iter()
+            .filter(|r| r.result.is_err() && !r.entry.ignore_errors)
+    }
+
+    /// Whether every line was applied, or failed but ignores errors.
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: usize, name: &str, value: &str, ignore_errors: bool) -> ConfEntry {
+        ConfEntry {
+            line,
+            name: name.into(),
+            value: value.into(),
+            ignore_errors,
+        }
+    }
+
+    #[test]
+    fn conf_parse() {
+        let conf: Conf = "# comment\n\
+                          ; also a comment\n\
+                          kern.ipc.somaxconn=4096\n\
+                          \n\
+                          kern.hostname = \"build host\"  # trailing\n\
+                          kern.domainname='a # b'\n\
+                          -net.inet.tcp.cc.algorithm=htcp # trailing\n\
+                          net/ipv4/conf/eth0.100/forwarding = 1\n\
+                          kern.empty=\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            conf.entries,
+            vec![
+                entry(3, "kern.ipc.somaxconn", "4096", false),
+                entry(5, "kern.hostname", "build host", false),
+                entry(6, "kern.domainname", "a # b", false),
+                entry(7, "net.inet.tcp.cc.algorithm", "htcp", true),
+                entry(8, "net.ipv4.conf.eth0/100.forwarding", "1", false),
+                entry(9, "kern.empty", "", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn conf_parse_errors() {
+        for (text, line) in &[
+            ("kern.maxfiles", 1),
+            ("# comment\n=1", 2),
+            ("kern.hostname=\"unterminated", 1),
+            ("kern.hostname=\"a\" b", 1),
+        ] {
+            match text.parse::<Conf>() {
+                Err(SysctlError::ParseError { line: l, .. }) => assert_eq!(l, *line, "{}", text),
+                r => panic!("{}: unexpected {:?}", text, r),
+            }
+        }
+    }
+
+    #[test]
+    fn conf_apply_report() {
+        let conf: Conf = "nonexistent.a=1\n-nonexistent.b=2".parse().unwrap();
+        let report = conf.apply();
+        assert_eq!(report.results.len(), 2);
+        assert!(report.results.iter().all(|r| r.result.is_err()));
+        let failures: Vec<_> = report.failures().map(|r| r.entry.line).collect();
+        assert_eq!(failures, vec![1]);
+        assert!(!report.is_ok());
+        assert!(report.results[0]
+            .to_string()
+            .starts_with("line 1: nonexistent.a: "));
+    }
+}
@@ -0,0 +1,72 @@
+//! Helpers for the `dev` part of the tree. Only available on FreeBSD.
+
+use super::{Ctl, CtlValue, SysctlError, Temperature};
+
+/// Return the temperature of every core with a sensor, as pairs of core
+/// number and temperature sorted by core.
+///
+/// The sensors are `dev.cpu.<N>.temperature`, provided by drivers like
+/// coretemp(4) and amdtemp(4). Cores without a sensor, or whose sensor
+/// can not be read, are left out, so the result is empty if no such
+/// driver is loaded.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let temperatures = sysctl::cpu_temperatures().expect("could not read dev.cpu");
+///     for (core, t) in temperatures {
+///         println!("cpu{}: {}", core, t);
+///     }
+/// }
+/// ```
+pub fn cpu_temperatures() -> Result<Vec<(usize, Temperature)>, SysctlError> {
+    let mut temperatures = vec![];
+
+    for ctl in Ctl::new("dev.cpu")? {
+        let ctl = ctl?;
+        let core = match cpu_temperature_index(&ctl.name()?) {
+            Some(core) => core,
+            None => continue,
+        };
+        if let Ok(CtlValue::Temperature(t)) = ctl.value() {
+            temperatures.push((core, t));
+        }
+    }
+
+    temperatures.sort_by_key(|&(core, _)| core);
+    Ok(temperatures)
+}
+
+// The core number of a name like dev.cpu.3.temperature.
+fn cpu_temperature_index(name: &str) -> Option<usize> {
+    name.strip_prefix("dev.cpu.")?
+        .strip_suffix(".temperature")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dev_cpu_temperature_index() {
+        assert_eq!(cpu_temperature_index("dev.cpu.0.temperature"), Some(0));
+        assert_eq!(cpu_temperature_index("dev.cpu.12.temperature"), Some(12));
+        assert_eq!(cpu_temperature_index("dev.cpu.0.freq"), None);
+        assert_eq!(cpu_temperature_index("dev.cpu.x.temperature"), None);
+        assert_eq!(cpu_temperature_index("dev.cpu.0.cx.temperature"), None);
+        assert_eq!(cpu_temperature_index("dev.cpufreq.0.temperature"), None);
+    }
+
+    #[test]
+    fn dev_cpu_temperatures() {
+        let temperatures = cpu_temperatures().unwrap();
+        let cores: Vec<usize> = temperatures.iter().map(|&(core, _)| core).collect();
+        let mut sorted = cores.clone();
+        sorted.sort();
+        assert_eq!(cores, sorted);
+    }
+}
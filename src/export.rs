@@ -0,0 +1,843 @@
+//! Exporting numeric sysctls as metrics.
+//!
+//! An `Exporter` reads the numeric sysctls of the whole tree, or of the
+//! parts it includes, and renders them in the Prometheus text format,
+//! like prometheus_sysctl_exporter(8) on FreeBSD. Parts of the tree can
+//! be excluded to keep the number of metrics in check, e.g. the per-CPU
+//! or per-interface statistics. A pattern is a name whose components may
+//! contain the globs `*` and `?`, and selects the sysctls it names and
+//! everything below them, so `net.inet.tcp` selects everything below it
+//! and `dev.cpu.*.freq` the frequency of every CPU.
+//!
+//! Metric names are formed like prometheus_sysctl_exporter(8) does,
+//! `kern.maxfiles` becomes `sysctl_kern_maxfiles`. `MetricNames` changes
+//! the prefix, strips nodes from the start of names or renames single
+//! sysctls.
+//!
+//! Every metric is classified as a counter, which only grows, or a gauge.
+//! Writable sysctls are settings and thus gauges. Read-only statistics
+//! (`CTLFLAG_STATS`) of unsigned type and sysctls named like counters,
+//! e.g. `net.inet.ip.intr_queue_drops`, are counters; everything else is a
+//! gauge. `Exporter::kind()` overrides the classification of a sysctl.
+//!
+//! The metrics can be sent to Graphite in its plaintext protocol as well.
+//! Graphite paths keep the dots of the sysctl names, `kern.maxfiles`
+//! becomes `sysctl.kern.maxfiles`. `GraphitePaths` changes the prefix,
+//! e.g. to add the host name, and strips and renames like `MetricNames`.
+//!
+//! Raw values can be turned into what dashboards show with a `Transform`
+//! per sysctl: scaled and offset, e.g. from pages to bytes or from tenths
+//! of a Kelvin to degrees Celsius, and with integers that stand for a
+//! state labelled, e.g. `state="up"`. Prometheus gets the label as a
+//! label, Graphite as a tag. `Transform::format()` renders single values
+//! the same way for people, as does `watch::CsvWriter`.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::export::Exporter;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # fn main() {}
+//! # #[cfg(not(target_os = "linux"))]
+//! fn main() {
+//!     let exporter = Exporter::new()
+//!         .include("kern.ipc")
+//!         .include("vm.stats")
+//!         .exclude("vm.stats.sys");
+//!     print!("{}", exporter.prometheus().expect("could not read metrics"));
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::io::Write as IoWrite;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libc::c_uint;
+
+use super::query::{is_glob, name_matches};
+use super::{
+    numeric, under, Ctl, CtlIter, CtlType, CtlValue, SysctlError, CTLFLAG_STATS, CTLFLAG_WR,
+};
+
+/// The value of a numeric sysctl, see `Exporter::collect()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// The name of the sysctl, e.g. `kern.maxfiles`.
+    pub sysctl: String,
+    /// The name of the metric, e.g. `sysctl_kern_maxfiles`.
+    pub name: String,
+    pub value: f64,
+    /// The description of the sysctl, `None` where there is none.
+    pub help: Option<String>,
+    pub kind: MetricKind,
+    /// The label of the raw value as key and label, e.g. `("state",
+    /// "up")`, see `Transform::label()`.
+    pub label: Option<(String, String)>,
+}
+
+/// Whether a metric counts events or measures a current state.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MetricKind {
+    /// Only ever grows, until the system restarts.
+    Counter,
+    /// Goes up and down.
+    Gauge,
+}
+
+impl MetricKind {
+    // The name of the type in the Prometheus text format.
+    fn prometheus(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+// Last name components that count events.
+const COUNTER_WORDS: &[&str] = &[
+    "bytes", "calls", "drops", "errors", "faults", "intr", "packets", "swtch", "total",
+];
+
+/// How the names of sysctls map to metric names, see the module
+/// documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricNames {
+    prefix: String,
+    strip: Vec<String>,
+    renames: BTreeMap<String, String>,
+}
+
+impl Default for MetricNames {
+    fn default() -> Self {
+        MetricNames {
+            prefix: "sysctl_".to_owned(),
+            strip: vec![],
+            renames: BTreeMap::new(),
+        }
+    }
+}
+
+impl MetricNames {
+    /// The names of prometheus_sysctl_exporter(8).
+    pub fn new() -> Self {
+        MetricNames::default()
+    }
+
+    /// Start metric names with `prefix` instead of `sysctl_`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Remove the node from the start of the names below it, e.g.
+    /// stripping `vm.stats.vm` names `vm.stats.vm.v_free_count`
+    /// `sysctl_v_free_count`. The longest matching node is removed.
+    pub fn strip(mut self, node: &str) -> Self {
+        self.strip.push(node.trim_end_matches('.').to_owned());
+        self
+    }
+
+    /// Name the metric of the sysctl `metric`, without the prefix.
+    pub fn rename(mut self, sysctl: &str, metric: &str) -> Self {
+        self.renames.insert(sysctl.to_owned(), metric.to_owned());
+        self
+    }
+
+    /// The metric name of the sysctl. Characters that metric names may
+    /// not contain become underscores.
+    pub fn name(&self, sysctl: &str) -> String {
+        if let Some(metric) = self.renames.get(sysctl) {
+            return metric.clone();
+        }
+        let mut name = self.prefix.clone();
+        name.extend(strip(sysctl, &self.strip).chars().map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+            _ => '_',
+        }));
+        name
+    }
+}
+
+/// How the names of sysctls map to Graphite paths, see the module
+/// documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphitePaths {
+    prefix: String,
+    strip: Vec<String>,
+    renames: BTreeMap<String, String>,
+}
+
+impl Default for GraphitePaths {
+    fn default() -> Self {
+        GraphitePaths {
+            prefix: "sysctl.".to_owned(),
+            strip: vec![],
+            renames: BTreeMap::new(),
+        }
+    }
+}
+
+impl GraphitePaths {
+    /// Paths below `sysctl.`.
+    pub fn new() -> Self {
+        GraphitePaths::default()
+    }
+
+    /// Start paths with `prefix` instead of `sysctl.`, e.g.
+    /// `servers.web1.sysctl.`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Remove the node from the start of the names below it. The longest
+    /// matching node is removed.
+    pub fn strip(mut self, node: &str) -> Self {
+        self.strip.push(node.trim_end_matches('.').to_owned());
+        self
+    }
+
+    /// Send the sysctl as `path`, without the prefix.
+    pub fn rename(mut self, sysctl: &str, path: &str) -> Self {
+        self.renames.insert(sysctl.to_owned(), path.to_owned());
+        self
+    }
+
+    /// The Graphite path of the sysctl. Whitespace and characters
+    /// Graphite uses for globbing become underscores.
+    pub fn path(&self, sysctl: &str) -> String {
+        if let Some(path) = self.renames.get(sysctl) {
+            return format!("{}{}", self.prefix, path);
+        }
+        let mut path = self.prefix.clone();
+        path.extend(strip(sysctl, &self.strip).chars().map(|c| match c {
+            '*' | '?' | '[' | ']' | '{' | '}' | ',' | '/' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        }));
+        path
+    }
+}
+
+// The name with the longest of the nodes below which it is removed.
+fn strip<'a>(sysctl: &'a str, nodes: &[String]) -> &'a str {
+    nodes
+        .iter()
+        .filter(|node| sysctl.len() > node.len() && under(sysctl, node))
+        .max_by_key(|node| node.len())
+        .map_or(sysctl, |node| &sysctl[node.len() + 1..])
+}
+
+/// Converts the raw value of a numeric sysctl, see the module
+/// documentation. The value is multiplied by the scale, then the offset is
+/// added. Labels are looked up by the raw value.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::export::Transform;
+/// use sysctl::CtlValue;
+///
+/// fn main() {
+///     let celsius = Transform::new().scale(0.1).offset(-273.15).unit("°C");
+///     assert_eq!(celsius.format(&CtlValue::Int(3231)), "49.95 °C");
+///     let link = Transform::new().label_key("link").label(0, "down").label(1, "up");
+///     assert_eq!(link.format(&CtlValue::Int(1)), "up");
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    scale: f64,
+    offset: f64,
+    unit: Option<String>,
+    label_key: String,
+    labels: BTreeMap<i64, String>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            scale: 1.0,
+            offset: 0.0,
+            unit: None,
+            label_key: "state".to_owned(),
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+impl Transform {
+    /// A transform leaving values as they are.
+    pub fn new() -> Self {
+        Transform::default()
+    }
+
+    /// Multiply values by `scale`, e.g. by the page size for page counts.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Add `offset` to values after scaling them.
+    pub fn offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The unit of the converted values, appended by `format()`.
+    pub fn unit(mut self, unit: &str) -> Self {
+        self.unit = Some(unit.to_owned());
+        self
+    }
+
+    /// Label the raw value, e.g. the states of an enum.
+    pub fn label(mut self, raw: i64, label: &str) -> Self {
+        self.labels.insert(raw, label.to_owned());
+        self
+    }
+
+    /// Export labels under `key` instead of `state`.
+    pub fn label_key(mut self, key: &str) -> Self {
+        self.label_key = key.to_owned();
+        self
+    }
+
+    /// The converted value.
+    pub fn apply(&self, raw: f64) -> f64 {
+        let value = raw * self.scale + self.offset;
+        // Round away the noise of scaling, e.g. 49.950000000000045, where
+        // the digits are not needed for the value itself
+        if value.abs() < 1e9 {
+            (value * 1e6).round() / 1e6
+        } else {
+            value
+        }
+    }
+
+    /// The label of the raw value, if it has one.
+    pub fn label_of(&self, raw: i128) -> Option<&str> {
+        let raw = i64::try_from(raw).ok()?;
+        self.labels.get(&raw).map(String::as_str)
+    }
+
+    /// Render a value for people: the label of a numeric value if it has
+    /// one, else the converted value with its unit. Values that are not
+    /// numeric are rendered as they are.
+    pub fn format(&self, value: &CtlValue) -> String {
+        let raw = match numeric(value) {
+            Some(raw) => raw,
+            None => return value.to_string(),
+        };
+        if let Some(label) = self.label_of(raw) {
+            return label.to_owned();
+        }
+        let converted = self.apply(raw as f64);
+        match self.unit {
+            Some(ref unit) => format!("{} {}", converted, unit),
+            None => converted.to_string(),
+        }
+    }
+
+    // The converted value and the label as exported, the one conversion
+    // behind every format.
+    pub(crate) fn convert(&self, raw: i128) -> (f64, Option<(String, String)>) {
+        let label = self
+            .label_of(raw)
+            .map(|label| (self.label_key.clone(), label.to_owned()));
+        (self.apply(raw as f64), label)
+    }
+}
+
+/// Reads numeric sysctls as metrics, see the module documentation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exporter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    names: MetricNames,
+    paths: GraphitePaths,
+    kinds: BTreeMap<String, MetricKind>,
+    transforms: BTreeMap<String, Transform>,
+}
+
+impl Exporter {
+    /// An exporter of the whole tree.
+    pub fn new() -> Self {
+        Exporter::default()
+    }
+
+    /// Export the sysctls matching the pattern. Once a pattern is
+    /// included, only the sysctls matching one of them are exported.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(pattern.to_owned());
+        self
+    }
+
+    /// Leave out the sysctls matching the pattern, even if they are
+    /// included.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_owned());
+        self
+    }
+
+    /// Name the metrics by `names` instead of the defaults.
+    pub fn names(mut self, names: MetricNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Send the metrics to Graphite under `paths` instead of the default
+    /// paths.
+    pub fn paths(mut self, paths: GraphitePaths) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Classify the metric of the sysctl as `kind`, whatever its flags
+    /// and name suggest.
+    pub fn kind(mut self, sysctl: &str, kind: MetricKind) -> Self {
+        self.kinds.insert(sysctl.to_owned(), kind);
+        self
+    }
+
+    /// Convert the value of the sysctl with `transform` before exporting
+    /// it.
+    pub fn transform(mut self, sysctl: &str, transform: Transform) -> Self {
+        self.transforms.insert(sysctl.to_owned(), transform);
+        self
+    }
+
+    // The transform of the sysctl, for exporters reading values
+    // themselves.
+    pub(crate) fn transform_of(&self, sysctl: &str) -> Option<&Transform> {
+        self.transforms.get(sysctl)
+    }
+
+    /// Whether the sysctl of the given name is exported.
+    pub fn exports(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| name_matches(p, name)))
+            && !self.exclude.iter().any(|p| name_matches(p, name))
+    }
+
+    /// Read the numeric sysctls that are exported, sorted by name.
+    /// Sysctls that can not be read are left out.
+    ///
+    /// Fails if the node of an include pattern does not exist, or if
+    /// walking the tree fails.
+    pub fn collect(&self) -> Result<Vec<Metric>, SysctlError> {
+        let patterns = if self.include.is_empty() {
+            vec![String::new()]
+        } else {
+            self.include.clone()
+        };
+
+        // Patterns may overlap, keep every sysctl once
+        let mut metrics = BTreeMap::new();
+        for pattern in &patterns {
+            for ctl in candidates(pattern)? {
+                let sysctl = match ctl.name() {
+                    Ok(sysctl) => sysctl,
+                    Err(_) => continue,
+                };
+                if metrics.contains_key(&sysctl) || !self.exports(&sysctl) {
+                    continue;
+                }
+                let (ctl_type, flags, value) = match (ctl.value_type(), ctl.flags(), ctl.value()) {
+                    (Ok(ctl_type), Ok(flags), Ok(value)) => (ctl_type, flags.bits(), value),
+                    _ => continue,
+                };
+                let raw = match numeric(&value) {
+                    Some(raw) => raw,
+                    None => continue,
+                };
+                let kind = match self.kinds.get(&sysctl) {
+                    Some(&kind) => kind,
+                    None => classify(&sysctl, ctl_type, flags),
+                };
+                #[cfg(not(target_os = "macos"))]
+                let help = ctl.description().ok().filter(|d| !d.is_empty());
+                #[cfg(target_os = "macos")]
+                let help = None;
+                let (value, label) = match self.transform_of(&sysctl) {
+                    Some(transform) => transform.convert(raw),
+                    None => (raw as f64, None),
+                };
+                let metric = Metric {
+                    name: self.names.name(&sysctl),
+                    sysctl: sysctl.clone(),
+                    value,
+                    help,
+                    kind,
+                    label,
+                };
+                metrics.insert(sysctl, metric);
+            }
+        }
+
+        Ok(metrics.into_values().collect())
+    }
+
+    /// Read the exported sysctls and render them in the Prometheus text
+    /// format.
+    pub fn prometheus(&self) -> Result<String, SysctlError> {
+        Ok(prometheus(&self.collect()?))
+    }
+
+    /// Read the exported sysctls and render them in the Graphite
+    /// plaintext protocol, timestamped now.
+    pub fn graphite(&self) -> Result<String, SysctlError> {
+        Ok(graphite(&self.collect()?, &self.paths, SystemTime::now()))
+    }
+
+    /// Read the exported sysctls and send them to a Graphite server, e.g.
+    /// carbon listening on port 2003. Returns the number of metrics sent.
+    pub fn send_graphite<A: ToSocketAddrs>(&self, addr: A) -> Result<usize, SysctlError> {
+        let metrics = self.collect()?;
+        let lines = graphite(&metrics, &self.paths, SystemTime::now());
+        let mut stream = TcpStream::connect(addr).map_err(SysctlError::IoError)?;
+        stream
+            .write_all(lines.as_bytes())
+            .map_err(SysctlError::IoError)?;
+        Ok(metrics.len())
+    }
+}
+
+/// Render metrics in the Prometheus text format.
+pub fn prometheus(metrics: &[Metric]) -> String {
+    let mut out = String::new();
+    for m in metrics {
+        if let Some(ref help) = m.help {
+            writeln!(out, "# HELP {} {}", m.name, escape_help(help)).unwrap();
+        }
+        writeln!(out, "# TYPE {} {}", m.name, m.kind.prometheus()).unwrap();
+        match m.label {
+            Some((ref key, ref label)) => writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                m.name,
+                label_name(key),
+                escape_label(label),
+                m.value
+            )
+            .unwrap(),
+            None => writeln!(out, "{} {}", m.name, m.value).unwrap(),
+        }
+    }
+    out
+}
+
+// The sysctls the pattern may select: everything below the node named by
+// its components up to the first glob, or that sysctl alone if it is not
+// a node. The walk stops at the first error, the iterator would return it
+// again and again.
+fn candidates(pattern: &str) -> Result<Vec<Ctl>, SysctlError> {
+    let literal = pattern
+        .split('.')
+        .take_while(|c| !c.is_empty() && !is_glob(c))
+        .collect::<Vec<_>>();
+    if literal.is_empty() {
+        return CtlIter::root().collect();
+    }
+    let node = Ctl::new(&literal.join("."))?;
+    if node.value_type()? != CtlType::Node {
+        return Ok(vec![node]);
+    }
+    CtlIter::below(node).collect()
+}
+
+/// Render metrics in the Graphite plaintext protocol, one
+/// `path value timestamp` line each.
+pub fn graphite(metrics: &[Metric], paths: &GraphitePaths, time: SystemTime) -> String {
+    let timestamp = time.duration_since(UNIX_EPOCH).map_or(0, |t| t.as_secs());
+    let mut out = String::new();
+    for m in metrics {
+        let path = paths.path(&m.sysctl);
+        match m.label {
+            // Tagged as Graphite 1.1 takes it
+            Some((ref key, ref label)) => writeln!(
+                out,
+                "{};{}={} {} {}",
+                path,
+                graphite_tag(key),
+                graphite_tag(label),
+                m.value,
+                timestamp
+            )
+            .unwrap(),
+            None => writeln!(out, "{} {} {}", path, m.value, timestamp).unwrap(),
+        }
+    }
+    out
+}
+
+// Guess whether the sysctl counts events, see the module documentation.
+fn classify(sysctl: &str, ctl_type: CtlType, flags: c_uint) -> MetricKind {
+    if flags & CTLFLAG_WR != 0 {
+        return MetricKind::Gauge;
+    }
+    let unsigned = matches!(
+        ctl_type,
+        CtlType::Uint | CtlType::Ulong | CtlType::U64 | CtlType::U32
+    );
+    if unsigned && flags & CTLFLAG_STATS != 0 {
+        return MetricKind::Counter;
+    }
+    let last = sysctl.rsplit('.').next().unwrap_or(sysctl);
+    if last.split('_').any(|word| COUNTER_WORDS.contains(&word)) {
+        return MetricKind::Counter;
+    }
+    MetricKind::Gauge
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(label: &str) -> String {
+    escape_help(label).replace('"', "\\\"")
+}
+
+// Label names may only contain letters, digits and underscores.
+fn label_name(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+// Tags may not contain the separators of the protocol.
+fn graphite_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| match c {
+            ';' | '!' | '^' | '=' | '~' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "linux"))]
+    const NUMERIC: &str = "kern.maxproc";
+    #[cfg(target_os = "linux")]
+    const NUMERIC: &str = "kernel.pid_max";
+
+    #[test]
+    fn export_filters() {
+        let exporter = Exporter::new();
+        assert!(exporter.exports("kern.maxfiles"));
+
+        let exporter = Exporter::new()
+            .include("net.inet.tcp")
+            .include("dev.cpu.*.freq")
+            .exclude("net.inet.tcp.hostcache");
+        assert!(exporter.exports("net.inet.tcp.mssdflt"));
+        assert!(exporter.exports("dev.cpu.0.freq"));
+        assert!(exporter.exports("dev.cpu.0.freq.levels"));
+        assert!(!exporter.exports("dev.cpu.0.temperature"));
+        assert!(!exporter.exports("net.inet.tcp.hostcache.count"));
+        assert!(!exporter.exports("net.inet.tcpx"));
+        assert!(!exporter.exports("kern.maxfiles"));
+    }
+
+    #[test]
+    fn export_names() {
+        let names = MetricNames::new();
+        assert_eq!(names.name("dev.cpu.0.%desc"), "sysctl_dev_cpu_0__desc");
+
+        let names = MetricNames::new()
+            .prefix("freebsd_")
+            .strip("vm.stats")
+            .strip("vm.stats.vm.")
+            .rename("kern.maxfiles", "max_files");
+        assert_eq!(
+            names.name("vm.stats.vm.v_free_count"),
+            "freebsd_v_free_count"
+        );
+        assert_eq!(names.name("vm.stats.sys.v_swtch"), "freebsd_sys_v_swtch");
+        assert_eq!(names.name("vm.stats"), "freebsd_vm_stats");
+        assert_eq!(names.name("vm.statsx.y"), "freebsd_vm_statsx_y");
+        assert_eq!(names.name("kern.maxfiles"), "max_files");
+    }
+
+    #[test]
+    fn export_prometheus() {
+        let metrics = vec![
+            Metric {
+                sysctl: "kern.maxfiles".into(),
+                name: "sysctl_kern_maxfiles".into(),
+                value: 1000.0,
+                help: Some("Maximum number\nof files".into()),
+                kind: MetricKind::Gauge,
+                label: None,
+            },
+            Metric {
+                sysctl: "vm.loadavg".into(),
+                name: "sysctl_vm_loadavg".into(),
+                value: 0.5,
+                help: None,
+                kind: MetricKind::Counter,
+                label: None,
+            },
+        ];
+        assert_eq!(
+            prometheus(&metrics),
+            "# HELP sysctl_kern_maxfiles Maximum number\\nof files\n\
+             # TYPE sysctl_kern_maxfiles gauge\n\
+             sysctl_kern_maxfiles 1000\n\
+             # TYPE sysctl_vm_loadavg counter\n\
+             sysctl_vm_loadavg 0.5\n"
+        );
+    }
+
+    #[test]
+    fn export_graphite() {
+        let paths = GraphitePaths::new()
+            .prefix("servers.web1.")
+            .strip("vm.stats.vm")
+            .rename("kern.maxfiles", "files.max");
+        assert_eq!(
+            paths.path("vm.stats.vm.v_free_count"),
+            "servers.web1.v_free_count"
+        );
+        assert_eq!(
+            paths.path("dev.cpu.0.%desc"),
+            "servers.web1.dev.cpu.0.%desc"
+        );
+        assert_eq!(paths.path("a.b c.d*"), "servers.web1.a.b_c.d_");
+        assert_eq!(paths.path("kern.maxfiles"), "servers.web1.files.max");
+
+        let metric = Metric {
+            sysctl: "vm.loadavg".into(),
+            name: "sysctl_vm_loadavg".into(),
+            value: 0.5,
+            help: None,
+            kind: MetricKind::Gauge,
+            label: None,
+        };
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            graphite(&[metric], &GraphitePaths::new(), time),
+            "sysctl.vm.loadavg 0.5 1700000000\n"
+        );
+    }
+
+    #[test]
+    fn export_transform() {
+        let pages = Transform::new().scale(4096.0).unit("B");
+        assert_eq!(pages.apply(2.0), 8192.0);
+        assert_eq!(pages.format(&CtlValue::Uint(2)), "8192 B");
+        assert_eq!(pages.format(&CtlValue::String("x".into())), "x");
+        assert_eq!(pages.apply(1e12), 4.096e15);
+
+        let celsius = Transform::new().scale(0.1).offset(-273.15);
+        assert_eq!(celsius.apply(3231.0), 49.95);
+        assert_eq!(celsius.format(&CtlValue::Int(3231)), "49.95");
+
+        let state = Transform::new().label(0, "down").label(1, "up");
+        assert_eq!(state.label_of(1), Some("up"));
+        assert_eq!(state.label_of(2), None);
+        assert_eq!(state.label_of(i128::MAX), None);
+        assert_eq!(state.format(&CtlValue::Int(0)), "down");
+        assert_eq!(state.format(&CtlValue::Int(2)), "2");
+        assert_eq!(state.convert(1), (1.0, Some(("state".into(), "up".into()))));
+        let link = state.label_key("link state");
+        assert_eq!(
+            link.convert(0),
+            (0.0, Some(("link state".into(), "down".into())))
+        );
+        assert_eq!(link.convert(2), (2.0, None));
+
+        // Every format takes the label the same way
+        let metric = Metric {
+            sysctl: "a.b".into(),
+            name: "sysctl_a_b".into(),
+            value: 1.0,
+            help: None,
+            kind: MetricKind::Gauge,
+            label: Some(("link state".into(), "up \"x\";y".into())),
+        };
+        assert_eq!(
+            prometheus(std::slice::from_ref(&metric)),
+            "# TYPE sysctl_a_b gauge\nsysctl_a_b{link_state=\"up \\\"x\\\";y\"} 1\n"
+        );
+        assert_eq!(
+            graphite(&[metric], &GraphitePaths::new(), UNIX_EPOCH),
+            "sysctl.a.b;link_state=up_\"x\"_y 1 0\n"
+        );
+
+        let metrics = Exporter::new()
+            .include(NUMERIC)
+            .transform(
+                NUMERIC,
+                Transform::new().scale(0.0).offset(7.0).label(0, "zero"),
+            )
+            .collect()
+            .unwrap();
+        assert_eq!(metrics[0].value, 7.0);
+        assert_eq!(metrics[0].label, None);
+    }
+
+    #[test]
+    fn export_classify() {
+        use super::super::{CTLFLAG_RD, CTLFLAG_RW};
+
+        let stats = CTLFLAG_RD | CTLFLAG_STATS;
+        assert_eq!(
+            classify("net.inet.ip.intr_queue_drops", CtlType::Int, CTLFLAG_RD),
+            MetricKind::Counter
+        );
+        assert_eq!(
+            classify("kern.ipc.numopensockets", CtlType::Int, CTLFLAG_RD),
+            MetricKind::Gauge
+        );
+        assert_eq!(
+            classify("vm.stats.sys.v_swtch", CtlType::Uint, CTLFLAG_RD),
+            MetricKind::Counter
+        );
+        assert_eq!(classify("a.b", CtlType::U64, stats), MetricKind::Counter);
+        assert_eq!(classify("a.b", CtlType::Int, stats), MetricKind::Gauge);
+        assert_eq!(
+            classify("a.total", CtlType::U64, CTLFLAG_RW | CTLFLAG_STATS),
+            MetricKind::Gauge
+        );
+    }
+
+    #[test]
+    fn export_collect() {
+        let metrics = Exporter::new().include(NUMERIC).collect().unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].sysctl, NUMERIC);
+        assert!(metrics[0].value > 0.0);
+        assert_eq!(metrics[0].kind, MetricKind::Gauge);
+
+        let metrics = Exporter::new()
+            .include(NUMERIC)
+            .kind(NUMERIC, MetricKind::Counter)
+            .collect()
+            .unwrap();
+        assert_eq!(metrics[0].kind, MetricKind::Counter);
+
+        let lines = Exporter::new().include(NUMERIC).graphite().unwrap();
+        assert!(lines.starts_with(&format!("sysctl.{} ", NUMERIC)));
+
+        let metrics = Exporter::new()
+            .include(NUMERIC)
+            .include(NUMERIC)
+            .exclude("*")
+            .collect()
+            .unwrap();
+        assert!(metrics.is_empty());
+
+        assert!(Exporter::new().include("nonexistent").collect().is_err());
+    }
+}
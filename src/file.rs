@@ -0,0 +1,204 @@
+//! Open files of all processes, decoded from `kern.file`.
+//!
+//! `kern.file` returns an array of `struct xfile` records, one per open
+//! file descriptor in the system. Only available on FreeBSD.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let files = sysctl::file::open_files().expect("could not read kern.file");
+//!     for f in files.iter().filter(|f| f.pid == 1) {
+//!         println!("fd {}: {:?}", f.fd, f.file_type);
+//!     }
+//! }
+//! ```
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::{pid_t, uid_t};
+
+use super::{fetch_oid, name2oid, SysctlError};
+
+// Offsets of the fields of struct xfile, see sys/file.h
+const XF_SIZE: usize = 0;
+const XF_PID: usize = 8;
+const XF_UID: usize = 12;
+const XF_FD: usize = 16;
+const XF_FILE: usize = 24;
+const XF_TYPE: usize = 32;
+const XF_COUNT: usize = 36;
+const XF_MSGCOUNT: usize = 40;
+const XF_OFFSET: usize = 48;
+const XF_DATA: usize = 56;
+const XF_VNODE: usize = 64;
+const XF_FLAG: usize = 72;
+// Size of struct xfile up to and including the last field we decode
+const XF_MINSIZE: usize = 76;
+
+/// The kind of object behind a file descriptor (`DTYPE_*`).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FileType {
+    Vnode,
+    Socket,
+    Pipe,
+    Fifo,
+    Kqueue,
+    Crypto,
+    Mqueue,
+    Shm,
+    Sem,
+    Pts,
+    Dev,
+    Procdesc,
+    Eventfd,
+    Linuxtfd,
+    Unknown(i16),
+}
+
+impl From<i16> for FileType {
+    fn from(t: i16) -> Self {
+        match t {
+            1 => FileType::Vnode,
+            2 => FileType::Socket,
+            3 => FileType::Pipe,
+            4 => FileType::Fifo,
+            5 => FileType::Kqueue,
+            6 => FileType::Crypto,
+            7 => FileType::Mqueue,
+            8 => FileType::Shm,
+            9 => FileType::Sem,
+            10 => FileType::Pts,
+            11 => FileType::Dev,
+            12 => FileType::Procdesc,
+            13 => FileType::Eventfd,
+            14 => FileType::Linuxtfd,
+            t => FileType::Unknown(t),
+        }
+    }
+}
+
+/// An open file descriptor of a process.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenFile {
+    /// Owning process.
+    pub pid: pid_t,
+    /// Effective uid of the owning process.
+    pub uid: uid_t,
+    /// Descriptor number.
+    pub fd: i32,
+    pub file_type: FileType,
+    /// Reference count of the file.
+    pub count: i32,
+    /// References from message queues.
+    pub msgcount: i32,
+    /// Current file offset.
+    pub offset: i64,
+    /// File status flags, see fcntl(2).
+    pub flags: u32,
+    /// Kernel address of the struct file.
+    pub file: u64,
+    /// Kernel address of the descriptor specific data.
+    pub data: u64,
+    /// Kernel address of the vnode, if any.
+    pub vnode: u64,
+}
+
+/// Return the open files of all processes.
+pub fn open_files() -> Result<Vec<OpenFile>, SysctlError> {
+    let oid = name2oid("kern.file")?;
+    let buf = fetch_oid(&oid, 0)?;
+    decode(&buf)
+}
+
+// Decode an array of struct xfile. Each record starts with its own size,
+// which is used as the stride so newer kernels with a larger struct work.
+fn decode(mut buf: &[u8]) -> Result<Vec<OpenFile>, SysctlError> {
+    let mut files = vec![];
+
+    while !buf.is_empty() {
+        if buf.len() < XF_MINSIZE {
+            return Err(SysctlError::ShortRead {
+                read: buf.len(),
+                reported: XF_MINSIZE,
+            });
+        }
+        let size = NativeEndian::read_u64(&buf[XF_SIZE..]) as usize;
+        if size < XF_MINSIZE || size > buf.len() {
+            return Err(SysctlError::ExtractionError);
+        }
+
+        files.push(OpenFile {
+            pid: NativeEndian::read_i32(&buf[XF_PID..]),
+            uid: NativeEndian::read_u32(&buf[XF_UID..]),
+            fd: NativeEndian::read_i32(&buf[XF_FD..]),
+            file_type: FileType::from(NativeEndian::read_i16(&buf[XF_TYPE..])),
+            count: NativeEndian::read_i32(&buf[XF_COUNT..]),
+            msgcount: NativeEndian::read_i32(&buf[XF_MSGCOUNT..]),
+            offset: NativeEndian::read_i64(&buf[XF_OFFSET..]),
+            flags: NativeEndian::read_u32(&buf[XF_FLAG..]),
+            file: NativeEndian::read_u64(&buf[XF_FILE..]),
+            data: NativeEndian::read_u64(&buf[XF_DATA..]),
+            vnode: NativeEndian::read_u64(&buf[XF_VNODE..]),
+        });
+
+        buf = &buf[size..];
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn xfile(pid: i32, fd: i32, dtype: i16) -> Vec<u8> {
+        let mut b = vec![];
+        b.write_u64::<NativeEndian>(128).unwrap(); // xf_size
+        b.write_i32::<NativeEndian>(pid).unwrap();
+        b.write_u32::<NativeEndian>(1001).unwrap(); // xf_uid
+        b.write_i32::<NativeEndian>(fd).unwrap();
+        b.write_i32::<NativeEndian>(0).unwrap();
+        b.write_u64::<NativeEndian>(0xfffff80001).unwrap(); // xf_file
+        b.write_i16::<NativeEndian>(dtype).unwrap();
+        b.write_i16::<NativeEndian>(0).unwrap();
+        b.write_i32::<NativeEndian>(2).unwrap(); // xf_count
+        b.write_i32::<NativeEndian>(0).unwrap(); // xf_msgcount
+        b.write_i32::<NativeEndian>(0).unwrap();
+        b.write_i64::<NativeEndian>(4096).unwrap(); // xf_offset
+        b.write_u64::<NativeEndian>(0xfffff80002).unwrap(); // xf_data
+        b.write_u64::<NativeEndian>(0xfffff80003).unwrap(); // xf_vnode
+        b.write_u32::<NativeEndian>(3).unwrap(); // xf_flag
+        b.resize(128, 0);
+        b
+    }
+
+    #[test]
+    fn file_decode() {
+        let mut buf = xfile(42, 3, 1);
+        buf.extend(xfile(43, 0, 10));
+
+        let files = decode(&buf).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].pid, 42);
+        assert_eq!(files[0].uid, 1001);
+        assert_eq!(files[0].fd, 3);
+        assert_eq!(files[0].file_type, FileType::Vnode);
+        assert_eq!(files[0].count, 2);
+        assert_eq!(files[0].offset, 4096);
+        assert_eq!(files[0].flags, 3);
+        assert_eq!(files[0].vnode, 0xfffff80003);
+        assert_eq!(files[1].pid, 43);
+        assert_eq!(files[1].file_type, FileType::Pts);
+
+        assert!(decode(&buf[..100]).is_err());
+    }
+
+    #[test]
+    fn file_open_files() {
+        let pid = unsafe { libc::getpid() };
+        let files = open_files().unwrap();
+        assert!(files.iter().any(|f| f.pid == pid));
+    }
+}
@@ -0,0 +1,705 @@
+//! A FUSE filesystem presenting the sysctl tree as files, like `/proc/sys`
+//! on Linux.
+//!
+//! Every node is a directory and every other sysctl a file below it, e.g.
+//! `kern.ostype` is `kern/ostype` below the mount point. Reading a file
+//! returns the value as text followed by a newline, or the bytes of opaque
+//! values. Writing the textual form of a value to a file sets it. The
+//! kernel checks permissions against the modes of the files: everybody
+//! may read, and root may write the writable sysctls unless the
+//! filesystem is mounted read-only.
+//!
+//! The filesystem speaks the FUSE kernel protocol itself, it needs no
+//! libfuse. Mounting needs root, or `vfs.usermount` on FreeBSD. Only
+//! available with the `fuse` feature.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! use sysctl::fuse::SysctlFs;
+//!
+//! fn main() {
+//!     // Serves requests until unmounted, e.g. with umount(8)
+//!     SysctlFs::new()
+//!         .mount("/compat/linux/proc/sys")
+//!         .expect("could not mount");
+//! }
+//! ```
+
+use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
+use libc::{c_int, c_void};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    name2oid, next_oid, oid2name, oidfmt, value_from_str, Ctl, CtlType, CtlValue, SysctlError,
+    CTLFLAG_WR,
+};
+
+// The protocol version spoken, minor versions down to 7.8 are understood
+const FUSE_KERNEL_VERSION: u32 = 7;
+const FUSE_KERNEL_MINOR_VERSION: u32 = 31;
+
+const FUSE_LOOKUP: u32 = 1;
+const FUSE_FORGET: u32 = 2;
+const FUSE_GETATTR: u32 = 3;
+const FUSE_SETATTR: u32 = 4;
+const FUSE_OPEN: u32 = 14;
+const FUSE_READ: u32 = 15;
+const FUSE_WRITE: u32 = 16;
+const FUSE_STATFS: u32 = 17;
+const FUSE_RELEASE: u32 = 18;
+const FUSE_FLUSH: u32 = 25;
+const FUSE_INIT: u32 = 26;
+const FUSE_OPENDIR: u32 = 27;
+const FUSE_READDIR: u32 = 28;
+const FUSE_RELEASEDIR: u32 = 29;
+const FUSE_ACCESS: u32 = 34;
+const FUSE_INTERRUPT: u32 = 36;
+const FUSE_DESTROY: u32 = 38;
+const FUSE_BATCH_FORGET: u32 = 42;
+
+const FOPEN_DIRECT_IO: u32 = 1;
+// The file types of modes, the same on every system
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const ROOT_INODE: u64 = 1;
+const MAX_WRITE: u32 = 64 * 1024;
+const IN_HEADER_LEN: usize = 40;
+const OUT_HEADER_LEN: usize = 16;
+// How long the kernel may cache names and attributes, in seconds
+const TTL: u64 = 1;
+
+/// The sysctl tree as a FUSE filesystem, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct SysctlFs {
+    read_only: bool,
+}
+
+impl SysctlFs {
+    /// A filesystem setting sysctls written to.
+    pub fn new() -> Self {
+        SysctlFs::default()
+    }
+
+    /// Mount the filesystem read-only, no sysctl can be set through it.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Mount the filesystem at `mountpoint`, an existing directory, and
+    /// serve requests until it is unmounted, e.g. with `unmount()`.
+    pub fn mount<P: AsRef<Path>>(&self, mountpoint: P) -> Result<(), SysctlError> {
+        let dev = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/fuse")
+            .map_err(SysctlError::IoError)?;
+        mount(&dev, mountpoint.as_ref(), self.read_only)?;
+        Server::new(dev, self.read_only).serve()
+    }
+}
+
+/// Unmount the filesystem at `mountpoint`, e.g. from another thread to
+/// make `SysctlFs::mount()` return.
+pub fn unmount<P: AsRef<Path>>(mountpoint: P) -> Result<(), SysctlError> {
+    let path = c_path(mountpoint.as_ref())?;
+    #[cfg(target_os = "linux")]
+    let ret = unsafe { libc::umount2(path.as_ptr(), 0) };
+    #[cfg(target_os = "freebsd")]
+    let ret = unsafe { libc::unmount(path.as_ptr(), 0) };
+    if ret != 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn c_path(path: &Path) -> Result<CString, SysctlError> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| SysctlError::IoError(io::Error::new(io::ErrorKind::InvalidInput, e)))
+}
+
+#[cfg(target_os = "linux")]
+fn mount(dev: &File, mountpoint: &Path, read_only: bool) -> Result<(), SysctlError> {
+    let target = c_path(mountpoint)?;
+    let options = format!(
+        "fd={},rootmode=40000,user_id={},group_id={},default_permissions,allow_other",
+        dev.as_raw_fd(),
+        unsafe { libc::getuid() },
+        unsafe { libc::getgid() }
+    );
+    let options = CString::new(options).expect("no NUL in mount options");
+    let mut flags = libc::MS_NOSUID | libc::MS_NODEV;
+    if read_only {
+        flags |= libc::MS_RDONLY;
+    }
+    let ret = unsafe {
+        libc::mount(
+            b"sysctl\0".as_ptr() as *const _,
+            target.as_ptr(),
+            b"fuse.sysctl\0".as_ptr() as *const _,
+            flags,
+            options.as_ptr() as *const c_void,
+        )
+    };
+    if ret != 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "freebsd")]
+fn mount(dev: &File, mountpoint: &Path, read_only: bool) -> Result<(), SysctlError> {
+    let target = c_path(mountpoint)?;
+    let fd = CString::new(dev.as_raw_fd().to_string()).expect("no NUL in a number");
+    let mut iov = vec![];
+    option(&mut iov, b"fstype\0", b"fusefs\0");
+    option(&mut iov, b"fspath\0", target.as_bytes_with_nul());
+    option(&mut iov, b"from\0", b"/dev/fuse\0");
+    option(&mut iov, b"fd\0", fd.as_bytes_with_nul());
+    option(&mut iov, b"default_permissions\0", b"");
+    option(&mut iov, b"allow_other\0", b"");
+    let mut flags = libc::MNT_NOSUID;
+    if read_only {
+        flags |= libc::MNT_RDONLY;
+    }
+    let ret = unsafe { libc::nmount(iov.as_mut_ptr(), iov.len() as libc::c_uint, flags) };
+    if ret != 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+// Add a mount option, flag options have an empty value.
+#[cfg(target_os = "freebsd")]
+fn option(iov: &mut Vec<libc::iovec>, name: &[u8], value: &[u8]) {
+    iov.push(libc::iovec {
+        iov_base: name.as_ptr() as *mut c_void,
+        iov_len: name.len(),
+    });
+    iov.push(libc::iovec {
+        iov_base: if value.is_empty() {
+            ::std::ptr::null_mut()
+        } else {
+            value.as_ptr() as *mut c_void
+        },
+        iov_len: value.len(),
+    });
+}
+
+// The header of every request.
+struct InHeader {
+    opcode: u32,
+    unique: u64,
+    nodeid: u64,
+}
+
+// A directory entry, with the file type of its mode.
+struct DirEntry {
+    ino: u64,
+    kind: u32,
+    name: String,
+}
+
+struct Server {
+    dev: File,
+    read_only: bool,
+    // The minor protocol version agreed on
+    minor: u32,
+    // The OID of every inode by its number less one, the root has none
+    oids: Vec<Vec<c_int>>,
+    inodes: HashMap<Vec<c_int>, u64>,
+    // The contents of open files and directories by handle, read when
+    // they are opened so that reads at any offset are consistent
+    files: HashMap<u64, Vec<u8>>,
+    dirs: HashMap<u64, Vec<DirEntry>>,
+    next_handle: u64,
+    mounted: u64,
+}
+
+impl Server {
+    fn new(dev: File, read_only: bool) -> Self {
+        let mut inodes = HashMap::new();
+        inodes.insert(vec![], ROOT_INODE);
+        Server {
+            dev,
+            read_only,
+            minor: 0,
+            oids: vec![vec![]],
+            inodes,
+            files: HashMap::new(),
+            dirs: HashMap::new(),
+            next_handle: 0,
+            mounted: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    fn serve(&mut self) -> Result<(), SysctlError> {
+        let mut buf = vec![0; MAX_WRITE as usize + 4096];
+        loop {
+            let n = match (&self.dev).read(&mut buf) {
+                Ok(n) => n,
+                // Unmounted
+                Err(ref e) if e.raw_os_error() == Some(libc::ENODEV) => return Ok(()),
+                // The request was interrupted before it was read
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(SysctlError::IoError(e)),
+            };
+            if n < IN_HEADER_LEN {
+                return Err(SysctlError::ShortRead {
+                    read: n,
+                    reported: IN_HEADER_LEN,
+                });
+            }
+            let header = InHeader {
+                opcode: NativeEndian::read_u32(&buf[4..8]),
+                unique: NativeEndian::read_u64(&buf[8..16]),
+                nodeid: NativeEndian::read_u64(&buf[16..24]),
+            };
+            let reply = match self.handle(&header, &buf[IN_HEADER_LEN..n]) {
+                Some(reply) => reply,
+                None => continue,
+            };
+            self.reply(header.unique, reply)?;
+            if header.opcode == FUSE_DESTROY {
+                return Ok(());
+            }
+        }
+    }
+
+    fn reply(&self, unique: u64, reply: Result<Vec<u8>, c_int>) -> Result<(), SysctlError> {
+        let (error, body) = match reply {
+            Ok(body) => (0, body),
+            Err(errno) => (-errno, vec![]),
+        };
+        let mut out = Vec::with_capacity(OUT_HEADER_LEN + body.len());
+        out.write_u32::<NativeEndian>((OUT_HEADER_LEN + body.len()) as u32)
+            .and_then(|_| out.write_i32::<NativeEndian>(error))
+            .and_then(|_| out.write_u64::<NativeEndian>(unique))
+            .map_err(SysctlError::IoError)?;
+        out.extend(body);
+        match (&self.dev).write(&out) {
+            Ok(_) => Ok(()),
+            // The request was interrupted meanwhile
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+            Err(e) => Err(SysctlError::IoError(e)),
+        }
+    }
+
+    // The reply to a request, None for those that get none.
+    fn handle(&mut self, header: &InHeader, body: &[u8]) -> Option<Result<Vec<u8>, c_int>> {
+        let ino = header.nodeid;
+        let reply = match header.opcode {
+            FUSE_INIT => self.init(body),
+            FUSE_LOOKUP => self.lookup(ino, body),
+            FUSE_GETATTR | FUSE_SETATTR => self.attr(ino).map(|attr| self.attr_out(&attr)),
+            FUSE_OPEN => self.open(ino, body),
+            FUSE_READ => self.read(body),
+            FUSE_WRITE => self.write(ino, body),
+            FUSE_RELEASE => {
+                self.files.remove(&read_u64(body, 0));
+                Ok(vec![])
+            }
+            FUSE_OPENDIR => self.opendir(ino),
+            FUSE_READDIR => self.readdir(body),
+            FUSE_RELEASEDIR => {
+                self.dirs.remove(&read_u64(body, 0));
+                Ok(vec![])
+            }
+            FUSE_STATFS => Ok(statfs()),
+            FUSE_FLUSH | FUSE_ACCESS | FUSE_DESTROY => Ok(vec![]),
+            FUSE_FORGET | FUSE_BATCH_FORGET | FUSE_INTERRUPT => return None,
+            _ => Err(libc::ENOSYS),
+        };
+        Some(reply)
+    }
+
+    fn init(&mut self, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        if body.len() < 8 {
+            return Err(libc::EINVAL);
+        }
+        let major = NativeEndian::read_u32(&body[0..4]);
+        let minor = NativeEndian::read_u32(&body[4..8]);
+        let mut out = vec![];
+        // A newer kernel sends INIT again with the version replied
+        if major > FUSE_KERNEL_VERSION {
+            out.write_u32::<NativeEndian>(FUSE_KERNEL_VERSION).unwrap();
+            out.write_u32::<NativeEndian>(FUSE_KERNEL_MINOR_VERSION)
+                .unwrap();
+            return Ok(out);
+        }
+        if major < FUSE_KERNEL_VERSION || minor < 8 {
+            return Err(libc::EPROTO);
+        }
+        self.minor = minor.min(FUSE_KERNEL_MINOR_VERSION);
+        let max_readahead = read_u32(body, 8);
+        for v in &[FUSE_KERNEL_VERSION, self.minor, max_readahead, 0] {
+            out.write_u32::<NativeEndian>(*v).unwrap();
+        }
+        // max_background, congestion_threshold, max_write
+        out.write_u16::<NativeEndian>(0).unwrap();
+        out.write_u16::<NativeEndian>(0).unwrap();
+        out.write_u32::<NativeEndian>(MAX_WRITE).unwrap();
+        if self.minor >= 23 {
+            // time_gran, max_pages, map_alignment, flags2 and unused
+            out.write_u32::<NativeEndian>(1).unwrap();
+            out.resize(64, 0);
+        }
+        Ok(out)
+    }
+
+    fn lookup(&mut self, parent: u64, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        let name = body.split(|&b| b == 0).next().unwrap_or(&[]);
+        let name = str::from_utf8(name).map_err(|_| libc::ENOENT)?;
+        let parent = self.oid(parent)?.clone();
+        let full = if parent.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}.{}", oid2name(&parent).map_err(errno)?, name)
+        };
+        // Dots would name a sysctl further down
+        let oid = name2oid(&full).map_err(|_| libc::ENOENT)?;
+        if name.contains('.') || oid.len() != parent.len() + 1 || !oid.starts_with(&parent) {
+            return Err(libc::ENOENT);
+        }
+        let ino = self.inode(oid);
+        let attr = self.attr(ino)?;
+
+        let mut out = vec![];
+        for v in &[ino, 0, TTL, TTL] {
+            out.write_u64::<NativeEndian>(*v).unwrap();
+        }
+        out.write_u32::<NativeEndian>(0).unwrap();
+        out.write_u32::<NativeEndian>(0).unwrap();
+        out.extend(self.attr_bytes(&attr));
+        Ok(out)
+    }
+
+    fn open(&mut self, ino: u64, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        let attr = self.attr(ino)?;
+        if attr.dir {
+            return Err(libc::EISDIR);
+        }
+        let flags = read_u32(body, 0) as c_int;
+        let writing = flags & libc::O_ACCMODE != libc::O_RDONLY;
+        if writing && self.read_only {
+            return Err(libc::EROFS);
+        }
+        let content = if flags & libc::O_ACCMODE == libc::O_WRONLY {
+            vec![]
+        } else {
+            let ctl = Ctl::from_oid(self.oid(ino)?.clone());
+            match ctl.value().map_err(errno)? {
+                CtlValue::Node(b) | CtlValue::Struct(b) => b,
+                v => format!("{}\n", v).into_bytes(),
+            }
+        };
+        let fh = self.new_handle();
+        self.files.insert(fh, content);
+
+        let mut out = vec![];
+        out.write_u64::<NativeEndian>(fh).unwrap();
+        out.write_u32::<NativeEndian>(FOPEN_DIRECT_IO).unwrap();
+        out.write_u32::<NativeEndian>(0).unwrap();
+        Ok(out)
+    }
+
+    fn read(&self, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        let (fh, offset, size) = (read_u64(body, 0), read_u64(body, 8), read_u32(body, 16));
+        let content = self.files.get(&fh).ok_or(libc::EBADF)?;
+        let start = (offset as usize).min(content.len());
+        let end = start.saturating_add(size as usize).min(content.len());
+        Ok(content[start..end].to_vec())
+    }
+
+    fn write(&mut self, ino: u64, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        if self.read_only {
+            return Err(libc::EROFS);
+        }
+        let size = read_u32(body, 16);
+        // The data follows fuse_write_in, which was shorter before 7.9
+        let start = if self.minor < 9 { 24 } else { 40 };
+        let data = body.get(start..start + size as usize).ok_or(libc::EINVAL)?;
+        let text = str::from_utf8(data).map_err(|_| libc::EINVAL)?;
+
+        let ctl = Ctl::from_oid(self.oid(ino)?.clone());
+        let value =
+            value_from_str(ctl.value_type().map_err(errno)?, text).map_err(|_| libc::EINVAL)?;
+        ctl.set_value(value).map_err(errno)?;
+
+        let mut out = vec![];
+        out.write_u32::<NativeEndian>(size).unwrap();
+        out.write_u32::<NativeEndian>(0).unwrap();
+        Ok(out)
+    }
+
+    fn opendir(&mut self, ino: u64) -> Result<Vec<u8>, c_int> {
+        let parent = self.oid(ino)?.clone();
+        let mut entries = vec![
+            DirEntry {
+                ino,
+                kind: libc::DT_DIR as u32,
+                name: ".".to_owned(),
+            },
+            DirEntry {
+                // The kernel knows the parent, the number is not used
+                ino: ROOT_INODE,
+                kind: libc::DT_DIR as u32,
+                name: "..".to_owned(),
+            },
+        ];
+        for oid in children(&parent).map_err(errno)? {
+            let name = match oid2name(&oid) {
+                Ok(name) => name.rsplit('.').next().unwrap_or_default().to_owned(),
+                Err(_) => continue,
+            };
+            if name.is_empty() || name.contains('/') {
+                continue;
+            }
+            let ino = self.inode(oid);
+            let kind = match self.attr(ino) {
+                Ok(ref attr) if attr.dir => libc::DT_DIR,
+                Ok(_) => libc::DT_REG,
+                Err(_) => continue,
+            };
+            entries.push(DirEntry {
+                ino,
+                kind: kind as u32,
+                name,
+            });
+        }
+        let fh = self.new_handle();
+        self.dirs.insert(fh, entries);
+
+        let mut out = vec![];
+        out.write_u64::<NativeEndian>(fh).unwrap();
+        out.write_u64::<NativeEndian>(0).unwrap();
+        Ok(out)
+    }
+
+    fn readdir(&self, body: &[u8]) -> Result<Vec<u8>, c_int> {
+        let (fh, offset, size) = (read_u64(body, 0), read_u64(body, 8), read_u32(body, 16));
+        let entries = self.dirs.get(&fh).ok_or(libc::EBADF)?;
+        let mut out = vec![];
+        for (i, entry) in entries.iter().enumerate().skip(offset as usize) {
+            let len = (24 + entry.name.len() + 7) & !7;
+            if out.len() + len > size as usize {
+                break;
+            }
+            // The offset is that of the next entry
+            out.write_u64::<NativeEndian>(entry.ino).unwrap();
+            out.write_u64::<NativeEndian>(i as u64 + 1).unwrap();
+            out.write_u32::<NativeEndian>(entry.name.len() as u32)
+                .unwrap();
+            out.write_u32::<NativeEndian>(entry.kind).unwrap();
+            out.extend(entry.name.as_bytes());
+            let padded = out.len() + (8 - out.len() % 8) % 8;
+            out.resize(padded, 0);
+        }
+        Ok(out)
+    }
+
+    fn new_handle(&mut self) -> u64 {
+        self.next_handle += 1;
+        self.next_handle
+    }
+
+    fn oid(&self, ino: u64) -> Result<&Vec<c_int>, c_int> {
+        self.oids
+            .get((ino as usize).wrapping_sub(1))
+            .ok_or(libc::ENOENT)
+    }
+
+    // The inode of the OID, numbered on first sight
+    fn inode(&mut self, oid: Vec<c_int>) -> u64 {
+        if let Some(&ino) = self.inodes.get(&oid) {
+            return ino;
+        }
+        self.oids.push(oid.clone());
+        let ino = self.oids.len() as u64;
+        self.inodes.insert(oid, ino);
+        ino
+    }
+
+    fn attr(&self, ino: u64) -> Result<Attr, c_int> {
+        let oid = self.oid(ino)?;
+        if oid.is_empty() {
+            return Ok(Attr {
+                ino,
+                dir: true,
+                writable: false,
+            });
+        }
+        let info = oidfmt(oid).map_err(errno)?;
+        Ok(Attr {
+            ino,
+            dir: info.ctl_type == CtlType::Node,
+            writable: info.flags & CTLFLAG_WR != 0 && !self.read_only,
+        })
+    }
+
+    fn attr_out(&self, attr: &Attr) -> Vec<u8> {
+        let mut out = vec![];
+        out.write_u64::<NativeEndian>(TTL).unwrap();
+        out.write_u32::<NativeEndian>(0).unwrap();
+        out.write_u32::<NativeEndian>(0).unwrap();
+        out.extend(self.attr_bytes(attr));
+        out
+    }
+
+    // A fuse_attr, without blksize and padding before 7.9
+    fn attr_bytes(&self, attr: &Attr) -> Vec<u8> {
+        let mode = match (attr.dir, attr.writable) {
+            (true, _) => S_IFDIR | 0o555,
+            (false, true) => S_IFREG | 0o644,
+            (false, false) => S_IFREG | 0o444,
+        };
+        let mut out = vec![];
+        // ino, size, blocks, atime, mtime, ctime
+        for v in &[attr.ino, 0, 0, self.mounted, self.mounted, self.mounted] {
+            out.write_u64::<NativeEndian>(*v).unwrap();
+        }
+        // atimensec, mtimensec, ctimensec, mode, nlink, uid, gid, rdev
+        let nlink = if attr.dir { 2 } else { 1 };
+        for v in &[0, 0, 0, mode, nlink, 0, 0, 0] {
+            out.write_u32::<NativeEndian>(*v).unwrap();
+        }
+        if self.minor >= 9 {
+            out.write_u32::<NativeEndian>(512).unwrap();
+            out.write_u32::<NativeEndian>(0).unwrap();
+        }
+        out
+    }
+}
+
+struct Attr {
+    ino: u64,
+    dir: bool,
+    writable: bool,
+}
+
+// A fuse_statfs_out of an empty filesystem.
+fn statfs() -> Vec<u8> {
+    let mut out = vec![0; 80];
+    // bsize and namelen
+    NativeEndian::write_u32(&mut out[40..44], 512);
+    NativeEndian::write_u32(&mut out[44..48], 255);
+    out
+}
+
+// The OIDs of the direct children of a node, the root if it is empty.
+fn children(node: &[c_int]) -> Result<Vec<Vec<c_int>>, SysctlError> {
+    // Start past the internal sysctl.* tree at the root, like CtlIter
+    let mut cursor = if node.is_empty() {
+        vec![0, c_int::MAX]
+    } else {
+        node.to_vec()
+    };
+    let mut children = vec![];
+    while let Some(next) = next_oid(&cursor)? {
+        if next.len() <= node.len() || !next.starts_with(node) {
+            break;
+        }
+        let child = next[..node.len() + 1].to_vec();
+        // The first OID after the subtree of the child
+        cursor = child.clone();
+        cursor.push(c_int::MAX);
+        children.push(child);
+    }
+    Ok(children)
+}
+
+// The errno to reply with for an error.
+fn errno(e: SysctlError) -> c_int {
+    match e {
+        SysctlError::IoError(ref e) => e.raw_os_error().unwrap_or(libc::EIO),
+        SysctlError::NoReadAccess | SysctlError::NoWriteAccess => libc::EACCES,
+        SysctlError::ParseError { .. } | SysctlError::Utf8Error(_) => libc::EINVAL,
+        _ => libc::EIO,
+    }
+}
+
+fn read_u32(body: &[u8], at: usize) -> u32 {
+    body.get(at..at + 4).map_or(0, NativeEndian::read_u32)
+}
+
+fn read_u64(body: &[u8], at: usize) -> u64 {
+    body.get(at..at + 8).map_or(0, NativeEndian::read_u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuse_init() {
+        let mut server = Server::new(File::open("/dev/null").unwrap(), false);
+        let mut body = vec![];
+        for v in &[7u32, 36, 65536, 0] {
+            body.write_u32::<NativeEndian>(*v).unwrap();
+        }
+        let out = server.init(&body).unwrap();
+        assert_eq!(out.len(), 64);
+        assert_eq!(read_u32(&out, 4), FUSE_KERNEL_MINOR_VERSION);
+        assert_eq!(read_u32(&out, 20), MAX_WRITE);
+
+        // Older kernels get the shorter reply and attributes
+        NativeEndian::write_u32(&mut body[4..8], 8);
+        assert_eq!(server.init(&body).unwrap().len(), 24);
+        let attr = Attr {
+            ino: ROOT_INODE,
+            dir: true,
+            writable: false,
+        };
+        assert_eq!(server.attr_bytes(&attr).len(), 80);
+        NativeEndian::write_u32(&mut body[4..8], 7);
+        assert_eq!(server.init(&body), Err(libc::EPROTO));
+    }
+
+    #[test]
+    fn fuse_readdir() {
+        let mut server = Server::new(File::open("/dev/null").unwrap(), false);
+        let names = ["a", "longer_name", "c"];
+        let entries = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| DirEntry {
+                ino: i as u64 + 2,
+                kind: libc::DT_REG as u32,
+                name: name.to_string(),
+            })
+            .collect();
+        server.dirs.insert(1, entries);
+
+        let request = |offset: u64, size: u32| {
+            let mut body = vec![];
+            body.write_u64::<NativeEndian>(1).unwrap();
+            body.write_u64::<NativeEndian>(offset).unwrap();
+            body.write_u32::<NativeEndian>(size).unwrap();
+            body
+        };
+        let out = server.readdir(&request(0, 4096)).unwrap();
+        // 24 bytes of header and the name, padded to 8 bytes
+        assert_eq!(out.len(), 32 + 40 + 32);
+        assert_eq!(read_u64(&out, 32), 3);
+        assert_eq!(read_u64(&out, 40), 2);
+        assert_eq!(&out[56..67], b"longer_name");
+        // Only what fits, and from an offset
+        assert_eq!(server.readdir(&request(0, 40)).unwrap().len(), 32);
+        let rest = server.readdir(&request(2, 4096)).unwrap();
+        assert_eq!(read_u64(&rest, 0), 4);
+        assert!(server.readdir(&request(3, 4096)).unwrap().is_empty());
+    }
+}
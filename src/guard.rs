@@ -0,0 +1,103 @@
+//! Temporarily changing a sysctl.
+//!
+//! `Ctl::set_scoped()` sets a new value and returns a `CtlGuard` that
+//! writes the previous value back when it goes out of scope, e.g. at the
+//! end of a test or benchmark, also when it panics.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! use sysctl::{Ctl, CtlValue};
+//!
+//! fn main() {
+//!     let ctl = Ctl::new("kern.ipc.somaxconn").unwrap();
+//!     {
+//!         let _guard = ctl.set_scoped(CtlValue::Int(8192)).unwrap();
+//!         // run the benchmark
+//!     }
+//!     // the old value is back
+//! }
+//! ```
+
+use super::{Ctl, CtlValue, SysctlError};
+
+/// Restores the previous value of a sysctl when dropped, returned by
+/// `Ctl::set_scoped()`.
+#[derive(Debug)]
+#[must_use = "the previous value is restored when the guard is dropped"]
+pub struct CtlGuard {
+    ctl: Ctl,
+    // None once restored or committed
+    old: Option<CtlValue>,
+}
+
+impl CtlGuard {
+    /// The value that will be restored.
+    pub fn old_value(&self) -> Option<&CtlValue> {
+        self.old.as_ref()
+    }
+
+    /// Keep the new value instead of restoring the previous one.
+    pub fn commit(mut self) {
+        self.old = None;
+    }
+
+    /// Restore the previous value now, returning the value after
+    /// restoring it. Unlike dropping the guard this reports failures.
+    pub fn restore(mut self) -> Result<CtlValue, SysctlError> {
+        match self.old.take() {
+            Some(old) => self.ctl.set_value(old),
+            None => self.ctl.value(),
+        }
+    }
+}
+
+impl Drop for CtlGuard {
+    fn drop(&mut self) {
+        if let Some(old) = self.old.take() {
+            // Nothing sensible to do about a failure here
+            let _ = self.ctl.set_value(old);
+        }
+    }
+}
+
+impl Ctl {
+    /// Set the value until the returned guard is dropped, see the `guard`
+    /// module.
+    ///
+    /// Fails, without changing anything, if the current value can not be
+    /// read, and if the new value can not be set.
+    pub fn set_scoped(&self, value: CtlValue) -> Result<CtlGuard, SysctlError> {
+        let old = self.value()?;
+        self.set_value(value)?;
+        Ok(CtlGuard {
+            ctl: self.clone(),
+            old: Some(old),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_commit() {
+        let guard = CtlGuard {
+            ctl: Ctl::from_oid(vec![]),
+            old: Some(CtlValue::Int(1)),
+        };
+        assert_eq!(guard.old_value(), Some(&CtlValue::Int(1)));
+        // Does not try to restore the value
+        guard.commit();
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn guard_read_only() {
+        let ctl = Ctl::new("kern.ostype").unwrap();
+        let old = ctl.value().unwrap();
+        assert!(ctl.set_scoped(CtlValue::String("x".into())).is_err());
+        assert_eq!(ctl.value().unwrap(), old);
+    }
+}
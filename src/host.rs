@@ -0,0 +1,74 @@
+//! Host and domain name of the system.
+//!
+//! The sysctls holding the names differ between platforms, these helpers
+//! pick the right one.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     println!("hostname: {}", sysctl::hostname().expect("could not read hostname"));
+//! }
+//! ```
+
+use super::{set_value, value, CtlValue, SysctlError};
+
+#[cfg(target_os = "linux")]
+const HOSTNAME: &str = "kernel.hostname";
+#[cfg(not(target_os = "linux"))]
+const HOSTNAME: &str = "kern.hostname";
+
+#[cfg(target_os = "linux")]
+const DOMAINNAME: &str = "kernel.domainname";
+#[cfg(target_os = "macos")]
+const DOMAINNAME: &str = "kern.nisdomainname";
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const DOMAINNAME: &str = "kern.domainname";
+
+/// Return the hostname.
+pub fn hostname() -> Result<String, SysctlError> {
+    get(HOSTNAME)
+}
+
+/// Set the hostname, returning the new value. Requires root privileges.
+pub fn set_hostname(name: &str) -> Result<String, SysctlError> {
+    set(HOSTNAME, name)
+}
+
+/// Return the NIS domain name, which is empty unless configured.
+pub fn domainname() -> Result<String, SysctlError> {
+    get(DOMAINNAME)
+}
+
+/// Set the NIS domain name, returning the new value. Requires root
+/// privileges.
+pub fn set_domainname(name: &str) -> Result<String, SysctlError> {
+    set(DOMAINNAME, name)
+}
+
+fn get(name: &str) -> Result<String, SysctlError> {
+    match value(name)? {
+        CtlValue::String(s) => Ok(s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+fn set(name: &str, new: &str) -> Result<String, SysctlError> {
+    match set_value(name, CtlValue::String(new.to_owned()))? {
+        CtlValue::String(s) => Ok(s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_hostname() {
+        let name = hostname().expect("could not read hostname");
+        assert!(!name.is_empty());
+        assert!(domainname().is_ok());
+    }
+}
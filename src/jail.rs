@@ -0,0 +1,444 @@
+//! Jails: the parameters jail(8) accepts, the sysctls that can be changed
+//! from within a jail, and applying sysctl settings within running jails.
+//! Only available on FreeBSD.
+//!
+//! Host agents of container platforms keep the settings of every jail in
+//! their configuration. `apply()` takes a map from jails to settings and
+//! writes every set from within its jail, as the kernel keeps some values
+//! per jail, e.g. those of a virtual network stack. It has to run as root
+//! on the host. The outcome of every setting is collected in a report per
+//! jail.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::jail;
+//!
+//! fn main() {
+//!     if jail::jailed().expect("could not read security.jail.jailed") {
+//!         for name in jail::writable_in_jail().expect("could not walk tree") {
+//!             println!("{}", name);
+//!         }
+//!     }
+//!     for p in jail::params().expect("could not list jail parameters") {
+//!         println!("{} ({:?})", p.name, p.param_type);
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::FromRawFd;
+use std::ptr;
+
+use libc::{c_int, c_uint, c_void};
+
+use super::{value, Ctl, CtlInfo, CtlIter, CtlType, CtlValue, SysctlError};
+
+/// The type of a jail parameter, from the type and format of its
+/// `security.jail.param` sysctl.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JailParamType {
+    Int,
+    Uint,
+    Long,
+    Ulong,
+    /// A boolean, set with `name` or `noname`.
+    Bool,
+    /// `new`, `inherit` or `disable`, e.g. for `host`.
+    JailSys,
+    String,
+    /// A list of IPv4 addresses.
+    Ipv4,
+    /// A list of IPv6 addresses.
+    Ipv6,
+    /// A type libjail does not know either.
+    Other(CtlType),
+}
+
+/// A parameter for jail(8) and jail_set(2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct JailParam {
+    /// The name of the parameter, e.g. `host.hostname`.
+    pub name: String,
+    pub param_type: JailParamType,
+    /// `None` where the kernel has none.
+    pub description: Option<String>,
+}
+
+/// Return whether the process runs inside a jail, from
+/// `security.jail.jailed`.
+pub fn jailed() -> Result<bool, SysctlError> {
+    let jailed = value("security.jail.jailed")?;
+    Ok(jailed.as_int().ok_or(SysctlError::ExtractionError)? != 0)
+}
+
+/// Return the parameters the kernel accepts for jails, in the order of
+/// the tree.
+pub fn params() -> Result<Vec<JailParam>, SysctlError> {
+    let mut params = vec![];
+
+    for ctl in Ctl::new("security.jail.param")? {
+        let ctl = ctl?;
+        let name = match param_name(&ctl.name()?) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let info = ctl.info()?;
+        params.push(JailParam {
+            name,
+            param_type: param_type(&info),
+            description: ctl.description().ok().filter(|d| !d.is_empty()),
+        });
+    }
+
+    Ok(params)
+}
+
+/// Return the names of the sysctls root may set from within a jail,
+/// i.e. the writable ones with `CTLFLAG_PRISON`. See `Ctl::can_write()`
+/// for whether the current process may set a sysctl.
+pub fn writable_in_jail() -> Result<Vec<String>, SysctlError> {
+    let mut names = vec![];
+
+    for ctl in CtlIter::root() {
+        let ctl = ctl?;
+        let info = ctl.info()?;
+        if info.is_writable() && info.is_prison() {
+            names.push(ctl.name()?);
+        }
+    }
+
+    Ok(names)
+}
+
+// The parameter of a name like security.jail.param.host.hostname.
+// Parameters that have sub-parameters too, like ip4, end in a dot.
+fn param_name(name: &str) -> Option<&str> {
+    let param = name.strip_prefix("security.jail.param.")?;
+    let param = param.strip_suffix('.').unwrap_or(param);
+    if param.is_empty() {
+        None
+    } else {
+        Some(param)
+    }
+}
+
+// The type as libjail determines it, see jailparam_type() in jail.c.
+fn param_type(info: &CtlInfo) -> JailParamType {
+    match (info.ctl_type(), info.fmt()) {
+        (CtlType::Int, "B") => JailParamType::Bool,
+        (CtlType::Int, "E") => JailParamType::JailSys,
+        (CtlType::Int, "IU") | (CtlType::Uint, _) => JailParamType::Uint,
+        (CtlType::Int, _) => JailParamType::Int,
+        (CtlType::Long, _) => JailParamType::Long,
+        (CtlType::Ulong, _) => JailParamType::Ulong,
+        (CtlType::String, _) => JailParamType::String,
+        (CtlType::Struct, "S,in_addr") => JailParamType::Ipv4,
+        (CtlType::Struct, "S,in6_addr") => JailParamType::Ipv6,
+        (t, _) => JailParamType::Other(t),
+    }
+}
+
+/// The outcome of applying the settings of one jail, see `apply()`.
+#[derive(Debug)]
+pub struct JailReport {
+    /// The name or JID of the jail, as given.
+    pub jail: String,
+    /// Why none of the settings were applied, e.g. because the jail does
+    /// not exist.
+    pub error: Option<SysctlError>,
+    /// The outcome of every setting, in the order given. Empty if `error`
+    /// is set.
+    pub settings: Vec<(String, Result<(), SysctlError>)>,
+}
+
+impl JailReport {
+    /// Whether every setting was applied.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.settings.iter().all(|(_, r)| r.is_ok())
+    }
+}
+
+/// Write the settings of every jail, given by name or JID, from within
+/// that jail. Returns a report per jail, in the order of the map.
+///
+/// Values must have the type of their sysctl, as checked on the host.
+/// The settings of a jail are written by a child process attached to the
+/// jail, which needs root privileges on the host.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::collections::BTreeMap;
+/// use sysctl::{jail, CtlValue};
+///
+/// fn main() {
+///     let mut settings = BTreeMap::new();
+///     settings.insert(
+///         "www".to_owned(),
+///         vec![("net.inet.tcp.msl".to_owned(), CtlValue::Int(10000))],
+///     );
+///     for report in jail::apply(&settings) {
+///         if let Some(ref e) = report.error {
+///             println!("{}: {}", report.jail, e);
+///         }
+///         for (name, result) in &report.settings {
+///             println!("{}: {} {:?}", report.jail, name, result);
+///         }
+///     }
+/// }
+/// ```
+pub fn apply(jails: &BTreeMap<String, Vec<(String, CtlValue)>>) -> Vec<JailReport> {
+    jails
+        .iter()
+        .map(|(jail, settings)| apply_in(jail, settings))
+        .collect()
+}
+
+fn apply_in(jail: &str, settings: &[(String, CtlValue)]) -> JailReport {
+    let mut report = JailReport {
+        jail: jail.to_owned(),
+        error: None,
+        settings: vec![],
+    };
+
+    // Everything that allocates happens before forking
+    let encoded: Vec<Result<(CString, Vec<u8>), SysctlError>> = settings
+        .iter()
+        .map(|(name, value)| encode(name, value))
+        .collect();
+    let writes: Vec<&(CString, Vec<u8>)> = encoded.iter().filter_map(|e| e.as_ref().ok()).collect();
+    let errnos = match jid(jail).and_then(|jid| write_in(jid, &writes)) {
+        Ok(errnos) => errnos,
+        Err(e) => {
+            report.error = Some(e);
+            return report;
+        }
+    };
+
+    let mut errnos = errnos.into_iter();
+    report.settings = settings
+        .iter()
+        .zip(encoded)
+        .map(|((name, _), encoded)| {
+            let result = encoded.and_then(|_| match errnos.next() {
+                Some(0) => Ok(()),
+                Some(errno) => Err(SysctlError::IoError(io::Error::from_raw_os_error(errno))),
+                None => Err(SysctlError::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the process writing within the jail exited early",
+                ))),
+            });
+            (name.clone(), result)
+        })
+        .collect();
+    report
+}
+
+// The name and value of a setting as sysctlbyname(3) takes them.
+fn encode(name: &str, value: &CtlValue) -> Result<(CString, Vec<u8>), SysctlError> {
+    let ctl_type = Ctl::new(name)?.value_type()?;
+    if ctl_type != CtlType::from(value) {
+        return Err(SysctlError::InvalidValue(format!(
+            "{} is {:?}, not {:?}",
+            name,
+            ctl_type,
+            CtlType::from(value)
+        )));
+    }
+    let bytes = match *value {
+        CtlValue::Int(v) | CtlValue::S32(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::Uint(v) | CtlValue::U32(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::Long(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::Ulong(v) | CtlValue::U64(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::S64(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::U8(v) => vec![v],
+        CtlValue::U16(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::S8(v) => v.to_ne_bytes().to_vec(),
+        CtlValue::S16(v) => v.to_ne_bytes().to_vec(),
+        // The kernel terminates the string itself
+        CtlValue::String(ref s) => s.as_bytes().to_vec(),
+        _ => return Err(SysctlError::UnknownType),
+    };
+    let name = CString::new(name).map_err(|e| SysctlError::IoError(e.into()))?;
+    Ok((name, bytes))
+}
+
+// The JID of a jail given by name or JID.
+fn jid(jail: &str) -> Result<c_int, SysctlError> {
+    if let Ok(jid) = jail.parse::<c_int>() {
+        return Ok(jid);
+    }
+    let mut key = b"name\0".to_vec();
+    let mut name = CString::new(jail)
+        .map_err(|e| SysctlError::IoError(e.into()))?
+        .into_bytes_with_nul();
+    let mut iov = [
+        libc::iovec {
+            iov_base: key.as_mut_ptr() as *mut c_void,
+            iov_len: key.len(),
+        },
+        libc::iovec {
+            iov_base: name.as_mut_ptr() as *mut c_void,
+            iov_len: name.len(),
+        },
+    ];
+    let jid = unsafe { libc::jail_get(iov.as_mut_ptr(), iov.len() as c_uint, 0) };
+    if jid < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    Ok(jid)
+}
+
+// Write the settings from a child process attached to the jail. Returns
+// the errno of every write, 0 for success, in order. The child reports
+// whether it could attach, then the writes, over a pipe.
+fn write_in(jid: c_int, writes: &[&(CString, Vec<u8>)]) -> Result<Vec<c_int>, SysctlError> {
+    let mut fds = [0 as c_int; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    let (rd, wr) = (fds[0], fds[1]);
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        let e = io::Error::last_os_error();
+        unsafe {
+            libc::close(rd);
+            libc::close(wr);
+        }
+        return Err(SysctlError::IoError(e));
+    }
+    if pid == 0 {
+        // Only async-signal-safe calls from here on
+        unsafe {
+            libc::close(rd);
+            let attached = libc::jail_attach(jid);
+            send(wr, if attached == 0 { 0 } else { errno() });
+            if attached == 0 {
+                for write in writes {
+                    let ret = libc::sysctlbyname(
+                        write.0.as_ptr(),
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                        write.1.as_ptr() as *const c_void,
+                        write.1.len(),
+                    );
+                    send(wr, if ret == 0 { 0 } else { errno() });
+                }
+            }
+            libc::_exit(0);
+        }
+    }
+
+    unsafe { libc::close(wr) };
+    let mut report = vec![];
+    let read = unsafe { File::from_raw_fd(rd) }.read_to_end(&mut report);
+    let mut status = 0;
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+    read.map_err(SysctlError::IoError)?;
+
+    let words: Vec<c_int> = report
+        .chunks_exact(mem::size_of::<c_int>())
+        .map(|w| c_int::from_ne_bytes([w[0], w[1], w[2], w[3]]))
+        .collect();
+    match words.split_first() {
+        Some((&0, errnos)) => Ok(errnos.to_vec()),
+        Some((&errno, _)) => Err(SysctlError::IoError(io::Error::from_raw_os_error(errno))),
+        None => Err(SysctlError::IoError(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the process attaching to the jail exited early",
+        ))),
+    }
+}
+
+// Write one word to the pipe, which does so atomically.
+unsafe fn send(fd: c_int, word: c_int) {
+    libc::write(
+        fd,
+        &word as *const c_int as *const c_void,
+        mem::size_of::<c_int>(),
+    );
+}
+
+fn errno() -> c_int {
+    io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{CTLFLAG_PRISON, CTLFLAG_RW};
+    use super::*;
+
+    #[test]
+    fn jail_param_name() {
+        assert_eq!(
+            param_name("security.jail.param.host.hostname"),
+            Some("host.hostname")
+        );
+        assert_eq!(param_name("security.jail.param.ip4."), Some("ip4"));
+        assert_eq!(param_name("security.jail.param."), None);
+        assert_eq!(param_name("security.jail.jailed"), None);
+    }
+
+    #[test]
+    fn jail_param_type() {
+        let t = |ctl_type, fmt| param_type(&CtlInfo::new(ctl_type, fmt, CTLFLAG_RW));
+        assert_eq!(t(CtlType::Int, "B"), JailParamType::Bool);
+        assert_eq!(t(CtlType::Int, "E"), JailParamType::JailSys);
+        assert_eq!(t(CtlType::Int, "IU"), JailParamType::Uint);
+        assert_eq!(t(CtlType::Int, "I"), JailParamType::Int);
+        assert_eq!(t(CtlType::String, "A"), JailParamType::String);
+        assert_eq!(t(CtlType::Struct, "S,in6_addr"), JailParamType::Ipv6);
+        assert_eq!(
+            t(CtlType::Struct, "S,foo"),
+            JailParamType::Other(CtlType::Struct)
+        );
+
+        assert!(CtlInfo::new(CtlType::Int, "I", CTLFLAG_RW | CTLFLAG_PRISON).is_prison());
+        assert!(!CtlInfo::new(CtlType::Int, "I", CTLFLAG_RW).is_prison());
+    }
+
+    #[test]
+    fn jail_live() {
+        let params = params().unwrap();
+        let hostname = params.iter().find(|p| p.name == "host.hostname").unwrap();
+        assert_eq!(hostname.param_type, JailParamType::String);
+        jailed().unwrap();
+        let writable = writable_in_jail().unwrap();
+        assert!(writable.contains(&"kern.hostname".to_owned()));
+    }
+
+    #[test]
+    fn jail_encode() {
+        let (name, bytes) = encode("kern.maxfiles", &CtlValue::Int(1000)).unwrap();
+        assert_eq!(name.as_bytes(), b"kern.maxfiles");
+        assert_eq!(bytes, 1000i32.to_ne_bytes().to_vec());
+        match encode("kern.maxfiles", &CtlValue::String("x".into())) {
+            Err(SysctlError::InvalidValue(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(encode("kern.nonexistent", &CtlValue::Int(1)).is_err());
+    }
+
+    #[test]
+    fn jail_apply() {
+        let mut settings = BTreeMap::new();
+        settings.insert(
+            "sysctl-rs-nonexistent".to_owned(),
+            vec![("kern.maxfiles".to_owned(), CtlValue::Int(1000))],
+        );
+        let reports = apply(&settings);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].jail, "sysctl-rs-nonexistent");
+        assert!(reports[0].error.is_some());
+        assert!(reports[0].settings.is_empty());
+        assert!(!reports[0].is_ok());
+    }
+}
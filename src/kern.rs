@@ -0,0 +1,327 @@
+//! Helpers for the `kern` part of the tree. Only available on FreeBSD.
+
+use super::{
+    decode_utf8, fetch_oid, is_strict, name2oid, set_value, value, Ctl, CtlValue, SysctlError,
+};
+
+// The number of states in kern.cp_time, see sys/resource.h
+const CPUSTATES: usize = 5;
+
+/// Return the contents of the kernel message buffer. Unlike dmesg(8),
+/// the `<pri>` syslog priority prefixes of messages are kept.
+///
+/// The buffer may grow while it is read, which is handled by retrying
+/// with a larger buffer. Invalid UTF-8 sequences are replaced, as the
+/// buffer may contain arbitrary bytes logged by drivers, unless in strict
+/// mode, see `set_strict()`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let dmesg = sysctl::kern::msgbuf().expect("could not read kern.msgbuf");
+///     for line in dmesg.lines().take(5) {
+///         println!("{}", line);
+///     }
+/// }
+/// ```
+pub fn msgbuf() -> Result<String, SysctlError> {
+    let oid = name2oid("kern.msgbuf")?;
+    let mut buf = fetch_oid(&oid, 0)?;
+
+    // Unused parts of the buffer are zeroed
+    buf.retain(|&b| b != 0);
+
+    decode_utf8(&buf)
+}
+
+/// Clear the kernel message buffer. Requires root privileges.
+pub fn msgbuf_clear() -> Result<(), SysctlError> {
+    set_value("kern.msgbuf_clear", CtlValue::Int(1)).map(|_| ())
+}
+
+/// Check whether the kernel advertises a feature in `kern.features`,
+/// like feature_present(3).
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     if sysctl::feature_present("inet6") {
+///         println!("IPv6 is available");
+///     }
+/// }
+/// ```
+pub fn feature_present(name: &str) -> bool {
+    match value(&format!("kern.features.{}", name)) {
+        Ok(CtlValue::Int(i)) => i != 0,
+        _ => false,
+    }
+}
+
+/// Return the names of all features advertised in `kern.features`.
+pub fn features() -> Result<Vec<String>, SysctlError> {
+    let mut features = vec![];
+
+    for ctl in Ctl::new("kern.features")? {
+        let ctl = ctl?;
+        if let CtlValue::Int(0) = ctl.value()? {
+            continue;
+        }
+        let name = ctl.name()?;
+        if let Some(feature) = name.strip_prefix("kern.features.") {
+            features.push(feature.to_owned());
+        }
+    }
+
+    Ok(features)
+}
+
+/// A timecounter available to the kernel, from `kern.timecounter.choice`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timecounter {
+    pub name: String,
+    /// Higher is better, negative values are never chosen automatically.
+    pub quality: i32,
+}
+
+/// Return the available timecounters, in the order the kernel lists them.
+pub fn timecounters() -> Result<Vec<Timecounter>, SysctlError> {
+    match value("kern.timecounter.choice")? {
+        CtlValue::String(s) => parse_timecounters(&s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+/// Return the name of the active timecounter.
+pub fn timecounter() -> Result<String, SysctlError> {
+    match value("kern.timecounter.hardware")? {
+        CtlValue::String(s) => Ok(s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+/// Switch to another timecounter, returning the name of the now active
+/// one. Requires root privileges.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let best = sysctl::kern::timecounters()
+///         .expect("could not read timecounters")
+///         .into_iter()
+///         .max_by_key(|tc| tc.quality)
+///         .expect("no timecounters");
+///     sysctl::kern::set_timecounter(&best.name).expect("could not switch timecounter");
+/// }
+/// ```
+pub fn set_timecounter(name: &str) -> Result<String, SysctlError> {
+    match set_value("kern.timecounter.hardware", CtlValue::String(name.to_owned()))? {
+        CtlValue::String(s) => Ok(s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+/// Time spent by a CPU in each state since boot, in ticks of the
+/// statistics clock (`kern.clockrate`), from `kern.cp_time` or
+/// `kern.cp_times`.
+///
+/// The difference of two samples, see `since()`, gives the usage in
+/// between.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// fn main() {
+///     let before = sysctl::kern::cp_time().expect("could not read kern.cp_time");
+///     thread::sleep(Duration::from_millis(100));
+///     let after = sysctl::kern::cp_time().expect("could not read kern.cp_time");
+///     let delta = after.since(&before).expect("counters went back");
+///     let busy = delta.total() - delta.idle;
+///     println!("{:.1}% busy", 100.0 * busy as f64 / delta.total().max(1) as f64);
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CpuTimes {
+    pub user: u64,
+    /// User time of processes with a positive nice value.
+    pub nice: u64,
+    pub system: u64,
+    pub interrupt: u64,
+    pub idle: u64,
+}
+
+impl CpuTimes {
+    /// The sum of all states.
+    pub fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.interrupt + self.idle
+    }
+
+    /// The ticks spent in each state since the earlier sample.
+    ///
+    /// The counters are not read atomically, so one may seem to go back.
+    /// It is counted as zero ticks, unless in strict mode, see
+    /// `set_strict()`, where this fails instead.
+    pub fn since(&self, earlier: &CpuTimes) -> Result<CpuTimes, SysctlError> {
+        self.delta(earlier, is_strict())
+    }
+
+    fn delta(&self, earlier: &CpuTimes, strict: bool) -> Result<CpuTimes, SysctlError> {
+        let sub = |state: &str, now: u64, then: u64| match now.checked_sub(then) {
+            Some(ticks) => Ok(ticks),
+            None if strict => Err(SysctlError::InvalidValue(format!(
+                "{} ticks went back from {} to {}",
+                state, then, now
+            ))),
+            None => Ok(0),
+        };
+        Ok(CpuTimes {
+            user: sub("user", self.user, earlier.user)?,
+            nice: sub("nice", self.nice, earlier.nice)?,
+            system: sub("system", self.system, earlier.system)?,
+            interrupt: sub("interrupt", self.interrupt, earlier.interrupt)?,
+            idle: sub("idle", self.idle, earlier.idle)?,
+        })
+    }
+}
+
+/// Return the CPU times summed over all CPUs, from `kern.cp_time`.
+pub fn cp_time() -> Result<CpuTimes, SysctlError> {
+    cpu_times(value("kern.cp_time")?)?
+        .into_iter()
+        .next()
+        .ok_or(SysctlError::ExtractionError)
+}
+
+/// Return the CPU times of every CPU, from `kern.cp_times`.
+pub fn cp_times() -> Result<Vec<CpuTimes>, SysctlError> {
+    cpu_times(value("kern.cp_times")?)
+}
+
+// Split an array of longs into CPUSTATES sized records.
+fn cpu_times(value: CtlValue) -> Result<Vec<CpuTimes>, SysctlError> {
+    let ticks: Vec<u64> = match value {
+        CtlValue::LongArray(a) => a.into_iter().map(|t| t as u64).collect(),
+        CtlValue::UlongArray(a) => a,
+        _ => return Err(SysctlError::ExtractionError),
+    };
+    if ticks.len() % CPUSTATES != 0 {
+        return Err(SysctlError::ExtractionError);
+    }
+
+    Ok(ticks
+        .chunks_exact(CPUSTATES)
+        .map(|t| CpuTimes {
+            user: t[0],
+            nice: t[1],
+            system: t[2],
+            interrupt: t[3],
+            idle: t[4],
+        })
+        .collect())
+}
+
+// Parse the "name(quality) name(quality) ..." format of
+// kern.timecounter.choice.
+fn parse_timecounters(choice: &str) -> Result<Vec<Timecounter>, SysctlError> {
+    choice
+        .split_whitespace()
+        .map(|tc| {
+            let invalid = || SysctlError::InvalidValue(format!("invalid timecounter {:?}", tc));
+            let open = tc.rfind('(').ok_or_else(invalid)?;
+            if !tc.ends_with(')') {
+                return Err(invalid());
+            }
+            Ok(Timecounter {
+                name: tc[..open].to_owned(),
+                quality: tc[open + 1..tc.len() - 1].parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kern_msgbuf() {
+        match msgbuf() {
+            Ok(s) => assert!(!s.contains('\0')),
+            // Reading the buffer may be restricted to root
+            Err(SysctlError::IoError(ref e)) if e.raw_os_error() == Some(libc::EPERM) => {}
+            Err(e) => panic!("could not read kern.msgbuf: {}", e),
+        }
+    }
+
+    #[test]
+    fn kern_features() {
+        let features = features().expect("could not read kern.features");
+        for feature in &features {
+            assert!(feature_present(feature));
+        }
+        assert!(!feature_present("nonexistent"));
+    }
+
+    #[test]
+    fn kern_parse_timecounters() {
+        let tcs = parse_timecounters("TSC-low(1000) ACPI-fast(900) i8254(0) dummy(-1000000)")
+            .unwrap();
+        assert_eq!(tcs.len(), 4);
+        assert_eq!(tcs[0].name, "TSC-low");
+        assert_eq!(tcs[0].quality, 1000);
+        assert_eq!(tcs[3].name, "dummy");
+        assert_eq!(tcs[3].quality, -1000000);
+
+        assert!(parse_timecounters("").unwrap().is_empty());
+        assert!(parse_timecounters("TSC").is_err());
+        assert!(parse_timecounters("TSC(high)").is_err());
+    }
+
+    #[test]
+    fn kern_cpu_times() {
+        let times = cpu_times(CtlValue::LongArray((1..=10).collect())).unwrap();
+        assert_eq!(times.len(), 2);
+        assert_eq!(
+            times[1],
+            CpuTimes {
+                user: 6,
+                nice: 7,
+                system: 8,
+                interrupt: 9,
+                idle: 10,
+            }
+        );
+        assert_eq!(times[1].total(), 40);
+        assert_eq!(times[1].delta(&times[0], false).unwrap().total(), 25);
+        // Counters going back count as zero, or fail in strict mode
+        assert_eq!(times[0].delta(&times[1], false).unwrap().total(), 0);
+        assert!(times[0].delta(&times[1], true).is_err());
+        assert!(times[1].delta(&times[0], true).is_ok());
+
+        assert!(cpu_times(CtlValue::LongArray(vec![1, 2, 3])).is_err());
+        assert!(cpu_times(CtlValue::Long(1)).is_err());
+    }
+
+    #[test]
+    fn kern_cp_times() {
+        let total = cp_time().expect("could not read kern.cp_time");
+        let per_cpu = cp_times().expect("could not read kern.cp_times");
+        assert!(!per_cpu.is_empty());
+        assert!(total.total() > 0);
+    }
+
+    #[test]
+    fn kern_timecounter() {
+        let active = timecounter().expect("could not read timecounter");
+        let tcs = timecounters().expect("could not read timecounters");
+        assert!(tcs.iter().any(|tc| tc.name == active));
+    }
+}
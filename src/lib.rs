@@ -37,6 +37,25 @@
 //!         println!("Value: {}", val);
 //!     }
 //! }
+//!
+//! #[cfg(target_os = "linux")]
+//! fn main() {
+//!     let ctl = sysctl::Ctl::new("kernel.pid_max")
+//!         .expect("could not get sysctl");
+//!
+//!     // descriptions come from the kernel documentation, if installed
+//!     let d = ctl.description()
+//!         .expect("could not get description");
+//!
+//!     println!("Description: {:?}", d);
+//!
+//!     let val_enum = ctl.value()
+//!         .expect("could not get value");
+//!
+//!     if let sysctl::CtlValue::Int(val) = val_enum {
+//!         println!("Value: {}", val);
+//!     }
+//! }
 //! ```
 //! # Example: Get value as struct
 //! ```
@@ -55,11 +74,17 @@
 //!     profhz: c_int, /* profiling clock frequency */
 //! }
 //!
+//! // Only integers, so any bytes are a valid ClockInfo
+//! unsafe impl sysctl::SysctlPod for ClockInfo {}
+//!
 //! fn main() {
 //!     println!("{:?}", sysctl::value_as::<ClockInfo>("kern.clockrate"));
 //! }
 //! ```
 
+// The impls failure_derive generates for SysctlError trip this lint
+#![allow(unknown_lints, non_local_definitions)]
+
 #[macro_use]
 extern crate bitflags;
 extern crate byteorder;
@@ -67,27 +92,145 @@ extern crate libc;
 
 #[macro_use]
 extern crate failure;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
 
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "topology")]
+extern crate roxmltree;
+
+#[cfg(not(target_os = "linux"))]
 use libc::sysctl;
+#[cfg(not(target_os = "linux"))]
 use libc::BUFSIZ;
-use libc::{c_int, c_uchar, c_uint, c_void};
+use libc::{c_int, c_long, c_uint, c_ulong};
+#[cfg(not(target_os = "linux"))]
+use libc::{c_uchar, c_void};
 
-use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
+use std::borrow::Cow;
 use std::cmp;
 use std::convert;
+use std::convert::TryFrom;
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 use std::f32;
 use std::io;
+use std::iter::FusedIterator;
 use std::mem;
 use std::ptr;
 use std::str;
 use std::str::FromStr;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+#[cfg(target_os = "freebsd")]
+pub mod acpi;
+pub mod audit;
+pub mod backend;
+pub mod baseline;
+pub mod batch;
+pub mod bundle;
+pub mod cache;
+pub mod codegen;
+pub mod conf;
+pub mod export;
+#[cfg(target_os = "freebsd")]
+pub mod dev;
+#[cfg(target_os = "freebsd")]
+pub mod file;
+#[cfg(all(feature = "fuse", any(target_os = "freebsd", target_os = "linux")))]
+pub mod fuse;
+pub mod guard;
+pub mod host;
+#[cfg(target_os = "freebsd")]
+pub mod jail;
+#[cfg(target_os = "freebsd")]
+pub mod kern;
+#[cfg(target_os = "linux")]
+pub mod linux;
+pub mod meta;
+#[cfg(target_os = "freebsd")]
+pub mod net;
+#[cfg(target_os = "netbsd")]
+pub mod netbsd;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+#[cfg(target_os = "openbsd")]
+pub mod openbsd;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod policy;
+pub mod prelude;
+#[cfg(all(target_os = "freebsd", target_pointer_width = "64"))]
+pub mod proc;
+pub mod protocol;
+pub mod query;
+pub mod schema;
+pub mod snapshot;
+#[cfg(not(target_os = "linux"))]
+pub mod system;
+#[cfg(feature = "topology")]
+pub mod topology;
+pub mod types;
+#[cfg(target_os = "freebsd")]
+pub mod vm;
+pub mod walk;
+pub mod watch;
+
+pub use cache::warm_cache;
+#[cfg(target_os = "freebsd")]
+pub use dev::cpu_temperatures;
+pub use host::{domainname, hostname, set_domainname, set_hostname};
+#[cfg(target_os = "freebsd")]
+pub use kern::feature_present;
+#[cfg(target_os = "netbsd")]
+pub use netbsd::oid2name;
+#[cfg(target_os = "netbsd")]
+use netbsd::{name2oid, oid2description, oidfmt};
+#[cfg(target_os = "openbsd")]
+pub use openbsd::oid2name;
+#[cfg(target_os = "openbsd")]
+use openbsd::{name2oid, oid2description, oidfmt};
+#[cfg(target_os = "linux")]
+pub use linux::oid2name;
+#[cfg(target_os = "linux")]
+use linux::{name2oid, oid2description, oidfmt};
+pub use query::all;
+pub use schema::schema;
+pub use snapshot::{snapshot, snapshot_below};
+#[cfg(not(target_os = "linux"))]
+pub use system::{boottime, loadavg, uptime, SystemInfo};
+#[cfg(target_os = "freebsd")]
+pub use vm::{swap_info, vm_stats, VmStats};
+
+/// Result type returned by the functions of this crate.
+pub type Result<T, E = SysctlError> = std::result::Result<T, E>;
 
 // CTL* constants belong to libc crate but have not been added there yet.
 // They will be removed from here once in the libc crate.
 pub const CTL_MAXNAME: c_uint = 24;
 
+// Meta-OIDs below CTL_SYSCTL describing the tree itself, see sys/sysctl.h.
+pub const CTL_SYSCTL: c_int = 0;
+pub const CTL_SYSCTL_DEBUG: c_int = 0; /* printf all nodes */
+pub const CTL_SYSCTL_NAME: c_int = 1; /* string name of OID */
+pub const CTL_SYSCTL_NEXT: c_int = 2; /* next OID, honoring CTLFLAG_SKIP */
+pub const CTL_SYSCTL_NAME2OID: c_int = 3; /* int array of name */
+pub const CTL_SYSCTL_OIDFMT: c_int = 4; /* OID's kind and format */
+pub const CTL_SYSCTL_OIDDESCR: c_int = 5; /* OID's description */
+pub const CTL_SYSCTL_OIDLABEL: c_int = 6; /* aggregation label */
+pub const CTL_SYSCTL_NEXTNOSKIP: c_int = 7; /* next OID, ignoring CTLFLAG_SKIP */
+
 pub const CTLTYPE: c_uint = 0xf; /* mask for the type */
 
 pub const CTLTYPE_NODE: c_uint = 1;
@@ -134,8 +277,21 @@ pub const CTLFLAG_SECURE3: c_uint = 136314880;
 pub const CTLMASK_SECURE: c_uint = 15728640;
 pub const CTLSHIFT_SECURE: c_uint = 20;
 
-/// Represents control flags of a sysctl
 bitflags! {
+    /// Represents control flags of a sysctl
+    ///
+    /// Formatting the flags lists their symbolic names like `sysctl -t`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::CtlFlags;
+    ///
+    /// let flags = CtlFlags::RWTUN | CtlFlags::MPSAFE;
+    /// assert!(flags.contains(CtlFlags::TUN));
+    /// assert_eq!(flags.to_string(), "RW, TUN, MPSAFE");
+    /// ```
     pub struct CtlFlags : c_uint {
         /// Allow reads of variable
         const RD = CTLFLAG_RD;
@@ -161,7 +317,7 @@ bitflags! {
         const DYN = CTLFLAG_DYN;
 
         /// Skip this sysctl when listing
-        const SKIP = CTLFLAG_DORMANT;
+        const SKIP = CTLFLAG_SKIP;
 
         /// Secure level
         const SECURE_MASK = 0x00F00000;
@@ -201,12 +357,49 @@ bitflags! {
     }
 }
 
+impl CtlFlags {
+    /// The securelevel below which the sysctl can be written, `None` if it
+    /// is not restricted, see `CtlInfo::secure_level()`.
+    pub fn secure_level(&self) -> Option<c_uint> {
+        secure_level(self.bits())
+    }
+}
+
+impl fmt::Display for CtlFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", flag_names(self.bits()).join(", "))
+    }
+}
+
+// With the serde feature, flags are (de)serialized as a list of their
+// symbolic names, e.g. ["RW", "SECURE3", "TUN"].
+#[cfg(feature = "serde")]
+impl serde::Serialize for CtlFlags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(flag_names(self.bits()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CtlFlags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+        flags_from_names(&names)
+            .map(CtlFlags::from_bits_truncate)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 /// An Enum that represents a sysctl's type information.
 ///
 /// # Example
 ///
 /// ```
 /// extern crate sysctl;
+/// # #[cfg(target_os = "linux")]
+/// # fn main() {}
+/// # #[cfg(not(target_os = "linux"))]
+/// # fn main() {
 ///
 /// let val_enum = &sysctl::value("kern.osrevision")
 ///     .expect("could not get kern.osrevision sysctl");
@@ -214,8 +407,10 @@ bitflags! {
 /// let val_type: sysctl::CtlType = val_enum.into();
 ///
 /// assert_eq!(val_type, sysctl::CtlType::Int);
+/// # }
 /// ```
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u32)]
 pub enum CtlType {
     Node = 1,
@@ -263,6 +458,8 @@ impl<'a> convert::From<&'a CtlValue> for CtlType {
             &CtlValue::S16(_) => CtlType::S16,
             &CtlValue::S32(_) => CtlType::S32,
             &CtlValue::U32(_) => CtlType::U32,
+            &CtlValue::LongArray(_) => CtlType::Long,
+            &CtlValue::UlongArray(_) => CtlType::Ulong,
             #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
             &CtlValue::Temperature(_) => CtlType::Temperature,
         }
@@ -270,7 +467,7 @@ impl<'a> convert::From<&'a CtlValue> for CtlType {
 }
 
 impl CtlType {
-    fn min_type_size(self: &Self) -> usize {
+    fn min_type_size(&self) -> usize {
         match self {
             &CtlType::None => 0,
             &CtlType::Node => 0,
@@ -307,13 +504,14 @@ impl CtlType {
 ///     println!("Value: {}", val);
 /// }
 /// ```
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CtlValue {
     None,
     Node(Vec<u8>),
     Int(i32),
     String(String),
-    S64(u64),
+    S64(i64),
     Struct(Vec<u8>),
     Uint(u32),
     Long(i64),
@@ -325,16 +523,138 @@ pub enum CtlValue {
     S16(i16),
     S32(i32),
     U32(u32),
+    /// A `Long` sysctl returning more than one value, like `kern.cp_time`.
+    LongArray(Vec<i64>),
+    /// An `Ulong` sysctl returning more than one value.
+    UlongArray(Vec<u64>),
     #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
     Temperature(Temperature),
 }
 
+impl CtlValue {
+    /// Return the bytes of the value: the raw data of `Node` and
+    /// `Struct` values, the UTF-8 bytes of strings without trailing NUL
+    /// and the encoding of numbers in native byte order, with longs as
+    /// wide as a C long. Temperatures are encoded as their raw integer,
+    /// as an `IK` sysctl reports it: an `i32`, or an `i64` if it does not
+    /// fit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::CtlValue;
+    ///
+    /// assert_eq!(&*CtlValue::U16(0x1234).as_bytes(), &0x1234u16.to_ne_bytes());
+    /// ```
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = vec![];
+        match *self {
+            CtlValue::None => return Cow::Borrowed(&[]),
+            CtlValue::Node(ref b) | CtlValue::Struct(ref b) => return Cow::Borrowed(b),
+            CtlValue::String(ref s) => return Cow::Borrowed(s.as_bytes()),
+            CtlValue::Int(v) | CtlValue::S32(v) => bytes.write_i32::<NativeEndian>(v),
+            CtlValue::U64(v) => bytes.write_u64::<NativeEndian>(v),
+            CtlValue::Ulong(v) => write_ulong(&mut bytes, v),
+            CtlValue::Uint(v) | CtlValue::U32(v) => bytes.write_u32::<NativeEndian>(v),
+            CtlValue::S64(v) => bytes.write_i64::<NativeEndian>(v),
+            CtlValue::Long(v) => write_long(&mut bytes, v),
+            CtlValue::U8(v) => bytes.write_u8(v),
+            CtlValue::U16(v) => bytes.write_u16::<NativeEndian>(v),
+            CtlValue::S8(v) => bytes.write_i8(v),
+            CtlValue::S16(v) => bytes.write_i16::<NativeEndian>(v),
+            CtlValue::LongArray(ref a) => a.iter().try_for_each(|&v| write_long(&mut bytes, v)),
+            CtlValue::UlongArray(ref a) => a.iter().try_for_each(|&v| write_ulong(&mut bytes, v)),
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+            CtlValue::Temperature(t) => match i32::try_from(t.raw) {
+                Ok(raw) => bytes.write_i32::<NativeEndian>(raw),
+                Err(_) => bytes.write_i64::<NativeEndian>(t.raw),
+            },
+        }
+        .expect("Error writing value to byte array");
+        Cow::Owned(bytes)
+    }
+
+    /// Return the value of any of the integer variants as `i64`, or
+    /// `None` if it is not an integer or does not fit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::CtlValue;
+    ///
+    /// assert_eq!(CtlValue::U16(80).as_int(), Some(80));
+    /// assert_eq!(CtlValue::U64(u64::MAX).as_int(), None);
+    /// ```
+    pub fn as_int(&self) -> Option<i64> {
+        numeric(self).and_then(|n| i64::try_from(n).ok())
+    }
+
+    /// Return the value of any of the integer variants as `u64`, or
+    /// `None` if it is not an integer or is negative.
+    pub fn as_uint(&self) -> Option<u64> {
+        numeric(self).and_then(|n| u64::try_from(n).ok())
+    }
+
+    /// Convert an integer value to the variant of another integer type,
+    /// e.g. to write it to a sysctl of that type.
+    ///
+    /// Fails with `InvalidValue` if the value is not an integer or does not
+    /// fit the target type, instead of truncating it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::{CtlType, CtlValue};
+    ///
+    /// let v = CtlValue::Int(-1).convert(CtlType::S64).unwrap();
+    /// assert_eq!(v, CtlValue::S64(-1));
+    /// assert!(CtlValue::Int(-1).convert(CtlType::U64).is_err());
+    /// ```
+    pub fn convert(&self, ctl_type: CtlType) -> Result<CtlValue, SysctlError> {
+        let n = numeric(self)
+            .ok_or_else(|| SysctlError::InvalidValue(format!("{:?} is not an integer", self)))?;
+        let err = |_| SysctlError::InvalidValue(format!("{} does not fit into {:?}", n, ctl_type));
+        match ctl_type {
+            CtlType::Int => i32::try_from(n).map(CtlValue::Int).map_err(err),
+            CtlType::S64 => i64::try_from(n).map(CtlValue::S64).map_err(err),
+            CtlType::Uint => u32::try_from(n).map(CtlValue::Uint).map_err(err),
+            CtlType::Long => i64::try_from(n).map(CtlValue::Long).map_err(err),
+            CtlType::Ulong => u64::try_from(n).map(CtlValue::Ulong).map_err(err),
+            CtlType::U64 => u64::try_from(n).map(CtlValue::U64).map_err(err),
+            CtlType::U8 => u8::try_from(n).map(CtlValue::U8).map_err(err),
+            CtlType::U16 => u16::try_from(n).map(CtlValue::U16).map_err(err),
+            CtlType::S8 => i8::try_from(n).map(CtlValue::S8).map_err(err),
+            CtlType::S16 => i16::try_from(n).map(CtlValue::S16).map_err(err),
+            CtlType::S32 => i32::try_from(n).map(CtlValue::S32).map_err(err),
+            CtlType::U32 => u32::try_from(n).map(CtlValue::U32).map_err(err),
+            t => Err(SysctlError::InvalidValue(format!("{:?} is not an integer type", t))),
+        }
+    }
+
+    /// Return the text of a `String` value.
+    pub fn as_string(&self) -> Option<&str> {
+        match *self {
+            CtlValue::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for CtlValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(not(target_os = "macos"))]
+        {
+            if let &CtlValue::Temperature(t) = self {
+                return write!(f, "{}", t);
+            }
+        }
         if let &CtlValue::Int(val) = self {
             write!(f, "{}", val)
         }
-        else if let &CtlValue::String(ref val_s) = self {
+        else if let CtlValue::String(val_s) = self {
            write!(f, "{}", val_s) 
         } 
         else if let &CtlValue::S64(val_s64) = self {
@@ -370,70 +690,301 @@ impl fmt::Display for CtlValue {
         else if let &CtlValue::U32(val_u32) = self {
             write!(f, "{}", val_u32)
         }
+        else if let CtlValue::LongArray(a) = self {
+            write!(f, "{}", join(a))
+        }
+        else if let CtlValue::UlongArray(a) = self {
+            write!(f, "{}", join(a))
+        }
         else {
-            write!(f, "{}", "unknown value format for Display. use {:?}")
+            write!(f, "unknown value format for Display. use {{:?}}")
         }
     }
 }
 
-impl convert::Into<String> for CtlValue {
-    fn into(self) -> String {
-        if let CtlValue::Int(val) = self {
+impl convert::From<CtlValue> for String {
+    fn from(value: CtlValue) -> String {
+        if let CtlValue::Int(val) = value {
             val.to_string()
         }
-        else if let CtlValue::String(val_s) = self {
+        else if let CtlValue::String(val_s) = value {
            val_s.to_string() 
         } 
-        else if let CtlValue::S64(val_s64) = self {
+        else if let CtlValue::S64(val_s64) = value {
             val_s64.to_string()
         }
-        else if let CtlValue::Uint(val_uint) = self {
+        else if let CtlValue::Uint(val_uint) = value {
             val_uint.to_string()
         }
-        else if let CtlValue::Long(val_long) = self {
+        else if let CtlValue::Long(val_long) = value {
             val_long.to_string()
         }
-        else if let CtlValue::Ulong(val_ulong) = self {
+        else if let CtlValue::Ulong(val_ulong) = value {
             val_ulong.to_string()
         }
-        else if let CtlValue::U64(val_u64) = self {
+        else if let CtlValue::U64(val_u64) = value {
             val_u64.to_string()
         }
-        else if let CtlValue::U8(val_u8) = self {
+        else if let CtlValue::U8(val_u8) = value {
             val_u8.to_string()
         }
-        else if let CtlValue::U16(val_u16) = self {
+        else if let CtlValue::U16(val_u16) = value {
             val_u16.to_string()
         }
-        else if let CtlValue::S8(val_s8) = self {
+        else if let CtlValue::S8(val_s8) = value {
             val_s8.to_string()
         }
-        else if let CtlValue::S16(val_s16) = self {
+        else if let CtlValue::S16(val_s16) = value {
             val_s16.to_string()
         }
-        else if let CtlValue::S32(val_s32) = self {
+        else if let CtlValue::S32(val_s32) = value {
             val_s32.to_string()
         }
-        else if let CtlValue::U32(val_u32) = self {
+        else if let CtlValue::U32(val_u32) = value {
             val_u32.to_string()
         }
+        else if let CtlValue::LongArray(a) = value {
+            join(&a)
+        }
+        else if let CtlValue::UlongArray(a) = value {
+            join(&a)
+        }
         else {
             String::from("unknown value format for Display. use {:?}")
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct CtlInfo {
+// Whether the dotted name is the node prefix or below it.
+fn under(name: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('.');
+    name == prefix || (name.starts_with(prefix) && name[prefix.len()..].starts_with('.'))
+}
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Turn best-effort conversions into errors for the whole process.
+///
+/// Some values are approximated rather than failing by default, e.g. text
+/// from the kernel with invalid UTF-8 sequences has them replaced. In
+/// strict mode, such values fail with an error instead, for programs that
+/// prefer failing loudly.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     sysctl::set_strict(true);
+///     assert!(sysctl::is_strict());
+/// }
+/// ```
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+/// Whether best-effort conversions are errors, see `set_strict()`.
+pub fn is_strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+// Decode text read from the kernel, replacing invalid UTF-8 sequences
+// unless in strict mode.
+#[cfg_attr(not(target_os = "freebsd"), allow(dead_code))]
+fn decode_utf8(bytes: &[u8]) -> Result<String, SysctlError> {
+    if is_strict() {
+        str::from_utf8(bytes)
+            .map(str::to_owned)
+            .map_err(SysctlError::Utf8Error)
+    } else {
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+// The elements of an array value separated by spaces, as sysctl(8) prints
+// them.
+fn join<T: fmt::Display>(a: &[T]) -> String {
+    a.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+// The value of any of the integer variants, widened so that every variant
+// fits.
+fn numeric(val: &CtlValue) -> Option<i128> {
+    match *val {
+        CtlValue::Int(n) => Some(n as i128),
+        CtlValue::S64(n) => Some(n as i128),
+        CtlValue::Uint(n) => Some(n as i128),
+        CtlValue::Long(n) => Some(n as i128),
+        CtlValue::Ulong(n) => Some(n as i128),
+        CtlValue::U64(n) => Some(n as i128),
+        CtlValue::U8(n) => Some(n as i128),
+        CtlValue::U16(n) => Some(n as i128),
+        CtlValue::S8(n) => Some(n as i128),
+        CtlValue::S16(n) => Some(n as i128),
+        CtlValue::S32(n) => Some(n as i128),
+        CtlValue::U32(n) => Some(n as i128),
+        _ => None,
+    }
+}
+
+// The error for writing a sysctl that can only be set as a loader
+// tunable, None if the value can not be written as text.
+fn tunable_only_error(name: &str, value: &CtlValue) -> Option<SysctlError> {
+    let text = match *value {
+        CtlValue::String(ref s) => s.clone(),
+        ref v => numeric(v)?.to_string(),
+    };
+    Some(SysctlError::TunableOnly {
+        name: name.to_owned(),
+        loader_conf: format!("{}=\"{}\"", name, text),
+    })
+}
+
+// Parse the textual form of a value of the given type, as accepted by
+// sysctl(8) and sysctl.conf(5).
+fn value_from_str(ctl_type: CtlType, s: &str) -> Result<CtlValue, String> {
+    let s = s.trim();
+    let err = |e: std::num::ParseIntError| format!("invalid {:?} value {:?}: {}", ctl_type, s, e);
+    match ctl_type {
+        CtlType::Int => s.parse().map(CtlValue::Int).map_err(err),
+        CtlType::String => Ok(CtlValue::String(s.to_owned())),
+        CtlType::S64 => s.parse().map(CtlValue::S64).map_err(err),
+        CtlType::Uint => s.parse().map(CtlValue::Uint).map_err(err),
+        // Arrays like kern.cp_time are printed space separated
+        CtlType::Long if s.contains(' ') => s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(CtlValue::LongArray)
+            .map_err(err),
+        CtlType::Long => s.parse().map(CtlValue::Long).map_err(err),
+        CtlType::Ulong if s.contains(' ') => s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(CtlValue::UlongArray)
+            .map_err(err),
+        CtlType::Ulong => s.parse().map(CtlValue::Ulong).map_err(err),
+        CtlType::U64 => s.parse().map(CtlValue::U64).map_err(err),
+        CtlType::U8 => s.parse().map(CtlValue::U8).map_err(err),
+        CtlType::U16 => s.parse().map(CtlValue::U16).map_err(err),
+        CtlType::S8 => s.parse().map(CtlValue::S8).map_err(err),
+        CtlType::S16 => s.parse().map(CtlValue::S16).map_err(err),
+        CtlType::S32 => s.parse().map(CtlValue::S32).map_err(err),
+        CtlType::U32 => s.parse().map(CtlValue::U32).map_err(err),
+        t => Err(format!("values of type {:?} cannot be parsed from text", t)),
+    }
+}
+
+/// The type, format and flags of a sysctl.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::Ctl;
+///
+/// # #[cfg(target_os = "linux")]
+/// # fn main() {}
+/// # #[cfg(not(target_os = "linux"))]
+/// fn main() {
+///     let info = Ctl::new("kern.clockrate")
+///         .and_then(|ctl| ctl.info())
+///         .expect("could not get sysctl info");
+///     println!("{:?} {:?}", info.struct_name(), info.flags());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtlInfo {
     ctl_type: CtlType,
     fmt: String,
     flags: u32,
 }
+
+impl CtlInfo {
+    /// Metadata for a sysctl of the type, with the format string and
+    /// `CTLFLAG_*` bits the kernel would report, e.g. for a
+    /// `backend::MockBackend`.
+    pub fn new(ctl_type: CtlType, fmt: &str, flags: c_uint) -> CtlInfo {
+        CtlInfo {
+            ctl_type,
+            fmt: fmt.to_owned(),
+            flags: (flags & !CTLTYPE) | (ctl_type as c_uint & CTLTYPE),
+        }
+    }
+
+    pub fn ctl_type(&self) -> CtlType {
+        self.ctl_type
+    }
+
+    /// The format string, e.g. `I` or `S,clockinfo`.
+    pub fn fmt(&self) -> &str {
+        &self.fmt
+    }
+
+    /// The raw kind as reported by the kernel, the type in the lowest
+    /// bits and the `CTLFLAG_*` bits above.
+    pub fn kind(&self) -> c_uint {
+        self.flags
+    }
+
+    pub fn flags(&self) -> CtlFlags {
+        CtlFlags::from_bits_truncate(self.flags)
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.flags & CTLFLAG_RD != 0
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.flags & CTLFLAG_WR != 0
+    }
+
+    /// Whether the sysctl can be set as a loader tunable.
+    pub fn is_tunable(&self) -> bool {
+        self.flags & CTLFLAG_TUN != 0
+    }
+
+    /// Whether root may write the sysctl from within a jail, if it is
+    /// writable at all.
+    pub fn is_prison(&self) -> bool {
+        self.flags & CTLFLAG_PRISON != 0
+    }
+
+    /// Whether writing is restricted by the securelevel.
+    pub fn is_secure(&self) -> bool {
+        self.flags & CTLFLAG_SECURE != 0
+    }
+
+    /// The securelevel below which the sysctl can be written, `None` if
+    /// it is not restricted.
+    pub fn secure_level(&self) -> Option<c_uint> {
+        secure_level(self.flags)
+    }
+
+    /// The name of the struct for opaque values, e.g. `clockinfo` for a
+    /// format of `S,clockinfo`.
+    pub fn struct_name(&self) -> Option<&str> {
+        if self.fmt.starts_with("S,") {
+            Some(&self.fmt[2..])
+        } else {
+            None
+        }
+    }
+}
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 impl CtlInfo {
     fn is_temperature(&self) -> bool {
         self.fmt.starts_with("IK")
     }
+
+    // The decimal places of a temperature, "IK" is deciKelvin and "IK3"
+    // milliKelvin.
+    fn temperature_precision(&self) -> u32 {
+        match self.fmt.get(2..).map(|p| p.parse::<u32>()) {
+            Some(Ok(x)) if x <= 9 => x,
+            _ => 1,
+        }
+    }
 }
 
 #[derive(Debug, Fail)]
@@ -457,16 +1008,57 @@ pub enum SysctlError {
     #[fail(display = "Value is not writeable")]
     NoWriteAccess,
 
+    #[fail(display = "Value is write-only")]
+    WriteOnly,
+
     #[fail(
         display = "sysctl returned a short read: read {} bytes, while a size of {} was reported",
         read,
         reported
     )]
     ShortRead { read: usize, reported: usize },
+
+    #[fail(
+        display = "Type mismatch: sysctl is of type {:?}, got a value of type {:?}",
+        expected,
+        got
+    )]
+    TypeMismatch { expected: CtlType, got: CtlType },
+
+    #[fail(
+        display = "Size mismatch: expected {} bytes, got {} bytes",
+        expected,
+        got
+    )]
+    SizeMismatch { expected: usize, got: usize },
+
+    #[fail(display = "Invalid value: {}", _0)]
+    InvalidValue(String),
+
+    #[fail(display = "Error parsing line {}: {}", line, reason)]
+    ParseError { line: usize, reason: String },
+
+    #[fail(display = "Not supported on this platform")]
+    NotSupported,
+
+    #[fail(
+        display = "{} can only be set at boot, add {} to /boot/loader.conf",
+        name,
+        loader_conf
+    )]
+    TunableOnly { name: String, loader_conf: String },
 }
 
 /// A custom type for temperature sysctls.
 ///
+/// The kernel reports temperatures as fixed-point Kelvin, e.g. `IK` in
+/// deciKelvin and `IK3` in milliKelvin. The raw value and its precision
+/// are kept as they are, so no digits are lost.
+///
+/// Formatting shows degrees Celsius with the precision of the sysctl,
+/// like `59.9°C`, unless a precision is given with `{:.2}`. Use
+/// `display()` for other units.
+///
 /// # Example
 /// ```
 /// extern crate sysctl;
@@ -480,76 +1072,387 @@ pub enum SysctlError {
 /// #       }
 /// #   };
 ///     if let Ok(sysctl::CtlValue::Temperature(val)) = ctl.value() {
-///         println!("Temperature: {:.2}K, {:.2}F, {:.2}C",
+///         println!("Temperature: {}, {:.2}K, {:.2}F",
+///                  val,
 ///                  val.kelvin(),
-///                  val.fahrenheit(),
-///                  val.celsius());
+///                  val.fahrenheit());
 ///     } else {
 ///         panic!("Error, not a temperature ctl!")
 ///     }
 /// }
+/// #[cfg(not(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd")))]
+/// fn main() {}
 /// ```
 /// Not available on MacOS
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Temperature {
-    value: f32, // Kelvin
+    // Kelvin times 10^precision
+    raw: i64,
+    precision: u32,
+}
+
+/// The unit to format a `Temperature` in, see `Temperature::display()`.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TemperatureUnit {
+    Kelvin,
+    Celsius,
+    Fahrenheit,
 }
+
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
 impl Temperature {
+    /// A temperature of `raw / 10^precision` Kelvin, e.g. `from_raw(3330, 1)`
+    /// for 333.0 K as reported by an `IK` sysctl.
+    ///
+    /// # Panics
+    ///
+    /// If the precision is larger than 9.
+    pub fn from_raw(raw: i64, precision: u32) -> Self {
+        assert!(precision <= 9, "temperature precision {} is too large", precision);
+        Temperature { raw, precision }
+    }
+
+    /// The fixed-point value, in Kelvin times `10^precision()`.
+    pub fn raw(&self) -> i64 {
+        self.raw
+    }
+
+    /// The number of decimal places of the raw value.
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// The temperature in milliKelvin, rounded towards zero if the raw
+    /// value is more precise.
+    pub fn millikelvin(&self) -> i64 {
+        if self.precision <= 3 {
+            self.raw * 10i64.pow(3 - self.precision)
+        } else {
+            self.raw / 10i64.pow(self.precision - 3)
+        }
+    }
+
     pub fn kelvin(&self) -> f32 {
-        self.value
+        self.kelvin_f64() as f32
     }
     pub fn celsius(&self) -> f32 {
-        self.value - 273.15
+        (self.kelvin_f64() - 273.15) as f32
     }
     pub fn fahrenheit(&self) -> f32 {
-        1.8 * self.celsius() + 32.0
+        (1.8 * (self.kelvin_f64() - 273.15) + 32.0) as f32
     }
-}
 
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
-    // Request command for OID
-    let oid: [c_int; 2] = [0, 3];
+    /// Format the temperature in the given unit.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// # #[cfg(not(target_os = "macos"))]
+    /// # fn main() {
+    /// use sysctl::{Temperature, TemperatureUnit};
+    ///
+    /// let t = Temperature::from_raw(3330, 1);
+    /// assert_eq!(t.to_string(), "59.9°C");
+    /// assert_eq!(t.display(TemperatureUnit::Kelvin).to_string(), "333.0K");
+    /// # }
+    /// # #[cfg(target_os = "macos")]
+    /// # fn main() {}
+    /// ```
+    pub fn display(&self, unit: TemperatureUnit) -> TemperatureDisplay {
+        TemperatureDisplay {
+            temperature: *self,
+            unit,
+        }
+    }
 
-    let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
+    fn kelvin_f64(&self) -> f64 {
+        self.raw as f64 / 10f64.powi(self.precision as i32)
+    }
 
-    // We get results in this vector
-    let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
+    // The raw value scaled to the given, larger or equal, precision.
+    fn scaled(&self, precision: u32) -> i128 {
+        i128::from(self.raw) * 10i128.pow(precision - self.precision)
+    }
 
-    let ret = unsafe {
-        sysctl(
-            oid.as_ptr(),
-            2,
-            res.as_mut_ptr() as *mut c_void,
-            &mut len,
-            name.as_ptr() as *const c_void,
-            name.len(),
-        )
-    };
-    if ret < 0 {
-        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    // The raw value at any precision, rounded half away from zero.
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    fn raw_at(&self, precision: u32) -> i128 {
+        if precision >= self.precision {
+            return self.scaled(precision);
+        }
+        let raw = i128::from(self.raw);
+        let div = 10i128.pow(self.precision - precision);
+        (raw + raw.signum() * div / 2) / div
     }
+}
 
-    // len is in bytes, convert to number of c_ints
-    len /= mem::size_of::<c_int>();
+// Equal temperatures compare equal regardless of their precision.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+impl PartialEq for Temperature {
+    fn eq(&self, other: &Temperature) -> bool {
+        self.partial_cmp(other) == Some(cmp::Ordering::Equal)
+    }
+}
 
-    // Trim result vector
-    res.truncate(len);
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+impl PartialOrd for Temperature {
+    fn partial_cmp(&self, other: &Temperature) -> Option<cmp::Ordering> {
+        let precision = cmp::max(self.precision, other.precision);
+        self.scaled(precision).partial_cmp(&other.scaled(precision))
+    }
+}
 
-    Ok(res)
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.display(TemperatureUnit::Celsius).fmt(f)
+    }
 }
 
-#[cfg(any(target_os = "macos", target_os = "linux"))]
-fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
-    // Request command for OID
-    let mut oid: [c_int; 2] = [0, 3];
+/// Formats a `Temperature` in a chosen unit, returned by
+/// `Temperature::display()`.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+#[derive(Debug, Copy, Clone)]
+pub struct TemperatureDisplay {
+    temperature: Temperature,
+    unit: TemperatureUnit,
+}
 
-    let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+impl fmt::Display for TemperatureDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t = &self.temperature;
+        let precision = f.precision().unwrap_or(t.precision as usize);
+        let kelvin = t.kelvin_f64();
+        match self.unit {
+            TemperatureUnit::Kelvin => write!(f, "{:.*}K", precision, kelvin),
+            TemperatureUnit::Celsius => write!(f, "{:.*}°C", precision, kelvin - 273.15),
+            TemperatureUnit::Fahrenheit => {
+                write!(f, "{:.*}°F", precision, 1.8 * (kelvin - 273.15) + 32.0)
+            }
+        }
+    }
+}
 
-    // We get results in this vector
-    let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
+// Format a type and flags the way `sysctl -t` style listings do, e.g.
+// "int, RW, TUN, SECURE3".
+fn type_flags_string(ctl_type: CtlType, flags: c_uint) -> String {
+    let type_name = match ctl_type {
+        CtlType::Node => "node",
+        CtlType::Int => "int",
+        CtlType::String => "string",
+        CtlType::S64 => "int64_t",
+        CtlType::Struct => "opaque",
+        CtlType::Uint => "uint",
+        CtlType::Long => "long",
+        CtlType::Ulong => "ulong",
+        CtlType::U64 => "uint64_t",
+        CtlType::U8 => "uint8_t",
+        CtlType::U16 => "uint16_t",
+        CtlType::S8 => "int8_t",
+        CtlType::S16 => "int16_t",
+        CtlType::S32 => "int32_t",
+        CtlType::U32 => "uint32_t",
+        CtlType::None => "none",
+        #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
+        CtlType::Temperature => "temperature",
+    };
+    let mut parts = vec![type_name.to_owned()];
+    parts.extend(flag_names(flags));
+    parts.join(", ")
+}
+
+// Flags named by their own bit. Access and securelevel are handled
+// separately, as they are combined into a single name.
+const NAMED_FLAGS: [(c_uint, &str); 13] = [
+    (CTLFLAG_TUN, "TUN"),
+    (CTLFLAG_NOFETCH, "NOFETCH"),
+    (CTLFLAG_ANYBODY, "ANYBODY"),
+    (CTLFLAG_PRISON, "PRISON"),
+    (CTLFLAG_VNET, "VNET"),
+    (CTLFLAG_CAPRD, "CAPRD"),
+    (CTLFLAG_CAPWR, "CAPWR"),
+    (CTLFLAG_STATS, "STATS"),
+    (CTLFLAG_MPSAFE, "MPSAFE"),
+    (CTLFLAG_DYN, "DYN"),
+    (CTLFLAG_SKIP, "SKIP"),
+    (CTLFLAG_DORMANT, "DORMANT"),
+    (CTLFLAG_DYING, "DYING"),
+];
+
+fn secure_level(flags: c_uint) -> Option<c_uint> {
+    if flags & CTLFLAG_SECURE == 0 {
+        return None;
+    }
+    // The mask holds the securelevel minus one, so CTLFLAG_SECURE alone
+    // is CTLFLAG_SECURE1
+    Some(((flags & CTLMASK_SECURE) >> CTLSHIFT_SECURE) + 1)
+}
+
+// Whether the kernel lets a process with the given effective UID write a
+// sysctl with the flags, see Ctl::can_write().
+#[cfg(not(target_os = "linux"))]
+fn write_permitted(flags: c_uint, euid: libc::uid_t, securelevel: i64, jailed: bool) -> bool {
+    if flags & CTLFLAG_WR == 0 {
+        return false;
+    }
+    if flags & CTLFLAG_ANYBODY == 0 && euid != 0 {
+        return false;
+    }
+    // SECUREn permits writes while the securelevel is below n
+    if let Some(level) = secure_level(flags) {
+        if securelevel >= i64::from(level) {
+            return false;
+        }
+    }
+    !jailed || flags & CTLFLAG_PRISON != 0
+}
+
+#[cfg(target_os = "freebsd")]
+fn jailed() -> bool {
+    match value("security.jail.jailed") {
+        Ok(v) => v.as_int().unwrap_or(0) != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(any(target_os = "freebsd", target_os = "linux")))]
+fn jailed() -> bool {
+    false
+}
+
+// The symbolic names of the flags, e.g. ["RW", "SECURE3", "TUN"].
+fn flag_names(flags: c_uint) -> Vec<String> {
+    let mut names = vec![];
+
+    match flags & CTLFLAG_RW {
+        CTLFLAG_RW => names.push("RW".to_owned()),
+        CTLFLAG_RD => names.push("RD".to_owned()),
+        CTLFLAG_WR => names.push("WR".to_owned()),
+        _ => {}
+    }
+    if let Some(level) = secure_level(flags) {
+        names.push(format!("SECURE{}", level));
+    }
+    for &(flag, name) in NAMED_FLAGS.iter() {
+        if flags & flag != 0 {
+            names.push(name.to_owned());
+        }
+    }
+
+    names
+}
+
+// The inverse of flag_names().
+#[cfg(feature = "serde")]
+fn flags_from_names<S: AsRef<str>>(names: &[S]) -> Result<c_uint, String> {
+    let mut flags = 0;
+
+    for name in names {
+        let name = name.as_ref();
+        flags |= match name {
+            "RW" => CTLFLAG_RW,
+            "RD" => CTLFLAG_RD,
+            "WR" => CTLFLAG_WR,
+            _ if name.starts_with("SECURE") => match name[6..].parse::<c_uint>() {
+                Ok(level) if (1..=16).contains(&level) => {
+                    CTLFLAG_SECURE | ((level - 1) << CTLSHIFT_SECURE)
+                }
+                _ => return Err(format!("invalid securelevel flag {}", name)),
+            },
+            _ => match NAMED_FLAGS.iter().find(|&&(_, n)| n == name) {
+                Some(&(flag, _)) => flag,
+                None => return Err(format!("unknown flag {}", name)),
+            },
+        };
+    }
+
+    Ok(flags)
+}
+
+/// The unit of a sysctl value, as inferred by `Ctl::unit()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Unit {
+    Bytes,
+    Pages,
+    Milliseconds,
+    Percent,
+    Kelvin,
+}
+
+// Guess the unit from the format string and description. Only the
+// temperature format is authoritative, the rest are common phrasings
+// found in descriptions.
+fn infer_unit(fmt: &str, description: Option<&str>) -> Option<Unit> {
+    if fmt.starts_with("IK") {
+        return Some(Unit::Kelvin);
+    }
+    let desc = description?.to_lowercase();
+    let words: Vec<&str> = desc
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let has = |w: &str| words.contains(&w);
+
+    if has("bytes") {
+        Some(Unit::Bytes)
+    } else if has("pages") {
+        Some(Unit::Pages)
+    } else if has("milliseconds") || has("msec") || has("ms") {
+        Some(Unit::Milliseconds)
+    } else if has("percent") || has("percentage") || desc.contains('%') {
+        Some(Unit::Percent)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
+    // Request command for OID
+    let oid: [c_int; 2] = [CTL_SYSCTL, CTL_SYSCTL_NAME2OID];
+
+    let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
+
+    // We get results in this vector
+    let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
+
+    let ret = unsafe {
+        sysctl(
+            oid.as_ptr(),
+            2,
+            res.as_mut_ptr() as *mut c_void,
+            &mut len,
+            name.as_ptr() as *const c_void,
+            name.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+
+    // len is in bytes, convert to number of c_ints
+    len /= mem::size_of::<c_int>();
+
+    // Trim result vector
+    res.truncate(len);
+
+    Ok(res)
+}
+
+#[cfg(target_os = "macos")]
+fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
+    // Request command for OID
+    let mut oid: [c_int; 2] = [CTL_SYSCTL, CTL_SYSCTL_NAME2OID];
+
+    let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
+
+    // We get results in this vector
+    let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
 
     let ret = unsafe {
         sysctl(
@@ -574,10 +1477,10 @@ fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
     Ok(res)
 }
 
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+#[cfg(target_os = "freebsd")]
 fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
     // Request command for type info
-    let mut qoid: Vec<c_int> = vec![0, 4];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_OIDFMT];
     qoid.extend(oid);
 
     // Store results here
@@ -598,14 +1501,14 @@ fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
     }
 
     // 'Kind' is the first 32 bits of result buffer
-    let kind = LittleEndian::read_u32(&buf);
+    let kind = NativeEndian::read_u32(&buf);
 
     // 'Type' is the first 4 bits of 'Kind'
     let ctltype_val = kind & CTLTYPE as u32;
 
-    // 'fmt' is after 'Kind' in result buffer
+    // 'fmt' is after 'Kind' in result buffer, without the trailing NUL
     let fmt: String = match str::from_utf8(&buf[mem::size_of::<u32>()..buf_len]) {
-        Ok(x) => x.to_owned(),
+        Ok(x) => x.trim_end_matches('\0').to_owned(),
         Err(e) => return Err(SysctlError::Utf8Error(e)),
     };
 
@@ -619,58 +1522,55 @@ fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
 
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 fn temperature(info: &CtlInfo, val: &Vec<u8>) -> Result<CtlValue, SysctlError> {
-    let prec: u32 = {
-        match info.fmt.len() {
-            l if l > 2 => match info.fmt[2..3].parse::<u32>() {
-                Ok(x) if x <= 9 => x,
-                _ => 1,
-            },
-            _ => 1,
-        }
-    };
+    check_width(info.ctl_type, val)?;
+    let prec = info.temperature_precision();
 
-    let base = 10u32.pow(prec) as f32;
-
-    let make_temp = move |f: f32| -> Result<CtlValue, SysctlError> {
-        Ok(CtlValue::Temperature(Temperature { value: f / base }))
+    let make_temp = move |raw: i64| -> Result<CtlValue, SysctlError> {
+        Ok(CtlValue::Temperature(Temperature::from_raw(raw, prec)))
     };
+    let too_large = |v: u64| SysctlError::InvalidValue(format!("temperature {} out of range", v));
 
     match info.ctl_type {
-        CtlType::Int => make_temp(LittleEndian::read_i32(&val) as f32),
-        CtlType::S64 => make_temp(LittleEndian::read_u64(&val) as f32),
-        CtlType::Uint => make_temp(LittleEndian::read_u32(&val) as f32),
-        CtlType::Long => make_temp(LittleEndian::read_i64(&val) as f32),
-        CtlType::Ulong => make_temp(LittleEndian::read_u64(&val) as f32),
-        CtlType::U64 => make_temp(LittleEndian::read_u64(&val) as f32),
-        CtlType::U8 => make_temp(val[0] as u8 as f32),
-        CtlType::U16 => make_temp(LittleEndian::read_u16(&val) as f32),
-        CtlType::S8 => make_temp(val[0] as i8 as f32),
-        CtlType::S16 => make_temp(LittleEndian::read_i16(&val) as f32),
-        CtlType::S32 => make_temp(LittleEndian::read_i32(&val) as f32),
-        CtlType::U32 => make_temp(LittleEndian::read_u32(&val) as f32),
+        CtlType::Int => make_temp(NativeEndian::read_i32(&val) as i64),
+        CtlType::S64 => make_temp(NativeEndian::read_i64(&val)),
+        CtlType::Uint => make_temp(NativeEndian::read_u32(&val) as i64),
+        CtlType::Long => make_temp(read_long(val)),
+        CtlType::Ulong | CtlType::U64 => {
+            let v = match info.ctl_type {
+                CtlType::Ulong => read_ulong(val),
+                _ => NativeEndian::read_u64(val),
+            };
+            make_temp(i64::try_from(v).map_err(|_| too_large(v))?)
+        }
+        CtlType::U8 => make_temp(val[0] as i64),
+        CtlType::U16 => make_temp(NativeEndian::read_u16(&val) as i64),
+        CtlType::S8 => make_temp(val[0] as i8 as i64),
+        CtlType::S16 => make_temp(NativeEndian::read_i16(&val) as i64),
+        CtlType::S32 => make_temp(NativeEndian::read_i32(&val) as i64),
+        CtlType::U32 => make_temp(NativeEndian::read_u32(&val) as i64),
         _ => Err(SysctlError::UnknownType),
     }
 }
 
-#[cfg(any(target_os = "macos", target_os = "linux"))]
+// The integer value a temperature is written as, in the fixed-point
+// format of the sysctl.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+fn temperature_value(info: &CtlInfo, t: Temperature) -> Result<CtlValue, SysctlError> {
+    let raw = t.raw_at(info.temperature_precision());
+    let raw = i64::try_from(raw)
+        .map_err(|_| SysctlError::InvalidValue(format!("temperature {} out of range", t)))?;
+    CtlValue::S64(raw).convert(info.ctl_type)
+}
+
+#[cfg(target_os = "macos")]
 fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
     // Request command for type info
-    let mut qoid: Vec<c_int> = vec![0, 4];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_OIDFMT];
     qoid.extend(oid);
 
     // Store results here
     let mut buf: [c_uchar; BUFSIZ as usize] = [0; BUFSIZ as usize];
     let mut buf_len = mem::size_of_val(&buf);
-    #[cfg(target_os = "linux")]
-    let ret = unsafe {
-        sysctl(qoid.as_mut_ptr(),
-               qoid.len() as i32,
-               buf.as_mut_ptr() as *mut c_void,
-               &mut buf_len,
-               ptr::null_mut(),
-               0)
-    };
-    #[cfg(target_os = "macos")]
     let ret = unsafe {
         sysctl(
             qoid.as_mut_ptr(),
@@ -686,14 +1586,14 @@ fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
     }
 
     // 'Kind' is the first 32 bits of result buffer
-    let kind = LittleEndian::read_u32(&buf);
+    let kind = NativeEndian::read_u32(&buf);
 
     // 'Type' is the first 4 bits of 'Kind'
     let ctltype_val = kind & CTLTYPE as u32;
 
-    // 'fmt' is after 'Kind' in result buffer
+    // 'fmt' is after 'Kind' in result buffer, without the trailing NUL
     let fmt: String = match str::from_utf8(&buf[mem::size_of::<u32>()..buf_len]) {
-        Ok(x) => x.to_owned(),
+        Ok(x) => x.trim_end_matches('\0').to_owned(),
         Err(e) => return Err(SysctlError::Utf8Error(e)),
     };
 
@@ -737,7 +1637,7 @@ pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
 ///     println!("Value: {:?}", sysctl::value("kern.osrevision"));
 /// }
 /// ```
-#[cfg(any(target_os = "macos", target_os = "linux"))]
+#[cfg(target_os = "macos")]
 pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
     match name2oid(name) {
         Ok(mut v) => value_oid(&mut v),
@@ -745,6 +1645,25 @@ pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
     }
 }
 
+/// Takes the name of the OID as argument and returns
+/// a result containing the sysctl value if success,
+/// or a SysctlError on failure
+///
+/// The value is read from `/proc/sys`, see the `linux` module.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     println!("Value: {:?}", sysctl::value("kernel.osrelease"));
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
+    linux::value(name)
+}
+
 /// Takes an OID as argument and returns a result
 /// containing the sysctl value if success, or a SysctlError
 /// on failure
@@ -761,91 +1680,16 @@ pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
 /// ```
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 pub fn value_oid(oid: &Vec<i32>) -> Result<CtlValue, SysctlError> {
-    let info: CtlInfo = try!(oidfmt(&oid));
-
-    // Check if the value is readable
-    if !(info.flags & CTLFLAG_RD == CTLFLAG_RD) {
-        return Err(SysctlError::NoReadAccess);
-    }
-
-    // First get size of value in bytes
-    let mut val_len = 0;
-    let ret = unsafe {
-        sysctl(
-            oid.as_ptr(),
-            oid.len() as u32,
-            ptr::null_mut(),
-            &mut val_len,
-            ptr::null(),
-            0,
-        )
-    };
-    if ret < 0 {
-        return Err(SysctlError::IoError(io::Error::last_os_error()));
-    }
-
-    // If the length reported is shorter than the type we will convert it into,
-    // LittleEndian::read_* will panic. Therefore, expand the value length to at
-    // Least the size of the value.
-    let val_minsize = cmp::max(val_len, info.ctl_type.min_type_size());
-
-    // Then get value
-    let mut val: Vec<c_uchar> = vec![0; val_minsize];
-    let mut new_val_len = val_len;
-    let ret = unsafe {
-        sysctl(
-            oid.as_ptr(),
-            oid.len() as u32,
-            val.as_mut_ptr() as *mut c_void,
-            &mut new_val_len,
-            ptr::null(),
-            0,
-        )
-    };
-    if ret < 0 {
-        return Err(SysctlError::IoError(io::Error::last_os_error()));
-    }
-
-    // Confirm that we did not read out of bounds
-    assert!(new_val_len <= val_len);
-    // Confirm that we got the bytes we requested
-    if new_val_len < val_len {
-        return Err(SysctlError::ShortRead {
-            read: new_val_len,
-            reported: val_len,
-        });
-    }
-
-    // Special treatment for temperature ctls.
-    if info.is_temperature() {
-        return temperature(&info, &val);
-    }
+    let info: CtlInfo = oidfmt(oid)?;
+    value_oid_info(oid, &info)
+}
 
-    // Wrap in Enum and return
-    match info.ctl_type {
-        CtlType::None => Ok(CtlValue::None),
-        CtlType::Node => Ok(CtlValue::Node(val)),
-        CtlType::Int => Ok(CtlValue::Int(LittleEndian::read_i32(&val))),
-        CtlType::String => match val.len() {
-            0 => Ok(CtlValue::String("".to_string())),
-            l => str::from_utf8(&val[..l - 1])
-                .map_err(|e| SysctlError::Utf8Error(e))
-                .map(|s| CtlValue::String(s.into())),
-        },
-        CtlType::S64 => Ok(CtlValue::S64(LittleEndian::read_u64(&val))),
-        CtlType::Struct => Ok(CtlValue::Struct(val)),
-        CtlType::Uint => Ok(CtlValue::Uint(LittleEndian::read_u32(&val))),
-        CtlType::Long => Ok(CtlValue::Long(LittleEndian::read_i64(&val))),
-        CtlType::Ulong => Ok(CtlValue::Ulong(LittleEndian::read_u64(&val))),
-        CtlType::U64 => Ok(CtlValue::U64(LittleEndian::read_u64(&val))),
-        CtlType::U8 => Ok(CtlValue::U8(val[0])),
-        CtlType::U16 => Ok(CtlValue::U16(LittleEndian::read_u16(&val))),
-        CtlType::S8 => Ok(CtlValue::S8(val[0] as i8)),
-        CtlType::S16 => Ok(CtlValue::S16(LittleEndian::read_i16(&val))),
-        CtlType::S32 => Ok(CtlValue::S32(LittleEndian::read_i32(&val))),
-        CtlType::U32 => Ok(CtlValue::U32(LittleEndian::read_u32(&val))),
-        _ => Err(SysctlError::UnknownType),
-    }
+// Read the value of oid, whose metadata has already been fetched.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+fn value_oid_info(oid: &[c_int], info: &CtlInfo) -> Result<CtlValue, SysctlError> {
+    check_readable(info)?;
+    let val = fetch_oid(oid, info.ctl_type.min_type_size())?;
+    decode_value(info, val)
 }
 
 /// Takes an OID as argument and returns a result
@@ -863,108 +1707,140 @@ pub fn value_oid(oid: &Vec<i32>) -> Result<CtlValue, SysctlError> {
 /// }
 /// ```
 #[cfg(any(target_os = "macos", target_os = "linux"))]
+#[allow(clippy::ptr_arg)]
 pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
-    let info: CtlInfo = try!(oidfmt(&oid));
+    let info: CtlInfo = oidfmt(oid)?;
+    value_oid_info(oid, &info)
+}
 
-    // Check if the value is readable
-    if !(info.flags & CTLFLAG_RD == CTLFLAG_RD) {
-        return Err(SysctlError::NoReadAccess);
-    }
+// Read the value of oid, whose metadata has already been fetched.
+#[cfg(target_os = "macos")]
+fn value_oid_info(oid: &[c_int], info: &CtlInfo) -> Result<CtlValue, SysctlError> {
+    check_readable(info)?;
+    let val = fetch_oid(oid, info.ctl_type.min_type_size())?;
+    decode_value(info, val)
+}
 
-    // First get size of value in bytes
-    let mut val_len = 0;
-    #[cfg(target_os = "linux")]
-    let ret = unsafe {
-        sysctl(oid.as_mut_ptr(),
-               oid.len() as i32,
-               ptr::null_mut(),
-               &mut val_len,
-               ptr::null_mut(),
-               0)
-    };
-    #[cfg(target_os = "macos")]
-    let ret = unsafe {
-        sysctl(
-            oid.as_mut_ptr(),
-            oid.len() as u32,
-            ptr::null_mut(),
-            &mut val_len,
-            ptr::null_mut(),
-            0,
-        )
-    };
-    if ret < 0 {
-        return Err(SysctlError::IoError(io::Error::last_os_error()));
-    }
+// Read the value of oid, whose metadata has already been fetched. The
+// value is parsed from the text in /proc/sys.
+#[cfg(target_os = "linux")]
+fn value_oid_info(oid: &[c_int], info: &CtlInfo) -> Result<CtlValue, SysctlError> {
+    check_readable(info)?;
+    linux::value(&oid2name(oid)?)
+}
 
-    // If the length reported is shorter than the type we will convert it into,
-    // LittleEndian::read_* will panic. Therefore, expand the value length to at
-    // Least the size of the value.
-    let val_minsize = cmp::max(val_len, info.ctl_type.min_type_size());
+// Fail for sysctls whose value can not be read.
+fn check_readable(info: &CtlInfo) -> Result<(), SysctlError> {
+    // Write-only values can never be read, report them as such
+    if info.flags & CTLFLAG_RW == CTLFLAG_WR {
+        return Err(SysctlError::WriteOnly);
+    }
 
-    // Then get value
-    let mut val: Vec<c_uchar> = vec![0; val_minsize];
-    let mut new_val_len = val_len;
-    #[cfg(target_os = "linux")]
-    let ret = unsafe {
-        sysctl(oid.as_mut_ptr(),
-               oid.len() as i32,
-               val.as_mut_ptr() as *mut c_void,
-               &mut new_val_len,
-               ptr::null_mut(),
-               0)
-    };
-    #[cfg(target_os = "macos")]
-    let ret = unsafe {
-        sysctl(
-            oid.as_mut_ptr(),
-            oid.len() as u32,
-            val.as_mut_ptr() as *mut c_void,
-            &mut new_val_len,
-            ptr::null_mut(),
-            0,
-        )
-    };
-    if ret < 0 {
-        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    // Check if the value is readable
+    if info.flags & CTLFLAG_RD != CTLFLAG_RD {
+        return Err(SysctlError::NoReadAccess);
     }
+    Ok(())
+}
 
-    // Confirm that we did not read out of bounds
-    assert!(new_val_len <= val_len);
-    // Confirm that we got the bytes we requested
-    if new_val_len < val_len {
-        return Err(SysctlError::ShortRead {
-            read: new_val_len,
-            reported: val_len,
-        });
+// Decode the raw value as read from the kernel, in the byte order of the
+// host.
+fn decode_value(info: &CtlInfo, val: Vec<u8>) -> Result<CtlValue, SysctlError> {
+    // Special treatment for temperature ctls.
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    {
+        if info.is_temperature() {
+            return temperature(info, &val);
+        }
     }
 
+    check_width(info.ctl_type, &val)?;
+
     // Wrap in Enum and return
     match info.ctl_type {
         CtlType::None => Ok(CtlValue::None),
         CtlType::Node => Ok(CtlValue::Node(val)),
-        CtlType::Int => Ok(CtlValue::Int(LittleEndian::read_i32(&val))),
-        CtlType::String => match str::from_utf8(&val[..val.len() - 1]) {
-            Ok(s) => Ok(CtlValue::String(s.into())),
-            Err(e) => Err(SysctlError::Utf8Error(e)),
+        CtlType::Int => Ok(CtlValue::Int(NativeEndian::read_i32(&val))),
+        CtlType::String => match val.len() {
+            0 => Ok(CtlValue::String("".to_string())),
+            l => str::from_utf8(&val[..l - 1])
+                .map_err(SysctlError::Utf8Error)
+                .map(|s| CtlValue::String(s.into())),
         },
-        CtlType::S64 => Ok(CtlValue::S64(LittleEndian::read_u64(&val))),
+        CtlType::S64 => Ok(CtlValue::S64(NativeEndian::read_i64(&val))),
         CtlType::Struct => Ok(CtlValue::Struct(val)),
-        CtlType::Uint => Ok(CtlValue::Uint(LittleEndian::read_u32(&val))),
-        CtlType::Long => Ok(CtlValue::Long(LittleEndian::read_i64(&val))),
-        CtlType::Ulong => Ok(CtlValue::Ulong(LittleEndian::read_u64(&val))),
-        CtlType::U64 => Ok(CtlValue::U64(LittleEndian::read_u64(&val))),
+        CtlType::Uint => Ok(CtlValue::Uint(NativeEndian::read_u32(&val))),
+        CtlType::Long if val.len() > LONG_SIZE => Ok(CtlValue::LongArray(
+            val.chunks_exact(LONG_SIZE).map(read_long).collect(),
+        )),
+        CtlType::Long => Ok(CtlValue::Long(read_long(&val))),
+        CtlType::Ulong if val.len() > LONG_SIZE => Ok(CtlValue::UlongArray(
+            val.chunks_exact(LONG_SIZE).map(read_ulong).collect(),
+        )),
+        CtlType::Ulong => Ok(CtlValue::Ulong(read_ulong(&val))),
+        CtlType::U64 => Ok(CtlValue::U64(NativeEndian::read_u64(&val))),
         CtlType::U8 => Ok(CtlValue::U8(val[0])),
-        CtlType::U16 => Ok(CtlValue::U16(LittleEndian::read_u16(&val))),
+        CtlType::U16 => Ok(CtlValue::U16(NativeEndian::read_u16(&val))),
         CtlType::S8 => Ok(CtlValue::S8(val[0] as i8)),
-        CtlType::S16 => Ok(CtlValue::S16(LittleEndian::read_i16(&val))),
-        CtlType::S32 => Ok(CtlValue::S32(LittleEndian::read_i32(&val))),
-        CtlType::U32 => Ok(CtlValue::U32(LittleEndian::read_u32(&val))),
+        CtlType::S16 => Ok(CtlValue::S16(NativeEndian::read_i16(&val))),
+        CtlType::S32 => Ok(CtlValue::S32(NativeEndian::read_i32(&val))),
+        CtlType::U32 => Ok(CtlValue::U32(NativeEndian::read_u32(&val))),
         #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
         _ => Err(SysctlError::UnknownType),
     }
 }
 
+// Longs are as wide as the C long of the target, so 4 bytes on 32-bit
+// targets. The values are widened to 64 bits, the casts are no-ops on
+// 64-bit targets.
+const LONG_SIZE: usize = mem::size_of::<c_long>();
+
+// Fail if the value is too short for a number of the type, as a sysctl
+// may report fewer bytes than its type takes.
+fn check_width(ctl_type: CtlType, val: &[u8]) -> Result<(), SysctlError> {
+    let width = match ctl_type {
+        CtlType::U8 | CtlType::S8 => 1,
+        CtlType::U16 | CtlType::S16 => 2,
+        CtlType::Int | CtlType::Uint | CtlType::S32 | CtlType::U32 => 4,
+        CtlType::S64 | CtlType::U64 => 8,
+        CtlType::Long | CtlType::Ulong => LONG_SIZE,
+        _ => return Ok(()),
+    };
+    if val.len() < width {
+        return Err(SysctlError::SizeMismatch {
+            expected: width,
+            got: val.len(),
+        });
+    }
+    Ok(())
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn read_long(b: &[u8]) -> i64 {
+    let mut raw = [0; LONG_SIZE];
+    raw.copy_from_slice(&b[..LONG_SIZE]);
+    c_long::from_ne_bytes(raw) as i64
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn read_ulong(b: &[u8]) -> u64 {
+    let mut raw = [0; LONG_SIZE];
+    raw.copy_from_slice(&b[..LONG_SIZE]);
+    c_ulong::from_ne_bytes(raw) as u64
+}
+
+// Values out of range of a 32-bit long are truncated, encode_value()
+// rejects them before.
+fn write_long(bytes: &mut Vec<u8>, v: i64) -> io::Result<()> {
+    bytes.extend_from_slice(&(v as c_long).to_ne_bytes());
+    Ok(())
+}
+
+fn write_ulong(bytes: &mut Vec<u8>, v: u64) -> io::Result<()> {
+    bytes.extend_from_slice(&(v as c_ulong).to_ne_bytes());
+    Ok(())
+}
+
 /// A generic function that takes a string as argument and
 /// returns a result containing the sysctl value if success,
 /// or a SysctlError on failure.
@@ -988,12 +1864,15 @@ pub fn value_oid(oid: &mut Vec<i32>) -> Result<CtlValue, SysctlError> {
 ///     profhz: c_int, /* profiling clock frequency */
 /// }
 ///
+/// // Only integers, so any bytes are a valid ClockInfo
+/// unsafe impl sysctl::SysctlPod for ClockInfo {}
+///
 /// fn main() {
 ///     println!("{:?}", sysctl::value_as::<ClockInfo>("kern.clockrate"));
 /// }
 /// ```
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-pub fn value_as<T>(name: &str) -> Result<Box<T>, SysctlError> {
+pub fn value_as<T: SysctlPod>(name: &str) -> Result<Box<T>, SysctlError> {
     match name2oid(name) {
         Ok(v) => value_oid_as::<T>(&v),
         Err(e) => Err(e),
@@ -1023,12 +1902,15 @@ pub fn value_as<T>(name: &str) -> Result<Box<T>, SysctlError> {
 ///     profhz: c_int, /* profiling clock frequency */
 /// }
 ///
+/// // Only integers, so any bytes are a valid ClockInfo
+/// unsafe impl sysctl::SysctlPod for ClockInfo {}
+///
 /// fn main() {
 ///     println!("{:?}", sysctl::value_as::<ClockInfo>("kern.clockrate"));
 /// }
 /// ```
 #[cfg(any(target_os = "macos", target_os = "linux"))]
-pub fn value_as<T>(name: &str) -> Result<Box<T>, SysctlError> {
+pub fn value_as<T: SysctlPod>(name: &str) -> Result<Box<T>, SysctlError> {
     match name2oid(name) {
         Ok(mut v) => value_oid_as::<T>(&mut v),
         Err(e) => Err(e),
@@ -1058,6 +1940,9 @@ pub fn value_as<T>(name: &str) -> Result<Box<T>, SysctlError> {
 ///     profhz: c_int, /* profiling clock frequency */
 /// }
 ///
+/// // Only integers, so any bytes are a valid ClockInfo
+/// unsafe impl sysctl::SysctlPod for ClockInfo {}
+///
 /// #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 /// fn main() {
 ///     let oid = vec![libc::CTL_KERN, libc::KERN_CLOCKRATE];
@@ -1070,47 +1955,9 @@ pub fn value_as<T>(name: &str) -> Result<Box<T>, SysctlError> {
 /// }
 /// ```
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-pub fn value_oid_as<T>(oid: &Vec<i32>) -> Result<Box<T>, SysctlError> {
-    let val_enum = try!(value_oid(oid));
-
-    // Some structs are apparently reported as Node so this check is invalid..
-    // let ctl_type = CtlType::from(&val_enum);
-    // assert_eq!(CtlType::Struct, ctl_type, "Error type is not struct/opaque");
-
-    // TODO: refactor this when we have better clue to what's going on
-    if let CtlValue::Struct(val) = val_enum {
-        // Make sure we got correct data size
-        assert_eq!(
-            mem::size_of::<T>(),
-            val.len(),
-            "Error memory size mismatch. Size of struct {}, size of data retrieved {}.",
-            mem::size_of::<T>(),
-            val.len()
-        );
-
-        // val is Vec<u8>
-        let val_array: Box<[u8]> = val.into_boxed_slice();
-        let val_raw: *mut T = Box::into_raw(val_array) as *mut T;
-        let val_box: Box<T> = unsafe { Box::from_raw(val_raw) };
-        Ok(val_box)
-    } else if let CtlValue::Node(val) = val_enum {
-        // Make sure we got correct data size
-        assert_eq!(
-            mem::size_of::<T>(),
-            val.len(),
-            "Error memory size mismatch. Size of struct {}, size of data retrieved {}.",
-            mem::size_of::<T>(),
-            val.len()
-        );
-
-        // val is Vec<u8>
-        let val_array: Box<[u8]> = val.into_boxed_slice();
-        let val_raw: *mut T = Box::into_raw(val_array) as *mut T;
-        let val_box: Box<T> = unsafe { Box::from_raw(val_raw) };
-        Ok(val_box)
-    } else {
-        Err(SysctlError::ExtractionError)
-    }
+pub fn value_oid_as<T: SysctlPod>(oid: &Vec<i32>) -> Result<Box<T>, SysctlError> {
+    let val_enum = value_oid(oid)?;
+    value_into_box(val_enum)
 }
 
 /// A generic function that takes an OID as argument and
@@ -1136,6 +1983,9 @@ pub fn value_oid_as<T>(oid: &Vec<i32>) -> Result<Box<T>, SysctlError> {
 ///     profhz: c_int, /* profiling clock frequency */
 /// }
 ///
+/// // Only integers, so any bytes are a valid ClockInfo
+/// unsafe impl sysctl::SysctlPod for ClockInfo {}
+///
 /// #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 /// fn main() {
 ///     let oid = vec![libc::CTL_KERN, libc::KERN_CLOCKRATE];
@@ -1146,49 +1996,144 @@ pub fn value_oid_as<T>(oid: &Vec<i32>) -> Result<Box<T>, SysctlError> {
 ///     let mut oid = vec![libc::CTL_KERN, libc::KERN_CLOCKRATE];
 ///     println!("{:?}", sysctl::value_oid_as::<ClockInfo>(&mut oid));
 /// }
+/// # #[cfg(target_os = "linux")]
+/// # fn main() {}
 /// ```
 #[cfg(any(target_os = "macos", target_os = "linux"))]
-pub fn value_oid_as<T>(oid: &mut Vec<i32>) -> Result<Box<T>, SysctlError> {
-    let val_enum = try!(value_oid(oid));
+pub fn value_oid_as<T: SysctlPod>(oid: &mut Vec<i32>) -> Result<Box<T>, SysctlError> {
+    let val_enum = value_oid(oid)?;
+    value_into_box(val_enum)
+}
 
-    // Some structs are apparently reported as Node so this check is invalid..
-    // let ctl_type = CtlType::from(&val_enum);
-    // assert_eq!(CtlType::Struct, ctl_type, "Error type is not struct/opaque");
+// How often fetching a value is retried when it grew in between.
+#[cfg(not(target_os = "linux"))]
+const FETCH_RETRIES: usize = 10;
+
+// Read the raw value of oid. The buffer is at least min_size bytes long,
+// so the fixed size types can always be decoded from it.
+//
+// Tables like kern.proc.all can grow or shrink between asking for the
+// size and fetching them. The buffer leaves some slack for entries added
+// in between, and if the value outgrew it anyway, the kernel fails with
+// ENOMEM and the fetch is retried a few times before giving up.
+#[cfg(not(target_os = "linux"))]
+fn fetch_oid(oid: &[c_int], min_size: usize) -> Result<Vec<u8>, SysctlError> {
+    let mut mib = oid.to_vec();
+
+    for _ in 0..FETCH_RETRIES {
+        // First get size of value in bytes
+        let mut val_len = 0;
+        if unsafe { meta::raw(&mut mib, ptr::null_mut(), &mut val_len, &[]) } < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+        let buf_len = val_len + val_len / 10;
 
-    // TODO: refactor this when we have better clue to what's going on
-    if let CtlValue::Struct(val) = val_enum {
-        // Make sure we got correct data size
-        assert_eq!(
-            mem::size_of::<T>(),
-            val.len(),
-            "Error memory size mismatch. Size of struct {}, size of data retrieved {}.",
-            mem::size_of::<T>(),
-            val.len()
-        );
+        // If the length reported is shorter than the type we will convert
+        // it into, NativeEndian::read_* will panic. Therefore, expand the
+        // buffer to at least the size of the type.
+        let mut val: Vec<c_uchar> = vec![0; cmp::max(buf_len, min_size)];
+        let mut new_val_len = buf_len;
+        let ret = unsafe {
+            meta::raw(
+                &mut mib,
+                val.as_mut_ptr() as *mut c_void,
+                &mut new_val_len,
+                &[],
+            )
+        };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::ENOMEM) {
+                continue;
+            }
+            return Err(SysctlError::IoError(e));
+        }
 
-        // val is Vec<u8>
-        let val_array: Box<[u8]> = val.into_boxed_slice();
-        let val_raw: *mut T = Box::into_raw(val_array) as *mut T;
-        let val_box: Box<T> = unsafe { Box::from_raw(val_raw) };
-        Ok(val_box)
-    } else if let CtlValue::Node(val) = val_enum {
-        // Make sure we got correct data size
-        assert_eq!(
-            mem::size_of::<T>(),
-            val.len(),
-            "Error memory size mismatch. Size of struct {}, size of data retrieved {}.",
-            mem::size_of::<T>(),
-            val.len()
-        );
+        // Confirm that we did not read out of bounds
+        if new_val_len > buf_len {
+            return Err(SysctlError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("read {} bytes into a buffer of {}", new_val_len, buf_len),
+            )));
+        }
 
-        // val is Vec<u8>
-        let val_array: Box<[u8]> = val.into_boxed_slice();
-        let val_raw: *mut T = Box::into_raw(val_array) as *mut T;
-        let val_box: Box<T> = unsafe { Box::from_raw(val_raw) };
-        Ok(val_box)
-    } else {
-        Err(SysctlError::ExtractionError)
+        val.truncate(cmp::max(new_val_len, min_size));
+        return Ok(val);
+    }
+
+    Err(SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOMEM)))
+}
+
+/// Marker for types that can be read from the raw bytes of a sysctl
+/// value by `value_as()`.
+///
+/// # Safety
+///
+/// Implementors must be valid for any sequence of `size_of::<T>()`
+/// bytes. That holds for `#[repr(C)]` structs made up of integers,
+/// floats and arrays of them, padding is fine. It does not hold for
+/// references, pointers, `bool`, enums or types with a `Drop` impl.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// #[repr(C)]
+/// struct LoadAvg {
+///     ldavg: [u32; 3],
+///     fscale: u64,
+/// }
+/// unsafe impl sysctl::SysctlPod for LoadAvg {}
+/// # fn main() {}
+/// ```
+///
+/// Types not marked as such are rejected at compile time:
+/// ```compile_fail
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let _ = sysctl::value_as::<String>("kern.ostype");
+/// }
+/// ```
+pub unsafe trait SysctlPod: Sized {}
+
+unsafe impl SysctlPod for u8 {}
+unsafe impl SysctlPod for i8 {}
+unsafe impl SysctlPod for u16 {}
+unsafe impl SysctlPod for i16 {}
+unsafe impl SysctlPod for u32 {}
+unsafe impl SysctlPod for i32 {}
+unsafe impl SysctlPod for u64 {}
+unsafe impl SysctlPod for i64 {}
+unsafe impl SysctlPod for usize {}
+unsafe impl SysctlPod for isize {}
+unsafe impl SysctlPod for f32 {}
+unsafe impl SysctlPod for f64 {}
+unsafe impl<T: SysctlPod, const N: usize> SysctlPod for [T; N] {}
+
+// Copy the raw bytes of a Struct or Node value into a T.
+fn value_into_box<T: SysctlPod>(val_enum: CtlValue) -> Result<Box<T>, SysctlError> {
+    // Some structs are apparently reported as Node so this check is invalid..
+    // let ctl_type = CtlType::from(&val_enum);
+    // assert_eq!(CtlType::Struct, ctl_type, "Error type is not struct/opaque");
+
+    let val = match val_enum {
+        CtlValue::Struct(val) | CtlValue::Node(val) => val,
+        _ => return Err(SysctlError::ExtractionError),
+    };
+
+    // Make sure we got correct data size
+    if val.len() != mem::size_of::<T>() {
+        return Err(SysctlError::SizeMismatch {
+            expected: mem::size_of::<T>(),
+            got: val.len(),
+        });
     }
+
+    // The bytes are not aligned for T, copy them into a proper
+    // allocation. T is valid for any bytes of its size.
+    let value: T = unsafe { ptr::read_unaligned(val.as_ptr() as *const T) };
+    Ok(Box::new(value))
 }
 
 /// Sets the value of a sysctl.
@@ -1208,7 +2153,7 @@ pub fn value_oid_as<T>(oid: &mut Vec<i32>) -> Result<Box<T>, SysctlError> {
 /// ```
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 pub fn set_value(name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
-    let oid = try!(name2oid(name));
+    let oid = name2oid(name)?;
     set_oid_value(&oid, value)
 }
 
@@ -1224,108 +2169,167 @@ pub fn set_value(name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
 ///     println!("{:?}", sysctl::set_value("hw.usb.debug", sysctl::CtlValue::Int(1)));
 /// }
 /// ```
-#[cfg(any(target_os = "macos", target_os = "linux"))]
+#[cfg(target_os = "macos")]
 pub fn set_value(name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
-    let mut oid = try!(name2oid(name));
+    let mut oid = name2oid(name)?;
     set_oid_value(&mut oid, value)
 }
 
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-pub fn set_oid_value(oid: &Vec<c_int>, value: CtlValue) -> Result<CtlValue, SysctlError> {
-    let info: CtlInfo = try!(oidfmt(&oid));
+/// Sets the value of a sysctl.
+/// Fetches and returns the new value if successful, or a SysctlError
+/// on failure
+///
+/// The value is written to `/proc/sys`, see the `linux` module.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+///
+/// fn main() {
+///     println!("{:?}", sysctl::set_value("net.ipv4.ip_forward", sysctl::CtlValue::Int(1)));
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn set_value(name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
+    linux::set_value(name, value)
+}
 
+// Check that the value can be written to the sysctl and encode it the way
+// the kernel expects it.
+fn encode_value(info: &CtlInfo, value: CtlValue) -> Result<Vec<u8>, SysctlError> {
     // Check if the value is writeable
-    if !(info.flags & CTLFLAG_WR == CTLFLAG_WR) {
+    if info.flags & CTLFLAG_WR != CTLFLAG_WR {
         return Err(SysctlError::NoWriteAccess);
     }
 
+    // Temperatures are stored as integers
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    let value = match value {
+        CtlValue::Temperature(t) if info.is_temperature() => temperature_value(info, t)?,
+        value => value,
+    };
+
     let ctl_type = CtlType::from(&value);
-    assert_eq!(
-        info.ctl_type, ctl_type,
-        "Error type mismatch. Type given {:?}, sysctl type: {:?}",
-        ctl_type, info.ctl_type
-    );
+    if info.ctl_type != ctl_type {
+        return Err(SysctlError::TypeMismatch {
+            expected: info.ctl_type,
+            got: ctl_type,
+        });
+    }
 
-    // TODO rest of the types
+    // Longs are narrower than the value on 32-bit targets
+    #[allow(clippy::unnecessary_cast)]
+    let fits = match value {
+        CtlValue::Long(v) => v as c_long as i64 == v,
+        CtlValue::Ulong(v) => v as c_ulong as u64 == v,
+        _ => true,
+    };
+    if !fits {
+        return Err(SysctlError::InvalidValue(format!("{} out of range", value)));
+    }
 
-    if let CtlValue::Int(v) = value {
-        let mut bytes = vec![];
-        bytes
-            .write_i32::<LittleEndian>(v)
-            .expect("Error parsing value to byte array");
+    // Numbers are written in the same encoding they are read in. The
+    // kernel terminates strings itself.
+    match value {
+        CtlValue::Int(_)
+        | CtlValue::Uint(_)
+        | CtlValue::Long(_)
+        | CtlValue::Ulong(_)
+        | CtlValue::S64(_)
+        | CtlValue::U64(_)
+        | CtlValue::U8(_)
+        | CtlValue::U16(_)
+        | CtlValue::U32(_)
+        | CtlValue::S8(_)
+        | CtlValue::S16(_)
+        | CtlValue::S32(_)
+        | CtlValue::String(_) => Ok(value.as_bytes().into_owned()),
+        _ => Err(SysctlError::InvalidValue(format!(
+            "values of type {:?} cannot be written",
+            ctl_type
+        ))),
+    }
+}
 
-        // Set value
-        let ret = unsafe {
-            sysctl(
-                oid.as_ptr(),
-                oid.len() as u32,
-                ptr::null_mut(),
-                ptr::null_mut(),
-                bytes.as_ptr() as *const c_void,
-                bytes.len(),
-            )
-        };
-        if ret < 0 {
-            return Err(SysctlError::IoError(io::Error::last_os_error()));
-        }
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+pub fn set_oid_value(oid: &Vec<c_int>, value: CtlValue) -> Result<CtlValue, SysctlError> {
+    let info: CtlInfo = oidfmt(oid)?;
+    set_oid_value_info(oid, &info, value)
+}
+
+// Write the value of oid, whose metadata has already been fetched.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+fn set_oid_value_info(
+    oid: &Vec<c_int>,
+    info: &CtlInfo,
+    value: CtlValue,
+) -> Result<CtlValue, SysctlError> {
+    let bytes = encode_value(info, value)?;
+
+    // Set value, OpenBSD declares the new value as mutable
+    let ret = unsafe {
+        sysctl(
+            oid.as_ptr(),
+            oid.len() as u32,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            bytes.as_ptr() as *mut c_void,
+            bytes.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
     }
 
     // Get the new value and return for confirmation
-    self::value_oid(oid)
+    value_oid_info(oid, info)
 }
 
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 pub fn set_oid_value(oid: &mut Vec<c_int>, value: CtlValue) -> Result<CtlValue, SysctlError> {
-    let info: CtlInfo = try!(oidfmt(&oid));
-
-    // Check if the value is writeable
-    if !(info.flags & CTLFLAG_WR == CTLFLAG_WR) {
-        return Err(SysctlError::NoWriteAccess);
-    }
-
-    let ctl_type = CtlType::from(&value);
-    assert_eq!(
-        info.ctl_type, ctl_type,
-        "Error type mismatch. Type given {:?}, sysctl type: {:?}",
-        ctl_type, info.ctl_type
-    );
-
-    // TODO rest of the types
+    let info: CtlInfo = oidfmt(oid)?;
+    set_oid_value_info(oid, &info, value)
+}
 
-    if let CtlValue::Int(v) = value {
-        let mut bytes = vec![];
-        bytes
-            .write_i32::<LittleEndian>(v)
-            .expect("Error parsing value to byte array");
+// Write the value of oid, whose metadata has already been fetched.
+#[cfg(target_os = "macos")]
+fn set_oid_value_info(
+    oid: &mut Vec<c_int>,
+    info: &CtlInfo,
+    value: CtlValue,
+) -> Result<CtlValue, SysctlError> {
+    let bytes = encode_value(info, value)?;
 
-        // Set value
-        #[cfg(target_os = "linux")]
-        let ret = unsafe {
-            sysctl(oid.as_mut_ptr(),
-                   oid.len() as i32,
-                   ptr::null_mut(),
-                   ptr::null_mut(),
-                   bytes.as_ptr() as *mut c_void,
-                   bytes.len())
-        };
-        #[cfg(target_os = "macos")]
-        let ret = unsafe {
-            sysctl(
-                oid.as_mut_ptr(),
-                oid.len() as u32,
-                ptr::null_mut(),
-                ptr::null_mut(),
-                bytes.as_ptr() as *mut c_void,
-                bytes.len(),
-            )
-        };
-        if ret < 0 {
-            return Err(SysctlError::IoError(io::Error::last_os_error()));
-        }
+    // Set value
+    let ret = unsafe {
+        sysctl(
+            oid.as_mut_ptr(),
+            oid.len() as u32,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            bytes.as_ptr() as *mut c_void,
+            bytes.len(),
+        )
+    };
+    if ret < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
     }
 
     // Get the new value and return for confirmation
-    self::value_oid(oid)
+    value_oid_info(oid, info)
+}
+
+// Write the value of oid. /proc/sys takes the value as text, it is only
+// encoded for the checks.
+#[cfg(target_os = "linux")]
+#[allow(clippy::ptr_arg)] // the same signature as on macOS
+fn set_oid_value_info(
+    oid: &mut Vec<c_int>,
+    info: &CtlInfo,
+    value: CtlValue,
+) -> Result<CtlValue, SysctlError> {
+    encode_value(info, value.clone())?;
+    linux::set_value(&oid2name(oid)?, value)
 }
 
 /// Returns a result containing the sysctl description if success,
@@ -1341,40 +2345,106 @@ pub fn set_oid_value(oid: &mut Vec<c_int>, value: CtlValue) -> Result<CtlValue,
 /// ```
 #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
 pub fn description(name: &str) -> Result<String, SysctlError> {
-    let oid: Vec<c_int> = try!(name2oid(name));
+    let oid: Vec<c_int> = name2oid(name)?;
     oid2description(&oid)
 }
 
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
-fn oid2description(oid: &Vec<c_int>) -> Result<String, SysctlError> {
+/// Returns a result containing the sysctl description if success,
+/// or a SysctlError on failure.
+///
+/// Linux has no descriptions in the kernel, they are taken from its
+/// documentation where it is installed, see `linux::description()`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     println!("Description: {:?}", sysctl::description("kernel.pid_max"));
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn description(name: &str) -> Result<String, SysctlError> {
+    linux::description(name)
+}
+
+/// Descriptions are not available on macOS, this always fails with
+/// `SysctlError::NotSupported`.
+#[cfg(target_os = "macos")]
+pub fn description(_name: &str) -> Result<String, SysctlError> {
+    Err(SysctlError::NotSupported)
+}
+
+/// Returns the description of the sysctl with the given OID, without
+/// resolving a name first.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// extern crate libc;
+///
+/// fn main() {
+///     let oid = [libc::CTL_KERN, libc::KERN_OSREV];
+///     println!("Description: {:?}", sysctl::description_oid(&oid));
+/// }
+/// ```
+#[cfg(not(target_os = "macos"))]
+pub fn description_oid(oid: &[c_int]) -> Result<String, SysctlError> {
+    oid2description(oid)
+}
+
+/// Descriptions are not available on macOS, this always fails with
+/// `SysctlError::NotSupported`.
+#[cfg(target_os = "macos")]
+pub fn description_oid(_oid: &[c_int]) -> Result<String, SysctlError> {
+    Err(SysctlError::NotSupported)
+}
+
+#[cfg(target_os = "freebsd")]
+fn oid2description(oid: &[c_int]) -> Result<String, SysctlError> {
     // Request command for description
-    let mut qoid: Vec<c_int> = vec![0, 5];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_OIDDESCR];
+    qoid.extend(oid);
+
+    // Store results in u8 array
+    let mut buf: [c_uchar; BUFSIZ as usize] = [0; BUFSIZ as usize];
+    let mut buf_len = mem::size_of_val(&buf);
+    let ret = unsafe {
+        sysctl(
+            qoid.as_ptr(),
+            qoid.len() as u32,
+            buf.as_mut_ptr() as *mut c_void,
+            &mut buf_len,
+            ptr::null(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+
+    // Use buf_len - 1 so that we remove the trailing NULL
+    match str::from_utf8(&buf[..buf_len - 1]) {
+        Ok(s) => Ok(s.to_owned()),
+        Err(e) => Err(SysctlError::Utf8Error(e)),
+    }
+}
+#[cfg(target_os = "freebsd")]
+fn oid2label(oid: &Vec<c_int>) -> Result<String, SysctlError> {
+    // Request command for label
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_OIDLABEL];
     qoid.extend(oid);
 
     // Store results in u8 array
     let mut buf: [c_uchar; BUFSIZ as usize] = [0; BUFSIZ as usize];
     let mut buf_len = mem::size_of_val(&buf);
-    #[cfg(target_os = "linux")]  
-        let qoid_ptr = qoid.as_mut_ptr();
-    #[cfg(target_os = "linux")]  
-        let null_ptr = ptr::null_mut();
-    #[cfg(target_os = "linux")]  
-        let qoid_len = qoid.len() as i32;
-    
-    #[cfg(not(target_os = "linux"))] 
-        let qoid_ptr = qoid.as_ptr();
-    #[cfg(not(target_os = "linux"))] 
-        let qoid_len = qoid.len() as u32;
-    #[cfg(not(target_os = "linux"))] 
-        let null_ptr = ptr::null();
-    
     let ret = unsafe {
         sysctl(
-            qoid_ptr,
-            qoid_len,
+            qoid.as_ptr(),
+            qoid.len() as u32,
             buf.as_mut_ptr() as *mut c_void,
             &mut buf_len,
-            null_ptr,
+            ptr::null(),
             0,
         )
     };
@@ -1388,6 +2458,7 @@ fn oid2description(oid: &Vec<c_int>) -> Result<String, SysctlError> {
         Err(e) => Err(SysctlError::Utf8Error(e)),
     }
 }
+
 //NOT WORKING ON MacOS
 // #[cfg(target_os = "macos")]
 // pub fn description(name: &str) -> Result<String, String> {
@@ -1420,10 +2491,23 @@ fn oid2description(oid: &Vec<c_int>) -> Result<String, SysctlError> {
 //     }
 // }
 
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-fn oid2name(oid: &Vec<c_int>) -> Result<String, SysctlError> {
+/// Returns the name of the sysctl with the given OID, e.g. to log OIDs
+/// received from other subsystems.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// extern crate libc;
+///
+/// fn main() {
+///     let oid = [libc::CTL_KERN, libc::KERN_OSTYPE];
+///     assert_eq!(sysctl::oid2name(&oid).expect("could not get name"), "kern.ostype");
+/// }
+/// ```
+#[cfg(target_os = "freebsd")]
+pub fn oid2name(oid: &[c_int]) -> Result<String, SysctlError> {
     // Request command for name
-    let mut qoid: Vec<c_int> = vec![0, 1];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_NAME];
     qoid.extend(oid);
 
     // Store results in u8 array
@@ -1450,23 +2534,32 @@ fn oid2name(oid: &Vec<c_int>) -> Result<String, SysctlError> {
     }
 }
 
-#[cfg(any(target_os = "macos", target_os = "linux"))]
-fn oid2name(oid: &Vec<c_int>) -> Result<String, SysctlError> {
+/// Returns the name of the sysctl with the given OID, e.g. to log OIDs
+/// received from other subsystems.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// extern crate libc;
+///
+/// fn main() {
+///     let oid = [libc::CTL_KERN, libc::KERN_OSTYPE];
+///     assert_eq!(sysctl::oid2name(&oid).expect("could not get name"), "kern.ostype");
+/// }
+/// ```
+#[cfg(target_os = "macos")]
+pub fn oid2name(oid: &[c_int]) -> Result<String, SysctlError> {
     // Request command for name
-    let mut qoid: Vec<c_int> = vec![0, 1];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_NAME];
     qoid.extend(oid);
 
     // Store results in u8 array
     let mut buf: [c_uchar; BUFSIZ as usize] = [0; BUFSIZ as usize];
     let mut buf_len = mem::size_of_val(&buf);
-    #[cfg(target_os = "macos")]
-    let m_qoid_len: u32 = qoid.len() as u32;
-    #[cfg(target_os = "linux")]
-    let m_qoid_len: i32 = qoid.len()as i32;
     let ret = unsafe {
         sysctl(
             qoid.as_mut_ptr(),
-            m_qoid_len,
+            qoid.len() as u32,
             buf.as_mut_ptr() as *mut c_void,
             &mut buf_len,
             ptr::null_mut(),
@@ -1485,10 +2578,10 @@ fn oid2name(oid: &Vec<c_int>) -> Result<String, SysctlError> {
 }
 
 /// Get the next OID.
-#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+#[cfg(target_os = "freebsd")]
 pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
     // Request command for next oid
-    let mut qoid: Vec<c_int> = vec![0, 2];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_NEXT];
     qoid.extend(oid);
 
     let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
@@ -1525,24 +2618,39 @@ pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
 }
 
 /// Get the next OID.
-#[cfg(any(target_os = "macos", target_os = "linux"))]
+#[cfg(target_os = "netbsd")]
+pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
+    netbsd::next_oid(oid)
+}
+
+/// Get the next OID.
+#[cfg(target_os = "openbsd")]
+pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
+    openbsd::next_oid(oid)
+}
+
+/// Get the next OID.
+#[cfg(target_os = "linux")]
+#[allow(clippy::ptr_arg)] // the same signature as on the BSDs
+pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
+    linux::next_oid(oid)
+}
+
+/// Get the next OID.
+#[cfg(target_os = "macos")]
 pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
     // Request command for next oid
-    let mut qoid: Vec<c_int> = vec![0, 2];
+    let mut qoid: Vec<c_int> = vec![CTL_SYSCTL, CTL_SYSCTL_NEXT];
     qoid.extend(oid);
 
     let mut len: usize = CTL_MAXNAME as usize * mem::size_of::<c_int>();
 
     // We get results in this vector
     let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
-    #[cfg(target_os = "macos")]
-    let m_qoid_len: u32 = qoid.len() as u32;
-    #[cfg(target_os = "linux")]
-    let m_qoid_len: i32 = qoid.len()as i32;
     let ret = unsafe {
         sysctl(
             qoid.as_mut_ptr(),
-            m_qoid_len,
+            qoid.len() as u32,
             res.as_mut_ptr() as *mut c_void,
             &mut len,
             ptr::null_mut(),
@@ -1567,10 +2675,41 @@ pub fn next_oid(oid: &Vec<c_int>) -> Result<Option<Vec<c_int>>, SysctlError> {
     Ok(Some(res))
 }
 
+/// The value and metadata of a sysctl, read at one point in time.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CtlSnapshot {
+    /// The dotted name, e.g. `kern.osrevision`.
+    pub name: String,
+    pub oid: Vec<c_int>,
+    pub value: CtlValue,
+    pub ctl_type: CtlType,
+    pub flags: CtlFlags,
+    pub fmt: String,
+    /// `None` where descriptions are not available.
+    pub description: Option<String>,
+    /// When the value was read.
+    pub timestamp: SystemTime,
+}
+
 /// This struct represents a system control.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// A `Ctl` constructed from a name resolves the OID and fetches the type
+/// and flags once, later calls only need a single syscall for the value.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Ctl {
     pub oid: Vec<c_int>,
+    // Metadata fetched at construction, None if it is fetched on demand.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    info: Option<CtlInfo>,
+}
+
+// Two handles are the same sysctl regardless of what they have cached.
+impl PartialEq for Ctl {
+    fn eq(&self, other: &Ctl) -> bool {
+        self.oid == other.oid
+    }
 }
 
 impl FromStr for Ctl {
@@ -1578,8 +2717,12 @@ impl FromStr for Ctl {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let oid = name2oid(s)?;
+        let info = oidfmt(&oid)?;
 
-        Ok(Ctl { oid })
+        Ok(Ctl {
+            oid,
+            info: Some(info),
+        })
     }
 }
 
@@ -1600,6 +2743,53 @@ impl Ctl {
         Ctl::from_str(name)
     }
 
+    /// Construct a Ctl from an already resolved OID. No syscalls are
+    /// made, the metadata is fetched whenever it is needed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// extern crate libc;
+    /// use sysctl::Ctl;
+    ///
+    /// fn main() {
+    ///     let ctl = Ctl::from_oid(vec![libc::CTL_KERN, libc::KERN_OSTYPE]);
+    ///     println!("{:?}", ctl.value());
+    /// }
+    /// ```
+    pub fn from_oid(oid: Vec<c_int>) -> Self {
+        Ctl { oid, info: None }
+    }
+
+    // The cached metadata, or freshly fetched if there is none.
+    fn cached_info(&self) -> Result<Cow<'_, CtlInfo>, SysctlError> {
+        match self.info {
+            Some(ref info) => Ok(Cow::Borrowed(info)),
+            None => oidfmt(&self.oid).map(Cow::Owned),
+        }
+    }
+
+    /// Returns the resolved MIB of the sysctl, e.g. to pass it on to
+    /// other libc interfaces.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sysctl;
+    /// # use sysctl::Ctl;
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {
+    /// let ctl = Ctl::new("kern.ostype").expect("could not get sysctl");
+    /// assert_eq!(ctl.oid(), &[1, 1]);
+    /// # }
+    /// ```
+    pub fn oid(&self) -> &[c_int] {
+        &self.oid
+    }
+
     /// Returns a result containing the sysctl name on success, or a
     /// SysctlError on failure.
     ///
@@ -1608,13 +2798,39 @@ impl Ctl {
     /// ```
     /// # extern crate sysctl;
     /// # use sysctl::Ctl;
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {
     /// let ctl = Ctl::new("kern.osrelease").expect("could not get sysctl");
     /// assert_eq!(ctl.name().expect("could not get name"), "kern.osrelease");
+    /// # }
     /// ```
-    pub fn name(self: &Self) -> Result<String, SysctlError> {
+    pub fn name(&self) -> Result<String, SysctlError> {
         oid2name(&self.oid)
     }
 
+    /// Returns the type, format and flags of the sysctl.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sysctl;
+    /// # use sysctl::Ctl;
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {
+    /// let ctl = Ctl::new("kern.ostype").expect("could not get sysctl");
+    /// let info = ctl.info().expect("could not get info");
+    /// assert!(info.is_readable());
+    /// assert!(!info.is_writable());
+    /// # }
+    /// ```
+    pub fn info(&self) -> Result<CtlInfo, SysctlError> {
+        self.cached_info().map(Cow::into_owned)
+    }
+
     /// Returns a result containing the sysctl value type on success,
     /// or a Sysctl Error on failure.
     ///
@@ -1623,34 +2839,41 @@ impl Ctl {
     /// ```
     /// # extern crate sysctl;
     /// # use sysctl::{Ctl, CtlType};
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {
     /// let ctl = Ctl::new("kern.osrelease")
     ///     .expect("Could not get kern.osrelease sysctl");
     /// let value_type = ctl.value_type()
     ///         .expect("Could not get kern.osrelease value type");
     /// assert_eq!(value_type, CtlType::String);
+    /// # }
     /// ```
-    pub fn value_type(self: &Self) -> Result<CtlType, SysctlError> {
-        let info = oidfmt(&self.oid)?;
+    pub fn value_type(&self) -> Result<CtlType, SysctlError> {
+        let info = self.cached_info()?;
         Ok(info.ctl_type)
     }
 
     /// Returns a result containing the sysctl description if success, or an
-    /// Error on failure.
+    /// Error on failure. Fails with `SysctlError::NotSupported` on macOS.
     ///
     /// # Example
     /// ```
     /// extern crate sysctl;
     /// use sysctl::Ctl;
     ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
     /// fn main() {
     ///     let osrevision = sysctl::Ctl::new("kern.osrevision")
     ///         .expect("could not get kern.osrevision sysctl");
     ///     println!("Description: {:?}", osrevision.description())
     /// }
     /// ```
-    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd", target_os = "linux"))]
-    pub fn description(self: &Self) -> Result<String, SysctlError> {
-        oid2description(&self.oid)
+    pub fn description(&self) -> Result<String, SysctlError> {
+        description_oid(&self.oid)
     }
 
     /// Returns a result containing the sysctl value on success, or a
@@ -1661,35 +2884,18 @@ impl Ctl {
     /// extern crate sysctl;
     /// extern crate libc;
     ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
     /// fn main() {
     ///     let osrevision = sysctl::Ctl::new("kern.osrevision")
     ///         .expect("could not get kern.osrevisio sysctl");
     ///     println!("Value: {:?}", osrevision.value());
     /// }
     /// ```
-    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-    pub fn value(self: &Self) -> Result<CtlValue, SysctlError> {
-        value_oid(&self.oid)
-    }
-
-    /// Returns a result containing the sysctl value on success, or a
-    /// SysctlError on failure.
-    ///
-    /// # Example
-    /// ```
-    /// extern crate sysctl;
-    /// extern crate libc;
-    ///
-    /// fn main() {
-    ///     let osrevision = sysctl::Ctl::new("kern.osrevision")
-    ///         .expect("could not get kern.osrevisio sysctl");
-    ///     println!("Value: {:?}", osrevision.value());
-    /// }
-    /// ```
-    #[cfg(any(target_os = "macos", target_os = "linux"))]
-    pub fn value(self: &Self) -> Result<CtlValue, SysctlError> {
-        let mut oid = self.oid.clone();
-        value_oid(&mut oid)
+    pub fn value(&self) -> Result<CtlValue, SysctlError> {
+        let info = self.cached_info()?;
+        value_oid_info(&self.oid, &info)
     }
 
     /// A generic method that takes returns a result containing the sysctl
@@ -1713,6 +2919,9 @@ impl Ctl {
     ///     profhz: c_int, /* profiling clock frequency */
     /// }
     ///
+    /// // Only integers, so any bytes are a valid ClockInfo
+    /// unsafe impl sysctl::SysctlPod for ClockInfo {}
+    ///
     /// fn main() {
     ///     let clockrate = sysctl::Ctl::new("kern.clockrate")
     ///         .expect("could not get clockrate sysctl");
@@ -1720,8 +2929,8 @@ impl Ctl {
     /// }
     /// ```
     #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-    pub fn value_as<T>(self: &Self) -> Result<Box<T>, SysctlError> {
-        value_oid_as::<T>(&self.oid)
+    pub fn value_as<T: SysctlPod>(&self) -> Result<Box<T>, SysctlError> {
+        value_into_box(self.value()?)
     }
 
     /// A generic method that takes returns a result containing the sysctl
@@ -1745,6 +2954,12 @@ impl Ctl {
     ///     profhz: c_int, /* profiling clock frequency */
     /// }
     ///
+    /// // Only integers, so any bytes are a valid ClockInfo
+    /// unsafe impl sysctl::SysctlPod for ClockInfo {}
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
     /// fn main() {
     ///     let clockrate = sysctl::Ctl::new("kern.clockrate")
     ///         .expect("could not get clockrate sysctl");
@@ -1752,9 +2967,34 @@ impl Ctl {
     /// }
     /// ```
     #[cfg(any(target_os = "macos", target_os = "linux"))]
-    pub fn value_as<T>(self: &Self) -> Result<Box<T>, SysctlError> {
-        let mut oid = self.oid.clone();
-        value_oid_as::<T>(&mut oid)
+    pub fn value_as<T: SysctlPod>(&self) -> Result<Box<T>, SysctlError> {
+        value_into_box(self.value()?)
+    }
+
+    /// Decode the value of sysctls holding one of the well-known structs
+    /// like `kern.clockrate`, selected by the struct name in the format
+    /// string. See the `types` module.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let loadavg = Ctl::new("vm.loadavg").expect("could not get control");
+    ///     println!("{}", loadavg.value_struct().expect("could not decode value"));
+    /// }
+    /// ```
+    pub fn value_struct(&self) -> Result<types::StructValue, SysctlError> {
+        let info = self.cached_info()?;
+        let name = info.struct_name().ok_or(SysctlError::ExtractionError)?;
+        match value_oid_info(&self.oid, &info)? {
+            CtlValue::Struct(bytes) | CtlValue::Node(bytes) => types::decode(name, &bytes),
+            _ => Err(SysctlError::ExtractionError),
+        }
     }
 
     /// Sets the value of a sysctl.
@@ -1775,8 +3015,9 @@ impl Ctl {
     /// #   usbdebug.set_value(original).unwrap();
     /// }
     #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
-    pub fn set_value(self: &Self, value: CtlValue) -> Result<CtlValue, SysctlError> {
-        set_oid_value(&self.oid, value)
+    pub fn set_value(&self, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        let info = self.cached_info()?;
+        set_oid_value_info(&self.oid, &info, value)
     }
 
     /// Sets the value of a sysctl.
@@ -1797,9 +3038,74 @@ impl Ctl {
     /// #   usbdebug.set_value(original).unwrap();
     /// }
     #[cfg(any(target_os = "macos", target_os = "linux"))]
-    pub fn set_value(self: &Self, value: CtlValue) -> Result<CtlValue, SysctlError> {
+    pub fn set_value(&self, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        let info = self.cached_info()?;
         let mut oid = self.oid.clone();
-        set_oid_value(&mut oid, value)
+        set_oid_value_info(&mut oid, &info, value)
+    }
+
+    /// Sets the value of an opaque or struct sysctl from a `T`, the
+    /// counterpart of `value_as()`. The size of `T` has to match the size
+    /// the kernel reports for the value.
+    /// Fetches and returns the new value if successful, or returns a
+    /// SysctlError on failure.
+    ///
+    /// # Example
+    /// ```no_run
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// // A struct a driver exports, copied from its header
+    /// #[derive(Debug)]
+    /// #[repr(C)]
+    /// struct Limits {
+    ///     low: u32,
+    ///     high: u32,
+    /// }
+    /// unsafe impl sysctl::SysctlPod for Limits {}
+    ///
+    /// fn main() {
+    ///     let ctl = Ctl::new("dev.mydrv.0.limits").expect("could not get control");
+    ///     let set = ctl.set_value_as(&Limits { low: 10, high: 90 });
+    ///     println!("dev.mydrv.0.limits: -> {:?}", set);
+    /// }
+    /// ```
+    pub fn set_value_as<T: SysctlPod>(&self, val: &T) -> Result<Box<T>, SysctlError> {
+        let info = self.cached_info()?;
+        if info.flags & CTLFLAG_WR != CTLFLAG_WR {
+            return Err(SysctlError::NoWriteAccess);
+        }
+        if info.ctl_type != CtlType::Struct && info.ctl_type != CtlType::Node {
+            return Err(SysctlError::TypeMismatch {
+                expected: info.ctl_type,
+                got: CtlType::Struct,
+            });
+        }
+
+        // The kernel only accepts values of the size it reports
+        let mut mib = self.oid.clone();
+        let mut len = 0;
+        if unsafe { meta::raw(&mut mib, ptr::null_mut(), &mut len, &[]) } < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+        if len != mem::size_of::<T>() {
+            return Err(SysctlError::SizeMismatch {
+                expected: len,
+                got: mem::size_of::<T>(),
+            });
+        }
+
+        let mut bytes = vec![0u8; mem::size_of::<T>()];
+        unsafe {
+            ptr::copy_nonoverlapping(val as *const T as *const u8, bytes.as_mut_ptr(), bytes.len());
+        }
+        let mut old_len = 0;
+        if unsafe { meta::raw(&mut mib, ptr::null_mut(), &mut old_len, &bytes) } < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+
+        // Get the new value and return for confirmation
+        value_into_box(value_oid_info(&self.oid, &info)?)
     }
 
     /// Get the flags for a sysctl.
@@ -1812,6 +3118,9 @@ impl Ctl {
     /// extern crate sysctl;
     /// use sysctl::{Ctl, CtlFlags};
     ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
     /// fn main() {
     ///     let osrev = Ctl::new("kern.osrevision")
     ///         .expect("could not get control");
@@ -1823,12 +3132,360 @@ impl Ctl {
     ///     assert!(readable);
     /// }
     /// ```
-    pub fn flags(self: &Self) -> Result<CtlFlags, SysctlError> {
-        let info: CtlInfo = oidfmt(&self.oid)?;
+    pub fn flags(&self) -> Result<CtlFlags, SysctlError> {
+        let info = self.cached_info()?;
         Ok(CtlFlags::from_bits_truncate(info.flags))
     }
+
+    /// Returns the value as text: strings as they are, integers of any
+    /// width formatted in decimal.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let ctl = Ctl::new("kern.osrevision").expect("could not get control");
+    ///     println!("{}", ctl.value_string().expect("could not get value"));
+    /// }
+    /// ```
+    pub fn value_string(&self) -> Result<String, SysctlError> {
+        match self.value()? {
+            CtlValue::String(s) => Ok(s),
+            v => match numeric(&v) {
+                Some(n) => Ok(n.to_string()),
+                None => Err(SysctlError::ExtractionError),
+            },
+        }
+    }
+
+    /// Returns the value of an integer sysctl of any width as `i64`.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let ctl = Ctl::new("kern.maxproc").expect("could not get control");
+    ///     println!("{}", ctl.value_i64().expect("could not get value"));
+    /// }
+    /// ```
+    pub fn value_i64(&self) -> Result<i64, SysctlError> {
+        self.value()?.as_int().ok_or(SysctlError::ExtractionError)
+    }
+
+    /// Returns an iterator over all sysctls below this one, e.g. all of
+    /// `dev.cpu`. Only the subtree is walked.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let ipc = Ctl::new("kern.ipc").expect("could not get control");
+    ///     for ctl in ipc.children() {
+    ///         println!("{:?}", ctl.and_then(|c| c.name()));
+    ///     }
+    /// }
+    /// ```
+    pub fn children(&self) -> CtlIter {
+        CtlIter::below(self.clone())
+    }
+
+    /// Returns the next sysctl below the same parent, or `None` if this is
+    /// the last one.
+    ///
+    /// Nodes without any leaves below them are invisible to the next-OID
+    /// query and are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let ostype = Ctl::new("kern.ostype").expect("could not get control");
+    ///     if let Ok(Some(next)) = ostype.next_sibling() {
+    ///         println!("{:?}", next.name());
+    ///     }
+    /// }
+    /// ```
+    pub fn next_sibling(&self) -> Result<Option<Ctl>, SysctlError> {
+        // The OIDs are numbered in the order the files are first seen,
+        // the names are what sorts the siblings.
+        #[cfg(target_os = "linux")]
+        return linux::next_sibling(&self.oid).map(|oid| oid.map(Ctl::from_oid));
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let (&last, parent) = match self.oid.split_last() {
+                Some(split) => split,
+                None => return Ok(None),
+            };
+
+            // Siblings are sorted by number, so look from where a sibling
+            // numbered one higher would be instead of walking everything
+            // below ourselves. A leaf right there is skipped by the
+            // next-OID query, so check for it first.
+            let mut probe = parent.to_vec();
+            match last.checked_add(1) {
+                Some(number) => probe.push(number),
+                None => return Ok(None),
+            }
+            match oidfmt(&probe) {
+                Ok(ref info) if info.ctl_type != CtlType::Node => {
+                    return Ok(Some(Ctl::from_oid(probe)))
+                }
+                _ => (),
+            }
+            match next_oid(&probe)? {
+                Some(oid) if oid.starts_with(parent) => {
+                    Ok(Some(Ctl::from_oid(oid[..parent.len() + 1].to_vec())))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+
+    /// Returns the previous sysctl below the same parent, or `None` if
+    /// this is the first one.
+    ///
+    /// There is no query for the previous OID, so this walks the parent
+    /// from its start.
+    pub fn prev_sibling(&self) -> Result<Option<Ctl>, SysctlError> {
+        let parent = match self.oid.split_last() {
+            Some((_, parent)) => parent,
+            None => return Ok(None),
+        };
+        let iter = if parent.is_empty() {
+            CtlIter::root()
+        } else {
+            CtlIter::below(Ctl::from_oid(parent.to_vec()))
+        };
+
+        let mut prev: Option<Ctl> = None;
+        for ctl in iter {
+            let oid = ctl?.oid;
+            let child = &oid[..parent.len() + 1];
+            if child == &self.oid[..] {
+                return Ok(prev);
+            }
+            if prev.as_ref().map(|p| &p.oid[..]) != Some(child) {
+                prev = Some(Ctl::from_oid(child.to_vec()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the value together with all metadata of the sysctl. The
+    /// metadata is only fetched once, so this takes fewer syscalls than
+    /// calling `name()`, `value()`, `value_type()`, `flags()` and
+    /// `description()` separately.
+    ///
+    /// With the `serde` feature the snapshot can be serialized as is,
+    /// e.g. to emit it as JSON.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let osrev = Ctl::new("kern.osrevision").expect("could not get control");
+    ///     let snapshot = osrev.snapshot().expect("could not take snapshot");
+    ///     println!("{:?} = {}", snapshot.ctl_type, snapshot.value);
+    /// }
+    /// ```
+    pub fn snapshot(&self) -> Result<CtlSnapshot, SysctlError> {
+        let info = self.cached_info()?.into_owned();
+        let value = value_oid_info(&self.oid, &info)?;
+        let timestamp = SystemTime::now();
+
+        Ok(CtlSnapshot {
+            name: oid2name(&self.oid)?,
+            description: self.description().ok(),
+            oid: self.oid.clone(),
+            value,
+            ctl_type: info.ctl_type,
+            flags: CtlFlags::from_bits_truncate(info.flags),
+            fmt: info.fmt,
+            timestamp,
+        })
+    }
+
+    /// Describe the type and flags of the sysctl in the style of
+    /// `sysctl -t` listings, e.g. `"int, RW, TUN, SECURE3"`.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let osrev = Ctl::new("kern.osrevision").expect("could not get control");
+    ///     println!("{}", osrev.type_flags().expect("could not get metadata"));
+    /// }
+    /// ```
+    pub fn type_flags(&self) -> Result<String, SysctlError> {
+        let info = self.cached_info()?;
+        Ok(type_flags_string(info.ctl_type, info.flags))
+    }
+
+    /// Whether the value can be read, judging by the flags.
+    pub fn can_read(&self) -> Result<bool, SysctlError> {
+        Ok(self.cached_info()?.is_readable())
+    }
+
+    /// Predict whether setting the value would be permitted, e.g. to grey
+    /// out settings a UI can not change.
+    ///
+    /// Besides the sysctl being writable, this checks that the effective
+    /// user is root unless anybody may write it, that the securelevel is
+    /// low enough for `CTLFLAG_SECURE` sysctls, and on FreeBSD that
+    /// sysctls written from within a jail are flagged `CTLFLAG_PRISON`.
+    /// On Linux it checks the permissions of the file below `/proc/sys`
+    /// and that the process has `CAP_SYS_ADMIN`, or `CAP_NET_ADMIN` for
+    /// `net.*`. The kernel may still refuse the write for other reasons.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let ostype = Ctl::new("kern.ostype").expect("could not get control");
+    ///     assert!(!ostype.can_write().expect("could not get metadata"));
+    /// }
+    /// ```
+    pub fn can_write(&self) -> Result<bool, SysctlError> {
+        let flags = self.cached_info()?.flags;
+        if flags & CTLFLAG_WR == 0 {
+            return Ok(false);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            linux::can_write(&self.name()?)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let securelevel = match secure_level(flags) {
+                Some(_) => value("kern.securelevel")?.as_int().unwrap_or(0),
+                None => 0,
+            };
+            let euid = unsafe { libc::geteuid() };
+            Ok(write_permitted(flags, euid, securelevel, jailed()))
+        }
+    }
+
+    /// Whether the sysctl can be set as a loader tunable, i.e. flagged
+    /// `CTLFLAG_TUN`. Tunables may or may not be writable at runtime too.
+    pub fn is_tunable(&self) -> Result<bool, SysctlError> {
+        Ok(self.cached_info()?.is_tunable())
+    }
+
+    /// Whether the sysctl can be set on the running system at all, as
+    /// opposed to only as a loader tunable. See `can_write()` for whether
+    /// the current process may set it.
+    pub fn is_runtime_settable(&self) -> Result<bool, SysctlError> {
+        Ok(self.cached_info()?.is_writable())
+    }
+
+    /// Like `set_value()`, but if the sysctl can only be set at boot,
+    /// fails with `SysctlError::TunableOnly` carrying the line to add to
+    /// loader.conf(5) instead.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::{Ctl, CtlValue, SysctlError};
+    ///
+    /// fn main() {
+    /// #   let ctl = match Ctl::new("kern.maxusers") {
+    /// #       Ok(c) => c,
+    /// #       Err(e) => {
+    /// #           println!("Couldn't get kern.maxusers: {}", e);
+    /// #           return;
+    /// #       }
+    /// #   };
+    ///     match ctl.set_runtime_value(CtlValue::Int(512)) {
+    ///         Err(SysctlError::TunableOnly { loader_conf, .. }) => {
+    ///             println!("add {} to /boot/loader.conf", loader_conf)
+    ///         }
+    ///         r => println!("{:?}", r),
+    ///     }
+    /// }
+    /// ```
+    pub fn set_runtime_value(&self, value: CtlValue) -> Result<CtlValue, SysctlError> {
+        let tunable = self.cached_info()?.is_tunable();
+        match self.set_value(value.clone()) {
+            Err(SysctlError::NoWriteAccess) if tunable => {
+                Err(tunable_only_error(&self.name()?, &value)
+                    .unwrap_or(SysctlError::NoWriteAccess))
+            }
+            r => r,
+        }
+    }
+
+    /// Make a best-effort guess at the unit of the value, from the
+    /// format string and the wording of the description. Returns `None`
+    /// if no unit could be inferred.
+    ///
+    /// # Example
+    /// ```
+    /// extern crate sysctl;
+    /// use sysctl::Ctl;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {}
+    /// # #[cfg(not(target_os = "linux"))]
+    /// fn main() {
+    ///     let physmem = Ctl::new("hw.physmem").expect("could not get control");
+    ///     println!("{:?}", physmem.unit());
+    /// }
+    /// ```
+    pub fn unit(&self) -> Result<Option<Unit>, SysctlError> {
+        let info = self.cached_info()?;
+        #[cfg(not(target_os = "macos"))]
+        let description = oid2description(&self.oid).ok();
+        // Descriptions are not available on macos
+        #[cfg(target_os = "macos")]
+        let description: Option<String> = None;
+        Ok(infer_unit(&info.fmt, description.as_deref()))
+    }
 }
 
+// Where a walk of the whole tree starts, it continues with the first
+// sysctl below. Linux has no OIDs to start from, the empty OID stands for
+// /proc/sys there.
+#[cfg(not(target_os = "linux"))]
+pub(crate) const FIRST_NODE: &[c_int] = &[1];
+#[cfg(target_os = "linux")]
+pub(crate) const FIRST_NODE: &[c_int] = &[];
+
 /// An iterator over Sysctl entries.
 pub struct CtlIter {
     // if we are iterating over a Node, only include OIDs
@@ -1836,48 +3493,94 @@ pub struct CtlIter {
     // OIDs.
     base: Ctl,
     current: Ctl,
+    cancel: Option<Arc<AtomicBool>>,
+    // Set once the walk left the base, so no more OIDs are queried
+    done: bool,
 }
 
 impl CtlIter {
     /// Return an iterator over the complete sysctl tree.
     pub fn root() -> Self {
         CtlIter {
-            base: Ctl { oid: vec![] },
-            current: Ctl { oid: vec![1] },
+            base: Ctl::from_oid(vec![]),
+            current: Ctl::from_oid(FIRST_NODE.to_vec()),
+            cancel: None,
+            done: false,
         }
     }
 
     /// Return an iterator over all sysctl entries below the given node.
+    /// The iteration ends at the first OID outside of the node, the rest
+    /// of the tree is not walked.
     pub fn below(node: Ctl) -> Self {
         CtlIter {
             base: node.clone(),
             current: node,
+            cancel: None,
+            done: false,
         }
     }
+
+    /// End the iteration early once `cancel` is set. The flag is checked
+    /// before every OID, so another thread can abort a long walk.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// extern crate sysctl;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let iter = sysctl::CtlIter::root().cancel_on(cancel.clone());
+    /// cancel.store(true, Ordering::Relaxed);
+    /// assert_eq!(iter.count(), 0);
+    /// ```
+    pub fn cancel_on(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
 }
 
 impl Iterator for CtlIter {
     type Item = Result<Ctl, SysctlError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(ref cancel) = self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+        }
+
         let oid = match next_oid(&self.current.oid) {
             Ok(Some(o)) => o,
-            Err(e) => return Some(Err(e)),
-            Ok(None) => return None,
+            // The walk can not go on from here, end it after the error
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
         };
 
         // We continue iterating as long as the oid starts with the base
-        let cont = oid.starts_with(&self.base.oid);
-
-        self.current = Ctl { oid };
-
-        match cont {
-            true => Some(Ok(self.current.clone())),
-            false => None,
+        if !oid.starts_with(&self.base.oid) {
+            self.done = true;
+            return None;
         }
+
+        self.current = Ctl::from_oid(oid);
+        Some(Ok(self.current.clone()))
     }
 }
 
+impl FusedIterator for CtlIter {}
+
 /// Ctl implements the IntoIterator trait to allow for easy iteration
 /// over nodes.
 ///
@@ -1897,7 +3600,7 @@ impl IntoIterator for Ctl {
     type Item = Result<Ctl, SysctlError>;
     type IntoIter = CtlIter;
 
-    fn into_iter(self: Self) -> Self::IntoIter {
+    fn into_iter(self) -> Self::IntoIter {
         CtlIter::below(self)
     }
 }
@@ -1908,6 +3611,50 @@ mod tests {
     use super::*;
     use std::process::Command;
 
+    // The same sysctls under their names on Linux, where sysctl(8) also
+    // prints the tree differently
+    #[cfg(not(target_os = "linux"))]
+    const KERN: &str = "kern";
+    #[cfg(target_os = "linux")]
+    const KERN: &str = "kernel";
+    #[cfg(not(target_os = "linux"))]
+    const OSRELEASE: &str = "kern.osrelease";
+    #[cfg(target_os = "linux")]
+    const OSRELEASE: &str = "kernel.osrelease";
+    // Any Int
+    #[cfg(not(target_os = "linux"))]
+    const OSREVISION: &str = "kern.osrevision";
+    #[cfg(target_os = "linux")]
+    const OSREVISION: &str = "kernel.pid_max";
+    #[cfg(not(target_os = "linux"))]
+    const VERSION: &str = "kern.version";
+    #[cfg(target_os = "linux")]
+    const VERSION: &str = "kernel.version";
+    #[cfg(not(target_os = "linux"))]
+    const ITERATE: &str = "security";
+    #[cfg(target_os = "linux")]
+    const ITERATE: &str = "user";
+    #[cfg(not(target_os = "linux"))]
+    const SEPARATOR: &str = ": ";
+    #[cfg(target_os = "linux")]
+    const SEPARATOR: &str = " = ";
+
+    #[test]
+    fn ctl_strict_decode() {
+        let invalid = b"ab\xffc";
+        assert_eq!(decode_utf8(b"abc").unwrap(), "abc");
+        assert_eq!(decode_utf8(invalid).unwrap(), "ab\u{fffd}c");
+        set_strict(true);
+        let strict = decode_utf8(invalid);
+        let valid = decode_utf8(b"abc");
+        set_strict(false);
+        match strict {
+            Err(SysctlError::Utf8Error(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        assert_eq!(valid.unwrap(), "abc");
+    }
+
     #[test]
     #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
     fn ctl_mib() {
@@ -1925,8 +3672,9 @@ mod tests {
         let name = oid2name(&oid).expect("Could not get name of kern.osrevision sysctl.");
 
         assert_eq!(name, "kern.osrevision");
+        assert!(oid2name(&[libc::CTL_KERN, 0x7fff_ffff]).is_err());
 
-        let ctl = Ctl { oid };
+        let ctl = Ctl::from_oid(oid);
         let name = ctl
             .name()
             .expect("Could not get name of kern.osrevision sysctl.");
@@ -1935,26 +3683,26 @@ mod tests {
 
     #[test]
     fn ctl_type() {
-        let oid = name2oid("kern").unwrap();
+        let oid = name2oid(KERN).unwrap();
         let fmt = oidfmt(&oid).unwrap();
         assert_eq!(fmt.ctl_type, CtlType::Node);
-        let kern = Ctl::new("kern").expect("Could not get kern node");
+        let kern = Ctl::new(KERN).expect("Could not get kern node");
         let value_type = kern.value_type().expect("Could not get kern value type");
         assert_eq!(value_type, CtlType::Node);
 
-        let oid = name2oid("kern.osrelease").unwrap();
+        let oid = name2oid(OSRELEASE).unwrap();
         let fmt = oidfmt(&oid).unwrap();
         assert_eq!(fmt.ctl_type, CtlType::String);
-        let osrelease = Ctl::new("kern.osrelease").expect("Could not get kern.osrelease sysctl");
+        let osrelease = Ctl::new(OSRELEASE).expect("Could not get kern.osrelease sysctl");
         let value_type = osrelease
             .value_type()
             .expect("Could notget kern.osrelease value type");
         assert_eq!(value_type, CtlType::String);
 
-        let oid = name2oid("kern.osrevision").unwrap();
+        let oid = name2oid(OSREVISION).unwrap();
         let fmt = oidfmt(&oid).unwrap();
         assert_eq!(fmt.ctl_type, CtlType::Int);
-        let osrevision = Ctl::new("kern.osrevision").expect("Could not get kern.osrevision sysctl");
+        let osrevision = Ctl::new(OSREVISION).expect("Could not get kern.osrevision sysctl");
         let value_type = osrevision
             .value_type()
             .expect("Could notget kern.osrevision value type");
@@ -1963,7 +3711,7 @@ mod tests {
 
     #[test]
     fn ctl_flags() {
-        let oid = name2oid("kern.osrelease").unwrap();
+        let oid = name2oid(OSRELEASE).unwrap();
         let fmt = oidfmt(&oid).unwrap();
 
         assert_eq!(fmt.flags & CTLFLAG_RD, CTLFLAG_RD);
@@ -1974,19 +3722,19 @@ mod tests {
     fn ctl_value_int() {
         let output = Command::new("sysctl")
             .arg("-n")
-            .arg("kern.osrevision")
+            .arg(OSREVISION)
             .output()
             .expect("failed to execute process");
         let rev_str = String::from_utf8_lossy(&output.stdout);
         let rev = rev_str.trim().parse::<i32>().unwrap();
-        let n = match value("kern.osrevision") {
+        let n = match value(OSREVISION) {
             Ok(CtlValue::Int(n)) => n,
             Ok(_) => 0,
             Err(_) => 0,
         };
         assert_eq!(n, rev);
 
-        let ctl = Ctl::new("kern.osrevision").expect("Could not get kern.osrevision sysctl.");
+        let ctl = Ctl::new(OSREVISION).expect("Could not get kern.osrevision sysctl.");
         let n = match ctl.value() {
             Ok(CtlValue::Int(n)) => n,
             Ok(_) => 0,
@@ -2017,17 +3765,17 @@ mod tests {
     fn ctl_value_string() {
         let output = Command::new("sysctl")
             .arg("-n")
-            .arg("kern.version")
+            .arg(VERSION)
             .output()
             .expect("failed to execute process");
         let ver = String::from_utf8_lossy(&output.stdout);
-        let s = match value("kern.version") {
+        let s = match value(VERSION) {
             Ok(CtlValue::String(s)) => s,
             _ => "...".into(),
         };
         assert_eq!(s.trim(), ver.trim());
 
-        let kernversion = Ctl::new("kern.version").unwrap();
+        let kernversion = Ctl::new(VERSION).unwrap();
         let s = match kernversion.value() {
             Ok(CtlValue::String(s)) => s,
             _ => "...".into(),
@@ -2052,6 +3800,25 @@ mod tests {
         assert_ne!(s, "0");
     }
 
+    #[test]
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn ctl_description_oid() {
+        let oid = name2oid("hw.ncpu").unwrap();
+        assert_eq!(
+            description_oid(&oid).unwrap(),
+            description("hw.ncpu").unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn ctl_description_not_supported() {
+        match Ctl::new("kern.osrevision").unwrap().description() {
+            Err(SysctlError::NotSupported) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     #[test]
     fn ctl_temperature_ik() {
@@ -2062,14 +3829,29 @@ mod tests {
         };
         let mut val = vec![];
         // Default value (IK) in deciKelvin integer
-        val.write_i32::<LittleEndian>(3330)
+        val.write_i32::<NativeEndian>(3330)
             .expect("Error parsing value to byte array");
 
         let t = temperature(&info, &val).unwrap();
         if let CtlValue::Temperature(tt) = t {
-            assert!(tt.kelvin() - 333.0 < 0.1);
-            assert!(tt.celsius() - 59.85 < 0.1);
-            assert!(tt.fahrenheit() - 139.73 < 0.1);
+            assert_eq!(tt.raw(), 3330);
+            assert_eq!(tt.precision(), 1);
+            assert_eq!(tt.millikelvin(), 333000);
+            assert!((tt.kelvin() - 333.0).abs() < 0.01);
+            assert!((tt.celsius() - 59.85).abs() < 0.01);
+            assert!((tt.fahrenheit() - 139.73).abs() < 0.01);
+            assert_eq!(tt.to_string(), "59.9°C");
+        } else {
+            assert!(false);
+        }
+
+        // Uncalibrated sensors may report negative values
+        let mut val = vec![];
+        val.write_i32::<NativeEndian>(-5).unwrap();
+        let t = temperature(&info, &val).unwrap();
+        if let CtlValue::Temperature(tt) = t {
+            assert_eq!(tt.millikelvin(), -500);
+            assert_eq!(tt.display(TemperatureUnit::Kelvin).to_string(), "-0.5K");
         } else {
             assert!(false);
         }
@@ -2085,17 +3867,504 @@ mod tests {
         };
         let mut val = vec![];
         // Set value in milliKelvin
-        val.write_i32::<LittleEndian>(333000)
+        val.write_i32::<NativeEndian>(333000)
             .expect("Error parsing value to byte array");
 
         let t = temperature(&info, &val).unwrap();
         if let CtlValue::Temperature(tt) = t {
-            assert!(tt.kelvin() - 333.0 < 0.1);
+            assert_eq!(tt.millikelvin(), 333000);
+            assert_eq!(tt, Temperature::from_raw(3330, 1));
+            assert_eq!(tt.display(TemperatureUnit::Kelvin).to_string(), "333.000K");
         } else {
             assert!(false);
         }
     }
 
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    #[test]
+    fn ctl_temperature_value() {
+        let info = |ctl_type, fmt: &str| CtlInfo {
+            ctl_type,
+            fmt: fmt.into(),
+            flags: CTLFLAG_RW,
+        };
+        let ik = info(CtlType::Int, "IK");
+        let ik3 = info(CtlType::Int, "IK3");
+        let t = Temperature::from_raw(348150, 3);
+        let negative = Temperature::from_raw(-348150, 3);
+
+        assert_eq!(temperature_value(&ik, t).unwrap(), CtlValue::Int(3482));
+        assert_eq!(temperature_value(&ik, negative).unwrap(), CtlValue::Int(-3482));
+        assert_eq!(temperature_value(&ik3, t).unwrap(), CtlValue::Int(348150));
+        assert_eq!(
+            temperature_value(&info(CtlType::U64, "IK4"), t).unwrap(),
+            CtlValue::U64(3481500)
+        );
+        assert!(temperature_value(&info(CtlType::U8, "IK"), t).is_err());
+        assert!(temperature_value(&info(CtlType::Uint, "IK"), negative).is_err());
+
+        // Reading it back gives the same temperature
+        let bytes = temperature_value(&ik3, t).unwrap().as_bytes().into_owned();
+        assert_eq!(temperature(&ik3, &bytes).unwrap(), CtlValue::Temperature(t));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn ctl_temperature_display() {
+        let t = Temperature::from_raw(3005, 1);
+        assert_eq!(t.to_string(), "27.4°C");
+        assert_eq!(format!("{:.2}", t), "27.35°C");
+        assert_eq!(t.display(TemperatureUnit::Kelvin).to_string(), "300.5K");
+        assert_eq!(t.display(TemperatureUnit::Fahrenheit).to_string(), "81.2°F");
+        assert_eq!(CtlValue::Temperature(t).to_string(), "27.4°C");
+
+        assert_eq!(Temperature::from_raw(-2731, 1).to_string(), "-546.2°C");
+        assert_eq!(Temperature::from_raw(300500, 3), t);
+        assert!(Temperature::from_raw(300501, 3) > t);
+        assert_eq!(Temperature::from_raw(3005, 4).millikelvin(), 300);
+    }
+
+    #[test]
+    fn ctl_type_flags_string() {
+        assert_eq!(type_flags_string(CtlType::Int, CTLFLAG_RD), "int, RD");
+        assert_eq!(
+            type_flags_string(CtlType::Int, CTLFLAG_RWTUN | CTLFLAG_SECURE3),
+            "int, RW, SECURE3, TUN"
+        );
+        assert_eq!(
+            type_flags_string(CtlType::Node, CTLFLAG_RD | CTLFLAG_MPSAFE),
+            "node, RD, MPSAFE"
+        );
+        assert_eq!(type_flags_string(CtlType::String, CTLFLAG_SECURE), "string, SECURE1");
+    }
+
+    #[test]
+    fn ctl_flags_display() {
+        let flags = CtlFlags::from_bits_truncate(CTLFLAG_RWTUN | CTLFLAG_SECURE3 | CTLFLAG_SKIP);
+        assert_eq!(flags.to_string(), "RW, SECURE3, TUN, SKIP");
+        assert_eq!(flags.secure_level(), Some(3));
+        assert!(flags.contains(CtlFlags::SKIP));
+        assert!(!flags.contains(CtlFlags::DORMANT));
+        assert_eq!(CtlFlags::RD.secure_level(), None);
+        assert_eq!(CtlFlags::empty().to_string(), "");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_write_permitted() {
+        assert!(write_permitted(CTLFLAG_RW, 0, 0, false));
+        assert!(!write_permitted(CTLFLAG_RD, 0, 0, false));
+        assert!(!write_permitted(CTLFLAG_RW, 1001, 0, false));
+        assert!(write_permitted(CTLFLAG_RW | CTLFLAG_ANYBODY, 1001, 0, false));
+        // SECURE1 only while the securelevel is 0 or below
+        assert!(write_permitted(CTLFLAG_RW | CTLFLAG_SECURE, 0, 0, false));
+        assert!(write_permitted(CTLFLAG_RW | CTLFLAG_SECURE, 0, -1, false));
+        assert!(!write_permitted(CTLFLAG_RW | CTLFLAG_SECURE, 0, 1, false));
+        assert!(write_permitted(CTLFLAG_RW | CTLFLAG_SECURE3, 0, 2, false));
+        assert!(!write_permitted(CTLFLAG_RW | CTLFLAG_SECURE3, 0, 3, false));
+        assert!(!write_permitted(CTLFLAG_RW, 0, 0, true));
+        assert!(write_permitted(CTLFLAG_RW | CTLFLAG_PRISON, 0, 0, true));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_can_read_write() {
+        let ostype = Ctl::new("kern.ostype").unwrap();
+        assert!(ostype.can_read().unwrap());
+        assert!(!ostype.can_write().unwrap());
+    }
+
+    #[test]
+    fn ctl_info_accessors() {
+        let info = CtlInfo {
+            ctl_type: CtlType::Struct,
+            fmt: "S,clockinfo".into(),
+            flags: CTLFLAG_RDTUN | CTLFLAG_SECURE2 | CtlType::Struct as c_uint,
+        };
+        assert!(info.is_readable());
+        assert!(!info.is_writable());
+        assert!(info.is_tunable());
+        assert!(info.is_secure());
+        assert_eq!(info.secure_level(), Some(2));
+        assert_eq!(info.struct_name(), Some("clockinfo"));
+        assert_eq!(info.kind() & CTLTYPE, 5);
+        assert!(info.flags().contains(CtlFlags::TUN));
+
+        let info = CtlInfo {
+            ctl_type: CtlType::Int,
+            fmt: "I".into(),
+            flags: CTLFLAG_RW,
+        };
+        assert!(info.is_writable());
+        assert_eq!(info.secure_level(), None);
+        assert_eq!(info.struct_name(), None);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_siblings() {
+        let ostype = Ctl::new("kern.ostype").unwrap();
+        let osrelease = Ctl::new("kern.osrelease").unwrap();
+        assert_eq!(ostype.next_sibling().unwrap(), Some(osrelease.clone()));
+        assert_eq!(osrelease.prev_sibling().unwrap(), Some(ostype));
+
+        let kern = Ctl::new("kern").unwrap();
+        let next = kern.next_sibling().unwrap().expect("kern is not the last node");
+        assert_eq!(next.oid().len(), 1);
+        assert_eq!(next.prev_sibling().unwrap(), Some(kern));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_snapshot() {
+        let ctl = Ctl::new("kern.osrevision").unwrap();
+        let snapshot = ctl.snapshot().expect("could not take snapshot");
+        assert_eq!(snapshot.name, "kern.osrevision");
+        assert_eq!(snapshot.oid, ctl.oid);
+        assert_eq!(snapshot.value, ctl.value().unwrap());
+        assert_eq!(snapshot.ctl_type, CtlType::Int);
+        assert!(snapshot.flags.contains(CtlFlags::RD));
+        assert_eq!(snapshot.fmt, "I");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_cached_info() {
+        let ctl = Ctl::new("kern.ostype").unwrap();
+        assert!(ctl.info.is_some());
+
+        let uncached = Ctl::from_oid(ctl.oid.clone());
+        assert!(uncached.info.is_none());
+        assert_eq!(ctl, uncached);
+        assert_eq!(ctl.value().unwrap(), uncached.value().unwrap());
+        assert_eq!(ctl.flags().unwrap(), uncached.flags().unwrap());
+        assert_eq!(ctl.value_type().unwrap(), CtlType::String);
+    }
+
+    #[test]
+    fn ctl_value_as_bytes() {
+        assert_eq!(&*CtlValue::Int(-2).as_bytes(), &[0xfe, 0xff, 0xff, 0xff]);
+        assert_eq!(
+            &*CtlValue::U64(1).as_bytes(),
+            &*native(&[1, 0, 0, 0, 0, 0, 0, 0], &[0, 0, 0, 0, 0, 0, 0, 1])
+        );
+        assert_eq!(&*CtlValue::U16(0x1234).as_bytes(), &*native(&[0x34, 0x12], &[0x12, 0x34]));
+        assert_eq!(&*CtlValue::S8(-1).as_bytes(), &[0xff]);
+        assert_eq!(&*CtlValue::String("abc".into()).as_bytes(), b"abc");
+        assert_eq!(&*CtlValue::Struct(vec![1, 2, 3]).as_bytes(), &[1, 2, 3]);
+        assert!(CtlValue::None.as_bytes().is_empty());
+        #[cfg(not(target_os = "macos"))]
+        {
+            let t = Temperature::from_raw(3330, 1);
+            assert_eq!(&*CtlValue::Temperature(t).as_bytes(), &3330i32.to_ne_bytes());
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "freebsd")]
+    fn ctl_value_growing() {
+        // The process table changes size while other tests run
+        let oid = name2oid("kern.proc.all").unwrap();
+        for _ in 0..100 {
+            match value_oid(&oid).expect("could not read kern.proc.all") {
+                CtlValue::Node(v) | CtlValue::Struct(v) => assert!(!v.is_empty()),
+                v => panic!("unexpected value {:?}", v),
+            }
+        }
+    }
+
+    // Pick the fixture matching the byte order of the host.
+    fn native(little: &[u8], big: &[u8]) -> Vec<u8> {
+        if cfg!(target_endian = "little") {
+            little.to_vec()
+        } else {
+            big.to_vec()
+        }
+    }
+
+    #[test]
+    fn ctl_decode_value() {
+        let info = |ctl_type, fmt: &str| CtlInfo {
+            ctl_type,
+            fmt: fmt.into(),
+            flags: CTLFLAG_RD,
+        };
+
+        let val = native(&[0x2a, 0x01, 0, 0], &[0, 0, 0x01, 0x2a]);
+        assert_eq!(decode_value(&info(CtlType::Int, "I"), val).unwrap(), CtlValue::Int(298));
+        let val = native(&[0xfe, 0xff, 0xff, 0xff], &[0xff, 0xff, 0xff, 0xfe]);
+        assert_eq!(decode_value(&info(CtlType::Int, "I"), val).unwrap(), CtlValue::Int(-2));
+        let val = native(&[0x34, 0x12], &[0x12, 0x34]);
+        assert_eq!(decode_value(&info(CtlType::U16, "SU"), val).unwrap(), CtlValue::U16(0x1234));
+        let val = native(&[8, 7, 6, 5, 4, 3, 2, 1], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            decode_value(&info(CtlType::U64, "QU"), val).unwrap(),
+            CtlValue::U64(0x0102_0304_0506_0708)
+        );
+        let val = native(
+            &[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe],
+        );
+        assert_eq!(decode_value(&info(CtlType::S64, "Q"), val).unwrap(), CtlValue::S64(-2));
+        assert_eq!(
+            decode_value(&info(CtlType::String, "A"), b"abc\0".to_vec()).unwrap(),
+            CtlValue::String("abc".into())
+        );
+
+        // Encoding is the inverse
+        let value = CtlValue::Ulong(0x0102_0304_0506_0708);
+        let val = value.as_bytes().into_owned();
+        assert_eq!(decode_value(&info(CtlType::Ulong, "LU"), val).unwrap(), value);
+        let value = CtlValue::S64(i64::MIN);
+        let val = value.as_bytes().into_owned();
+        assert_eq!(decode_value(&info(CtlType::S64, "Q"), val).unwrap(), value);
+
+        // Short values are rejected instead of read past their end
+        match decode_value(&info(CtlType::Int, "I"), vec![1, 2]) {
+            Err(SysctlError::SizeMismatch { expected: 4, got: 2 }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(decode_value(&info(CtlType::U8, "CU"), vec![]).is_err());
+        assert!(decode_value(&info(CtlType::Ulong, "LU"), vec![0; LONG_SIZE - 1]).is_err());
+        #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+        assert!(temperature(&info(CtlType::Int, "IK"), &vec![0; 3]).is_err());
+    }
+
+    #[test]
+    fn ctl_long_array() {
+        let info = CtlInfo {
+            ctl_type: CtlType::Long,
+            fmt: "LU".into(),
+            flags: CTLFLAG_RD,
+        };
+        let value = CtlValue::LongArray(vec![1, 2, -3, 4, 5]);
+        let val = value.as_bytes().into_owned();
+        assert_eq!(val.len(), 40);
+        assert_eq!(decode_value(&info, val).unwrap(), value);
+        assert_eq!(value.to_string(), "1 2 -3 4 5");
+        assert_eq!(CtlType::from(&value), CtlType::Long);
+        assert_eq!(value.as_int(), None);
+
+        // A single element stays a scalar
+        let val = CtlValue::Long(7).as_bytes().into_owned();
+        assert_eq!(decode_value(&info, val).unwrap(), CtlValue::Long(7));
+
+        assert_eq!(value_from_str(CtlType::Long, "1 2 -3 4 5").unwrap(), value);
+        assert_eq!(
+            value_from_str(CtlType::Ulong, " 1  2 ").unwrap(),
+            CtlValue::UlongArray(vec![1, 2])
+        );
+        assert!(value_from_str(CtlType::Ulong, "1 -2").is_err());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_value_struct() {
+        let clockrate = Ctl::new("kern.clockrate").unwrap();
+        match clockrate.value_struct().expect("could not decode kern.clockrate") {
+            types::StructValue::ClockInfo(c) => assert!(c.hz > 0),
+            v => panic!("unexpected value {:?}", v),
+        }
+        assert!(Ctl::new("kern.ostype").unwrap().value_struct().is_err());
+    }
+
+    #[test]
+    fn ctl_value_into_box() {
+        let val = value_into_box::<[u8; 4]>(CtlValue::Struct(vec![1, 2, 3, 4])).unwrap();
+        assert_eq!(*val, [1, 2, 3, 4]);
+        // Unaligned bytes are copied into an aligned allocation
+        let bytes = [0u8, 1, 2, 3, 4, 5, 6, 7, 8];
+        let val = value_into_box::<u64>(CtlValue::Struct(bytes[1..].to_vec())).unwrap();
+        assert_eq!(val.to_ne_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        match value_into_box::<[u8; 4]>(CtlValue::Node(vec![1, 2])) {
+            Err(SysctlError::SizeMismatch { expected: 4, got: 2 }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match value_into_box::<[u8; 4]>(CtlValue::Int(1)) {
+            Err(SysctlError::ExtractionError) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn ctl_set_type_mismatch() {
+        let info = CtlInfo {
+            ctl_type: CtlType::Int,
+            fmt: "I".into(),
+            flags: CTLFLAG_RW,
+        };
+        // Checked before anything is written, the OID does not matter
+        let mut oid = vec![0];
+        match set_oid_value_info(&mut oid, &info, CtlValue::String("1".into())) {
+            Err(SysctlError::TypeMismatch {
+                expected: CtlType::Int,
+                got: CtlType::String,
+            }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn ctl_set_value_as_checks() {
+        let mut info = CtlInfo {
+            ctl_type: CtlType::Int,
+            fmt: "I".into(),
+            flags: CTLFLAG_RW,
+        };
+        // Checked before anything is written, the OID does not matter
+        let ctl = Ctl {
+            oid: vec![0],
+            info: Some(info.clone()),
+        };
+        match ctl.set_value_as(&1u32) {
+            Err(SysctlError::TypeMismatch {
+                expected: CtlType::Int,
+                got: CtlType::Struct,
+            }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        info.ctl_type = CtlType::Struct;
+        info.flags = CTLFLAG_RD;
+        let ctl = Ctl {
+            oid: vec![0],
+            info: Some(info),
+        };
+        match ctl.set_value_as(&[0u8; 16]) {
+            Err(SysctlError::NoWriteAccess) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn ctl_tunable_only() {
+        match tunable_only_error("kern.maxusers", &CtlValue::Int(512)) {
+            Some(SysctlError::TunableOnly { name, loader_conf }) => {
+                assert_eq!(name, "kern.maxusers");
+                assert_eq!(loader_conf, "kern.maxusers=\"512\"");
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+        let e = tunable_only_error("kern.vty", &CtlValue::String("sc".into())).unwrap();
+        assert_eq!(
+            e.to_string(),
+            "kern.vty can only be set at boot, add kern.vty=\"sc\" to /boot/loader.conf"
+        );
+        assert!(tunable_only_error("kern.vty", &CtlValue::Struct(vec![0])).is_none());
+
+        let ctl = Ctl {
+            oid: vec![0],
+            info: Some(CtlInfo::new(CtlType::Int, "I", CTLFLAG_RDTUN)),
+        };
+        assert!(ctl.is_tunable().unwrap());
+        assert!(!ctl.is_runtime_settable().unwrap());
+    }
+
+    #[test]
+    fn ctl_value_accessors() {
+        assert_eq!(CtlValue::Int(-2).as_int(), Some(-2));
+        assert_eq!(CtlValue::Int(-2).as_uint(), None);
+        assert_eq!(CtlValue::U8(7).as_uint(), Some(7));
+        assert_eq!(CtlValue::Ulong(u64::MAX).as_int(), None);
+        assert_eq!(CtlValue::Ulong(u64::MAX).as_uint(), Some(u64::MAX));
+        assert_eq!(CtlValue::String("1".into()).as_int(), None);
+        assert_eq!(CtlValue::String("abc".into()).as_string(), Some("abc"));
+        assert_eq!(CtlValue::Int(1).as_string(), None);
+        assert_eq!(CtlValue::S64(-1).as_int(), Some(-1));
+        assert_eq!(CtlValue::S64(-1).as_uint(), None);
+    }
+
+    #[test]
+    fn ctl_value_convert() {
+        assert_eq!(CtlValue::S64(-5).convert(CtlType::Int).unwrap(), CtlValue::Int(-5));
+        assert_eq!(
+            CtlValue::Ulong(u64::MAX).convert(CtlType::U64).unwrap(),
+            CtlValue::U64(u64::MAX)
+        );
+        assert!(CtlValue::Ulong(u64::MAX).convert(CtlType::S64).is_err());
+        assert!(CtlValue::S64(-1).convert(CtlType::Ulong).is_err());
+        assert!(CtlValue::U16(300).convert(CtlType::U8).is_err());
+        assert!(CtlValue::String("1".into()).convert(CtlType::Int).is_err());
+        assert!(CtlValue::Int(1).convert(CtlType::String).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn ctl_flags_serde() {
+        extern crate serde_json;
+
+        let flags = CtlFlags::from_bits_truncate(CTLFLAG_RWTUN | CTLFLAG_SECURE3 | CTLFLAG_MPSAFE);
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#"["RW","SECURE3","TUN","MPSAFE"]"#);
+        assert_eq!(serde_json::from_str::<CtlFlags>(&json).unwrap(), flags);
+        assert!(serde_json::from_str::<CtlFlags>(r#"["RW","BOGUS"]"#).is_err());
+
+        let ctl = Ctl::from_oid(vec![1, 1]);
+        let json = serde_json::to_string(&ctl).unwrap();
+        assert_eq!(serde_json::from_str::<Ctl>(&json).unwrap(), ctl);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn ctl_snapshot_serde() {
+        extern crate serde_json;
+
+        let value = CtlValue::String("FreeBSD".into());
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"String":"FreeBSD"}"#);
+        assert_eq!(serde_json::from_str::<CtlValue>(&json).unwrap(), value);
+
+        let temperature = CtlValue::Temperature(Temperature::from_raw(3005, 1));
+        let json = serde_json::to_string(&temperature).unwrap();
+        assert_eq!(serde_json::from_str::<CtlValue>(&json).unwrap(), temperature);
+
+        let snapshot = CtlSnapshot {
+            name: "kern.osrevision".into(),
+            oid: vec![1, 3],
+            value: CtlValue::Int(199506),
+            ctl_type: CtlType::Int,
+            flags: CtlFlags::RD,
+            fmt: "I".into(),
+            description: Some("Operating system revision".into()),
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.starts_with(r#"{"name":"kern.osrevision","#));
+        assert_eq!(serde_json::from_str::<CtlSnapshot>(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn ctl_children() {
+        let ipc = Ctl::new("kern.ipc").unwrap();
+        let mut children = ipc.children();
+        let count = children
+            .by_ref()
+            .map(|c| c.expect("could not get child"))
+            .inspect(|c| assert!(c.oid.starts_with(&ipc.oid)))
+            .count();
+        assert!(count > 0);
+        assert!(children.next().is_none());
+    }
+
+    #[test]
+    fn ctl_iter_cancel() {
+        let cancel = Arc::new(AtomicBool::new(true));
+        assert!(CtlIter::root().cancel_on(cancel).next().is_none());
+    }
+
+    #[test]
+    fn ctl_infer_unit() {
+        assert_eq!(infer_unit("IK", None), Some(Unit::Kelvin));
+        assert_eq!(infer_unit("IK3", Some("CPU temperature")), Some(Unit::Kelvin));
+        assert_eq!(infer_unit("LU", Some("Physical memory size in bytes")), Some(Unit::Bytes));
+        assert_eq!(infer_unit("IU", Some("Maximum number of pages")), Some(Unit::Pages));
+        assert_eq!(infer_unit("I", Some("Delay (ms) before retrying")), Some(Unit::Milliseconds));
+        assert_eq!(infer_unit("I", Some("Percent of memory reserved")), Some(Unit::Percent));
+        assert_eq!(infer_unit("I", Some("Maximum number of files")), None);
+        assert_eq!(infer_unit("I", None), None);
+    }
+
     #[test]
     fn ctl_iterate_all() {
         let root = CtlIter::root();
@@ -2110,12 +4379,12 @@ mod tests {
     #[test]
     fn ctl_iterate() {
         let output = Command::new("sysctl")
-            .arg("security")
+            .arg(ITERATE)
             .output()
             .expect("failed to execute process");
         let expected = String::from_utf8_lossy(&output.stdout);
 
-        let security = Ctl::new("security").expect("could not get security node");
+        let security = Ctl::new(ITERATE).expect("could not get security node");
 
         let ctls = CtlIter::below(security);
         let mut actual: Vec<String> = vec!["".to_string()];
@@ -2159,6 +4428,8 @@ mod tests {
                 CtlValue::Struct(_) => "(opaque struct)".to_owned(),
                 CtlValue::Node(_) => "(node)".to_owned(),
                 CtlValue::String(s) => s.to_owned(),
+                CtlValue::LongArray(a) => format!("{:?}", a),
+                CtlValue::UlongArray(a) => format!("{:?}", a),
                 #[cfg(not(target_os = "macos"))]
                 CtlValue::Temperature(t) => format!("{} °C", t.celsius()),
             };
@@ -2180,7 +4451,7 @@ mod tests {
                 _ => {}
             };
 
-            actual.push(format!("{}: {}", name, formatted));
+            actual.push(format!("{}{}{}", name, SEPARATOR, formatted));
         }
         assert_eq!(actual.join("\n").trim(), expected.trim());
     }
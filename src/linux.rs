@@ -0,0 +1,764 @@
+//! Linux backend reading and writing `/proc/sys`.
+//!
+//! The sysctl(2) syscall has been removed from Linux, the tree is only
+//! available as files below `/proc/sys`. Dotted names are translated to
+//! paths by swapping dots and slashes, like sysctl(8) does, so
+//! `net.ipv4.conf.eth0/100.forwarding` is read from
+//! `/proc/sys/net/ipv4/conf/eth0.100/forwarding`.
+//!
+//! The functions of the crate, `Ctl` and `CtlIter` use this backend on
+//! Linux. The files have no OIDs, each component of a name is numbered
+//! in the order it is first seen below its node instead. These OIDs are
+//! only valid within the process and are not those of the removed
+//! sysctl(2). The tree is walked in the sorted order of the names.
+//!
+//! Every call of `value()` opens the file again. To sample a value often,
+//! keep it open as a `ProcFile`, which reads it with pread(2).
+//!
+//! `can_write()` predicts whether writing a sysctl would be permitted,
+//! from the permissions of its file and the capabilities of the process,
+//! without trying to write it.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::linux::ProcFile;
+//!
+//! fn main() {
+//!     for name in sysctl::linux::names("kernel").expect("could not list kernel") {
+//!         println!("{} = {:?}", name, sysctl::linux::value(&name));
+//!     }
+//!
+//!     let mut file = ProcFile::open("kernel.random.entropy_avail").expect("could not open");
+//!     for _ in 0..3 {
+//!         println!("{:?}", file.value());
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::Mutex;
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::c_int;
+
+use super::{
+    Ctl, CtlInfo, CtlIter, CtlType, CtlValue, SysctlError, CTLFLAG_ANYBODY, CTLFLAG_RD, CTLFLAG_WR,
+};
+
+const PROC_SYS: &str = "/proc/sys";
+
+// Where distributions install the kernel's sysctl documentation
+const DOC_DIRS: &[&str] = &[
+    "/usr/src/linux/Documentation/admin-guide/sysctl",
+    "/usr/share/doc/linux-doc/admin-guide/sysctl",
+];
+
+// From linux/capability.h
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_SYS_ADMIN: u32 = 21;
+
+/// Read the value of a sysctl.
+///
+/// The files hold text, a single number is returned as `Int`, or `U64`
+/// if it does not fit. Everything else, including lists of
+/// numbers, is returned as `String` with the trailing newline removed.
+pub fn value(name: &str) -> Result<CtlValue, SysctlError> {
+    let path = readable_path(name)?;
+    let text = fs::read_to_string(&path).map_err(SysctlError::IoError)?;
+    Ok(parse_value(&text))
+}
+
+/// Set the value of a sysctl. Fetches and returns the new value if
+/// successful.
+///
+/// Numbers and strings are written as text, the kernel parses them
+/// itself.
+pub fn set_value(name: &str, value: CtlValue) -> Result<CtlValue, SysctlError> {
+    let path = path(name)?;
+
+    let text = match value {
+        CtlValue::Node(_) | CtlValue::Struct(_) | CtlValue::Temperature(_) | CtlValue::None => {
+            return Err(SysctlError::InvalidValue(format!(
+                "values of type {:?} cannot be written",
+                CtlType::from(&value)
+            )))
+        }
+        ref v => v.to_string(),
+    };
+
+    let mode = fs::metadata(&path)
+        .map_err(SysctlError::IoError)?
+        .permissions()
+        .mode();
+    if mode & 0o222 == 0 {
+        return Err(SysctlError::NoWriteAccess);
+    }
+    fs::write(&path, text).map_err(SysctlError::IoError)?;
+
+    self::value(name)
+}
+
+/// Return the description of a sysctl, taken from the kernel's
+/// documentation if it is installed. Returns an empty string if the
+/// sysctl is not documented.
+///
+/// Only the top-level files like `kernel.rst` and `vm.rst` are searched,
+/// which describe the common sysctls but not e.g. those of the network
+/// stack.
+pub fn description(name: &str) -> Result<String, SysctlError> {
+    // Fail the same way as value() for sysctls that do not exist
+    let path = path(name)?;
+    fs::metadata(&path).map_err(SysctlError::IoError)?;
+
+    let top = name.split('.').next().unwrap_or("");
+    let leaf = name.rsplit('.').next().unwrap_or(name);
+
+    for dir in DOC_DIRS {
+        let doc = match fs::read_to_string(Path::new(dir).join(format!("{}.rst", top))) {
+            Ok(doc) => doc,
+            Err(_) => continue,
+        };
+        if let Some(description) = find_description(&doc, leaf) {
+            return Ok(description);
+        }
+    }
+
+    Ok(String::new())
+}
+
+/// Return the names of all sysctls below the node, sorted. An empty
+/// node lists the whole tree.
+pub fn names(node: &str) -> Result<Vec<String>, SysctlError> {
+    let iter = if node.is_empty() {
+        CtlIter::root()
+    } else if path(node)?.is_dir() {
+        CtlIter::below(Ctl::new(node)?)
+    } else {
+        // A leaf only lists itself
+        fs::metadata(path(node)?).map_err(SysctlError::IoError)?;
+        return Ok(vec![node.to_owned()]);
+    };
+
+    let mut names = vec![];
+    for ctl in iter {
+        names.push(ctl?.name()?);
+    }
+    names.sort();
+    Ok(names)
+}
+
+// The OIDs handed out so far. The components of each node are numbered
+// from 1, in the order they are first seen.
+#[derive(Default)]
+struct Oids {
+    numbers: HashMap<(Vec<c_int>, String), c_int>,
+    last: HashMap<Vec<c_int>, c_int>,
+    paths: HashMap<Vec<c_int>, String>,
+}
+
+static OIDS: Mutex<Option<Oids>> = Mutex::new(None);
+
+// Return the OID of a path relative to /proc/sys, numbering the
+// components that have not been seen yet.
+fn intern(relative: &str) -> Vec<c_int> {
+    let mut oids = OIDS.lock().unwrap_or_else(|e| e.into_inner());
+    let oids = oids.get_or_insert_with(Oids::default);
+
+    let mut oid = vec![];
+    let mut end = 0;
+    for component in relative.split('/') {
+        end += component.len();
+        let key = (oid.clone(), component.to_owned());
+        let number = match oids.numbers.get(&key) {
+            Some(&number) => number,
+            None => {
+                let last = oids.last.entry(oid.clone()).or_insert(0);
+                *last += 1;
+                oids.numbers.insert(key, *last);
+                *last
+            }
+        };
+        oid.push(number);
+        oids.paths
+            .entry(oid.clone())
+            .or_insert_with(|| relative[..end].to_owned());
+        end += 1;
+    }
+    oid
+}
+
+// The path relative to /proc/sys of an OID handed out before.
+fn relative_path(oid: &[c_int]) -> Result<String, SysctlError> {
+    let oids = OIDS.lock().unwrap_or_else(|e| e.into_inner());
+    oids.as_ref()
+        .and_then(|oids| oids.paths.get(oid).cloned())
+        .ok_or_else(not_found)
+}
+
+fn oid_path(oid: &[c_int]) -> Result<PathBuf, SysctlError> {
+    Ok(Path::new(PROC_SYS).join(relative_path(oid)?))
+}
+
+pub(crate) fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
+    fs::metadata(path(name)?).map_err(SysctlError::IoError)?;
+    Ok(intern(&swap_separators(name)))
+}
+
+/// Return the name of the sysctl with the given OID. Only OIDs this
+/// process got from a name or by walking the tree are known.
+pub fn oid2name(oid: &[c_int]) -> Result<String, SysctlError> {
+    Ok(swap_separators(&relative_path(oid)?))
+}
+
+pub(crate) fn oid2description(oid: &[c_int]) -> Result<String, SysctlError> {
+    description(&oid2name(oid)?)
+}
+
+// Directories are nodes. The access flags follow the permissions of the
+// file, the type is that of its current value.
+pub(crate) fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+    let path = oid_path(oid)?;
+    let metadata = fs::metadata(&path).map_err(SysctlError::IoError)?;
+    if metadata.is_dir() {
+        return Ok(CtlInfo::new(CtlType::Node, "N", CTLFLAG_RD));
+    }
+
+    let mode = metadata.permissions().mode();
+    let mut flags = 0;
+    if mode & 0o444 != 0 {
+        flags |= CTLFLAG_RD;
+    }
+    if mode & 0o222 != 0 {
+        flags |= CTLFLAG_WR;
+    }
+    if mode & 0o002 != 0 {
+        flags |= CTLFLAG_ANYBODY;
+    }
+
+    // Values only root can read are taken to be strings
+    let ctl_type = match fs::read_to_string(&path) {
+        Ok(text) if flags & CTLFLAG_RD != 0 => CtlType::from(&parse_value(&text)),
+        _ => CtlType::String,
+    };
+    let fmt = match ctl_type {
+        CtlType::Int => "I",
+        CtlType::U64 => "QU",
+        _ => "A",
+    };
+    Ok(CtlInfo::new(ctl_type, fmt, flags))
+}
+
+// The value of oid encoded like sysctl(2) returns it, strings with their
+// trailing NUL, for `KernelBackend`.
+pub(crate) fn read_oid(oid: &[c_int]) -> Result<Vec<u8>, SysctlError> {
+    let mut bytes = value(&oid2name(oid)?)?.as_bytes().into_owned();
+    if oidfmt(oid)?.ctl_type() == CtlType::String {
+        bytes.push(0);
+    }
+    Ok(bytes)
+}
+
+// Set oid from a value encoded like sysctl(2) takes it.
+pub(crate) fn write_oid(oid: &[c_int], bytes: &[u8]) -> Result<(), SysctlError> {
+    let value = decode_written(oidfmt(oid)?.ctl_type(), bytes)?;
+    set_value(&oid2name(oid)?, value).map(|_| ())
+}
+
+// A value as written to sysctl(2), of one of the types oidfmt() reports.
+// Unlike values read, strings carry no trailing NUL.
+fn decode_written(ctl_type: CtlType, bytes: &[u8]) -> Result<CtlValue, SysctlError> {
+    let width = |n: usize| match bytes.len() {
+        len if len == n => Ok(()),
+        len => Err(SysctlError::InvalidValue(format!(
+            "expected {} bytes for {:?}, got {}",
+            n, ctl_type, len
+        ))),
+    };
+    match ctl_type {
+        CtlType::Int => {
+            width(4)?;
+            Ok(CtlValue::Int(NativeEndian::read_i32(bytes)))
+        }
+        CtlType::U64 => {
+            width(8)?;
+            Ok(CtlValue::U64(NativeEndian::read_u64(bytes)))
+        }
+        CtlType::String => str::from_utf8(bytes)
+            .map(|s| CtlValue::String(s.to_owned()))
+            .map_err(SysctlError::Utf8Error),
+        _ => Err(SysctlError::UnknownType),
+    }
+}
+
+// The first sysctl after oid when walking the tree depth-first. An empty
+// OID stands for /proc/sys itself.
+pub(crate) fn next_oid(oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+    let mut current = if oid.is_empty() {
+        PathBuf::from(PROC_SYS)
+    } else {
+        oid_path(oid)?
+    };
+
+    // Nodes continue with their first sysctl
+    if let Some(leaf) = first_leaf(&current)? {
+        return Ok(Some(leaf));
+    }
+
+    // Otherwise with what follows the node or one of its parents
+    while current != Path::new(PROC_SYS) {
+        let parent = match current.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => break,
+        };
+        for entry in entries(&parent)? {
+            if entry.file_name() <= current.file_name() {
+                continue;
+            }
+            if entry.is_dir() {
+                if let Some(leaf) = first_leaf(&entry)? {
+                    return Ok(Some(leaf));
+                }
+            } else {
+                return leaf_oid(&entry).map(Some);
+            }
+        }
+        current = parent;
+    }
+
+    Ok(None)
+}
+
+// The sibling following the sysctl or node in the sorted order of the
+// names. Like for next_oid(), empty directories are skipped.
+pub(crate) fn next_sibling(oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+    let path = oid_path(oid)?;
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return Ok(None),
+    };
+    for entry in entries(parent)? {
+        if entry.file_name() <= path.file_name() {
+            continue;
+        }
+        if entry.is_dir() && first_leaf(&entry)?.is_none() {
+            continue;
+        }
+        return leaf_oid(&entry).map(Some);
+    }
+    Ok(None)
+}
+
+// The OID of the first sysctl below a directory, None for files and
+// empty directories.
+fn first_leaf(dir: &Path) -> Result<Option<Vec<c_int>>, SysctlError> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    for entry in entries(dir)? {
+        if entry.is_dir() {
+            if let Some(leaf) = first_leaf(&entry)? {
+                return Ok(Some(leaf));
+            }
+        } else {
+            return leaf_oid(&entry).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn leaf_oid(path: &Path) -> Result<Vec<c_int>, SysctlError> {
+    let relative = path
+        .strip_prefix(PROC_SYS)
+        .ok()
+        .and_then(|p| p.to_str())
+        .ok_or_else(not_found)?;
+    Ok(intern(relative))
+}
+
+// The entries of a directory, sorted. Directories that went away in the
+// meantime are empty.
+fn entries(dir: &Path) -> Result<Vec<PathBuf>, SysctlError> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(SysctlError::IoError(e)),
+    };
+
+    let mut entries = vec![];
+    for entry in read_dir {
+        entries.push(entry.map_err(SysctlError::IoError)?.path());
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn not_found() -> SysctlError {
+    SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT))
+}
+
+/// A sysctl file kept open to read its value repeatedly, without opening
+/// and closing it every time.
+#[derive(Debug)]
+pub struct ProcFile {
+    file: File,
+    buf: Vec<u8>,
+}
+
+impl ProcFile {
+    /// Open the file of a sysctl for reading.
+    pub fn open(name: &str) -> Result<Self, SysctlError> {
+        let path = readable_path(name)?;
+        Ok(ProcFile {
+            file: File::open(&path).map_err(SysctlError::IoError)?,
+            buf: vec![],
+        })
+    }
+
+    /// Read the current value, like `value()` does.
+    pub fn value(&mut self) -> Result<CtlValue, SysctlError> {
+        // The kernel formats the value anew for every read at offset 0
+        self.buf.clear();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = self
+                .file
+                .read_at(&mut chunk, self.buf.len() as u64)
+                .map_err(SysctlError::IoError)?;
+            if n == 0 {
+                break;
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+        let text = std::str::from_utf8(&self.buf).map_err(SysctlError::Utf8Error)?;
+        Ok(parse_value(text))
+    }
+}
+
+/// Whether this process may write the sysctl. The file must be writable
+/// for the effective user on a writable `/proc/sys`, and unless anybody
+/// may write it the process needs `CAP_NET_ADMIN` for the network stack
+/// (`net.*`) or `CAP_SYS_ADMIN` for everything else.
+///
+/// Fails if the sysctl does not exist.
+pub fn can_write(name: &str) -> Result<bool, SysctlError> {
+    let path = path(name)?;
+    let mode = fs::metadata(&path)
+        .map_err(SysctlError::IoError)?
+        .permissions()
+        .mode();
+
+    let c_path =
+        CString::new(path.as_os_str().as_bytes()).map_err(|e| SysctlError::IoError(e.into()))?;
+    // Checks the effective user like the write would, and a read-only mount
+    let ret = unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            libc::W_OK,
+            libc::AT_EACCESS,
+        )
+    };
+    if ret != 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => Ok(false),
+            _ => Err(SysctlError::IoError(e)),
+        };
+    }
+    if mode & 0o002 != 0 {
+        return Ok(true);
+    }
+
+    let status = fs::read_to_string("/proc/self/status").map_err(SysctlError::IoError)?;
+    let caps = effective_capabilities(&status).ok_or(SysctlError::ExtractionError)?;
+    let needed = if name.starts_with("net.") {
+        CAP_NET_ADMIN
+    } else {
+        CAP_SYS_ADMIN
+    };
+    Ok(caps & (1 << needed) != 0)
+}
+
+// The CapEff mask of a /proc/<pid>/status file.
+fn effective_capabilities(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+}
+
+// The path of a sysctl that can be read. Write-only values can never be
+// read, report them as such.
+fn readable_path(name: &str) -> Result<PathBuf, SysctlError> {
+    let path = path(name)?;
+    let mode = fs::metadata(&path)
+        .map_err(SysctlError::IoError)?
+        .permissions()
+        .mode();
+    if mode & 0o444 == 0 {
+        return Err(SysctlError::WriteOnly);
+    }
+    Ok(path)
+}
+
+// Translate a sysctl name to its path below /proc/sys.
+fn path(name: &str) -> Result<PathBuf, SysctlError> {
+    let relative = swap_separators(name);
+    if relative
+        .split('/')
+        .any(|c| c.is_empty() || c == "." || c == "..")
+    {
+        return Err(SysctlError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid sysctl name {:?}", name),
+        )));
+    }
+    Ok(Path::new(PROC_SYS).join(relative))
+}
+
+fn swap_separators(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '.' => '/',
+            '/' => '.',
+            c => c,
+        })
+        .collect()
+}
+
+fn parse_value(text: &str) -> CtlValue {
+    let text = text.trim_end_matches('\n');
+    if let Ok(i) = text.parse::<c_int>() {
+        CtlValue::Int(i)
+    } else if let Ok(u) = text.parse::<u64>() {
+        CtlValue::U64(u)
+    } else {
+        CtlValue::String(text.to_owned())
+    }
+}
+
+// Find the first paragraph of the section about leaf in a reStructuredText
+// document, where sections start with the name underlined by a line of
+// punctuation.
+fn find_description(doc: &str, leaf: &str) -> Option<String> {
+    let lines: Vec<&str> = doc.lines().collect();
+    let heading = lines.windows(2).position(|w| {
+        let underline = w[1].trim();
+        w[0].trim() == leaf
+            && !underline.is_empty()
+            && underline.chars().all(|c| c == '=' || c == '-' || c == '~')
+    })?;
+
+    let paragraph: Vec<&str> = lines[heading + 2..]
+        .iter()
+        .map(|l| l.trim())
+        .skip_while(|l| l.is_empty())
+        .take_while(|l| !l.is_empty())
+        .collect();
+    if paragraph.is_empty() {
+        None
+    } else {
+        Some(paragraph.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linux_path() {
+        assert_eq!(
+            path("net.ipv4.ip_forward").unwrap(),
+            Path::new("/proc/sys/net/ipv4/ip_forward")
+        );
+        assert_eq!(
+            path("net.ipv4.conf.eth0/100.forwarding").unwrap(),
+            Path::new("/proc/sys/net/ipv4/conf/eth0.100/forwarding")
+        );
+        assert!(path("").is_err());
+        assert!(path("kernel..ostype").is_err());
+        assert!(path("kernel.//.ostype").is_err());
+    }
+
+    #[test]
+    fn linux_parse_value() {
+        assert_eq!(parse_value("1\n"), CtlValue::Int(1));
+        assert_eq!(parse_value("-1\n"), CtlValue::Int(-1));
+        assert_eq!(parse_value("4294967296\n"), CtlValue::U64(4294967296));
+        assert_eq!(
+            parse_value("18446744073709551615\n"),
+            CtlValue::U64(18446744073709551615)
+        );
+        assert_eq!(
+            parse_value("4096\t131072\t6291456\n"),
+            CtlValue::String("4096\t131072\t6291456".into())
+        );
+        assert_eq!(parse_value("Linux\n"), CtlValue::String("Linux".into()));
+    }
+
+    #[test]
+    fn linux_find_description() {
+        let doc = "pid_max\n=======\n\nPID allocation wrap value.  When the kernel's next PID\nvalue reaches this value, it wraps back.\n\nSecond paragraph.\n\nrandomize_va_space\n==================\n\nThis option can be used to select the type of process\naddress space randomization.\n";
+        assert_eq!(
+            find_description(doc, "pid_max").unwrap(),
+            "PID allocation wrap value.  When the kernel's next PID value reaches this value, it wraps back."
+        );
+        assert!(find_description(doc, "randomize_va_space")
+            .unwrap()
+            .starts_with("This option"));
+        assert!(find_description(doc, "threads-max").is_none());
+    }
+
+    #[test]
+    fn linux_value() {
+        match value("kernel.ostype").expect("could not read kernel.ostype") {
+            CtlValue::String(s) => assert_eq!(s, "Linux"),
+            v => panic!("unexpected value {:?}", v),
+        }
+        match value("kernel.pid_max").expect("could not read kernel.pid_max") {
+            CtlValue::Int(i) => assert!(i > 0),
+            v => panic!("unexpected value {:?}", v),
+        }
+        assert!(value("kernel.nonexistent").is_err());
+        assert!(description("kernel.pid_max").is_ok());
+    }
+
+    #[test]
+    fn linux_oids() {
+        let kernel = name2oid("kernel").unwrap();
+        let ostype = name2oid("kernel.ostype").unwrap();
+        assert!(ostype.starts_with(&kernel));
+        assert_eq!(name2oid("kernel.ostype").unwrap(), ostype);
+        assert_eq!(oid2name(&ostype).unwrap(), "kernel.ostype");
+        assert!(name2oid("kernel.nonexistent").is_err());
+        assert!(oid2name(&[0x7fff_ffff]).is_err());
+
+        assert_eq!(oidfmt(&kernel).unwrap().ctl_type(), CtlType::Node);
+        let info = oidfmt(&ostype).unwrap();
+        assert_eq!(info.ctl_type(), CtlType::String);
+        assert_eq!(info.fmt(), "A");
+        assert!(info.is_readable() && !info.is_writable());
+        let info = oidfmt(&name2oid("kernel.pid_max").unwrap()).unwrap();
+        assert_eq!(info.ctl_type(), CtlType::Int);
+
+        // The walk visits the leaves in order and climbs out of nodes
+        let first = next_oid(&[]).unwrap().unwrap();
+        assert!(oidfmt(&first).unwrap().ctl_type() != CtlType::Node);
+        let next = next_oid(&ostype).unwrap().unwrap();
+        assert!(oid2name(&next).unwrap().as_str() > "kernel.ostype");
+        assert_eq!(
+            next_oid(&kernel).unwrap(),
+            first_leaf(Path::new("/proc/sys/kernel")).unwrap()
+        );
+    }
+
+    #[test]
+    fn linux_ctl() {
+        let ctl = Ctl::new("kernel.ostype").unwrap();
+        assert_eq!(ctl.name().unwrap(), "kernel.ostype");
+        assert_eq!(ctl.value().unwrap(), value("kernel.ostype").unwrap());
+        assert_eq!(read_oid(&ctl.oid).unwrap(), b"Linux\0".to_vec());
+        let names: Vec<String> = CtlIter::below(Ctl::new("kernel").unwrap())
+            .map(|c| c.unwrap().name().unwrap())
+            .collect();
+        assert!(names.contains(&"kernel.ostype".to_owned()));
+        assert!(names.iter().all(|n| n.starts_with("kernel.")));
+    }
+
+    #[test]
+    fn linux_ctl_iter_error() {
+        // An OID that was never handed out can not be walked from
+        let mut iter = CtlIter::below(Ctl::from_oid(vec![0x7fff_ffff]));
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn linux_siblings() {
+        let osrelease = Ctl::new("kernel.osrelease").unwrap();
+        let ostype = Ctl::new("kernel.ostype").unwrap();
+        assert_eq!(osrelease.next_sibling().unwrap(), Some(ostype.clone()));
+        assert_eq!(ostype.prev_sibling().unwrap(), Some(osrelease));
+
+        let kernel = Ctl::new("kernel").unwrap();
+        let next = kernel.next_sibling().unwrap().expect("kernel is not the last node");
+        assert_eq!(next.oid().len(), 1);
+        assert_eq!(next.prev_sibling().unwrap(), Some(kernel));
+    }
+
+    #[test]
+    fn linux_names() {
+        let kernel = names("kernel").expect("could not list kernel");
+        assert!(kernel.iter().any(|n| n == "kernel.ostype"));
+        assert!(kernel.iter().all(|n| n.starts_with("kernel.")));
+        assert_eq!(names("kernel.ostype").unwrap(), vec!["kernel.ostype"]);
+    }
+
+    #[test]
+    fn linux_proc_file() {
+        let mut file = ProcFile::open("kernel.ostype").unwrap();
+        assert_eq!(file.value().unwrap(), value("kernel.ostype").unwrap());
+        assert_eq!(file.value().unwrap(), CtlValue::String("Linux".into()));
+        assert!(ProcFile::open("kernel.nonexistent").is_err());
+    }
+
+    #[test]
+    fn linux_can_write() {
+        let status = "Name:\tcat\nCapPrm:\t0000000000000000\nCapEff:\t000001ffffffffff\nCapBnd:\t000001ffffffffff\n";
+        assert_eq!(effective_capabilities(status), Some(0x1ff_ffff_ffff));
+        assert_eq!(
+            effective_capabilities("CapEff:\t0000000000001000\n"),
+            Some(1 << CAP_NET_ADMIN)
+        );
+        assert_eq!(effective_capabilities("Name:\tcat\n"), None);
+
+        assert!(!can_write("kernel.ostype").unwrap());
+        assert!(can_write("kernel.nonexistent").is_err());
+    }
+
+    #[test]
+    fn linux_decode_written() {
+        assert_eq!(
+            decode_written(CtlType::String, b"abc").unwrap(),
+            CtlValue::String("abc".into())
+        );
+        assert_eq!(
+            decode_written(CtlType::Int, &(-2i32).to_ne_bytes()).unwrap(),
+            CtlValue::Int(-2)
+        );
+        assert_eq!(
+            decode_written(CtlType::U64, &7u64.to_ne_bytes()).unwrap(),
+            CtlValue::U64(7)
+        );
+        match decode_written(CtlType::Int, &[1, 2]) {
+            Err(SysctlError::InvalidValue(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(decode_written(CtlType::U64, &[0; 4]).is_err());
+        assert!(decode_written(CtlType::String, &[0xff]).is_err());
+    }
+
+    #[test]
+    fn linux_write_oid() {
+        // Set the domain name to what it is, where permitted
+        if !can_write("kernel.domainname").unwrap() {
+            return;
+        }
+        let oid = name2oid("kernel.domainname").unwrap();
+        let mut before = read_oid(&oid).unwrap();
+        assert_eq!(before.pop(), Some(0));
+        write_oid(&oid, &before).unwrap();
+        let mut after = read_oid(&oid).unwrap();
+        after.pop();
+        assert_eq!(after, before);
+    }
+}
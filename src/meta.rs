@@ -0,0 +1,221 @@
+//! Direct access to the meta-OID queries below OID 0.
+//!
+//! The kernel answers questions about the tree itself through OIDs of the
+//! form `0.<command>.<oid...>`. The functions of this crate use them
+//! internally; `MetaQuery` lets callers issue them without writing their
+//! own unsafe code.
+//!
+//! Linux has no sysctl(2), queries fail there with `ENOSYS`.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::meta::{MetaCommand, MetaQuery};
+//!
+//! # #[cfg(target_os = "linux")]
+//! # fn main() {}
+//! # #[cfg(not(target_os = "linux"))]
+//! fn main() {
+//!     let oid = MetaQuery::new(MetaCommand::Name2Oid)
+//!         .input(b"kern.ostype")
+//!         .run_oid()
+//!         .expect("could not resolve name");
+//!     let name = MetaQuery::new(MetaCommand::Name)
+//!         .oid(&oid)
+//!         .run_string()
+//!         .expect("could not get name");
+//!     assert_eq!(name, "kern.ostype");
+//! }
+//! ```
+
+use std::io;
+use std::mem;
+use std::ptr;
+use std::str;
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::{c_int, c_void};
+
+use super::{
+    SysctlError, CTL_SYSCTL, CTL_SYSCTL_NAME, CTL_SYSCTL_NAME2OID, CTL_SYSCTL_NEXT,
+    CTL_SYSCTL_OIDDESCR, CTL_SYSCTL_OIDFMT, CTL_SYSCTL_OIDLABEL,
+};
+
+/// The meta-OID commands.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MetaCommand {
+    /// Name of the OID.
+    Name,
+    /// The OID following the given one in the tree.
+    Next,
+    /// OID of the name passed as input.
+    Name2Oid,
+    /// Kind (type and flags) followed by the format string.
+    OidFmt,
+    /// Description of the OID.
+    OidDescr,
+    /// Label of the OID.
+    OidLabel,
+}
+
+impl MetaCommand {
+    /// The number of the command, i.e. the second component of the query.
+    pub fn code(self) -> c_int {
+        match self {
+            MetaCommand::Name => CTL_SYSCTL_NAME,
+            MetaCommand::Next => CTL_SYSCTL_NEXT,
+            MetaCommand::Name2Oid => CTL_SYSCTL_NAME2OID,
+            MetaCommand::OidFmt => CTL_SYSCTL_OIDFMT,
+            MetaCommand::OidDescr => CTL_SYSCTL_OIDDESCR,
+            MetaCommand::OidLabel => CTL_SYSCTL_OIDLABEL,
+        }
+    }
+}
+
+/// A meta-OID query, built up and then run against the kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaQuery {
+    command: MetaCommand,
+    oid: Vec<c_int>,
+    input: Vec<u8>,
+}
+
+impl MetaQuery {
+    pub fn new(command: MetaCommand) -> Self {
+        MetaQuery {
+            command,
+            oid: vec![],
+            input: vec![],
+        }
+    }
+
+    /// Set the OID the query is about.
+    pub fn oid(mut self, oid: &[c_int]) -> Self {
+        self.oid = oid.to_vec();
+        self
+    }
+
+    /// Set the data passed to the kernel, e.g. the name for `Name2Oid`.
+    pub fn input(mut self, input: &[u8]) -> Self {
+        self.input = input.to_vec();
+        self
+    }
+
+    /// The full OID of the query, `0.<command>.<oid...>`.
+    pub fn mib(&self) -> Vec<c_int> {
+        let mut mib = vec![CTL_SYSCTL, self.command.code()];
+        mib.extend(&self.oid);
+        mib
+    }
+
+    /// Run the query and return the raw reply.
+    pub fn run(&self) -> Result<Vec<u8>, SysctlError> {
+        let mut mib = self.mib();
+
+        // First get the size of the reply
+        let mut len = 0;
+        if unsafe { raw(&mut mib, ptr::null_mut(), &mut len, &self.input) } < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+
+        let mut buf: Vec<u8> = vec![0; len];
+        let ret = unsafe { raw(&mut mib, buf.as_mut_ptr() as *mut c_void, &mut len, &self.input) };
+        if ret < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Run the query and decode the reply as an OID, for `Name2Oid` and
+    /// `Next`.
+    pub fn run_oid(&self) -> Result<Vec<c_int>, SysctlError> {
+        let buf = self.run()?;
+        Ok(buf
+            .chunks_exact(mem::size_of::<c_int>())
+            .map(NativeEndian::read_i32)
+            .collect())
+    }
+
+    /// Run the query and decode the reply as a string, for `Name`,
+    /// `OidDescr` and `OidLabel`.
+    pub fn run_string(&self) -> Result<String, SysctlError> {
+        let buf = self.run()?;
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        match str::from_utf8(&buf[..end]) {
+            Ok(s) => Ok(s.to_owned()),
+            Err(e) => Err(SysctlError::Utf8Error(e)),
+        }
+    }
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+pub(crate) unsafe fn raw(
+    mib: &mut [c_int],
+    old: *mut c_void,
+    oldlen: &mut usize,
+    new: &[u8],
+) -> c_int {
+    // OpenBSD declares the new value as mutable
+    let newp = if new.is_empty() {
+        ptr::null_mut()
+    } else {
+        new.as_ptr() as *mut c_void
+    };
+    libc::sysctl(mib.as_ptr(), mib.len() as u32, old, oldlen, newp, new.len())
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) unsafe fn raw(
+    mib: &mut [c_int],
+    old: *mut c_void,
+    oldlen: &mut usize,
+    new: &[u8],
+) -> c_int {
+    let newp = if new.is_empty() {
+        ptr::null_mut()
+    } else {
+        new.as_ptr() as *mut c_void
+    };
+    libc::sysctl(mib.as_mut_ptr(), mib.len() as u32, old, oldlen, newp, new.len())
+}
+
+// sysctl(2) has been removed from Linux and current C libraries no longer
+// provide it, every call fails like the syscall did on kernels without it.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn raw(
+    _mib: &mut [c_int],
+    _old: *mut c_void,
+    _oldlen: &mut usize,
+    _new: &[u8],
+) -> c_int {
+    *libc::__errno_location() = libc::ENOSYS;
+    -1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_mib() {
+        let query = MetaQuery::new(MetaCommand::OidFmt).oid(&[1, 1]);
+        assert_eq!(query.mib(), vec![0, 4, 1, 1]);
+        assert_eq!(MetaQuery::new(MetaCommand::Name2Oid).mib(), vec![0, 3]);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn meta_name2oid() {
+        let oid = MetaQuery::new(MetaCommand::Name2Oid)
+            .input(b"kern.ostype")
+            .run_oid()
+            .unwrap();
+        assert_eq!(oid, vec![libc::CTL_KERN, libc::KERN_OSTYPE]);
+        let name = MetaQuery::new(MetaCommand::Name)
+            .oid(&oid)
+            .run_string()
+            .unwrap();
+        assert_eq!(name, "kern.ostype");
+    }
+}
@@ -0,0 +1,1158 @@
+//! Helpers for the `net` part of the tree. Only available on FreeBSD.
+
+use std::cmp;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::{c_int, pid_t, uid_t};
+
+use super::file::{open_files, FileType};
+use super::{
+    decode_utf8, fetch_oid, name2oid, set_value, value, Ctl, CtlFlags, CtlValue, SysctlError,
+};
+
+// Ports below this are reserved for privileged processes, see netinet/in.h
+const IPPORT_RESERVED: i32 = 1024;
+const IPPORT_MAX: i32 = 65535;
+
+/// The ephemeral port ranges from `net.inet.ip.portrange`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::net::PortRange;
+///
+/// fn main() {
+///     let range = PortRange::get().expect("could not read port range");
+///     println!("ephemeral ports: {}-{}", range.first, range.last);
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PortRange {
+    /// First port of the default range.
+    pub first: i32,
+    /// Last port of the default range.
+    pub last: i32,
+    /// First port of the high range.
+    pub hifirst: i32,
+    /// Last port of the high range.
+    pub hilast: i32,
+    /// First port of the reserved low range, allocated downwards.
+    pub lowfirst: i32,
+    /// Last port of the reserved low range.
+    pub lowlast: i32,
+}
+
+const FIELDS: [&str; 6] = ["first", "last", "hifirst", "hilast", "lowfirst", "lowlast"];
+
+impl PortRange {
+    /// Read the current port ranges.
+    pub fn get() -> Result<Self, SysctlError> {
+        let mut v = [0; 6];
+        for (val, field) in v.iter_mut().zip(FIELDS.iter()) {
+            *val = match value(&format!("net.inet.ip.portrange.{}", field))? {
+                CtlValue::Int(i) => i,
+                _ => return Err(SysctlError::ExtractionError),
+            };
+        }
+        Ok(PortRange {
+            first: v[0],
+            last: v[1],
+            hifirst: v[2],
+            hilast: v[3],
+            lowfirst: v[4],
+            lowlast: v[5],
+        })
+    }
+
+    /// Check that the ranges are well formed: the default and high ranges
+    /// must be non-empty and above the reserved ports, the low range must
+    /// lie within the reserved ports.
+    pub fn validate(&self) -> Result<(), SysctlError> {
+        let unreserved = |p: i32| (IPPORT_RESERVED..=IPPORT_MAX).contains(&p);
+        let reserved = |p: i32| (0..IPPORT_RESERVED).contains(&p);
+        let invalid = |msg: String| Err(SysctlError::InvalidValue(msg));
+
+        for &(name, p) in &[
+            ("first", self.first),
+            ("last", self.last),
+            ("hifirst", self.hifirst),
+            ("hilast", self.hilast),
+        ] {
+            if !unreserved(p) {
+                return invalid(format!(
+                    "portrange.{} {} outside {}-{}",
+                    name, p, IPPORT_RESERVED, IPPORT_MAX
+                ));
+            }
+        }
+        for &(name, p) in &[("lowfirst", self.lowfirst), ("lowlast", self.lowlast)] {
+            if !reserved(p) {
+                return invalid(format!(
+                    "portrange.{} {} outside 0-{}",
+                    name,
+                    p,
+                    IPPORT_RESERVED - 1
+                ));
+            }
+        }
+        if self.first > self.last {
+            return invalid(format!(
+                "portrange.first {} above portrange.last {}",
+                self.first, self.last
+            ));
+        }
+        if self.hifirst > self.hilast {
+            return invalid(format!(
+                "portrange.hifirst {} above portrange.hilast {}",
+                self.hifirst, self.hilast
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate the ranges and write all values that differ from the
+    /// current ones.
+    pub fn set(&self) -> Result<(), SysctlError> {
+        self.validate()?;
+
+        let current = PortRange::get()?;
+        let new = [
+            self.first,
+            self.last,
+            self.hifirst,
+            self.hilast,
+            self.lowfirst,
+            self.lowlast,
+        ];
+        let old = [
+            current.first,
+            current.last,
+            current.hifirst,
+            current.hilast,
+            current.lowfirst,
+            current.lowlast,
+        ];
+
+        for ((field, &new), &old) in FIELDS.iter().zip(new.iter()).zip(old.iter()) {
+            if new != old {
+                set_value(
+                    &format!("net.inet.ip.portrange.{}", field),
+                    CtlValue::Int(new),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the `dev.<driver>.<unit>` node of a network interface, e.g.
+/// `dev.em.0` for `em0`.
+///
+/// Interfaces that were renamed can not be mapped to their device.
+pub fn interface_device(ifname: &str) -> Result<Ctl, SysctlError> {
+    let split = ifname.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (driver, unit) = ifname.split_at(split);
+    if driver.is_empty() || unit.is_empty() {
+        return Err(SysctlError::InvalidValue(format!(
+            "interface {} is not of the form <driver><unit>",
+            ifname
+        )));
+    }
+
+    // Only device nodes have a %parent leaf, this rules out unrelated
+    // nodes that happen to have the same name
+    let node = format!("dev.{}.{}", driver, unit);
+    Ctl::new(&format!("{}.%parent", node))?;
+    Ctl::new(&node)
+}
+
+/// Return the writable sysctls of the device behind a network interface.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     if let Ok(tunables) = sysctl::net::interface_tunables("em0") {
+///         for ctl in tunables {
+///             println!("{:?} = {:?}", ctl.name(), ctl.value());
+///         }
+///     }
+/// }
+/// ```
+pub fn interface_tunables(ifname: &str) -> Result<Vec<Ctl>, SysctlError> {
+    let mut tunables = vec![];
+
+    for ctl in interface_device(ifname)? {
+        let ctl = ctl?;
+        if ctl.flags()?.contains(CtlFlags::WR) {
+            tunables.push(ctl);
+        }
+    }
+
+    Ok(tunables)
+}
+
+// The routing table and interface list, see sys/socket.h
+const CTL_NET: c_int = 4;
+const PF_ROUTE: c_int = 17;
+const NET_RT_DUMP: c_int = 1;
+const NET_RT_IFLIST: c_int = 3;
+
+const AF_INET: u8 = 2;
+const AF_INET6: u8 = 28;
+const AF_LINK: u8 = 18;
+
+// Routing message types and the sockaddrs following them, see net/route.h
+const RTM_VERSION: u8 = 5;
+const RTM_GET: u8 = 0x4;
+const RTM_NEWADDR: u8 = 0xc;
+const RTM_IFINFO: u8 = 0xe;
+const RTAX_DST: usize = 0;
+const RTAX_GATEWAY: usize = 1;
+const RTAX_NETMASK: usize = 2;
+const RTAX_IFP: usize = 4;
+const RTAX_IFA: usize = 5;
+const RTAX_BRD: usize = 7;
+const RTAX_MAX: usize = 8;
+
+// Offsets of the fields of struct if_msghdr and the struct if_data in it,
+// see net/if.h
+const IFM_ADDRS: usize = 4;
+const IFM_FLAGS: usize = 8;
+const IFM_INDEX: usize = 12;
+const IFM_DATA: usize = 16;
+const IFI_LINK_STATE: usize = IFM_DATA + 4;
+const IFI_MTU: usize = IFM_DATA + 8;
+const IFI_BAUDRATE: usize = IFM_DATA + 16;
+const IFI_IPACKETS: usize = IFM_DATA + 24;
+const IFI_IERRORS: usize = IFM_DATA + 32;
+const IFI_OPACKETS: usize = IFM_DATA + 40;
+const IFI_OERRORS: usize = IFM_DATA + 48;
+const IFI_COLLISIONS: usize = IFM_DATA + 56;
+const IFI_IBYTES: usize = IFM_DATA + 64;
+const IFI_OBYTES: usize = IFM_DATA + 72;
+const IFI_IMCASTS: usize = IFM_DATA + 80;
+const IFI_OMCASTS: usize = IFM_DATA + 88;
+const IFI_IQDROPS: usize = IFM_DATA + 96;
+const IFI_OQDROPS: usize = IFM_DATA + 104;
+const IFI_NOPROTO: usize = IFM_DATA + 112;
+const IF_MSGHDR_SIZE: usize = IFM_DATA + 152;
+
+// Offsets of the fields of struct ifa_msghdr
+const IFAM_ADDRS: usize = 4;
+const IFAM_INDEX: usize = 12;
+const IFA_MSGHDR_SIZE: usize = 20;
+
+// Offsets of the fields of struct rt_msghdr and the struct rt_metrics in
+// it, see net/route.h
+const RTM_INDEX: usize = 4;
+const RTM_FLAGS: usize = 8;
+const RTM_ADDRS: usize = 12;
+const RTM_RMX: usize = 40;
+const RMX_MTU: usize = RTM_RMX + 8;
+const RMX_PKSENT: usize = RTM_RMX + 72;
+const RT_MSGHDR_SIZE: usize = RTM_RMX + 112;
+
+// Offsets of the fields of struct xinpcb, the struct xsocket and the
+// struct in_conninfo in it, see netinet/in_pcb.h and sys/socketvar.h
+const XI_SOCKET: usize = 8;
+const XSO_SO: usize = XI_SOCKET + 8;
+const XSO_UID: usize = XI_SOCKET + 116;
+const XI_INC: usize = 248;
+const IE_FPORT: usize = XI_INC + 4;
+const IE_LPORT: usize = XI_INC + 6;
+const IE_FADDR: usize = XI_INC + 8;
+const IE_LADDR: usize = XI_INC + 24;
+const IE6_ZONEID: usize = XI_INC + 40;
+const XI_VFLAG: usize = 392;
+const XINPCB_SIZE: usize = 400;
+const INP_IPV6: u8 = 0x2;
+
+// Offsets of the fields of struct xtcpcb, see netinet/tcp_var.h
+const XT_INP: usize = 8;
+const XT_STATE: usize = 568;
+const XTCPCB_MINSIZE: usize = XT_STATE + 4;
+
+// Size of struct xinpgen, which comes before and after the records
+const XINPGEN_SIZE: usize = 64;
+
+/// The route is usable.
+pub const RTF_UP: i32 = 0x1;
+/// The destination is reached through a gateway.
+pub const RTF_GATEWAY: i32 = 0x2;
+/// A host route, without a netmask.
+pub const RTF_HOST: i32 = 0x4;
+/// Packets to the destination are rejected.
+pub const RTF_REJECT: i32 = 0x8;
+/// Added manually, e.g. by route(8).
+pub const RTF_STATIC: i32 = 0x800;
+/// Packets to the destination are silently dropped.
+pub const RTF_BLACKHOLE: i32 = 0x1000;
+
+/// A network interface and its counters, like `netstat -ib` shows them.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     for i in sysctl::net::interfaces().expect("could not list interfaces") {
+///         println!("{}: {} bytes in, {} bytes out", i.name, i.stats.in_bytes, i.stats.out_bytes);
+///         for a in &i.addresses {
+///             println!("    {}", a.address);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    pub name: String,
+    pub index: u16,
+    /// `IFF_*` flags.
+    pub flags: i32,
+    /// `LINK_STATE_*`, 2 if the link is up.
+    pub link_state: u8,
+    pub mtu: u32,
+    /// Line speed in bits per second.
+    pub baudrate: u64,
+    /// The link-level address, e.g. the MAC address, empty if there is
+    /// none.
+    pub link_address: Vec<u8>,
+    pub stats: InterfaceStats,
+    /// The IPv4 and IPv6 addresses.
+    pub addresses: Vec<InterfaceAddress>,
+}
+
+/// The counters of a network interface, from `struct if_data`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct InterfaceStats {
+    pub in_packets: u64,
+    pub in_errors: u64,
+    /// Packets dropped on input.
+    pub in_drops: u64,
+    pub in_bytes: u64,
+    pub in_multicasts: u64,
+    pub out_packets: u64,
+    pub out_errors: u64,
+    /// Packets dropped on output.
+    pub out_drops: u64,
+    pub out_bytes: u64,
+    pub out_multicasts: u64,
+    pub collisions: u64,
+    /// Packets for unsupported protocols.
+    pub no_proto: u64,
+}
+
+/// An address of a network interface.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    pub netmask: Option<IpAddr>,
+    /// The broadcast address, or the other end of point-to-point links.
+    pub broadcast: Option<IpAddr>,
+}
+
+/// Return the network interfaces with their counters and addresses, from
+/// the `NET_RT_IFLIST` sysctl.
+pub fn interfaces() -> Result<Vec<Interface>, SysctlError> {
+    let buf = fetch_oid(&[CTL_NET, PF_ROUTE, 0, 0, NET_RT_IFLIST, 0], 0)?;
+    decode_interfaces(&buf)
+}
+
+// Decode the RTM_IFINFO message of each interface, followed by the
+// RTM_NEWADDR messages of its addresses.
+fn decode_interfaces(buf: &[u8]) -> Result<Vec<Interface>, SysctlError> {
+    let mut interfaces: Vec<Interface> = vec![];
+
+    for (msg_type, msg) in messages(buf)? {
+        match msg_type {
+            RTM_IFINFO => {
+                let addrs = NativeEndian::read_i32(&msg[IFM_ADDRS..]);
+                let sockaddrs = sockaddrs(addrs, &msg[IF_MSGHDR_SIZE..]);
+                let (name, link_address) = match sockaddrs[RTAX_IFP] {
+                    Some(sa) => link_sockaddr(sa),
+                    None => (&[][..], vec![]),
+                };
+                interfaces.push(Interface {
+                    name: decode_utf8(name)?,
+                    index: NativeEndian::read_u16(&msg[IFM_INDEX..]),
+                    flags: NativeEndian::read_i32(&msg[IFM_FLAGS..]),
+                    link_state: msg[IFI_LINK_STATE],
+                    mtu: NativeEndian::read_u32(&msg[IFI_MTU..]),
+                    baudrate: NativeEndian::read_u64(&msg[IFI_BAUDRATE..]),
+                    link_address,
+                    stats: InterfaceStats {
+                        in_packets: NativeEndian::read_u64(&msg[IFI_IPACKETS..]),
+                        in_errors: NativeEndian::read_u64(&msg[IFI_IERRORS..]),
+                        in_drops: NativeEndian::read_u64(&msg[IFI_IQDROPS..]),
+                        in_bytes: NativeEndian::read_u64(&msg[IFI_IBYTES..]),
+                        in_multicasts: NativeEndian::read_u64(&msg[IFI_IMCASTS..]),
+                        out_packets: NativeEndian::read_u64(&msg[IFI_OPACKETS..]),
+                        out_errors: NativeEndian::read_u64(&msg[IFI_OERRORS..]),
+                        out_drops: NativeEndian::read_u64(&msg[IFI_OQDROPS..]),
+                        out_bytes: NativeEndian::read_u64(&msg[IFI_OBYTES..]),
+                        out_multicasts: NativeEndian::read_u64(&msg[IFI_OMCASTS..]),
+                        collisions: NativeEndian::read_u64(&msg[IFI_COLLISIONS..]),
+                        no_proto: NativeEndian::read_u64(&msg[IFI_NOPROTO..]),
+                    },
+                    addresses: vec![],
+                });
+            }
+            RTM_NEWADDR => {
+                let addrs = NativeEndian::read_i32(&msg[IFAM_ADDRS..]);
+                let index = NativeEndian::read_u16(&msg[IFAM_INDEX..]);
+                let sockaddrs = sockaddrs(addrs, &msg[IFA_MSGHDR_SIZE..]);
+                let address = match sockaddrs[RTAX_IFA].and_then(|sa| ip_sockaddr(sa, 0)) {
+                    Some(address) => address,
+                    // Link-level addresses are part of the interface
+                    None => continue,
+                };
+                let family = sockaddrs[RTAX_IFA].map_or(0, |sa| sa[1]);
+                let address = InterfaceAddress {
+                    address,
+                    netmask: sockaddrs[RTAX_NETMASK].and_then(|sa| ip_sockaddr(sa, family)),
+                    broadcast: sockaddrs[RTAX_BRD].and_then(|sa| ip_sockaddr(sa, family)),
+                };
+                if let Some(i) = interfaces.iter_mut().rev().find(|i| i.index == index) {
+                    i.addresses.push(address);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(interfaces)
+}
+
+/// A route of the routing table, like `netstat -rn` shows it.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     for r in sysctl::net::route_table().expect("could not dump the routing table") {
+///         println!("{} via {:?} on {}", r.destination, r.gateway, r.interface);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteEntry {
+    pub destination: IpAddr,
+    /// None for host routes.
+    pub netmask: Option<IpAddr>,
+    pub gateway: Option<RouteGateway>,
+    /// `RTF_*` flags.
+    pub flags: i32,
+    /// The name of the outgoing interface.
+    pub interface: String,
+    pub if_index: u16,
+    /// The path MTU, 0 if not set.
+    pub mtu: u64,
+    /// Packets sent using the route.
+    pub packets_sent: u64,
+}
+
+/// Where a route sends its packets.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteGateway {
+    /// A router, for routes with `RTF_GATEWAY`.
+    Address(IpAddr),
+    /// Directly on the link of an interface, with the link-level address
+    /// of the destination if known.
+    Link { index: u16, address: Vec<u8> },
+}
+
+impl RouteEntry {
+    /// Whether this is the default route of its address family.
+    pub fn is_default(&self) -> bool {
+        self.destination.is_unspecified() && self.netmask.map_or(false, |m| m.is_unspecified())
+    }
+}
+
+/// Return the routes of the default routing table, from the `NET_RT_DUMP`
+/// sysctl. Routes of other address families than IPv4 and IPv6 are left
+/// out.
+pub fn route_table() -> Result<Vec<RouteEntry>, SysctlError> {
+    let buf = fetch_oid(&[CTL_NET, PF_ROUTE, 0, 0, NET_RT_DUMP, 0], 0)?;
+    let mut routes = decode_routes(&buf)?;
+
+    let interfaces = interfaces()?;
+    for r in &mut routes {
+        if let Some(i) = interfaces.iter().find(|i| i.index == r.if_index) {
+            r.interface = i.name.clone();
+        }
+    }
+
+    Ok(routes)
+}
+
+// Decode the RTM_GET messages of a routing table dump, without the
+// interface names.
+fn decode_routes(buf: &[u8]) -> Result<Vec<RouteEntry>, SysctlError> {
+    let mut routes = vec![];
+
+    for (msg_type, msg) in messages(buf)? {
+        if msg_type != RTM_GET {
+            continue;
+        }
+        let addrs = NativeEndian::read_i32(&msg[RTM_ADDRS..]);
+        let sockaddrs = sockaddrs(addrs, &msg[RT_MSGHDR_SIZE..]);
+        let destination = match sockaddrs[RTAX_DST].and_then(|sa| ip_sockaddr(sa, 0)) {
+            Some(destination) => destination,
+            None => continue,
+        };
+        let family = sockaddrs[RTAX_DST].map_or(0, |sa| sa[1]);
+        let flags = NativeEndian::read_i32(&msg[RTM_FLAGS..]);
+        let gateway = sockaddrs[RTAX_GATEWAY].and_then(|sa| match sa.get(1) {
+            Some(&AF_LINK) if sa.len() >= 8 => Some(RouteGateway::Link {
+                index: NativeEndian::read_u16(&sa[2..]),
+                address: link_sockaddr(sa).1,
+            }),
+            _ => ip_sockaddr(sa, 0).map(RouteGateway::Address),
+        });
+        let netmask = match flags & RTF_HOST {
+            0 => sockaddrs[RTAX_NETMASK].and_then(|sa| ip_sockaddr(sa, family)),
+            _ => None,
+        };
+
+        routes.push(RouteEntry {
+            destination,
+            netmask,
+            gateway,
+            flags,
+            interface: String::new(),
+            if_index: NativeEndian::read_u16(&msg[RTM_INDEX..]),
+            mtu: NativeEndian::read_u64(&msg[RMX_MTU..]),
+            packets_sent: NativeEndian::read_u64(&msg[RMX_PKSENT..]),
+        });
+    }
+
+    Ok(routes)
+}
+
+/// The protocols of `connections()`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// The state of a TCP connection (`TCPS_*` in netinet/tcp_fsm.h).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    CloseWait,
+    FinWait1,
+    Closing,
+    LastAck,
+    FinWait2,
+    TimeWait,
+    Unknown(i32),
+}
+
+impl From<i32> for TcpState {
+    fn from(s: i32) -> Self {
+        match s {
+            0 => TcpState::Closed,
+            1 => TcpState::Listen,
+            2 => TcpState::SynSent,
+            3 => TcpState::SynReceived,
+            4 => TcpState::Established,
+            5 => TcpState::CloseWait,
+            6 => TcpState::FinWait1,
+            7 => TcpState::Closing,
+            8 => TcpState::LastAck,
+            9 => TcpState::FinWait2,
+            10 => TcpState::TimeWait,
+            s => TcpState::Unknown(s),
+        }
+    }
+}
+
+/// A TCP or UDP socket, like sockstat(1) shows it.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::net::Protocol;
+///
+/// fn main() {
+///     let tcp = sysctl::net::connections(Protocol::Tcp).expect("could not read the pcblist");
+///     for c in tcp {
+///         println!("{:?} {} -> {} {:?} pid {:?}", c.state, c.local, c.remote, c.uid, c.pid);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub protocol: Protocol,
+    pub local: SocketAddr,
+    /// The unspecified address and port 0 if not connected.
+    pub remote: SocketAddr,
+    /// None for UDP.
+    pub state: Option<TcpState>,
+    /// Owner of the socket.
+    pub uid: uid_t,
+    /// A process with the socket open. None if no process has, e.g. in
+    /// TIME_WAIT, or if `kern.file` does not show it.
+    pub pid: Option<pid_t>,
+    /// Kernel address of the struct socket, 0 if there is none.
+    pub socket: u64,
+}
+
+/// Return the TCP or UDP sockets, from `net.inet.tcp.pcblist` or
+/// `net.inet.udp.pcblist`.
+///
+/// The owning processes are found through `kern.file`, which only shows
+/// the processes of other users if `security.bsd.see_other_uids` allows.
+pub fn connections(protocol: Protocol) -> Result<Vec<Connection>, SysctlError> {
+    let name = match protocol {
+        Protocol::Tcp => "net.inet.tcp.pcblist",
+        Protocol::Udp => "net.inet.udp.pcblist",
+    };
+    let oid = name2oid(name)?;
+    let buf = fetch_oid(&oid, 0)?;
+    let mut connections = decode_pcblist(&buf, protocol)?;
+
+    let sockets: Vec<_> = open_files()?
+        .into_iter()
+        .filter(|f| f.file_type == FileType::Socket)
+        .collect();
+    for c in connections.iter_mut().filter(|c| c.socket != 0) {
+        c.pid = sockets.iter().find(|f| f.data == c.socket).map(|f| f.pid);
+    }
+
+    Ok(connections)
+}
+
+// Decode a pcblist: a struct xinpgen, a struct xtcpcb or struct xinpcb for
+// every socket, and another struct xinpgen. Every record starts with its
+// own size, which is used as the stride so newer kernels with larger
+// structs work.
+fn decode_pcblist(mut buf: &[u8], protocol: Protocol) -> Result<Vec<Connection>, SysctlError> {
+    let (inp, min_size) = match protocol {
+        Protocol::Tcp => (XT_INP, XTCPCB_MINSIZE),
+        Protocol::Udp => (0, XINPCB_SIZE),
+    };
+    let mut connections = vec![];
+    let mut first = true;
+    loop {
+        if buf.len() < 8 {
+            return Err(SysctlError::ShortRead {
+                read: buf.len(),
+                reported: 8,
+            });
+        }
+        let len = NativeEndian::read_u64(buf) as usize;
+        if len > buf.len() || len < XINPGEN_SIZE {
+            return Err(SysctlError::ExtractionError);
+        }
+        if len == XINPGEN_SIZE && !first {
+            break;
+        }
+        if !first {
+            if len < min_size {
+                return Err(SysctlError::ExtractionError);
+            }
+            connections.push(decode_xinpcb(&buf[inp..], protocol, buf));
+        }
+        first = false;
+        buf = &buf[len..];
+    }
+
+    Ok(connections)
+}
+
+// Decode the struct xinpcb of a record, taking the TCP state from the
+// struct xtcpcb around it.
+fn decode_xinpcb(xi: &[u8], protocol: Protocol, record: &[u8]) -> Connection {
+    let lport = u16::from_be_bytes([xi[IE_LPORT], xi[IE_LPORT + 1]]);
+    let fport = u16::from_be_bytes([xi[IE_FPORT], xi[IE_FPORT + 1]]);
+    let (local, remote) = if xi[XI_VFLAG] & INP_IPV6 != 0 {
+        // The zone of the connection, for the link-local addresses
+        let zone = NativeEndian::read_u32(&xi[IE6_ZONEID..]);
+        let addr = |offset: usize, port: u16| {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&xi[offset..offset + 16]);
+            let ip = Ipv6Addr::from(octets);
+            let link_local = ip.segments()[0] & 0xffc0 == 0xfe80;
+            SocketAddr::V6(SocketAddrV6::new(
+                ip,
+                port,
+                0,
+                if link_local { zone } else { 0 },
+            ))
+        };
+        (addr(IE_LADDR, lport), addr(IE_FADDR, fport))
+    } else {
+        // struct in_addr_4in6, the address after 12 bytes of padding
+        let addr = |offset: usize| {
+            let a = &xi[offset + 12..offset + 16];
+            Ipv4Addr::new(a[0], a[1], a[2], a[3])
+        };
+        (
+            SocketAddr::V4(SocketAddrV4::new(addr(IE_LADDR), lport)),
+            SocketAddr::V4(SocketAddrV4::new(addr(IE_FADDR), fport)),
+        )
+    };
+
+    Connection {
+        protocol,
+        local,
+        remote,
+        state: match protocol {
+            Protocol::Tcp => Some(TcpState::from(NativeEndian::read_i32(&record[XT_STATE..]))),
+            Protocol::Udp => None,
+        },
+        uid: NativeEndian::read_u32(&xi[XSO_UID..]),
+        pid: None,
+        socket: NativeEndian::read_u64(&xi[XSO_SO..]),
+    }
+}
+
+// Split a buffer of routing messages into their types and contents,
+// skipping messages of other versions. Messages are at least as long as
+// their header of the given type, so fields can be read without checks.
+fn messages(mut buf: &[u8]) -> Result<Vec<(u8, &[u8])>, SysctlError> {
+    let mut messages = vec![];
+
+    while !buf.is_empty() {
+        if buf.len() < 4 {
+            return Err(SysctlError::ShortRead {
+                read: buf.len(),
+                reported: 4,
+            });
+        }
+        let len = NativeEndian::read_u16(buf) as usize;
+        let (version, msg_type) = (buf[2], buf[3]);
+        let min_len = match msg_type {
+            RTM_IFINFO => IF_MSGHDR_SIZE,
+            RTM_NEWADDR => IFA_MSGHDR_SIZE,
+            RTM_GET => RT_MSGHDR_SIZE,
+            _ => 4,
+        };
+        if len < min_len || len > buf.len() {
+            return Err(SysctlError::ExtractionError);
+        }
+        if version == RTM_VERSION {
+            messages.push((msg_type, &buf[..len]));
+        }
+        buf = &buf[len..];
+    }
+
+    Ok(messages)
+}
+
+// The sockaddrs present according to the RTA_* bits, by RTAX_* index.
+// Each is padded to a multiple of the size of a long.
+fn sockaddrs(addrs: i32, mut buf: &[u8]) -> [Option<&[u8]>; RTAX_MAX] {
+    let mut sockaddrs = [None; RTAX_MAX];
+
+    for (i, sockaddr) in sockaddrs.iter_mut().enumerate() {
+        if addrs & (1 << i) == 0 || buf.is_empty() {
+            continue;
+        }
+        let len = buf[0] as usize;
+        let padded = match len {
+            0 => 8,
+            l => 1 + ((l - 1) | 7),
+        };
+        *sockaddr = Some(&buf[..cmp::min(len, buf.len())]);
+        buf = &buf[cmp::min(padded, buf.len())..];
+    }
+
+    sockaddrs
+}
+
+// The address of a sockaddr_in or sockaddr_in6. Netmasks may be cut
+// short after their last non-zero byte and lack the family, which is then
+// taken from the address they belong to.
+fn ip_sockaddr(sa: &[u8], family: u8) -> Option<IpAddr> {
+    let family = match sa.get(1) {
+        Some(&f) if f != 0 => f,
+        _ => family,
+    };
+    let mut b = [0u8; 28];
+    let len = cmp::min(sa.len(), b.len());
+    b[..len].copy_from_slice(&sa[..len]);
+
+    match family {
+        AF_INET => Some(IpAddr::V4(Ipv4Addr::new(b[4], b[5], b[6], b[7]))),
+        AF_INET6 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&b[8..24]);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+// The interface name and link-level address of a sockaddr_dl.
+fn link_sockaddr(sa: &[u8]) -> (&[u8], Vec<u8>) {
+    if sa.len() < 8 || sa[1] != AF_LINK {
+        return (&[], vec![]);
+    }
+    let data = &sa[8..];
+    let nlen = cmp::min(sa[5] as usize, data.len());
+    let alen = cmp::min(sa[6] as usize, data.len() - nlen);
+    (&data[..nlen], data[nlen..nlen + alen].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn net_portrange_validate() {
+        let range = PortRange {
+            first: 10000,
+            last: 65535,
+            hifirst: 49152,
+            hilast: 65535,
+            lowfirst: 1023,
+            lowlast: 600,
+        };
+        assert!(range.validate().is_ok());
+        assert!(PortRange {
+            first: 60000,
+            last: 50000,
+            ..range
+        }
+        .validate()
+        .is_err());
+        assert!(PortRange {
+            hifirst: 65535,
+            hilast: 49152,
+            ..range
+        }
+        .validate()
+        .is_err());
+        assert!(PortRange { first: 80, ..range }.validate().is_err());
+        assert!(PortRange {
+            lowfirst: 2000,
+            ..range
+        }
+        .validate()
+        .is_err());
+        assert!(PortRange {
+            hilast: 70000,
+            ..range
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn net_interface_device() {
+        for ifname in &["lo", "0", ""] {
+            match interface_device(ifname) {
+                Err(SysctlError::InvalidValue(_)) => {}
+                r => panic!("unexpected result for {:?}: {:?}", ifname, r),
+            }
+        }
+        assert!(interface_device("nonexistent0").is_err());
+    }
+
+    fn sockaddr_dl(index: u16, name: &str, lladdr: &[u8]) -> Vec<u8> {
+        let mut sa = vec![0; 8];
+        sa[1] = AF_LINK;
+        NativeEndian::write_u16(&mut sa[2..], index);
+        sa[5] = name.len() as u8;
+        sa[6] = lladdr.len() as u8;
+        sa.extend_from_slice(name.as_bytes());
+        sa.extend_from_slice(lladdr);
+        sa.resize(cmp::max(sa.len(), 20), 0);
+        sa[0] = sa.len() as u8;
+        sa
+    }
+
+    fn sockaddr_in(addr: [u8; 4]) -> Vec<u8> {
+        let mut sa = vec![16, AF_INET, 0, 0];
+        sa.extend_from_slice(&addr);
+        sa.resize(16, 0);
+        sa
+    }
+
+    fn sockaddr_in6(addr: Ipv6Addr) -> Vec<u8> {
+        let mut sa = vec![28, AF_INET6, 0, 0, 0, 0, 0, 0];
+        sa.extend_from_slice(&addr.octets());
+        sa.resize(28, 0);
+        sa
+    }
+
+    // A routing message with the header and the padded sockaddrs.
+    fn message(mut header: Vec<u8>, sockaddrs: &[Vec<u8>]) -> Vec<u8> {
+        for sa in sockaddrs {
+            let mut sa = sa.clone();
+            sa.resize(1 + ((sa.len() - 1) | 7), 0);
+            header.extend(sa);
+        }
+        let len = header.len() as u16;
+        NativeEndian::write_u16(&mut header, len);
+        header[2] = RTM_VERSION;
+        header
+    }
+
+    fn if_msghdr(index: u16, addrs: i32) -> Vec<u8> {
+        let mut h = vec![0; IF_MSGHDR_SIZE];
+        h[3] = RTM_IFINFO;
+        NativeEndian::write_i32(&mut h[IFM_ADDRS..], addrs);
+        NativeEndian::write_i32(&mut h[IFM_FLAGS..], 0x8843);
+        NativeEndian::write_u16(&mut h[IFM_INDEX..], index);
+        h[IFI_LINK_STATE] = 2;
+        NativeEndian::write_u32(&mut h[IFI_MTU..], 1500);
+        NativeEndian::write_u64(&mut h[IFI_IPACKETS..], 10);
+        NativeEndian::write_u64(&mut h[IFI_IBYTES..], 1000);
+        NativeEndian::write_u64(&mut h[IFI_OBYTES..], 2000);
+        NativeEndian::write_u64(&mut h[IFI_OQDROPS..], 3);
+        h
+    }
+
+    fn ifa_msghdr(index: u16, addrs: i32) -> Vec<u8> {
+        let mut h = vec![0; IFA_MSGHDR_SIZE];
+        h[3] = RTM_NEWADDR;
+        NativeEndian::write_i32(&mut h[IFAM_ADDRS..], addrs);
+        NativeEndian::write_u16(&mut h[IFAM_INDEX..], index);
+        h
+    }
+
+    #[test]
+    fn net_decode_interfaces() {
+        let mac = [0x00, 0x1b, 0x21, 0x0a, 0x0b, 0x0c];
+        let v6: Ipv6Addr = "fe80::1".parse().unwrap();
+        let mut buf = message(if_msghdr(1, 0x10), &[sockaddr_dl(1, "em0", &mac)]);
+        // netmask, address and broadcast, the netmask cut short
+        buf.extend(message(
+            ifa_msghdr(1, 0x4 | 0x20 | 0x80),
+            &[
+                vec![7, 0, 0, 0, 255, 255, 255],
+                sockaddr_in([192, 168, 1, 10]),
+                sockaddr_in([192, 168, 1, 255]),
+            ],
+        ));
+        buf.extend(message(ifa_msghdr(1, 0x20), &[sockaddr_in6(v6)]));
+        buf.extend(message(if_msghdr(2, 0x10), &[sockaddr_dl(2, "lo0", &[])]));
+
+        let interfaces = decode_interfaces(&buf).unwrap();
+        assert_eq!(interfaces.len(), 2);
+        let em0 = &interfaces[0];
+        assert_eq!(em0.name, "em0");
+        assert_eq!(em0.index, 1);
+        assert_eq!(em0.link_address, mac);
+        assert_eq!(em0.mtu, 1500);
+        assert_eq!(em0.link_state, 2);
+        assert_eq!(em0.stats.in_packets, 10);
+        assert_eq!(em0.stats.in_bytes, 1000);
+        assert_eq!(em0.stats.out_bytes, 2000);
+        assert_eq!(em0.stats.out_drops, 3);
+        assert_eq!(
+            em0.addresses,
+            vec![
+                InterfaceAddress {
+                    address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)),
+                    netmask: Some(IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0))),
+                    broadcast: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 255))),
+                },
+                InterfaceAddress {
+                    address: IpAddr::V6(v6),
+                    netmask: None,
+                    broadcast: None,
+                },
+            ]
+        );
+        assert_eq!(interfaces[1].name, "lo0");
+        assert!(interfaces[1].link_address.is_empty());
+
+        assert!(decode_interfaces(&buf[..buf.len() - 1]).is_err());
+    }
+
+    fn rt_msghdr(index: u16, flags: i32, addrs: i32) -> Vec<u8> {
+        let mut h = vec![0; RT_MSGHDR_SIZE];
+        h[3] = RTM_GET;
+        NativeEndian::write_u16(&mut h[RTM_INDEX..], index);
+        NativeEndian::write_i32(&mut h[RTM_FLAGS..], flags);
+        NativeEndian::write_i32(&mut h[RTM_ADDRS..], addrs);
+        NativeEndian::write_u64(&mut h[RMX_PKSENT..], 7);
+        h
+    }
+
+    #[test]
+    fn net_decode_routes() {
+        // default route, netmask without any bytes
+        let mut buf = message(
+            rt_msghdr(1, RTF_UP | RTF_GATEWAY | RTF_STATIC, 0x7),
+            &[
+                sockaddr_in([0, 0, 0, 0]),
+                sockaddr_in([192, 168, 1, 1]),
+                vec![0],
+            ],
+        );
+        // host route on the loopback interface
+        buf.extend(message(
+            rt_msghdr(2, RTF_UP | RTF_HOST, 0x3),
+            &[sockaddr_in([127, 0, 0, 1]), sockaddr_dl(2, "", &[])],
+        ));
+        buf.extend(message(
+            rt_msghdr(1, RTF_UP, 0x7),
+            &[
+                sockaddr_in6("2001:db8::".parse().unwrap()),
+                sockaddr_dl(1, "", &[0, 1, 2, 3, 4, 5]),
+                vec![12, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff],
+            ],
+        ));
+
+        let routes = decode_routes(&buf).unwrap();
+        assert_eq!(routes.len(), 3);
+        assert!(routes[0].is_default());
+        assert_eq!(
+            routes[0].gateway,
+            Some(RouteGateway::Address(IpAddr::V4(Ipv4Addr::new(
+                192, 168, 1, 1
+            ))))
+        );
+        assert_eq!(routes[0].flags & RTF_GATEWAY, RTF_GATEWAY);
+        assert_eq!(routes[0].if_index, 1);
+        assert_eq!(routes[0].packets_sent, 7);
+        assert_eq!(routes[1].destination, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(routes[1].netmask, None);
+        assert_eq!(
+            routes[1].gateway,
+            Some(RouteGateway::Link {
+                index: 2,
+                address: vec![]
+            })
+        );
+        assert!(!routes[1].is_default());
+        assert_eq!(
+            routes[2].netmask,
+            Some("ffff:ffff::".parse::<IpAddr>().unwrap())
+        );
+        assert_eq!(
+            routes[2].gateway,
+            Some(RouteGateway::Link {
+                index: 1,
+                address: vec![0, 1, 2, 3, 4, 5]
+            })
+        );
+    }
+
+    #[test]
+    fn net_route_table() {
+        let routes = route_table().unwrap();
+        assert!(routes
+            .iter()
+            .any(|r| r.destination == IpAddr::V4(Ipv4Addr::LOCALHOST) && r.interface == "lo0"));
+    }
+
+    fn xinpgen() -> Vec<u8> {
+        let mut b = vec![0; XINPGEN_SIZE];
+        NativeEndian::write_u64(&mut b, XINPGEN_SIZE as u64);
+        b
+    }
+
+    fn xinpcb(local: SocketAddr, remote: SocketAddr, socket: u64) -> Vec<u8> {
+        let mut b = vec![0; XINPCB_SIZE];
+        NativeEndian::write_u64(&mut b, XINPCB_SIZE as u64);
+        NativeEndian::write_u64(&mut b[XSO_SO..], socket);
+        NativeEndian::write_u32(&mut b[XSO_UID..], 1001);
+        b[IE_LPORT..IE_LPORT + 2].copy_from_slice(&local.port().to_be_bytes());
+        b[IE_FPORT..IE_FPORT + 2].copy_from_slice(&remote.port().to_be_bytes());
+        if let SocketAddr::V6(a) = local {
+            NativeEndian::write_u32(&mut b[IE6_ZONEID..], a.scope_id());
+        }
+        for &(offset, addr) in &[(IE_LADDR, local), (IE_FADDR, remote)] {
+            match addr {
+                SocketAddr::V4(a) => {
+                    b[offset + 12..offset + 16].copy_from_slice(&a.ip().octets());
+                    b[XI_VFLAG] = 0x1;
+                }
+                SocketAddr::V6(a) => {
+                    b[offset..offset + 16].copy_from_slice(&a.ip().octets());
+                    b[XI_VFLAG] = INP_IPV6;
+                }
+            }
+        }
+        b
+    }
+
+    fn xtcpcb(size: usize, inp: Vec<u8>, state: i32) -> Vec<u8> {
+        let mut b = vec![0; XT_INP];
+        NativeEndian::write_u64(&mut b, size as u64);
+        b.extend(inp);
+        b.resize(size, 0);
+        NativeEndian::write_i32(&mut b[XT_STATE..], state);
+        b
+    }
+
+    #[test]
+    fn net_decode_pcblist() {
+        let local: SocketAddr = "192.168.1.10:22".parse().unwrap();
+        let remote: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+        let any: SocketAddr = "[::]:0".parse().unwrap();
+        let listen: SocketAddr = "[fe80::1%2]:80".parse().unwrap();
+
+        let mut buf = xinpgen();
+        buf.extend(xtcpcb(744, xinpcb(local, remote, 0xfffff800_1000), 4));
+        // A newer kernel with a larger struct
+        buf.extend(xtcpcb(800, xinpcb(listen, any, 0xfffff800_2000), 1));
+        buf.extend(xinpgen());
+
+        let tcp = decode_pcblist(&buf, Protocol::Tcp).unwrap();
+        assert_eq!(tcp.len(), 2);
+        assert_eq!(tcp[0].local, local);
+        assert_eq!(tcp[0].remote, remote);
+        assert_eq!(tcp[0].state, Some(TcpState::Established));
+        assert_eq!(tcp[0].uid, 1001);
+        assert_eq!(tcp[0].socket, 0xfffff800_1000);
+        assert_eq!(tcp[0].pid, None);
+        assert_eq!(tcp[1].local, listen);
+        assert_eq!(tcp[1].remote, any);
+        assert_eq!(tcp[1].state, Some(TcpState::Listen));
+
+        let mut udp = xinpgen();
+        udp.extend(xinpcb(local, "0.0.0.0:0".parse().unwrap(), 1));
+        udp.extend(xinpgen());
+        let udp = decode_pcblist(&udp, Protocol::Udp).unwrap();
+        assert_eq!(udp.len(), 1);
+        assert_eq!(udp[0].local, local);
+        assert_eq!(udp[0].state, None);
+
+        // Without the trailing struct xinpgen
+        assert!(decode_pcblist(&buf[..buf.len() - XINPGEN_SIZE], Protocol::Tcp).is_err());
+    }
+
+    #[test]
+    fn net_connections() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tcp = connections(Protocol::Tcp).unwrap();
+        let c = tcp.iter().find(|c| c.local == addr).unwrap();
+        assert_eq!(c.state, Some(TcpState::Listen));
+        assert_eq!(c.pid, Some(unsafe { libc::getpid() }));
+        assert_eq!(c.uid, unsafe { libc::geteuid() });
+    }
+
+    #[test]
+    fn net_interfaces() {
+        let interfaces = interfaces().unwrap();
+        let lo0 = interfaces.iter().find(|i| i.name == "lo0").unwrap();
+        assert!(lo0.addresses.contains(&InterfaceAddress {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            netmask: Some(IpAddr::V4(Ipv4Addr::new(255, 0, 0, 0))),
+            broadcast: None,
+        }));
+    }
+
+    #[test]
+    fn net_portrange_get() {
+        let range = PortRange::get().expect("could not read port range");
+        assert!(range.validate().is_ok());
+    }
+}
@@ -0,0 +1,275 @@
+//! Tree queries for NetBSD.
+//!
+//! NetBSD has none of the meta-OIDs below `0` the other BSDs use.
+//! Names are resolved with sysctlnametomib(3), everything else is answered
+//! by `CTL_QUERY`, which lists the children of a node together with their
+//! names, types and flags, and `CTL_DESCRIBE` for descriptions.
+//!
+//! Finding the next OID has to walk the tree from the top, so iterating
+//! is slower than on FreeBSD.
+
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::ptr;
+use std::str;
+
+use libc::{c_char, c_int, c_void};
+
+use super::{CtlInfo, CtlType, SysctlError, CTLFLAG_RD, CTLFLAG_WR, CTL_MAXNAME};
+
+/// The NetBSD `CTLTYPE_*` numbering.
+pub const CTLTYPE_NODE: u32 = 1;
+pub const CTLTYPE_INT: u32 = 2;
+pub const CTLTYPE_STRING: u32 = 3;
+pub const CTLTYPE_QUAD: u32 = 4;
+pub const CTLTYPE_STRUCT: u32 = 5;
+pub const CTLTYPE_BOOL: u32 = 6;
+
+const SYSCTL_TYPEMASK: u32 = 0x0000_000f;
+const CTLFLAG_READWRITE: u32 = libc::CTLFLAG_READWRITE as u32;
+
+// struct sysctlnode from sys/sysctl.h. The kernel pads all pointers and
+// sizes to 64 bits, so the layout is the same for 32 and 64 bit
+// processes.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SysctlNode {
+    flags: u32,
+    num: i32,
+    name: [c_char; libc::SYSCTL_NAMELEN as usize],
+    ver: u32,
+    rsvd: u32,
+    un: [u64; 2],
+    size: u64,
+    func: u64,
+    parent: u64,
+    desc: u64,
+}
+
+impl SysctlNode {
+    fn query() -> Self {
+        let mut node: SysctlNode = unsafe { mem::zeroed() };
+        node.flags = libc::SYSCTL_VERSION as u32;
+        node
+    }
+
+    fn name(&self) -> Result<String, SysctlError> {
+        let bytes: Vec<u8> = self
+            .name
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        String::from_utf8(bytes).map_err(|e| SysctlError::Utf8Error(e.utf8_error()))
+    }
+
+    fn kind(&self) -> u32 {
+        self.flags & SYSCTL_TYPEMASK
+    }
+}
+
+/// Map a NetBSD `CTLTYPE_*` number to the type used by this crate.
+pub fn ctl_type(kind: u32) -> CtlType {
+    match kind {
+        CTLTYPE_NODE => CtlType::Node,
+        CTLTYPE_INT => CtlType::Int,
+        CTLTYPE_STRING => CtlType::String,
+        CTLTYPE_QUAD => CtlType::U64,
+        CTLTYPE_STRUCT => CtlType::Struct,
+        CTLTYPE_BOOL => CtlType::U8,
+        _ => CtlType::None,
+    }
+}
+
+// The format strings FreeBSD would report for the type.
+fn fmt(kind: u32) -> &'static str {
+    match kind {
+        CTLTYPE_NODE => "N",
+        CTLTYPE_INT => "I",
+        CTLTYPE_STRING => "A",
+        CTLTYPE_QUAD => "QU",
+        CTLTYPE_BOOL => "CU",
+        _ => "S",
+    }
+}
+
+// List the children of a node, sorted by number.
+fn children(parent: &[c_int]) -> Result<Vec<SysctlNode>, SysctlError> {
+    let mut mib = parent.to_vec();
+    mib.push(libc::CTL_QUERY);
+    let query = SysctlNode::query();
+    let size = mem::size_of::<SysctlNode>();
+
+    loop {
+        let mut len = 0;
+        let ret = unsafe {
+            libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as u32,
+                ptr::null_mut(),
+                &mut len,
+                &query as *const SysctlNode as *const c_void,
+                size,
+            )
+        };
+        if ret < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+
+        let mut nodes = vec![SysctlNode::query(); len / size];
+        let mut len = nodes.len() * size;
+        let ret = unsafe {
+            libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as u32,
+                nodes.as_mut_ptr() as *mut c_void,
+                &mut len,
+                &query as *const SysctlNode as *const c_void,
+                size,
+            )
+        };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            // Nodes may have been added in between, ask again
+            if e.raw_os_error() == Some(libc::ENOMEM) {
+                continue;
+            }
+            return Err(SysctlError::IoError(e));
+        }
+
+        nodes.truncate(len / size);
+        nodes.sort_by_key(|n| n.num);
+        return Ok(nodes);
+    }
+}
+
+fn node(oid: &[c_int]) -> Result<SysctlNode, SysctlError> {
+    let (last, parent) = match oid.split_last() {
+        Some(split) => split,
+        None => return Err(SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT))),
+    };
+    children(parent)?
+        .into_iter()
+        .find(|n| n.num == *last)
+        .ok_or_else(|| SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT)))
+}
+
+pub(crate) fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
+    let cname = CString::new(name)
+        .map_err(|_| SysctlError::InvalidValue(format!("invalid sysctl name {:?}", name)))?;
+    let mut res: Vec<c_int> = vec![0; CTL_MAXNAME as usize];
+    let mut len = res.len();
+
+    let ret = unsafe { libc::sysctlnametomib(cname.as_ptr(), res.as_mut_ptr(), &mut len) };
+    if ret < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+    res.truncate(len);
+    Ok(res)
+}
+
+/// Returns the name of the sysctl with the given OID.
+pub fn oid2name(oid: &[c_int]) -> Result<String, SysctlError> {
+    let mut names = vec![];
+    for i in 1..=oid.len() {
+        names.push(node(&oid[..i])?.name()?);
+    }
+    Ok(names.join("."))
+}
+
+pub(crate) fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+    let node = node(oid)?;
+
+    let mut flags = CTLFLAG_RD;
+    if node.flags & CTLFLAG_READWRITE == CTLFLAG_READWRITE {
+        flags |= CTLFLAG_WR;
+    }
+
+    Ok(CtlInfo {
+        ctl_type: ctl_type(node.kind()),
+        fmt: fmt(node.kind()).to_owned(),
+        flags,
+    })
+}
+
+pub(crate) fn oid2description(oid: &[c_int]) -> Result<String, SysctlError> {
+    let (last, parent) = match oid.split_last() {
+        Some(split) => split,
+        None => return Err(SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT))),
+    };
+    let mut mib = parent.to_vec();
+    mib.push(libc::CTL_DESCRIBE);
+    let mut query = SysctlNode::query();
+    query.num = *last;
+
+    // struct sysctldesc: number, version and length, then the text
+    let mut buf = vec![0u8; 1024];
+    let mut len = buf.len();
+    let ret = unsafe {
+        libc::sysctl(
+            mib.as_ptr(),
+            mib.len() as u32,
+            buf.as_mut_ptr() as *mut c_void,
+            &mut len,
+            &query as *const SysctlNode as *const c_void,
+            mem::size_of::<SysctlNode>(),
+        )
+    };
+    if ret < 0 {
+        return Err(SysctlError::IoError(io::Error::last_os_error()));
+    }
+
+    let header = 3 * mem::size_of::<u32>();
+    let text = &buf[header.min(len)..len];
+    let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+    match str::from_utf8(&text[..end]) {
+        Ok(s) => Ok(s.to_owned()),
+        Err(e) => Err(SysctlError::Utf8Error(e)),
+    }
+}
+
+pub(crate) fn next_oid(oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+    next_leaf(&[], Some(oid))
+}
+
+// Find the first leaf below parent that sorts after the rest of the OID
+// in after, or the first leaf at all if there is none.
+fn next_leaf(parent: &[c_int], after: Option<&[c_int]>) -> Result<Option<Vec<c_int>>, SysctlError> {
+    // Anything below the OID itself comes after it
+    let after = after.filter(|a| !a.is_empty());
+
+    for child in children(parent)? {
+        let mut oid = parent.to_vec();
+        oid.push(child.num);
+
+        let rest = match after {
+            Some(a) if child.num < a[0] => continue,
+            Some(a) if child.num == a[0] => Some(&a[1..]),
+            _ => None,
+        };
+
+        if child.kind() == CTLTYPE_NODE {
+            if let Some(leaf) = next_leaf(&oid, rest)? {
+                return Ok(Some(leaf));
+            }
+        } else if rest.is_none() {
+            return Ok(Some(oid));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netbsd_next_oid() {
+        let ostype = name2oid("kern.ostype").unwrap();
+        assert_eq!(oid2name(&ostype).unwrap(), "kern.ostype");
+        let next = next_oid(&ostype).unwrap().unwrap();
+        assert!(next > ostype);
+        assert_eq!(oidfmt(&ostype).unwrap().ctl_type, CtlType::String);
+    }
+}
@@ -0,0 +1,186 @@
+//! Async access to sysctls, enabled by the `async` feature.
+//!
+//! Every sysctl call is a syscall that may block, e.g. while the kernel
+//! collects the process table. The futures and streams of this module run
+//! the calls on tokio's blocking thread pool or on a dedicated thread, so
+//! they can be awaited from a reactor without stalling it.
+//!
+//! The futures must be created within a tokio runtime.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! extern crate tokio;
+//! use sysctl::Ctl;
+//!
+//! fn main() {
+//!     let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//!     let _runtime = rt.enter();
+//!     let ctl = Ctl::new("kern.ostype").unwrap();
+//!     let value = rt.block_on(ctl.value_async()).unwrap();
+//!     println!("{}", value);
+//! }
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinHandle};
+
+use super::watch::{CtlChange, CtlWatcher, WatchHandle};
+use super::{Ctl, CtlIter, CtlValue, SysctlError};
+
+// Items buffered by a stream before its thread waits for the consumer
+const STREAM_BUFFER: usize = 64;
+
+/// A sysctl call running on the blocking thread pool, returned by
+/// `Ctl::value_async()` and `Ctl::set_value_async()`.
+#[derive(Debug)]
+pub struct Blocking<T> {
+    handle: JoinHandle<Result<T, SysctlError>>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() -> Result<T, SysctlError> + Send + 'static,
+    {
+        Blocking {
+            handle: task::spawn_blocking(f),
+        }
+    }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = Result<T, SysctlError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Ready(Ok(res)) => Poll::Ready(res),
+            // The call panicked or the runtime is shutting down
+            // io::Error::other() needs Rust 1.74
+            #[allow(clippy::io_other_error)]
+            Poll::Ready(Err(e)) => Poll::Ready(Err(SysctlError::IoError(io::Error::new(
+                io::ErrorKind::Other,
+                e,
+            )))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// A stream of items produced on a dedicated thread, returned by
+/// `CtlIter::into_stream()` and `CtlWatcher::spawn_stream()`.
+///
+/// Dropping the stream stops the thread.
+#[derive(Debug)]
+pub struct CtlStream<T> {
+    rx: mpsc::Receiver<T>,
+}
+
+impl<T> Stream for CtlStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Ctl {
+    /// Returns a future resolving to the value of the sysctl, see
+    /// `value()`.
+    pub fn value_async(&self) -> Blocking<CtlValue> {
+        let ctl = self.clone();
+        Blocking::spawn(move || ctl.value())
+    }
+
+    /// Returns a future setting the value of the sysctl and resolving to
+    /// the new value, see `set_value()`.
+    pub fn set_value_async(&self, value: CtlValue) -> Blocking<CtlValue> {
+        let ctl = self.clone();
+        Blocking::spawn(move || ctl.set_value(value))
+    }
+}
+
+impl CtlIter {
+    /// Walk the tree on a dedicated thread and return the results as a
+    /// stream.
+    pub fn into_stream(self) -> CtlStream<Result<Ctl, SysctlError>> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        thread::spawn(move || {
+            for item in self {
+                if tx.blocking_send(item).is_err() {
+                    return;
+                }
+            }
+        });
+        CtlStream { rx }
+    }
+}
+
+impl CtlWatcher {
+    /// Start polling and return the changes as a stream, see `spawn()`.
+    pub fn spawn_stream(self) -> Result<(WatchHandle, CtlStream<CtlChange>), SysctlError> {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let closed = tx.clone();
+        let handle = self.spawn_while(
+            move |change| tx.blocking_send(change).is_ok(),
+            move || !closed.is_closed(),
+        )?;
+        Ok((handle, CtlStream { rx }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future;
+    use std::time::Duration;
+
+    #[cfg(not(target_os = "linux"))]
+    const NODE: &str = "kern";
+    #[cfg(target_os = "linux")]
+    const NODE: &str = "kernel";
+    #[cfg(not(target_os = "linux"))]
+    const OSTYPE: &str = "kern.ostype";
+    #[cfg(target_os = "linux")]
+    const OSTYPE: &str = "kernel.ostype";
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().build().unwrap()
+    }
+
+    fn next<T>(stream: &mut CtlStream<T>) -> Option<T> {
+        runtime().block_on(future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)))
+    }
+
+    #[test]
+    fn nonblocking_value() {
+        let ctl = Ctl::new(OSTYPE).unwrap();
+        let rt = runtime();
+        let _runtime = rt.enter();
+        let value = rt.block_on(ctl.value_async()).unwrap();
+        assert_eq!(value, ctl.value().unwrap());
+    }
+
+    #[test]
+    fn nonblocking_iter_stream() {
+        let mut stream = CtlIter::below(Ctl::new(NODE).unwrap()).into_stream();
+        assert!(next(&mut stream).unwrap().is_ok());
+    }
+
+    #[test]
+    fn nonblocking_watch_stream() {
+        let (handle, mut changes) = CtlWatcher::new(Duration::from_millis(10))
+            .watch(Ctl::new(OSTYPE).unwrap())
+            .spawn_stream()
+            .unwrap();
+        handle.stop();
+        assert!(next(&mut changes).is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! Tree queries for OpenBSD.
+//!
+//! The OpenBSD kernel can not resolve names or describe its OIDs, sysctl(8)
+//! carries tables of the names it knows instead. This module does the
+//! same for the `kern` and `hw` nodes, other sysctls have to be read with
+//! `value_oid()` and an OID from libc.
+
+use std::io;
+
+use libc::c_int;
+
+use super::{CtlInfo, CtlType, SysctlError, CTLFLAG_RD, CTLFLAG_WR};
+
+/// The OpenBSD `CTLTYPE_*` numbering.
+pub const CTLTYPE_NODE: u32 = 1;
+pub const CTLTYPE_INT: u32 = 2;
+pub const CTLTYPE_STRING: u32 = 3;
+pub const CTLTYPE_QUAD: u32 = 4;
+pub const CTLTYPE_STRUCT: u32 = 5;
+
+// From sys/sysctl.h, libc only has HW_NCPU
+const HW_MACHINE: c_int = 1;
+const HW_MODEL: c_int = 2;
+const HW_BYTEORDER: c_int = 4;
+const HW_PAGESIZE: c_int = 7;
+const HW_DISKNAMES: c_int = 8;
+const HW_DISKCOUNT: c_int = 10;
+const HW_CPUSPEED: c_int = 12;
+const HW_SETPERF: c_int = 13;
+const HW_VENDOR: c_int = 14;
+const HW_PRODUCT: c_int = 15;
+const HW_VERSION: c_int = 16;
+const HW_SERIALNO: c_int = 17;
+const HW_UUID: c_int = 18;
+const HW_PHYSMEM64: c_int = 19;
+const HW_USERMEM64: c_int = 20;
+const HW_NCPUFOUND: c_int = 21;
+const HW_ALLOWPOWERDOWN: c_int = 22;
+const HW_PERFPOLICY: c_int = 23;
+const HW_SMT: c_int = 24;
+const HW_NCPUONLINE: c_int = 25;
+
+/// A sysctl known by name.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Entry {
+    pub name: &'static str,
+    pub oid: &'static [c_int],
+    /// One of the `CTLTYPE_*` constants of this module.
+    pub kind: u32,
+    pub writable: bool,
+}
+
+const fn entry(name: &'static str, oid: &'static [c_int], kind: u32, writable: bool) -> Entry {
+    Entry {
+        name,
+        oid,
+        kind,
+        writable,
+    }
+}
+
+/// The sysctls known by name, sorted by OID.
+pub const ENTRIES: &[Entry] = &[
+    entry("kern", &[libc::CTL_KERN], CTLTYPE_NODE, false),
+    entry("kern.ostype", &[libc::CTL_KERN, libc::KERN_OSTYPE], CTLTYPE_STRING, false),
+    entry("kern.osrelease", &[libc::CTL_KERN, libc::KERN_OSRELEASE], CTLTYPE_STRING, false),
+    entry("kern.osrevision", &[libc::CTL_KERN, libc::KERN_OSREV], CTLTYPE_INT, false),
+    entry("kern.version", &[libc::CTL_KERN, libc::KERN_VERSION], CTLTYPE_STRING, false),
+    entry("kern.maxvnodes", &[libc::CTL_KERN, libc::KERN_MAXVNODES], CTLTYPE_INT, true),
+    entry("kern.maxproc", &[libc::CTL_KERN, libc::KERN_MAXPROC], CTLTYPE_INT, true),
+    entry("kern.maxfiles", &[libc::CTL_KERN, libc::KERN_MAXFILES], CTLTYPE_INT, true),
+    entry("kern.argmax", &[libc::CTL_KERN, libc::KERN_ARGMAX], CTLTYPE_INT, false),
+    entry("kern.securelevel", &[libc::CTL_KERN, libc::KERN_SECURELVL], CTLTYPE_INT, true),
+    entry("kern.hostname", &[libc::CTL_KERN, libc::KERN_HOSTNAME], CTLTYPE_STRING, true),
+    entry("kern.hostid", &[libc::CTL_KERN, libc::KERN_HOSTID], CTLTYPE_INT, true),
+    entry("kern.clockrate", &[libc::CTL_KERN, libc::KERN_CLOCKRATE], CTLTYPE_STRUCT, false),
+    entry("kern.ngroups", &[libc::CTL_KERN, libc::KERN_NGROUPS], CTLTYPE_INT, false),
+    entry("kern.job_control", &[libc::CTL_KERN, libc::KERN_JOB_CONTROL], CTLTYPE_INT, false),
+    entry("kern.saved_ids", &[libc::CTL_KERN, libc::KERN_SAVED_IDS], CTLTYPE_INT, false),
+    entry("kern.boottime", &[libc::CTL_KERN, libc::KERN_BOOTTIME], CTLTYPE_STRUCT, false),
+    entry("kern.domainname", &[libc::CTL_KERN, libc::KERN_DOMAINNAME], CTLTYPE_STRING, true),
+    entry("kern.maxpartitions", &[libc::CTL_KERN, libc::KERN_MAXPARTITIONS], CTLTYPE_INT, false),
+    entry("kern.rawpartition", &[libc::CTL_KERN, libc::KERN_RAWPARTITION], CTLTYPE_INT, false),
+    entry("kern.maxthread", &[libc::CTL_KERN, libc::KERN_MAXTHREAD], CTLTYPE_INT, true),
+    entry("kern.nthreads", &[libc::CTL_KERN, libc::KERN_NTHREADS], CTLTYPE_INT, false),
+    entry("kern.osversion", &[libc::CTL_KERN, libc::KERN_OSVERSION], CTLTYPE_STRING, false),
+    entry("kern.somaxconn", &[libc::CTL_KERN, libc::KERN_SOMAXCONN], CTLTYPE_INT, true),
+    entry("kern.sominconn", &[libc::CTL_KERN, libc::KERN_SOMINCONN], CTLTYPE_INT, true),
+    entry("kern.nosuidcoredump", &[libc::CTL_KERN, libc::KERN_NOSUIDCOREDUMP], CTLTYPE_INT, true),
+    entry("kern.fsync", &[libc::CTL_KERN, libc::KERN_FSYNC], CTLTYPE_INT, false),
+    entry("kern.sysvmsg", &[libc::CTL_KERN, libc::KERN_SYSVMSG], CTLTYPE_INT, false),
+    entry("kern.sysvsem", &[libc::CTL_KERN, libc::KERN_SYSVSEM], CTLTYPE_INT, false),
+    entry("kern.sysvshm", &[libc::CTL_KERN, libc::KERN_SYSVSHM], CTLTYPE_INT, false),
+    entry("kern.msgbufsize", &[libc::CTL_KERN, libc::KERN_MSGBUFSIZE], CTLTYPE_INT, false),
+    entry("kern.cp_time", &[libc::CTL_KERN, libc::KERN_CPTIME], CTLTYPE_STRUCT, false),
+    entry("kern.fscale", &[libc::CTL_KERN, libc::KERN_FSCALE], CTLTYPE_INT, false),
+    entry("kern.nprocs", &[libc::CTL_KERN, libc::KERN_NPROCS], CTLTYPE_INT, false),
+    entry("kern.nfiles", &[libc::CTL_KERN, libc::KERN_NFILES], CTLTYPE_INT, false),
+    entry("kern.ttycount", &[libc::CTL_KERN, libc::KERN_TTYCOUNT], CTLTYPE_INT, false),
+    entry("kern.numvnodes", &[libc::CTL_KERN, libc::KERN_NUMVNODES], CTLTYPE_INT, false),
+    entry("kern.maxclusters", &[libc::CTL_KERN, libc::KERN_MAXCLUSTERS], CTLTYPE_INT, true),
+    entry("kern.cachepct", &[libc::CTL_KERN, libc::KERN_CACHEPCT], CTLTYPE_INT, true),
+    entry("kern.global_ptrace", &[libc::CTL_KERN, libc::KERN_GLOBAL_PTRACE], CTLTYPE_INT, true),
+    entry("hw", &[libc::CTL_HW], CTLTYPE_NODE, false),
+    entry("hw.machine", &[libc::CTL_HW, HW_MACHINE], CTLTYPE_STRING, false),
+    entry("hw.model", &[libc::CTL_HW, HW_MODEL], CTLTYPE_STRING, false),
+    entry("hw.ncpu", &[libc::CTL_HW, libc::HW_NCPU], CTLTYPE_INT, false),
+    entry("hw.byteorder", &[libc::CTL_HW, HW_BYTEORDER], CTLTYPE_INT, false),
+    entry("hw.pagesize", &[libc::CTL_HW, HW_PAGESIZE], CTLTYPE_INT, false),
+    entry("hw.disknames", &[libc::CTL_HW, HW_DISKNAMES], CTLTYPE_STRING, false),
+    entry("hw.diskcount", &[libc::CTL_HW, HW_DISKCOUNT], CTLTYPE_INT, false),
+    entry("hw.cpuspeed", &[libc::CTL_HW, HW_CPUSPEED], CTLTYPE_INT, false),
+    entry("hw.setperf", &[libc::CTL_HW, HW_SETPERF], CTLTYPE_INT, true),
+    entry("hw.vendor", &[libc::CTL_HW, HW_VENDOR], CTLTYPE_STRING, false),
+    entry("hw.product", &[libc::CTL_HW, HW_PRODUCT], CTLTYPE_STRING, false),
+    entry("hw.version", &[libc::CTL_HW, HW_VERSION], CTLTYPE_STRING, false),
+    entry("hw.serialno", &[libc::CTL_HW, HW_SERIALNO], CTLTYPE_STRING, false),
+    entry("hw.uuid", &[libc::CTL_HW, HW_UUID], CTLTYPE_STRING, false),
+    entry("hw.physmem", &[libc::CTL_HW, HW_PHYSMEM64], CTLTYPE_QUAD, false),
+    entry("hw.usermem", &[libc::CTL_HW, HW_USERMEM64], CTLTYPE_QUAD, false),
+    entry("hw.ncpufound", &[libc::CTL_HW, HW_NCPUFOUND], CTLTYPE_INT, false),
+    entry("hw.allowpowerdown", &[libc::CTL_HW, HW_ALLOWPOWERDOWN], CTLTYPE_INT, true),
+    entry("hw.perfpolicy", &[libc::CTL_HW, HW_PERFPOLICY], CTLTYPE_STRING, true),
+    entry("hw.smt", &[libc::CTL_HW, HW_SMT], CTLTYPE_INT, true),
+    entry("hw.ncpuonline", &[libc::CTL_HW, HW_NCPUONLINE], CTLTYPE_INT, false),
+];
+
+/// Map an OpenBSD `CTLTYPE_*` number to the type used by this crate.
+pub fn ctl_type(kind: u32) -> CtlType {
+    match kind {
+        CTLTYPE_NODE => CtlType::Node,
+        CTLTYPE_INT => CtlType::Int,
+        CTLTYPE_STRING => CtlType::String,
+        CTLTYPE_QUAD => CtlType::S64,
+        CTLTYPE_STRUCT => CtlType::Struct,
+        _ => CtlType::None,
+    }
+}
+
+// The format strings FreeBSD would report for the type.
+fn fmt(kind: u32) -> &'static str {
+    match kind {
+        CTLTYPE_NODE => "N",
+        CTLTYPE_INT => "I",
+        CTLTYPE_STRING => "A",
+        CTLTYPE_QUAD => "Q",
+        _ => "S",
+    }
+}
+
+fn not_found() -> SysctlError {
+    SysctlError::IoError(io::Error::from_raw_os_error(libc::ENOENT))
+}
+
+fn find(oid: &[c_int]) -> Result<&'static Entry, SysctlError> {
+    ENTRIES.iter().find(|e| e.oid == oid).ok_or_else(not_found)
+}
+
+pub(crate) fn name2oid(name: &str) -> Result<Vec<c_int>, SysctlError> {
+    ENTRIES
+        .iter()
+        .find(|e| e.name == name)
+        .map(|e| e.oid.to_vec())
+        .ok_or_else(not_found)
+}
+
+/// Returns the name of the sysctl with the given OID.
+pub fn oid2name(oid: &[c_int]) -> Result<String, SysctlError> {
+    find(oid).map(|e| e.name.to_owned())
+}
+
+pub(crate) fn oidfmt(oid: &[c_int]) -> Result<CtlInfo, SysctlError> {
+    let entry = find(oid)?;
+
+    let mut flags = CTLFLAG_RD;
+    if entry.writable {
+        flags |= CTLFLAG_WR;
+    }
+
+    Ok(CtlInfo {
+        ctl_type: ctl_type(entry.kind),
+        fmt: fmt(entry.kind).to_owned(),
+        flags,
+    })
+}
+
+// The kernel has no descriptions.
+pub(crate) fn oid2description(oid: &[c_int]) -> Result<String, SysctlError> {
+    find(oid).map(|_| String::new())
+}
+
+pub(crate) fn next_oid(oid: &[c_int]) -> Result<Option<Vec<c_int>>, SysctlError> {
+    Ok(ENTRIES
+        .iter()
+        .filter(|e| e.kind != CTLTYPE_NODE)
+        .map(|e| e.oid)
+        .find(|&o| o > oid)
+        .map(|o| o.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openbsd_entries_sorted() {
+        for pair in ENTRIES.windows(2) {
+            assert!(pair[0].oid < pair[1].oid, "{} out of order", pair[1].name);
+        }
+    }
+
+    #[test]
+    fn openbsd_next_oid() {
+        let ostype = name2oid("kern.ostype").unwrap();
+        assert_eq!(oid2name(&ostype).unwrap(), "kern.ostype");
+        assert_eq!(next_oid(&[libc::CTL_KERN]).unwrap().unwrap(), ostype);
+        assert_eq!(oidfmt(&ostype).unwrap().ctl_type, CtlType::String);
+        assert!(next_oid(&[libc::CTL_HW, HW_NCPUONLINE]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,291 @@
+//! Bridging sysctls into OpenTelemetry metrics. Only available with the
+//! `otel` feature.
+//!
+//! `register()` registers the metrics of an `export::Exporter` as
+//! observable instruments of an OpenTelemetry `Meter`, counters and
+//! gauges as the exporter classifies them. The sysctls are read whenever
+//! the meter collects, so kernel statistics flow through the same
+//! pipelines as the metrics of the application, e.g. to an OTLP
+//! collector.
+//!
+//! Instruments are named like Graphite paths, `kern.maxfiles` becomes
+//! `sysctl.kern.maxfiles`. On FreeBSD, a node may carry a label naming
+//! what its children stand for, e.g. the device nodes below `dev` are
+//! labelled `device_index`. The child is then taken out of the name and
+//! becomes an attribute instead, so `dev.cpu.0.freq` and `dev.cpu.1.freq`
+//! are observed by the one instrument `sysctl.dev.cpu.freq`, with the
+//! attribute `device_index` set to `0` and `1`.
+//!
+//! Values are converted by the transforms of the exporter, see
+//! `export::Transform`, and their labels become attributes as well.
+//!
+//! # Example
+//! ```
+//! extern crate opentelemetry;
+//! extern crate sysctl;
+//! use sysctl::export::Exporter;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # const INCLUDE: &str = "kernel";
+//! # #[cfg(not(target_os = "linux"))]
+//! # const INCLUDE: &str = "kern";
+//! fn main() {
+//!     let meter = opentelemetry::global::meter("sysctl");
+//!     let exporter = Exporter::new().include(INCLUDE);
+//!     let instruments = sysctl::otel::register(&exporter, &meter).expect("could not read metrics");
+//!     println!("registered {} instruments", instruments.len());
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+#[cfg(target_os = "freebsd")]
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(target_os = "freebsd")]
+use libc::c_int;
+use opentelemetry::metrics::{AsyncInstrument, Meter, ObservableCounter, ObservableGauge};
+use opentelemetry::KeyValue;
+
+use super::export::{Exporter, MetricKind, Transform};
+use super::{numeric, Ctl, SysctlError, Unit};
+
+// The prefix of instrument names.
+const PREFIX: &str = "sysctl.";
+
+/// The instruments registered by `register()`. The meter keeps observing
+/// them through their callbacks, this only keeps hold of them.
+#[derive(Clone)]
+pub struct Instruments {
+    gauges: Vec<ObservableGauge<f64>>,
+    counters: Vec<ObservableCounter<f64>>,
+}
+
+impl Instruments {
+    /// The number of instruments registered.
+    pub fn len(&self) -> usize {
+        self.gauges.len() + self.counters.len()
+    }
+
+    /// Whether no instruments were registered, as no sysctls were
+    /// exported.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for Instruments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Instruments")
+            .field("gauges", &self.gauges.len())
+            .field("counters", &self.counters.len())
+            .finish()
+    }
+}
+
+// The sysctls observed by one instrument, with their attributes.
+struct Instrument {
+    kind: MetricKind,
+    help: Option<String>,
+    unit: Option<Unit>,
+    ctls: Vec<(Ctl, Vec<KeyValue>, Option<Transform>)>,
+}
+
+/// Register the metrics the exporter collects now as instruments of the
+/// meter. Each instrument reads its sysctls again whenever the meter
+/// collects, and leaves out those that can no longer be read. Sysctls
+/// that appear later are not picked up, register again to include them.
+///
+/// Units are inferred by `Ctl::unit()`, unless the values are
+/// transformed.
+///
+/// Fails if the exporter fails to collect, see `Exporter::collect()`.
+pub fn register(exporter: &Exporter, meter: &Meter) -> Result<Instruments, SysctlError> {
+    let mut labels = Labels::default();
+    let mut instruments: BTreeMap<String, Instrument> = BTreeMap::new();
+    for metric in exporter.collect()? {
+        let ctl = match Ctl::new(&metric.sysctl) {
+            Ok(ctl) => ctl,
+            Err(_) => continue,
+        };
+        let (name, attributes) = split(&metric.sysctl, &labels.of(&ctl));
+        let transform = exporter.transform_of(&metric.sysctl).cloned();
+        let instrument = instruments.entry(name).or_insert_with(|| Instrument {
+            kind: metric.kind,
+            help: metric.help.clone(),
+            unit: match transform {
+                Some(_) => None,
+                None => ctl.unit().ok().and_then(|u| u),
+            },
+            ctls: vec![],
+        });
+        let attributes = attributes
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect();
+        instrument.ctls.push((ctl, attributes, transform));
+    }
+
+    let mut registered = Instruments {
+        gauges: vec![],
+        counters: vec![],
+    };
+    for (name, instrument) in instruments {
+        let ctls = instrument.ctls;
+        let observe = move |observer: &dyn AsyncInstrument<f64>| {
+            for (ctl, attributes, transform) in &ctls {
+                let raw = match ctl.value().ok().as_ref().and_then(numeric) {
+                    Some(raw) => raw,
+                    None => continue,
+                };
+                match transform.as_ref().map(|t| t.convert(raw)) {
+                    Some((value, Some((key, label)))) => {
+                        let mut attributes = attributes.clone();
+                        attributes.push(KeyValue::new(key, label));
+                        observer.observe(value, &attributes);
+                    }
+                    Some((value, None)) => observer.observe(value, attributes),
+                    None => observer.observe(raw as f64, attributes),
+                }
+            }
+        };
+        // The builders differ in type, so set them up alike by a macro
+        macro_rules! build {
+            ($builder:expr) => {{
+                let mut builder = $builder.with_callback(observe);
+                if let Some(help) = instrument.help {
+                    builder = builder.with_description(help);
+                }
+                if let Some(unit) = instrument.unit {
+                    builder = builder.with_unit(ucum(unit));
+                }
+                builder.build()
+            }};
+        }
+        match instrument.kind {
+            MetricKind::Gauge => registered
+                .gauges
+                .push(build!(meter.f64_observable_gauge(name))),
+            MetricKind::Counter => registered
+                .counters
+                .push(build!(meter.f64_observable_counter(name))),
+        }
+    }
+    Ok(registered)
+}
+
+// The instrument name of a sysctl and its attributes. `labels[i]` is the
+// label of the node made of the first i + 1 components, if any; the
+// component following a labelled node is the value of the attribute.
+fn split(sysctl: &str, labels: &[Option<String>]) -> (String, Vec<(String, String)>) {
+    let mut components = vec![];
+    let mut attributes = vec![];
+    let mut label: Option<&String> = None;
+    for (i, component) in sysctl.split('.').enumerate() {
+        match label.take() {
+            Some(key) => attributes.push((key.clone(), component.to_owned())),
+            None => components.push(component),
+        }
+        label = labels.get(i).and_then(Option::as_ref);
+    }
+
+    // Instrument names may only contain letters, digits, `_`, `.`, `-`
+    // and `/`
+    let mut name = PREFIX.to_owned();
+    name.extend(components.join(".").chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-' | '/' => c,
+        _ => '_',
+    }));
+    (name, attributes)
+}
+
+// The unit in UCUM notation, as OpenTelemetry expects.
+fn ucum(unit: Unit) -> &'static str {
+    match unit {
+        Unit::Bytes => "By",
+        Unit::Pages => "{page}",
+        Unit::Milliseconds => "ms",
+        Unit::Percent => "%",
+        Unit::Kelvin => "K",
+    }
+}
+
+// The labels of the nodes above sysctls, looked up once per node.
+#[derive(Default)]
+struct Labels {
+    #[cfg(target_os = "freebsd")]
+    nodes: HashMap<Vec<c_int>, Option<String>>,
+}
+
+impl Labels {
+    // The label of every node above the sysctl, see `split()`.
+    #[cfg(target_os = "freebsd")]
+    fn of(&mut self, ctl: &Ctl) -> Vec<Option<String>> {
+        let oid = ctl.oid();
+        (1..oid.len())
+            .map(|len| {
+                self.nodes
+                    .entry(oid[..len].to_vec())
+                    .or_insert_with_key(|node| {
+                        super::oid2label(node).ok().filter(|l| !l.is_empty())
+                    })
+                    .clone()
+            })
+            .collect()
+    }
+
+    // Only FreeBSD labels nodes.
+    #[cfg(not(target_os = "freebsd"))]
+    fn of(&mut self, _ctl: &Ctl) -> Vec<Option<String>> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    const NUMERIC: &str = "kernel.pid_max";
+    #[cfg(not(target_os = "linux"))]
+    const NUMERIC: &str = "kern.maxproc";
+
+    #[test]
+    fn otel_split() {
+        assert_eq!(
+            split("kern.maxfiles", &[]),
+            ("sysctl.kern.maxfiles".to_owned(), vec![])
+        );
+        let labels = [None, Some("device_index".to_owned()), None, None];
+        assert_eq!(
+            split("dev.cpu.0.freq", &labels),
+            (
+                "sysctl.dev.cpu.freq".to_owned(),
+                vec![("device_index".to_owned(), "0".to_owned())]
+            )
+        );
+        // A label on the sysctl itself has no component to take
+        assert_eq!(
+            split("dev.cpu", &labels),
+            ("sysctl.dev.cpu".to_owned(), vec![])
+        );
+        assert_eq!(
+            split("net.ipv4.conf.eth0:1.forwarding", &[]).0,
+            "sysctl.net.ipv4.conf.eth0_1.forwarding"
+        );
+    }
+
+    #[test]
+    fn otel_register() {
+        // The global meter provider does nothing until one is installed,
+        // which is enough to build instruments
+        let meter = opentelemetry::global::meter("sysctl");
+        let instruments = register(&Exporter::new().include(NUMERIC), &meter).unwrap();
+        assert_eq!(instruments.len(), 1);
+        assert!(!instruments.is_empty());
+
+        let none = register(&Exporter::new().include(NUMERIC).exclude(NUMERIC), &meter).unwrap();
+        assert!(none.is_empty());
+        assert!(register(&Exporter::new().include("nonexistent.node"), &meter).is_err());
+    }
+}
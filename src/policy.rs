@@ -0,0 +1,202 @@
+//! Detect drift of the live system from a declared policy.
+//!
+//! A policy maps sysctl names to their desired values and lists prefixes
+//! of the tree that are deliberately left unmanaged. Checking a policy
+//! reports every managed sysctl whose live value differs from the desired
+//! one, tagged with the level assigned to it in the policy.
+//!
+//! Policies can be built in code or parsed from text. Desired values use
+//! the same syntax as baselines:
+//!
+//! ```text
+//! kern.ipc.somaxconn = 4096
+//! critical security.bsd.see_other_uids = 0
+//! info kern.maxfiles = 10000..
+//! unmanaged dev.cpu
+//! ```
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::policy::Policy;
+//!
+//! fn main() {
+//!     let policy: Policy = "critical kern.securelevel = 1..\nunmanaged kern.securelevel"
+//!         .parse()
+//!         .expect("could not parse policy");
+//!     for drift in policy.drift() {
+//!         println!("[{}] {}: expected {}", drift.level, drift.name, drift.desired);
+//!     }
+//! }
+//! ```
+
+use std::cmp;
+use std::fmt;
+use std::str::FromStr;
+
+use super::baseline::Expected;
+use super::conf::{lines, split_line};
+use super::{under, value, CtlValue, SysctlError};
+
+/// How serious a drift from the policy is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Level::Info => write!(f, "info"),
+            Level::Warning => write!(f, "warning"),
+            Level::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// A desired sysctl value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub desired: Expected,
+    pub level: Level,
+}
+
+/// A managed sysctl that does not have its desired value.
+#[derive(Debug)]
+pub struct Drift {
+    pub name: String,
+    pub desired: Expected,
+    /// The live value, or the error encountered reading it.
+    pub actual: Result<CtlValue, SysctlError>,
+    pub level: Level,
+}
+
+/// The desired state of the system.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    rules: Vec<Rule>,
+    unmanaged: Vec<String>,
+}
+
+impl Policy {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        Policy::default()
+    }
+
+    /// Declare the desired value of a sysctl.
+    pub fn desire(mut self, name: &str, desired: Expected, level: Level) -> Self {
+        self.rules.push(Rule {
+            name: name.to_owned(),
+            desired,
+            level,
+        });
+        self
+    }
+
+    /// Exclude a subtree from drift detection. The prefix matches the
+    /// named node itself and everything below it.
+    pub fn unmanaged(mut self, prefix: &str) -> Self {
+        self.unmanaged.push(prefix.to_owned());
+        self
+    }
+
+    /// The desired values of this policy, in order.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Check if a sysctl is managed by this policy.
+    pub fn is_managed(&self, name: &str) -> bool {
+        !self.unmanaged.iter().any(|p| under(name, p))
+    }
+
+    /// Compare the live system with the policy and return all managed
+    /// sysctls that drifted, ordered from most to least severe.
+    pub fn drift(&self) -> Vec<Drift> {
+        let mut drift: Vec<Drift> = self
+            .rules
+            .iter()
+            .filter(|r| self.is_managed(&r.name))
+            .filter_map(|r| {
+                let actual = value(&r.name);
+                let ok = match actual {
+                    Ok(ref v) => r.desired.matches(v),
+                    Err(_) => false,
+                };
+                if ok {
+                    return None;
+                }
+                Some(Drift {
+                    name: r.name.clone(),
+                    desired: r.desired.clone(),
+                    actual,
+                    level: r.level,
+                })
+            })
+            .collect();
+        drift.sort_by_key(|d| cmp::Reverse(d.level));
+        drift
+    }
+}
+
+impl FromStr for Policy {
+    type Err = SysctlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = Policy::new();
+
+        for (n, line) in lines(s) {
+            let err = |reason: String| SysctlError::ParseError { line: n, reason };
+
+            if let Some(prefix) = line.strip_prefix("unmanaged ") {
+                policy = policy.unmanaged(prefix.trim());
+                continue;
+            }
+
+            let (line, level) = match line.split_whitespace().next() {
+                Some("info") => (line["info".len()..].trim_start(), Level::Info),
+                Some("warning") => (line["warning".len()..].trim_start(), Level::Warning),
+                Some("critical") => (line["critical".len()..].trim_start(), Level::Critical),
+                _ => (line, Level::Warning),
+            };
+
+            let (name, desired) = split_line(line).map_err(&err)?;
+            let desired = desired.parse::<Expected>().map_err(&err)?;
+
+            policy = policy.desire(name, desired, level);
+        }
+
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_parse() {
+        let policy: Policy = "kern.ipc.somaxconn = 4096\n\
+                              critical security.bsd.see_other_uids = 0\n\
+                              info kern.maxfiles = 10000..\n\
+                              unmanaged dev.cpu\n"
+            .parse()
+            .unwrap();
+
+        let levels: Vec<Level> = policy.rules().iter().map(|r| r.level).collect();
+        assert_eq!(levels, vec![Level::Warning, Level::Critical, Level::Info]);
+        assert_eq!(policy.rules()[1].name, "security.bsd.see_other_uids");
+        assert_eq!(policy.rules()[1].desired, Expected::Exact("0".into()));
+
+        assert!(!policy.is_managed("dev.cpu"));
+        assert!(!policy.is_managed("dev.cpu.0.freq"));
+        assert!(policy.is_managed("dev.cpufreq.0.freq_driver"));
+        assert!(policy.is_managed("kern.ipc.somaxconn"));
+
+        assert!("critical kern.maxfiles".parse::<Policy>().is_err());
+    }
+}
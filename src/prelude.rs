@@ -0,0 +1,19 @@
+//! The commonly used types of this crate, for glob importing.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::prelude::*;
+//!
+//! fn ostype() -> Result<CtlValue> {
+//!     Ctl::new("kern.ostype")?.value()
+//! }
+//!
+//! fn main() {
+//!     println!("{:?}", ostype());
+//! }
+//! ```
+
+pub use super::{
+    Ctl, CtlFlags, CtlInfo, CtlIter, CtlType, CtlValue, Result, SysctlError, SysctlPod,
+};
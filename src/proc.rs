@@ -0,0 +1,326 @@
+//! Processes, decoded from the `kern.proc` tables.
+//!
+//! `kern.proc.all` returns an array of `struct kinfo_proc` records, one
+//! per process, `kern.proc.pid.<pid>` the record of a single process and
+//! `kern.proc.args.<pid>` its command line. Only available on 64-bit
+//! FreeBSD, the layout of `struct kinfo_proc` decoded here is the one of
+//! amd64, arm64, powerpc64 and riscv64.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let procs = sysctl::proc::process_list().expect("could not read kern.proc.all");
+//!     for p in procs.iter().filter(|p| p.uid == 0) {
+//!         println!("{:>6} {:?} {}", p.pid, p.state, p.command);
+//!     }
+//! }
+//! ```
+
+use std::time::SystemTime;
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::{c_int, gid_t, pid_t, uid_t};
+
+use super::types::TimeVal;
+use super::{decode_utf8, fetch_oid, name2oid, SysctlError};
+
+// Offsets of the fields of struct kinfo_proc on 64-bit targets, see
+// sys/user.h
+const KP_STRUCTSIZE: usize = 0;
+const KP_PID: usize = 72;
+const KP_PPID: usize = 76;
+const KP_PGID: usize = 80;
+const KP_SID: usize = 88;
+const KP_UID: usize = 168;
+const KP_RUID: usize = 172;
+const KP_RGID: usize = 180;
+const KP_SIZE: usize = 256;
+const KP_RSSIZE: usize = 264;
+const KP_PCTCPU: usize = 308;
+const KP_RUNTIME: usize = 328;
+const KP_START: usize = 336;
+const KP_FLAG: usize = 368;
+const KP_STAT: usize = 388;
+const KP_NICE: usize = 389;
+const KP_WMESG: usize = 411;
+const KP_LOGIN: usize = 420;
+const KP_COMM: usize = 447;
+const KP_JID: usize = 592;
+const KP_NUMTHREADS: usize = 596;
+// KINFO_PROC_SIZE, the size of struct kinfo_proc, which the kernel keeps
+// fixed by using up spare fields
+const KP_SIZEOF: usize = 1088;
+
+// Sizes of the string fields, including the NUL
+const WMESGLEN: usize = 9;
+const LOGNAMELEN: usize = 18;
+const COMMLEN: usize = 20;
+
+// The scale of ki_pctcpu
+const FSCALE: u32 = 1 << 11;
+
+/// The state of a process (`S*` in sys/proc.h).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProcessState {
+    /// Being created by fork.
+    Idle,
+    Running,
+    Sleeping,
+    Stopped,
+    Zombie,
+    /// Waiting for an interrupt.
+    Waiting,
+    /// Blocked on a lock.
+    Locked,
+    Unknown(i8),
+}
+
+impl From<i8> for ProcessState {
+    fn from(s: i8) -> Self {
+        match s {
+            1 => ProcessState::Idle,
+            2 => ProcessState::Running,
+            3 => ProcessState::Sleeping,
+            4 => ProcessState::Stopped,
+            5 => ProcessState::Zombie,
+            6 => ProcessState::Waiting,
+            7 => ProcessState::Locked,
+            s => ProcessState::Unknown(s),
+        }
+    }
+}
+
+/// A process, from its `struct kinfo_proc`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    pub pid: pid_t,
+    /// Parent process.
+    pub ppid: pid_t,
+    /// Process group.
+    pub pgid: pid_t,
+    /// Session.
+    pub sid: pid_t,
+    /// Effective user id.
+    pub uid: uid_t,
+    /// Real user id.
+    pub ruid: uid_t,
+    /// Real group id.
+    pub rgid: gid_t,
+    /// Jail, 0 outside of jails.
+    pub jid: c_int,
+    pub state: ProcessState,
+    pub nice: i8,
+    /// `P_*` flags.
+    pub flags: i64,
+    pub threads: c_int,
+    /// Command name, at most 19 bytes.
+    pub command: String,
+    /// What the process is waiting on, if sleeping.
+    pub wait_message: String,
+    /// Login name of the session.
+    pub login: String,
+    /// Virtual size in bytes.
+    pub virtual_size: u64,
+    /// Resident set size in pages.
+    pub resident_pages: i64,
+    /// Recent CPU usage, scaled by 2048.
+    pub pctcpu: u32,
+    /// CPU time used, in microseconds.
+    pub runtime: u64,
+    pub start: SystemTime,
+}
+
+impl Process {
+    /// Recent CPU usage in percent of one CPU, as shown by ps(1).
+    pub fn cpu_percent(&self) -> f64 {
+        f64::from(self.pctcpu) * 100.0 / f64::from(FSCALE)
+    }
+}
+
+/// Return all processes.
+pub fn process_list() -> Result<Vec<Process>, SysctlError> {
+    let oid = name2oid("kern.proc.all")?;
+    let buf = fetch_oid(&oid, 0)?;
+    decode(&buf)
+}
+
+/// Return the process with the pid.
+///
+/// Fails with `ESRCH` if there is no such process.
+pub fn process_by_pid(pid: pid_t) -> Result<Process, SysctlError> {
+    let mut oid = name2oid("kern.proc.pid")?;
+    oid.push(pid);
+    let buf = fetch_oid(&oid, 0)?;
+    decode(&buf)?
+        .into_iter()
+        .next()
+        .ok_or(SysctlError::ExtractionError)
+}
+
+/// Return the command line arguments of the process, starting with the
+/// program name. Invalid UTF-8 sequences are replaced unless in strict
+/// mode, see `set_strict()`.
+///
+/// Kernel processes have no arguments. Reading the arguments of a
+/// process of another user may be denied, see
+/// `security.bsd.see_other_uids`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let args = sysctl::proc::process_args(1).expect("could not read kern.proc.args");
+///     println!("init: {}", args.join(" "));
+/// }
+/// ```
+pub fn process_args(pid: pid_t) -> Result<Vec<String>, SysctlError> {
+    let mut oid = name2oid("kern.proc.args")?;
+    oid.push(pid);
+    let buf = fetch_oid(&oid, 0)?;
+    split_args(&buf)
+}
+
+// The arguments are NUL terminated strings, one after the other.
+fn split_args(buf: &[u8]) -> Result<Vec<String>, SysctlError> {
+    let buf = buf.strip_suffix(&[0]).unwrap_or(buf);
+    if buf.is_empty() {
+        return Ok(vec![]);
+    }
+    buf.split(|&b| b == 0).map(decode_utf8).collect()
+}
+
+// A NUL terminated string in a fixed size field.
+fn c_string(field: &[u8]) -> Result<String, SysctlError> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    decode_utf8(&field[..end])
+}
+
+// Decode an array of struct kinfo_proc. Each record starts with its own
+// size, a record of another size has a layout we do not know.
+fn decode(mut buf: &[u8]) -> Result<Vec<Process>, SysctlError> {
+    let mut procs = vec![];
+
+    while !buf.is_empty() {
+        if buf.len() < KP_SIZEOF {
+            return Err(SysctlError::ShortRead {
+                read: buf.len(),
+                reported: KP_SIZEOF,
+            });
+        }
+        let size = NativeEndian::read_i32(&buf[KP_STRUCTSIZE..]);
+        if size as usize != KP_SIZEOF {
+            return Err(SysctlError::InvalidValue(format!(
+                "struct kinfo_proc of {} bytes, expected {}",
+                size, KP_SIZEOF
+            )));
+        }
+
+        let start = TimeVal {
+            sec: NativeEndian::read_i64(&buf[KP_START..]),
+            usec: NativeEndian::read_i64(&buf[KP_START + 8..]),
+        };
+        procs.push(Process {
+            pid: NativeEndian::read_i32(&buf[KP_PID..]),
+            ppid: NativeEndian::read_i32(&buf[KP_PPID..]),
+            pgid: NativeEndian::read_i32(&buf[KP_PGID..]),
+            sid: NativeEndian::read_i32(&buf[KP_SID..]),
+            uid: NativeEndian::read_u32(&buf[KP_UID..]),
+            ruid: NativeEndian::read_u32(&buf[KP_RUID..]),
+            rgid: NativeEndian::read_u32(&buf[KP_RGID..]),
+            jid: NativeEndian::read_i32(&buf[KP_JID..]),
+            state: ProcessState::from(buf[KP_STAT] as i8),
+            nice: buf[KP_NICE] as i8,
+            flags: NativeEndian::read_i64(&buf[KP_FLAG..]),
+            threads: NativeEndian::read_i32(&buf[KP_NUMTHREADS..]),
+            command: c_string(&buf[KP_COMM..KP_COMM + COMMLEN])?,
+            wait_message: c_string(&buf[KP_WMESG..KP_WMESG + WMESGLEN])?,
+            login: c_string(&buf[KP_LOGIN..KP_LOGIN + LOGNAMELEN])?,
+            virtual_size: NativeEndian::read_u64(&buf[KP_SIZE..]),
+            resident_pages: NativeEndian::read_i64(&buf[KP_RSSIZE..]),
+            pctcpu: NativeEndian::read_u32(&buf[KP_PCTCPU..]),
+            runtime: NativeEndian::read_u64(&buf[KP_RUNTIME..]),
+            start: start.system_time(),
+        });
+
+        buf = &buf[KP_SIZEOF..];
+    }
+
+    Ok(procs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn kinfo_proc(size: usize, pid: i32, comm: &str) -> Vec<u8> {
+        let mut b = vec![0; size];
+        NativeEndian::write_i32(&mut b[KP_STRUCTSIZE..], size as i32);
+        NativeEndian::write_i32(&mut b[KP_PID..], pid);
+        NativeEndian::write_i32(&mut b[KP_PPID..], 1);
+        NativeEndian::write_u32(&mut b[KP_UID..], 1001);
+        NativeEndian::write_u64(&mut b[KP_SIZE..], 1 << 20);
+        NativeEndian::write_u32(&mut b[KP_PCTCPU..], FSCALE / 2);
+        NativeEndian::write_i64(&mut b[KP_START..], 1_600_000_000);
+        NativeEndian::write_i64(&mut b[KP_START + 8..], 500_000);
+        b[KP_STAT] = 3;
+        b[KP_NICE] = -5i8 as u8;
+        b[KP_WMESG..KP_WMESG + 6].copy_from_slice(b"select");
+        b[KP_COMM..KP_COMM + comm.len()].copy_from_slice(comm.as_bytes());
+        NativeEndian::write_i32(&mut b[KP_NUMTHREADS..], 2);
+        b
+    }
+
+    #[test]
+    fn proc_decode() {
+        let mut buf = kinfo_proc(KP_SIZEOF, 42, "sshd");
+        buf.extend(kinfo_proc(KP_SIZEOF, 43, "nineteen-bytes-long"));
+
+        let procs = decode(&buf).unwrap();
+        assert_eq!(procs.len(), 2);
+        assert_eq!(procs[0].pid, 42);
+        assert_eq!(procs[0].ppid, 1);
+        assert_eq!(procs[0].uid, 1001);
+        assert_eq!(procs[0].state, ProcessState::Sleeping);
+        assert_eq!(procs[0].nice, -5);
+        assert_eq!(procs[0].command, "sshd");
+        assert_eq!(procs[0].wait_message, "select");
+        assert_eq!(procs[0].threads, 2);
+        assert_eq!(procs[0].virtual_size, 1 << 20);
+        assert_eq!(procs[0].cpu_percent(), 50.0);
+        assert_eq!(
+            procs[0].start,
+            UNIX_EPOCH + Duration::from_millis(1_600_000_000_500)
+        );
+        assert_eq!(procs[1].pid, 43);
+        assert_eq!(procs[1].command, "nineteen-bytes-long");
+
+        assert!(decode(&buf[..1000]).is_err());
+        // Records of another layout, e.g. of a 32-bit process
+        match decode(&kinfo_proc(768, 1, "x")) {
+            Err(SysctlError::InvalidValue(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(decode(&kinfo_proc(1200, 1, "x")).is_err());
+    }
+
+    #[test]
+    fn proc_split_args() {
+        assert_eq!(split_args(b"ls\0-l\0\0").unwrap(), vec!["ls", "-l", ""]);
+        assert_eq!(split_args(b"/sbin/init\0").unwrap(), vec!["/sbin/init"]);
+        assert!(split_args(b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn proc_self() {
+        let pid = unsafe { libc::getpid() };
+        let me = process_by_pid(pid).unwrap();
+        assert_eq!(me.pid, pid);
+        assert_eq!(me.uid, unsafe { libc::geteuid() });
+        assert!(process_list().unwrap().iter().any(|p| p.pid == pid));
+        assert!(!process_args(pid).unwrap().is_empty());
+    }
+}
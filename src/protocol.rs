@@ -0,0 +1,115 @@
+//! Request and response types for reading and setting sysctls remotely.
+//!
+//! An agent running on the target host receives a `Request`, runs it with
+//! `Request::execute` and sends back the resulting `Response`. With the
+//! `serde` feature enabled all types implement `Serialize` and
+//! `Deserialize`, so any serde format can be used on the wire.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::protocol::Request;
+//!
+//! fn main() {
+//!     let request = Request::Read(vec!["kern.osrevision".into()]);
+//!     for item in request.execute().items {
+//!         println!("{}: {:?}", item.name, item.result);
+//!     }
+//! }
+//! ```
+
+use super::{set_value, value, CtlValue};
+
+/// A single sysctl assignment.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Setting {
+    pub name: String,
+    pub value: CtlValue,
+}
+
+/// An operation to perform on the target host.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Request {
+    /// Read the values of the named sysctls.
+    Read(Vec<String>),
+    /// Set sysctls to new values.
+    Apply(Vec<Setting>),
+}
+
+/// The outcome for a single sysctl of a request.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Item {
+    pub name: String,
+    /// The value read, or the value after setting it. Errors are
+    /// transported as their display string.
+    pub result: Result<CtlValue, String>,
+}
+
+/// The answer to a `Request`, with one item per requested sysctl in
+/// request order.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Response {
+    pub items: Vec<Item>,
+}
+
+impl Request {
+    /// Run the request against the local system.
+    ///
+    /// Every sysctl is handled independently; a failure for one of them
+    /// is reported in its item and does not stop the others.
+    pub fn execute(self) -> Response {
+        let items = match self {
+            Request::Read(names) => names
+                .into_iter()
+                .map(|name| Item {
+                    result: value(&name).map_err(|e| e.to_string()),
+                    name,
+                })
+                .collect(),
+            Request::Apply(settings) => settings
+                .into_iter()
+                .map(|s| Item {
+                    result: set_value(&s.name, s.value).map_err(|e| e.to_string()),
+                    name: s.name,
+                })
+                .collect(),
+        };
+        Response { items }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn protocol_roundtrip() {
+        let request = Request::Apply(vec![Setting {
+            name: "kern.maxfiles".into(),
+            value: CtlValue::Int(10000),
+        }]);
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+
+        let response = Response {
+            items: vec![
+                Item {
+                    name: "kern.ostype".into(),
+                    result: Ok(CtlValue::String("FreeBSD".into())),
+                },
+                Item {
+                    name: "kern.nonexistent".into(),
+                    result: Err("IO Error: No such file or directory".into()),
+                },
+            ],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+    }
+}
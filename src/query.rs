@@ -0,0 +1,188 @@
+//! Reading all sysctls matching a name pattern.
+//!
+//! Like `sysctl net.inet.tcp` on the command line, a pattern selects a
+//! node together with everything below it. Components of the pattern may
+//! contain the wildcards `*`, matching any number of characters, and `?`,
+//! matching a single one, but never match across dots. So
+//! `net.inet.*.recvspace` selects `net.inet.tcp.recvspace` and
+//! `net.inet.udp.recvspace`, while `net.inet.tcp.*` selects all of
+//! `net.inet.tcp`.
+//!
+//! Only the subtree below the components without wildcards is walked,
+//! and only values of matching sysctls are read.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # fn main() {}
+//! # #[cfg(not(target_os = "linux"))]
+//! fn main() {
+//!     for entry in sysctl::all("kern.os*").expect("could not query") {
+//!         match entry {
+//!             Ok((name, value)) => println!("{} = {}", name, value),
+//!             Err(e) => println!("error: {}", e),
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::iter;
+
+use super::{Ctl, CtlIter, CtlType, CtlValue, SysctlError};
+
+/// Iterator over the names and values of the sysctls matching a pattern,
+/// returned by `all()`.
+pub struct All {
+    pattern: Vec<String>,
+    ctls: Box<dyn Iterator<Item = Result<Ctl, SysctlError>>>,
+}
+
+/// Return the names and values of all sysctls matching the pattern, see
+/// the module documentation for the syntax. An empty pattern selects the
+/// whole tree.
+///
+/// Fails if the node to walk does not exist. Errors while walking or
+/// reading a value are returned by the iterator.
+pub fn all(pattern: &str) -> Result<All, SysctlError> {
+    let pattern: Vec<String> = if pattern.is_empty() {
+        vec![]
+    } else {
+        pattern.split('.').map(String::from).collect()
+    };
+    let base = pattern
+        .iter()
+        .take_while(|c| !is_glob(c))
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(".");
+
+    All::new(pattern, &base)
+}
+
+impl All {
+    fn new(pattern: Vec<String>, base: &str) -> Result<Self, SysctlError> {
+        let ctls: Box<dyn Iterator<Item = Result<Ctl, SysctlError>>> = if base.is_empty() {
+            Box::new(CtlIter::root())
+        } else {
+            let node = Ctl::new(base)?;
+            // A leaf only selects itself
+            if node.value_type()? == CtlType::Node {
+                Box::new(node.children())
+            } else {
+                Box::new(iter::once(Ok(node)))
+            }
+        };
+
+        Ok(All { pattern, ctls })
+    }
+}
+
+impl Iterator for All {
+    type Item = Result<(String, CtlValue), SysctlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ctl = match self.ctls.next()? {
+                Ok(ctl) => ctl,
+                Err(e) => return Some(Err(e)),
+            };
+            let name = match ctl.name() {
+                Ok(name) => name,
+                Err(e) => return Some(Err(e)),
+            };
+            if matches(&self.pattern, &name) {
+                return Some(ctl.value().map(|value| (name, value)));
+            }
+        }
+    }
+}
+
+// Whether the name is selected by the pattern, in the syntax of all().
+pub(crate) fn name_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<String> = if pattern.is_empty() {
+        vec![]
+    } else {
+        pattern.split('.').map(String::from).collect()
+    };
+    matches(&pattern, name)
+}
+
+pub(crate) fn is_glob(component: &str) -> bool {
+    component.contains(&['*', '?'][..])
+}
+
+// Whether the name is selected by the pattern, i.e. the pattern matches
+// its leading components.
+fn matches(pattern: &[String], name: &str) -> bool {
+    let mut components = name.split('.');
+    pattern.iter().all(|p| match components.next() {
+        Some(c) => glob_match(p.as_bytes(), c.as_bytes()),
+        None => false,
+    })
+}
+
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => (0..=s.len()).any(|i| glob_match(rest, &s[i..])),
+        Some((&p, rest)) => match s.split_first() {
+            Some((&c, s_rest)) => (p == b'?' || p == c) && glob_match(rest, s_rest),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> Vec<String> {
+        s.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn query_matches() {
+        assert!(matches(&pattern("net.inet.tcp"), "net.inet.tcp.mssdflt"));
+        assert!(matches(&pattern("net.inet.tcp.*"), "net.inet.tcp.hostcache.count"));
+        assert!(matches(&pattern("net.inet.*.recvspace"), "net.inet.udp.recvspace"));
+        assert!(matches(&pattern("kern.os*"), "kern.ostype"));
+        assert!(matches(&pattern("kern.os?ype"), "kern.ostype"));
+        assert!(matches(&[], "kern.ostype"));
+        assert!(!matches(&pattern("kern.os*"), "kern.hostname"));
+        assert!(!matches(&pattern("kern.os?ype"), "kern.osttype"));
+        assert!(!matches(&pattern("net.inet.tcp"), "net.inet.tcp6.x"));
+        assert!(!matches(&pattern("net.inet.tcp.*"), "net.inet.tcp"));
+        assert!(!matches(&pattern("kern.*"), "kernel.ostype"));
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn query_all() {
+        let entries: Vec<_> = all("kern.os*")
+            .expect("could not query")
+            .collect::<Result<_, _>>()
+            .expect("could not read value");
+        assert!(entries.iter().any(|(n, _)| n == "kern.ostype"));
+        assert!(entries.iter().all(|(n, _)| n.starts_with("kern.os")));
+
+        let ostype: Vec<_> = all("kern.ostype").unwrap().collect();
+        assert_eq!(ostype.len(), 1);
+        assert!(all("kern.nonexistent.*").is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn query_all() {
+        let entries: Vec<_> = all("kernel.os*")
+            .expect("could not query")
+            .collect::<Result<_, _>>()
+            .expect("could not read value");
+        assert!(entries
+            .iter()
+            .any(|(n, v)| n == "kernel.ostype" && *v == CtlValue::String("Linux".into())));
+        assert!(entries.iter().all(|(n, _)| n.starts_with("kernel.os")));
+        assert!(all("kernel.nonexistent.*").is_err());
+    }
+}
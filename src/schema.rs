@@ -0,0 +1,339 @@
+//! A structured description of the sysctl tree.
+//!
+//! `schema()` walks the whole tree and records the metadata of every
+//! sysctl without its value, only longs are read to tell whether they
+//! hold an array. With the `serde` feature enabled a `Schema`
+//! can be written out (e.g. as JSON) and inspected later by tools that
+//! have no access to the live system.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let schema = sysctl::schema().expect("could not read schema");
+//!     if let Some(entry) = schema.get("kern.osrevision") {
+//!         println!("{:?}: {:?}", entry.oid, entry.ctl_type);
+//!     }
+//! }
+//! ```
+//!
+//! A schema can also be used to validate a sysctl.conf(5) file offline:
+//!
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let schema = sysctl::schema().expect("could not read schema");
+//!     for error in schema.validate("kern.maxfiles=abc\nkern.osrelease=1.0") {
+//!         println!("{}", error);
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use libc::{c_int, c_uint};
+
+use super::conf::{dotted, lines, parse_value, split_line};
+use super::{
+    value_from_str, Ctl, CtlIter, CtlType, CtlValue, SysctlError, CTLFLAG_TUN, CTLFLAG_WR,
+};
+
+/// Metadata of a single sysctl.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SchemaEntry {
+    pub name: String,
+    pub oid: Vec<c_int>,
+    pub ctl_type: CtlType,
+    pub fmt: String,
+    /// Raw `CTLFLAG_*` bits.
+    pub flags: c_uint,
+    /// `None` where descriptions are not available.
+    pub description: Option<String>,
+    /// `None` if the sysctl has no label or labels are not supported.
+    pub label: Option<String>,
+    /// Whether a long held more than one value when the schema was taken,
+    /// like `kern.cp_time`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub array: bool,
+}
+
+/// The metadata of all sysctls, in tree order.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Schema {
+    pub entries: Vec<SchemaEntry>,
+}
+
+/// A problem found while validating a configuration against a schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Line number in the configuration, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A difference between two schemas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    /// The sysctl only exists in the newer schema.
+    Added(SchemaEntry),
+    /// The sysctl only exists in the older schema.
+    Removed(SchemaEntry),
+    /// The sysctl exists in both schemas with a different type or format.
+    Retyped { old: SchemaEntry, new: SchemaEntry },
+}
+
+impl SchemaChange {
+    /// The name of the changed sysctl.
+    pub fn name(&self) -> &str {
+        match *self {
+            SchemaChange::Added(ref e) | SchemaChange::Removed(ref e) => &e.name,
+            SchemaChange::Retyped { ref new, .. } => &new.name,
+        }
+    }
+}
+
+impl Schema {
+    /// Compare this schema with a newer one, e.g. dumped from a later OS
+    /// release. Sysctls are matched by name, as OID numbers of dynamic
+    /// nodes are not stable. Changes are sorted by name.
+    pub fn diff(&self, newer: &Schema) -> Vec<SchemaChange> {
+        let old: BTreeMap<&str, &SchemaEntry> =
+            self.entries.iter().map(|e| (e.name.as_str(), e)).collect();
+        let new: BTreeMap<&str, &SchemaEntry> =
+            newer.entries.iter().map(|e| (e.name.as_str(), e)).collect();
+
+        let mut changes: Vec<SchemaChange> = vec![];
+        for (name, o) in &old {
+            match new.get(name) {
+                None => changes.push(SchemaChange::Removed((*o).clone())),
+                Some(n) if n.ctl_type != o.ctl_type || n.fmt != o.fmt => {
+                    changes.push(SchemaChange::Retyped {
+                        old: (*o).clone(),
+                        new: (*n).clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, n) in &new {
+            if !old.contains_key(name) {
+                changes.push(SchemaChange::Added((*n).clone()));
+            }
+        }
+
+        changes.sort_by(|a, b| a.name().cmp(b.name()));
+        changes
+    }
+
+    /// Look up an entry by its name.
+    pub fn get(&self, name: &str) -> Option<&SchemaEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Check a configuration in sysctl.conf(5) syntax against this schema.
+    ///
+    /// Every `name=value` line is checked for a sysctl of that name, a
+    /// value that parses as the sysctl's type, and a sysctl that can be
+    /// written at runtime. Lines are read as by `conf::Conf`, including
+    /// quotes and comments. Returns all problems found, in line order.
+    ///
+    /// Other formats, like TOML, are out of scope; convert them to
+    /// `name=value` lines first.
+    pub fn validate(&self, config: &str) -> Vec<ValidationError> {
+        let mut errors = vec![];
+
+        for (n, line) in lines(config) {
+            let mut error = |message: String| errors.push(ValidationError { line: n, message });
+
+            let line = line.strip_prefix('-').unwrap_or(line);
+            let parsed =
+                split_line(line).and_then(|(name, val)| Ok((dotted(name), parse_value(val)?)));
+            let (name, val) = match parsed {
+                Ok(kv) => kv,
+                Err(e) => {
+                    error(e);
+                    continue;
+                }
+            };
+            let (name, val) = (name.as_str(), val.as_str());
+
+            let entry = match self.get(name) {
+                Some(e) => e,
+                None => {
+                    error(format!("unknown sysctl {}", name));
+                    continue;
+                }
+            };
+
+            if entry.ctl_type == CtlType::Node {
+                error(format!("{} is a node and has no value to set", name));
+                continue;
+            }
+            if let Err(e) = value_from_str(entry.ctl_type, val) {
+                error(format!("{}: {}", name, e));
+            }
+            if entry.flags & CTLFLAG_WR == 0 {
+                if entry.flags & CTLFLAG_TUN != 0 {
+                    error(format!(
+                        "{} is a boot-time tunable, set it in loader.conf instead",
+                        name
+                    ));
+                } else {
+                    error(format!("{} is read-only", name));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+/// Describe every sysctl of the running system.
+pub fn schema() -> Result<Schema, SysctlError> {
+    let mut entries = vec![];
+
+    for ctl in CtlIter::root() {
+        let ctl = ctl?;
+        let name = ctl.name()?;
+        let info = ctl.info()?;
+
+        entries.push(SchemaEntry {
+            description: ctl.description().ok(),
+            label: label(&ctl.oid),
+            array: is_array(&ctl, info.ctl_type),
+            name,
+            oid: ctl.oid,
+            ctl_type: info.ctl_type,
+            fmt: info.fmt,
+            flags: info.flags,
+        });
+    }
+
+    Ok(Schema { entries })
+}
+
+// Whether a long holds an array only shows in its value. Longs that
+// cannot be read are taken to hold a single value.
+fn is_array(ctl: &Ctl, ctl_type: CtlType) -> bool {
+    if ctl_type != CtlType::Long && ctl_type != CtlType::Ulong {
+        return false;
+    }
+    matches!(
+        ctl.value(),
+        Ok(CtlValue::LongArray(_)) | Ok(CtlValue::UlongArray(_))
+    )
+}
+
+#[cfg(target_os = "freebsd")]
+fn label(oid: &Vec<c_int>) -> Option<String> {
+    super::oid2label(oid).ok().filter(|l| !l.is_empty())
+}
+
+#[cfg(not(target_os = "freebsd"))]
+fn label(_oid: &Vec<c_int>) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, ctl_type: CtlType, flags: c_uint) -> SchemaEntry {
+        SchemaEntry {
+            name: name.to_owned(),
+            oid: vec![],
+            ctl_type,
+            fmt: String::new(),
+            flags,
+            description: None,
+            label: None,
+            array: false,
+        }
+    }
+
+    #[test]
+    fn schema_validate() {
+        use super::super::{CTLFLAG_RD, CTLFLAG_RDTUN, CTLFLAG_RW};
+
+        let schema = Schema {
+            entries: vec![
+                entry("kern", CtlType::Node, CTLFLAG_RD),
+                entry("kern.maxfiles", CtlType::Int, CTLFLAG_RW),
+                entry("kern.osrelease", CtlType::String, CTLFLAG_RD),
+                entry("kern.maxusers", CtlType::Int, CTLFLAG_RDTUN),
+                entry("kern.hostname", CtlType::String, CTLFLAG_RW),
+            ],
+        };
+
+        let config = "# comment\n\
+                      kern.maxfiles=1000 # trailing comment\n\
+                      kern.maxfiles=abc\n\
+                      kern.nonexistent=1\n\
+                      kern.osrelease=\"13.0\"\n\
+                      kern.maxusers=1\n\
+                      kern\n\
+                      kern=1\n\
+                      kern.hostname = \"a # b\" # quoted\n\
+                      -kern/maxfiles = 5\n";
+        let lines: Vec<usize> = schema.validate(config).iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn schema_diff() {
+        let mut retyped = entry("kern.maxfiles", CtlType::Long, 0);
+        retyped.fmt = "L".into();
+
+        let old = Schema {
+            entries: vec![
+                entry("kern.maxfiles", CtlType::Int, 0),
+                entry("kern.osrelease", CtlType::String, 0),
+                entry("vm.old", CtlType::Int, 0),
+            ],
+        };
+        let new = Schema {
+            entries: vec![
+                entry("kern.osrelease", CtlType::String, 0),
+                retyped.clone(),
+                entry("kern.new", CtlType::Int, 0),
+            ],
+        };
+
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                SchemaChange::Retyped {
+                    old: entry("kern.maxfiles", CtlType::Int, 0),
+                    new: retyped,
+                },
+                SchemaChange::Added(entry("kern.new", CtlType::Int, 0)),
+                SchemaChange::Removed(entry("vm.old", CtlType::Int, 0)),
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+
+    // kern.osrevision does not exist on linux
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn schema_entry() {
+        let schema = schema().expect("could not read schema");
+        let entry = schema
+            .get("kern.osrevision")
+            .expect("kern.osrevision missing from schema");
+        assert_eq!(entry.ctl_type, CtlType::Int);
+        assert_eq!(entry.oid, vec![libc::CTL_KERN, libc::KERN_OSREV]);
+    }
+}
@@ -0,0 +1,251 @@
+//! Capturing the values of the whole tree, or a part of it, at one point
+//! in time.
+//!
+//! Like `sysctl -a`, a snapshot contains every readable leaf except those
+//! flagged `CTLFLAG_SKIP`. Opaque values can be left out as well, e.g. to
+//! keep `kern.proc` tables out of a diagnostics dump. Values that can not
+//! be read, because they need privileges or vanished during the walk, are
+//! left out too.
+//!
+//! Two snapshots can be compared with `Snapshot::diff()`, e.g. to see
+//! which tunables a workload or a configuration change moved.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # fn main() {}
+//! # #[cfg(not(target_os = "linux"))]
+//! fn main() {
+//!     let before = sysctl::snapshot_below("kern").expect("could not take snapshot");
+//!     for (name, value) in &before.values {
+//!         println!("{} = {}", name, value);
+//!     }
+//!     let after = sysctl::snapshot_below("kern").expect("could not take snapshot");
+//!     for diff in before.diff(&after) {
+//!         println!("{}", diff);
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+
+use super::{Ctl, CtlIter, CtlType, CtlValue, SysctlError, CTLFLAG_SKIP};
+
+/// Take a snapshot of all readable sysctls.
+pub fn snapshot() -> Result<Snapshot, SysctlError> {
+    SnapshotOptions::new().take()
+}
+
+/// Take a snapshot of all readable sysctls below the node, e.g. `vm`.
+pub fn snapshot_below(node: &str) -> Result<Snapshot, SysctlError> {
+    SnapshotOptions::new().take_below(node)
+}
+
+/// The values of many sysctls, read at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// When the snapshot was started.
+    pub taken: SystemTime,
+    /// The values by name.
+    pub values: BTreeMap<String, CtlValue>,
+}
+
+impl Snapshot {
+    /// The sysctls whose value differs in `other`, taken later, sorted by
+    /// name. Sysctls present in only one of the snapshots are included
+    /// with the missing value as `None`.
+    pub fn diff(&self, other: &Snapshot) -> Vec<CtlDiff> {
+        let mut diffs: Vec<CtlDiff> = self
+            .values
+            .iter()
+            .filter(|&(name, before)| other.values.get(name) != Some(before))
+            .map(|(name, before)| CtlDiff {
+                name: name.clone(),
+                before: Some(before.clone()),
+                after: other.values.get(name).cloned(),
+            })
+            .collect();
+        diffs.extend(
+            other
+                .values
+                .iter()
+                .filter(|&(name, _)| !self.values.contains_key(name))
+                .map(|(name, after)| CtlDiff {
+                    name: name.clone(),
+                    before: None,
+                    after: Some(after.clone()),
+                }),
+        );
+        diffs.sort_by(|a, b| a.name.cmp(&b.name));
+        diffs
+    }
+}
+
+/// A sysctl that changed between two snapshots, see `Snapshot::diff()`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CtlDiff {
+    pub name: String,
+    /// `None` if the sysctl only appeared in the later snapshot.
+    pub before: Option<CtlValue>,
+    /// `None` if the sysctl vanished or could no longer be read.
+    pub after: Option<CtlValue>,
+}
+
+/// Formats like `kern.maxfiles: 1000 -> 2000`, with `-` for a missing
+/// value.
+impl fmt::Display for CtlDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.name)?;
+        match self.before {
+            Some(ref v) => write!(f, "{}", v)?,
+            None => write!(f, "-")?,
+        }
+        write!(f, " -> ")?;
+        match self.after {
+            Some(ref v) => write!(f, "{}", v),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+/// Which sysctls a snapshot includes.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SnapshotOptions {
+    skip_opaque: bool,
+}
+
+impl SnapshotOptions {
+    /// The defaults of `snapshot()`, including opaque values.
+    pub fn new() -> Self {
+        SnapshotOptions::default()
+    }
+
+    /// Leave out opaque and struct values.
+    pub fn skip_opaque(mut self, skip: bool) -> Self {
+        self.skip_opaque = skip;
+        self
+    }
+
+    /// Take a snapshot of the whole tree.
+    pub fn take(&self) -> Result<Snapshot, SysctlError> {
+        self.take_below("")
+    }
+
+    /// Take a snapshot of the sysctls below the node, or of the node
+    /// alone if it is a leaf. An empty name selects the whole tree.
+    pub fn take_below(&self, node: &str) -> Result<Snapshot, SysctlError> {
+        let taken = SystemTime::now();
+        let mut values = BTreeMap::new();
+        let ctls = if node.is_empty() {
+            CtlIter::root()
+        } else {
+            let ctl = Ctl::new(node)?;
+            if ctl.value_type()? != CtlType::Node {
+                self.insert(&mut values, &ctl)?;
+                return Ok(Snapshot { taken, values });
+            }
+            CtlIter::below(ctl)
+        };
+
+        for ctl in ctls {
+            self.insert(&mut values, &ctl?)?;
+        }
+        Ok(Snapshot { taken, values })
+    }
+
+    // Read the value of the sysctl into values, unless it is left out.
+    fn insert(
+        &self,
+        values: &mut BTreeMap<String, CtlValue>,
+        ctl: &Ctl,
+    ) -> Result<(), SysctlError> {
+        let info = ctl.info()?;
+        let opaque = info.ctl_type() == CtlType::Node || info.ctl_type() == CtlType::Struct;
+        if !info.is_readable() || info.kind() & CTLFLAG_SKIP != 0 || (opaque && self.skip_opaque) {
+            return Ok(());
+        }
+        if let Ok(value) = ctl.value() {
+            values.insert(ctl.name()?, value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_options() {
+        let options = SnapshotOptions::new().skip_opaque(true);
+        assert!(options.skip_opaque);
+        assert_eq!(SnapshotOptions::new(), SnapshotOptions::default());
+    }
+
+    fn values(pairs: &[(&str, i32)]) -> Snapshot {
+        Snapshot {
+            taken: SystemTime::now(),
+            values: pairs
+                .iter()
+                .map(|&(name, v)| (name.to_owned(), CtlValue::Int(v)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let before = values(&[("a.changed", 1), ("b.same", 2), ("c.gone", 3)]);
+        let after = values(&[("a.changed", 5), ("b.same", 2), ("a.new", 4)]);
+        let diffs = before.diff(&after);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[0].to_string(), "a.changed: 1 -> 5");
+        assert_eq!(diffs[1].to_string(), "a.new: - -> 4");
+        assert_eq!(
+            diffs[2],
+            CtlDiff {
+                name: "c.gone".into(),
+                before: Some(CtlValue::Int(3)),
+                after: None,
+            }
+        );
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn snapshot_kern() {
+        let snap = snapshot_below("kern").unwrap();
+        assert!(snap.values.contains_key("kern.ostype"));
+        assert!(snap.values.keys().all(|n| n.starts_with("kern.")));
+
+        let snap = snapshot_below("kern.ostype").unwrap();
+        assert_eq!(snap.values.len(), 1);
+
+        let snap = SnapshotOptions::new()
+            .skip_opaque(true)
+            .take_below("kern")
+            .unwrap();
+        assert!(!snap.values.contains_key("kern.clockrate"));
+        assert!(snap.values.values().all(|v| match *v {
+            CtlValue::Node(_) | CtlValue::Struct(_) => false,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn snapshot_kern() {
+        let snap = snapshot_below("kernel").unwrap();
+        assert_eq!(
+            snap.values["kernel.ostype"],
+            CtlValue::String("Linux".into())
+        );
+        assert!(snap.values.keys().all(|n| n.starts_with("kernel.")));
+    }
+}
@@ -0,0 +1,291 @@
+//! Basic facts about the running system, collected in one go.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//!
+//! fn main() {
+//!     let info = sysctl::SystemInfo::collect().expect("could not collect system info");
+//!     println!(
+//!         "{} {} on {} ({} cpus)",
+//!         info.ostype, info.osrelease, info.model, info.ncpu
+//!     );
+//! }
+//! ```
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use super::types::{LoadAvg, TimeVal};
+use super::{numeric, value, CtlValue, SysctlError};
+
+/// Hardware and OS information from the `hw` and `kern` trees.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SystemInfo {
+    /// CPU model, from `hw.model`.
+    pub model: String,
+    /// Machine class, from `hw.machine`.
+    pub machine: String,
+    /// Number of CPUs, from `hw.ncpu`.
+    pub ncpu: u32,
+    /// Physical memory in bytes, from `hw.physmem` (`hw.memsize` on macos).
+    pub physmem: u64,
+    /// Memory including that reserved by the firmware, from
+    /// `hw.realmem`. `None` where the sysctl does not exist.
+    pub realmem: Option<u64>,
+    /// From `kern.ostype`, e.g. `FreeBSD`.
+    pub ostype: String,
+    /// From `kern.osrelease`, e.g. `13.2-RELEASE`.
+    pub osrelease: String,
+    /// From `kern.osrevision`.
+    pub osrevision: i32,
+}
+
+impl SystemInfo {
+    /// Read all fields from the running system.
+    pub fn collect() -> Result<Self, SysctlError> {
+        Ok(SystemInfo {
+            model: string("hw.model")?,
+            machine: string("hw.machine")?,
+            ncpu: integer("hw.ncpu")? as u32,
+            physmem: integer(PHYSMEM)? as u64,
+            realmem: integer("hw.realmem").ok().map(|n| n as u64),
+            ostype: string("kern.ostype")?,
+            osrelease: string("kern.osrelease")?,
+            osrevision: integer("kern.osrevision")? as i32,
+        })
+    }
+}
+
+/// The release of the running kernel, parsed from `kern.osrelease`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use sysctl::system::OsRelease;
+///
+/// fn main() {
+///     let release = OsRelease::get().expect("could not read release");
+///     if release.at_least(13, 2) {
+///         println!("running {}.{} or later", 13, 2);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsRelease {
+    pub major: u32,
+    pub minor: u32,
+    /// Everything after the version number, e.g. `RELEASE-p4` for
+    /// `13.2-RELEASE-p4`. Empty if there is nothing.
+    pub branch: String,
+    /// From `kern.osreldate`, e.g. `1302001`. Only available on FreeBSD.
+    pub reldate: Option<i32>,
+}
+
+impl OsRelease {
+    /// Read the release of the running system.
+    pub fn get() -> Result<Self, SysctlError> {
+        let mut release: OsRelease = string("kern.osrelease")?.parse()?;
+        release.reldate = integer("kern.osreldate").ok().map(|n| n as i32);
+        Ok(release)
+    }
+
+    /// Whether this release is `major.minor` or later.
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+}
+
+impl FromStr for OsRelease {
+    type Err = SysctlError;
+
+    /// Parse a release like `13.2-RELEASE-p4` or `22.5.0`. Components
+    /// after the minor number are kept in `branch`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SysctlError::InvalidValue(format!("invalid OS release {:?}", s));
+
+        let end = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+        let mut numbers = s[..end].splitn(3, '.');
+        let major = numbers.next().unwrap_or("").parse().map_err(|_| invalid())?;
+        let minor = match numbers.next() {
+            Some(n) => n.parse().map_err(|_| invalid())?,
+            None => 0,
+        };
+        let branch = match numbers.next() {
+            Some(rest) => format!("{}{}", rest, &s[end..]),
+            None => s[end..].to_owned(),
+        };
+
+        Ok(OsRelease {
+            major,
+            minor,
+            branch: branch.trim_start_matches(&['-', '.'][..]).to_owned(),
+            reldate: None,
+        })
+    }
+}
+
+/// The version of macOS, which `kern.osrelease` only reports as the
+/// darwin kernel version.
+#[cfg(target_os = "macos")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsVersion {
+    /// Marketing version, e.g. `13.4.1`, from `kern.osproductversion`.
+    pub product_version: String,
+    /// Build number, e.g. `22F82`, from `kern.osversion`.
+    pub build: String,
+}
+
+#[cfg(target_os = "macos")]
+impl OsVersion {
+    /// Read the version of the running system.
+    pub fn get() -> Result<Self, SysctlError> {
+        Ok(OsVersion {
+            product_version: string("kern.osproductversion")?,
+            build: string("kern.osversion")?,
+        })
+    }
+}
+
+/// Return the time the system was booted, from `kern.boottime`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let boot = sysctl::system::boottime().expect("could not read boot time");
+///     println!("up for {:?}", boot.elapsed());
+/// }
+/// ```
+pub fn boottime() -> Result<SystemTime, SysctlError> {
+    let bytes = match value("kern.boottime")? {
+        CtlValue::Struct(b) | CtlValue::Node(b) => b,
+        _ => return Err(SysctlError::ExtractionError),
+    };
+    Ok(TimeVal::from_bytes(&bytes)?.system_time())
+}
+
+/// Return the load averages, from `vm.loadavg`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let load = sysctl::loadavg().expect("could not read load average");
+///     println!("{:.2} {:.2} {:.2}", load.one, load.five, load.fifteen);
+/// }
+/// ```
+pub fn loadavg() -> Result<LoadAvg, SysctlError> {
+    match value("vm.loadavg")? {
+        CtlValue::Struct(b) | CtlValue::Node(b) => LoadAvg::from_bytes(&b),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+/// Return the time since the system was booted, computed from
+/// `kern.boottime`. Changes of the wall clock since the boot, e.g. by
+/// NTP, are included.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let uptime = sysctl::uptime().expect("could not read uptime");
+///     println!("up {} days", uptime.as_secs() / 86400);
+/// }
+/// ```
+pub fn uptime() -> Result<Duration, SysctlError> {
+    // A wall clock set back to before the boot means no time has passed
+    Ok(SystemTime::now()
+        .duration_since(boottime()?)
+        .unwrap_or_default())
+}
+
+// hw.physmem is a 32 bit int on macos, use the 64 bit variant instead
+#[cfg(target_os = "macos")]
+const PHYSMEM: &str = "hw.memsize";
+#[cfg(not(target_os = "macos"))]
+const PHYSMEM: &str = "hw.physmem";
+
+fn string(name: &str) -> Result<String, SysctlError> {
+    match value(name)? {
+        CtlValue::String(s) => Ok(s),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+// The integer types of these sysctls differ between the BSDs
+fn integer(name: &str) -> Result<i128, SysctlError> {
+    numeric(&value(name)?).ok_or(SysctlError::ExtractionError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_collect() {
+        let info = SystemInfo::collect().expect("could not collect system info");
+        assert!(info.ncpu > 0);
+        assert!(info.physmem > 0);
+        assert!(!info.ostype.is_empty());
+        assert!(!info.osrelease.is_empty());
+    }
+
+    #[test]
+    fn system_os_release_parse() {
+        let r: OsRelease = "13.2-RELEASE-p4".parse().unwrap();
+        assert_eq!((r.major, r.minor, r.branch.as_str()), (13, 2, "RELEASE-p4"));
+        assert!(r.at_least(13, 2));
+        assert!(r.at_least(12, 4));
+        assert!(!r.at_least(13, 3));
+        assert!(!r.at_least(14, 0));
+
+        let r: OsRelease = "22.5.0".parse().unwrap();
+        assert_eq!((r.major, r.minor, r.branch.as_str()), (22, 5, "0"));
+        let r: OsRelease = "7.3".parse().unwrap();
+        assert_eq!((r.major, r.minor, r.branch.as_str()), (7, 3, ""));
+        let r: OsRelease = "15-CURRENT".parse().unwrap();
+        assert_eq!((r.major, r.minor, r.branch.as_str()), (15, 0, "CURRENT"));
+
+        assert!("".parse::<OsRelease>().is_err());
+        assert!("CURRENT".parse::<OsRelease>().is_err());
+    }
+
+    #[test]
+    fn system_os_release() {
+        let release = OsRelease::get().expect("could not read release");
+        assert!(release.major > 0);
+    }
+
+    #[test]
+    fn system_boottime() {
+        let boot = boottime().expect("could not read boot time");
+        assert!(boot > std::time::UNIX_EPOCH);
+        assert!(boot < SystemTime::now());
+
+        let uptime = uptime().expect("could not read uptime");
+        assert!(uptime > Duration::from_secs(0));
+        assert!(boot + uptime <= SystemTime::now());
+    }
+
+    #[test]
+    fn system_loadavg() {
+        let load = loadavg().expect("could not read load average");
+        assert!(load.one >= 0.0 && load.five >= 0.0 && load.fifteen >= 0.0);
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn system_os_version() {
+        let version = OsVersion::get().expect("could not read OS version");
+        assert!(!version.product_version.is_empty());
+        assert!(!version.build.is_empty());
+    }
+}
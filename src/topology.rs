@@ -0,0 +1,255 @@
+//! The CPU topology of the scheduler, parsed from the XML of
+//! `kern.sched.topology_spec`. Requires the `topology` feature.
+//!
+//! The topology is a tree of CPU groups: the whole package, the cores
+//! sharing a cache, the hardware threads of a core. ULE uses it to keep
+//! threads close to their caches; it helps the same way for pinning
+//! threads with cpuset(1) or `cpuset_setaffinity(2)`.
+//!
+//! ```xml
+//! <groups>
+//!  <group level="1" cache-level="3">
+//!   <cpu count="4" mask="f,0,0,0">0, 1, 2, 3</cpu>
+//!   <children>
+//!    <group level="2" cache-level="2">
+//!     <cpu count="2" mask="3,0,0,0">0, 1</cpu>
+//!     <flags><flag name="THREAD">THREAD group</flag><flag name="SMT">SMT group</flag></flags>
+//!    </group>
+//!    ...
+//! ```
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::topology::CpuTopology;
+//!
+//! fn main() {
+//!     let xml = "<groups><group level=\"1\" cache-level=\"0\">\
+//!                <cpu count=\"2\" mask=\"3,0,0,0\">0, 1</cpu></group></groups>";
+//!     let topology: CpuTopology = xml.parse().expect("could not parse topology");
+//!     assert_eq!(topology.cpus(), vec![0, 1]);
+//! }
+//! ```
+
+use std::str::FromStr;
+
+use roxmltree::{Document, Node};
+
+use super::SysctlError;
+#[cfg(target_os = "freebsd")]
+use super::{value, CtlValue};
+
+/// The CPU topology, see the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuTopology {
+    /// The top level groups, usually a single one with all CPUs.
+    pub groups: Vec<CpuGroup>,
+}
+
+/// A group of CPUs sharing a cache or a core.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuGroup {
+    /// Depth in the tree, starting at 1.
+    pub level: u32,
+    /// The cache shared by the CPUs, 0 for none.
+    pub cache_level: u32,
+    /// The CPU ids, as used by cpuset(2).
+    pub cpus: Vec<usize>,
+    /// `CG_FLAG_*` names, e.g. `SMT` for the hardware threads of a core.
+    pub flags: Vec<String>,
+    pub children: Vec<CpuGroup>,
+}
+
+impl CpuGroup {
+    /// Whether the CPUs are hardware threads of the same core.
+    pub fn is_smt(&self) -> bool {
+        self.flags.iter().any(|f| f == "SMT")
+    }
+
+    fn walk<'a>(&'a self, groups: &mut Vec<&'a CpuGroup>) {
+        groups.push(self);
+        for child in &self.children {
+            child.walk(groups);
+        }
+    }
+}
+
+impl CpuTopology {
+    /// All CPUs, sorted.
+    pub fn cpus(&self) -> Vec<usize> {
+        let mut cpus: Vec<usize> = self.groups.iter().flat_map(|g| g.cpus.clone()).collect();
+        cpus.sort();
+        cpus.dedup();
+        cpus
+    }
+
+    /// All groups, parents before their children.
+    pub fn groups(&self) -> Vec<&CpuGroup> {
+        let mut groups = vec![];
+        for group in &self.groups {
+            group.walk(&mut groups);
+        }
+        groups
+    }
+
+    /// The smallest group containing the CPU, e.g. its core. Its other
+    /// CPUs are the closest ones.
+    pub fn group_of(&self, cpu: usize) -> Option<&CpuGroup> {
+        self.groups()
+            .into_iter()
+            .filter(|g| g.cpus.contains(&cpu))
+            .max_by_key(|g| g.level)
+    }
+}
+
+impl FromStr for CpuTopology {
+    type Err = SysctlError;
+
+    /// Parse the XML of `kern.sched.topology_spec`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let doc = Document::parse(s).map_err(|e| SysctlError::ParseError {
+            line: e.pos().row as usize,
+            reason: e.to_string(),
+        })?;
+        let root = doc.root_element();
+        if !root.has_tag_name("groups") {
+            return Err(invalid(&doc, root, "expected <groups>"));
+        }
+        Ok(CpuTopology {
+            groups: parse_groups(&doc, root)?,
+        })
+    }
+}
+
+/// Return the CPU topology of the running system. Fails with `ENOENT`
+/// with schedulers other than ULE.
+#[cfg(target_os = "freebsd")]
+pub fn cpu_topology() -> Result<CpuTopology, SysctlError> {
+    match value("kern.sched.topology_spec")? {
+        CtlValue::String(s) => s.parse(),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+// The <group> elements below a <groups> or <children> element.
+fn parse_groups(doc: &Document, parent: Node) -> Result<Vec<CpuGroup>, SysctlError> {
+    parent
+        .children()
+        .filter(|n| n.has_tag_name("group"))
+        .map(|n| parse_group(doc, n))
+        .collect()
+}
+
+fn parse_group(doc: &Document, node: Node) -> Result<CpuGroup, SysctlError> {
+    let number = |name: &str| -> Result<u32, SysctlError> {
+        node.attribute(name)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| invalid(doc, node, &format!("invalid {} attribute", name)))
+    };
+    let mut group = CpuGroup {
+        level: number("level")?,
+        cache_level: number("cache-level")?,
+        cpus: vec![],
+        flags: vec![],
+        children: vec![],
+    };
+
+    for child in node.children().filter(Node::is_element) {
+        match child.tag_name().name() {
+            "cpu" => {
+                group.cpus = child
+                    .text()
+                    .unwrap_or("")
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|cpu| !cpu.is_empty())
+                    .map(|cpu| cpu.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid(doc, child, "invalid cpu list"))?;
+            }
+            "flags" => {
+                group.flags = child
+                    .children()
+                    .filter(|n| n.has_tag_name("flag"))
+                    .filter_map(|n| n.attribute("name"))
+                    .map(str::to_owned)
+                    .collect();
+            }
+            "children" => group.children = parse_groups(doc, child)?,
+            _ => {}
+        }
+    }
+
+    Ok(group)
+}
+
+fn invalid(doc: &Document, node: Node, reason: &str) -> SysctlError {
+    SysctlError::ParseError {
+        line: doc.text_pos_at(node.range().start).row as usize,
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"<groups>
+ <group level="1" cache-level="3">
+  <cpu count="4" mask="f,0,0,0">0, 1, 2, 3</cpu>
+  <children>
+   <group level="2" cache-level="2">
+    <cpu count="2" mask="3,0,0,0">0, 1</cpu>
+    <flags><flag name="THREAD">THREAD group</flag><flag name="SMT">SMT group</flag></flags>
+   </group>
+   <group level="2" cache-level="2">
+    <cpu count="2" mask="c,0,0,0">2, 3</cpu>
+    <flags><flag name="THREAD">THREAD group</flag><flag name="SMT">SMT group</flag></flags>
+   </group>
+  </children>
+ </group>
+</groups>
+"#;
+
+    #[test]
+    fn topology_parse() {
+        let topology: CpuTopology = SPEC.parse().unwrap();
+        assert_eq!(topology.cpus(), vec![0, 1, 2, 3]);
+        assert_eq!(topology.groups().len(), 3);
+
+        let package = &topology.groups[0];
+        assert_eq!(package.level, 1);
+        assert_eq!(package.cache_level, 3);
+        assert!(!package.is_smt());
+        assert_eq!(package.children.len(), 2);
+
+        let core = topology.group_of(3).unwrap();
+        assert_eq!(core.cpus, vec![2, 3]);
+        assert_eq!(core.flags, vec!["THREAD", "SMT"]);
+        assert!(core.is_smt());
+        assert_eq!(topology.group_of(4), None);
+    }
+
+    #[test]
+    fn topology_parse_errors() {
+        match "<groups><group level=\"1\">".parse::<CpuTopology>() {
+            Err(SysctlError::ParseError { line: 1, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match "<groups>\n<group level=\"1\" cache-level=\"x\"/></groups>".parse::<CpuTopology>() {
+            Err(SysctlError::ParseError { line: 2, .. }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        let bad_cpu =
+            "<groups><group level=\"1\" cache-level=\"0\"><cpu>0, a</cpu></group></groups>";
+        assert!(bad_cpu.parse::<CpuTopology>().is_err());
+        assert!("<cpus/>".parse::<CpuTopology>().is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "freebsd")]
+    fn topology_live() {
+        let topology = cpu_topology().unwrap();
+        assert!(!topology.cpus().is_empty());
+    }
+}
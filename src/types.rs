@@ -0,0 +1,438 @@
+//! Decoding of the opaque structs sysctl(8) knows how to print.
+//!
+//! Values with a format like `S,clockinfo` are returned as raw bytes by
+//! `Ctl::value()`. For the well-known structs below, `Ctl::value_struct()`
+//! decodes them, selected by the struct name in the format string.
+//!
+//! OpenBSD and NetBSD do not report struct names, their structs can be
+//! decoded with `decode()` or the `from_bytes()` constructors directly.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::types::StructValue;
+//! use sysctl::Ctl;
+//!
+//! # #[cfg(target_os = "linux")]
+//! # fn main() {}
+//! # #[cfg(not(target_os = "linux"))]
+//! fn main() {
+//!     let clockrate = Ctl::new("kern.clockrate").expect("could not get control");
+//!     if let Ok(StructValue::ClockInfo(c)) = clockrate.value_struct() {
+//!         println!("hz = {}", c.hz);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::c_long;
+
+use super::SysctlError;
+
+/// `struct clockinfo`, e.g. from `kern.clockrate`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClockInfo {
+    /// Clock frequency.
+    pub hz: i32,
+    /// Microseconds per hz tick.
+    pub tick: i32,
+    /// Statistics clock frequency.
+    pub stathz: i32,
+    /// Profiling clock frequency.
+    pub profhz: i32,
+}
+
+impl ClockInfo {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        let ints: Vec<i32> = bytes.chunks_exact(4).map(NativeEndian::read_i32).collect();
+        match (bytes.len(), &ints[..]) {
+            // hz, tick, a spare or tickadj field, stathz and profhz
+            (20, &[hz, tick, _, stathz, profhz]) => Ok(ClockInfo {
+                hz,
+                tick,
+                stathz,
+                profhz,
+            }),
+            // OpenBSD removed the third field
+            (16, &[hz, tick, stathz, profhz]) => Ok(ClockInfo {
+                hz,
+                tick,
+                stathz,
+                profhz,
+            }),
+            _ => Err(SysctlError::SizeMismatch {
+                expected: 20,
+                got: bytes.len(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ClockInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{ hz = {}, tick = {}, profhz = {}, stathz = {} }}",
+            self.hz, self.tick, self.profhz, self.stathz
+        )
+    }
+}
+
+/// `struct timeval`, e.g. from `kern.boottime`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeVal {
+    pub sec: i64,
+    pub usec: i64,
+}
+
+impl TimeVal {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size::<libc::timeval>(bytes)?;
+        // The buffer has no particular alignment
+        let tv: libc::timeval = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const libc::timeval) };
+        Ok(TimeVal {
+            sec: tv.tv_sec as i64,
+            usec: tv.tv_usec as i64,
+        })
+    }
+
+    /// The point in time, for timestamps like `kern.boottime`.
+    pub fn system_time(&self) -> SystemTime {
+        // The fields are added with their own signs, so -1s and 500000us
+        // is half a second before the epoch
+        let sec = Duration::from_secs(self.sec.unsigned_abs());
+        let usec = Duration::from_micros(self.usec.unsigned_abs());
+        let t = if self.sec < 0 {
+            UNIX_EPOCH - sec
+        } else {
+            UNIX_EPOCH + sec
+        };
+        if self.usec < 0 {
+            t - usec
+        } else {
+            t + usec
+        }
+    }
+}
+
+impl fmt::Display for TimeVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{ sec = {}, usec = {} }}", self.sec, self.usec)
+    }
+}
+
+// struct loadavg from sys/resource.h, fixpt_t is 32 bits everywhere
+#[repr(C)]
+struct RawLoadAvg {
+    ldavg: [u32; 3],
+    fscale: c_long,
+}
+
+/// `struct loadavg`, e.g. from `vm.loadavg`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LoadAvg {
+    /// Load average over the last minute.
+    pub one: f64,
+    /// Load average over the last 5 minutes.
+    pub five: f64,
+    /// Load average over the last 15 minutes.
+    pub fifteen: f64,
+}
+
+impl LoadAvg {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        check_size::<RawLoadAvg>(bytes)?;
+        let raw: RawLoadAvg = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const RawLoadAvg) };
+        if raw.fscale <= 0 {
+            return Err(SysctlError::ExtractionError);
+        }
+        let scale = raw.fscale as f64;
+        Ok(LoadAvg {
+            one: raw.ldavg[0] as f64 / scale,
+            five: raw.ldavg[1] as f64 / scale,
+            fifteen: raw.ldavg[2] as f64 / scale,
+        })
+    }
+}
+
+impl fmt::Display for LoadAvg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{ {:.2} {:.2} {:.2} }}", self.one, self.five, self.fifteen)
+    }
+}
+
+/// `struct vmtotal` from `vm.vmtotal`. Memory is counted in pages.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VmTotal {
+    /// Length of the run queue.
+    pub rq: i16,
+    /// Threads in disk wait.
+    pub dw: i16,
+    /// Threads in page wait.
+    pub pw: i16,
+    /// Threads sleeping in core.
+    pub sl: i16,
+    /// Swapped out runnable threads.
+    pub sw: i16,
+    /// Total virtual memory.
+    pub vm: u64,
+    /// Active virtual memory.
+    pub avm: u64,
+    /// Real memory in use.
+    pub rm: u64,
+    /// Active real memory.
+    pub arm: u64,
+    /// Shared virtual memory.
+    pub vmshr: u64,
+    /// Active shared virtual memory.
+    pub avmshr: u64,
+    /// Shared real memory.
+    pub rmshr: u64,
+    /// Active shared real memory.
+    pub armshr: u64,
+    /// Free memory.
+    pub free: u64,
+}
+
+impl VmTotal {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SysctlError> {
+        match bytes.len() {
+            // FreeBSD: nine 64 bit counters, then the threads and padding
+            88 => {
+                let mem = |i: usize| NativeEndian::read_u64(&bytes[i * 8..]);
+                let threads = |i: usize| NativeEndian::read_i16(&bytes[72 + i * 2..]);
+                Ok(VmTotal {
+                    rq: threads(0),
+                    dw: threads(1),
+                    pw: threads(2),
+                    sl: threads(3),
+                    sw: threads(4),
+                    vm: mem(0),
+                    avm: mem(1),
+                    rm: mem(2),
+                    arm: mem(3),
+                    vmshr: mem(4),
+                    avmshr: mem(5),
+                    rmshr: mem(6),
+                    armshr: mem(7),
+                    free: mem(8),
+                })
+            }
+            // The other BSDs: the threads, padding, then 32 bit counters
+            48 => {
+                let threads = |i: usize| NativeEndian::read_i16(&bytes[i * 2..]);
+                let mem = |i: usize| NativeEndian::read_u32(&bytes[12 + i * 4..]) as u64;
+                Ok(VmTotal {
+                    rq: threads(0),
+                    dw: threads(1),
+                    pw: threads(2),
+                    sl: threads(3),
+                    sw: threads(4),
+                    vm: mem(0),
+                    avm: mem(1),
+                    rm: mem(2),
+                    arm: mem(3),
+                    vmshr: mem(4),
+                    avmshr: mem(5),
+                    rmshr: mem(6),
+                    armshr: mem(7),
+                    free: mem(8),
+                })
+            }
+            len => Err(SysctlError::SizeMismatch {
+                expected: 88,
+                got: len,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for VmTotal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{{ rq = {}, dw = {}, pw = {}, sl = {}, sw = {}, vm = {}, avm = {}, rm = {}, arm = {}, \
+             vmshr = {}, avmshr = {}, rmshr = {}, armshr = {}, free = {} }}",
+            self.rq,
+            self.dw,
+            self.pw,
+            self.sl,
+            self.sw,
+            self.vm,
+            self.avm,
+            self.rm,
+            self.arm,
+            self.vmshr,
+            self.avmshr,
+            self.rmshr,
+            self.armshr,
+            self.free
+        )
+    }
+}
+
+/// A decoded well-known struct.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StructValue {
+    ClockInfo(ClockInfo),
+    TimeVal(TimeVal),
+    LoadAvg(LoadAvg),
+    VmTotal(VmTotal),
+}
+
+impl fmt::Display for StructValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StructValue::ClockInfo(ref c) => c.fmt(f),
+            StructValue::TimeVal(ref t) => t.fmt(f),
+            StructValue::LoadAvg(ref l) => l.fmt(f),
+            StructValue::VmTotal(ref v) => v.fmt(f),
+        }
+    }
+}
+
+/// Decode the raw bytes of a struct by its name, as found in format
+/// strings like `S,clockinfo`. Fails with `SysctlError::ExtractionError`
+/// for structs not known to this module.
+pub fn decode(struct_name: &str, bytes: &[u8]) -> Result<StructValue, SysctlError> {
+    match struct_name {
+        "clockinfo" => ClockInfo::from_bytes(bytes).map(StructValue::ClockInfo),
+        "timeval" => TimeVal::from_bytes(bytes).map(StructValue::TimeVal),
+        "loadavg" => LoadAvg::from_bytes(bytes).map(StructValue::LoadAvg),
+        "vmtotal" => VmTotal::from_bytes(bytes).map(StructValue::VmTotal),
+        _ => Err(SysctlError::ExtractionError),
+    }
+}
+
+fn check_size<T>(bytes: &[u8]) -> Result<(), SysctlError> {
+    if bytes.len() != mem::size_of::<T>() {
+        return Err(SysctlError::SizeMismatch {
+            expected: mem::size_of::<T>(),
+            got: bytes.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    #[test]
+    fn types_clockinfo() {
+        let mut bytes = vec![];
+        for &i in &[1000, 1000, 0, 127, 8128] {
+            bytes.write_i32::<NativeEndian>(i).unwrap();
+        }
+        let clock = ClockInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(clock.hz, 1000);
+        assert_eq!(clock.stathz, 127);
+        assert_eq!(clock.profhz, 8128);
+        assert_eq!(
+            clock.to_string(),
+            "{ hz = 1000, tick = 1000, profhz = 8128, stathz = 127 }"
+        );
+
+        // Without the spare field
+        let openbsd = ClockInfo::from_bytes(&[&bytes[..8], &bytes[12..]].concat()).unwrap();
+        assert_eq!(openbsd, clock);
+
+        match ClockInfo::from_bytes(&bytes[..12]) {
+            Err(SysctlError::SizeMismatch { expected: 20, got: 12 }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn types_timeval() {
+        let tv = libc::timeval {
+            tv_sec: 1_700_000_000,
+            tv_usec: 250_000,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &tv as *const libc::timeval as *const u8,
+                mem::size_of::<libc::timeval>(),
+            )
+        };
+        let t = TimeVal::from_bytes(bytes).unwrap();
+        assert_eq!(t, TimeVal { sec: 1_700_000_000, usec: 250_000 });
+        assert_eq!(
+            t.system_time(),
+            UNIX_EPOCH + Duration::from_millis(1_700_000_000_250)
+        );
+        assert!(TimeVal::from_bytes(&bytes[1..]).is_err());
+
+        let before_epoch = TimeVal {
+            sec: -1,
+            usec: 500_000,
+        };
+        assert_eq!(
+            before_epoch.system_time(),
+            UNIX_EPOCH - Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn types_loadavg() {
+        let raw = RawLoadAvg {
+            ldavg: [1024, 512, 2048],
+            fscale: 2048,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &raw as *const RawLoadAvg as *const u8,
+                mem::size_of::<RawLoadAvg>(),
+            )
+        };
+        let load = LoadAvg::from_bytes(bytes).unwrap();
+        assert_eq!((load.one, load.five, load.fifteen), (0.5, 0.25, 1.0));
+        assert_eq!(load.to_string(), "{ 0.50 0.25 1.00 }");
+    }
+
+    #[test]
+    fn types_vmtotal() {
+        let mut freebsd = vec![];
+        for i in 1..=9 {
+            freebsd.write_u64::<NativeEndian>(i * 100).unwrap();
+        }
+        for i in 1..=5 {
+            freebsd.write_i16::<NativeEndian>(i).unwrap();
+        }
+        freebsd.extend(&[0; 6]);
+        let total = VmTotal::from_bytes(&freebsd).unwrap();
+        assert_eq!((total.rq, total.sw), (1, 5));
+        assert_eq!((total.vm, total.free), (100, 900));
+
+        let mut other = vec![];
+        for i in 1..=5 {
+            other.write_i16::<NativeEndian>(i).unwrap();
+        }
+        other.extend(&[0; 2]);
+        for i in 1..=9 {
+            other.write_u32::<NativeEndian>(i * 100).unwrap();
+        }
+        assert_eq!(VmTotal::from_bytes(&other).unwrap(), total);
+    }
+
+    #[test]
+    fn types_decode() {
+        let bytes = [0; 20];
+        match decode("clockinfo", &bytes) {
+            Ok(StructValue::ClockInfo(c)) => assert_eq!(c.hz, 0),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(decode("vnode", &bytes).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Helpers for the `vm` part of the tree. Only available on FreeBSD.
+
+use std::io;
+
+use byteorder::{ByteOrder, NativeEndian};
+use libc::c_void;
+
+use super::meta::raw;
+use super::{decode_utf8, fetch_oid, name2oid, value, SysctlError};
+
+// Offsets of the fields of struct xswdev, see vm/vm_param.h. Version 1
+// had a 32 bit dev_t.
+const XSW_VERSION: usize = 0;
+const XSW_DEV: usize = 8;
+const XSW_FLAGS: usize = 16;
+const XSW_NBLKS: usize = 20;
+const XSW_USED: usize = 24;
+const XSWDEV_SIZE: usize = 28;
+const XSW_V1_DEV: usize = 4;
+const XSW_V1_FLAGS: usize = 8;
+const XSW_V1_NBLKS: usize = 12;
+const XSW_V1_USED: usize = 16;
+const XSWDEV_V1_SIZE: usize = 20;
+
+// The dev_t of swap that is not on a device, e.g. on NFS
+const NODEV: u64 = u64::MAX;
+// Longest device name, see sys/param.h
+const SPECNAMELEN: usize = 255;
+
+/// Memory usage by page queue, from the `vm.stats.vm` counters, like top(1)
+/// shows it.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     let vm = sysctl::vm_stats().expect("could not read vm.stats");
+///     println!(
+///         "{}M active, {}M wired, {}M free",
+///         vm.active_bytes() >> 20,
+///         vm.wired_bytes() >> 20,
+///         vm.free_bytes() >> 20
+///     );
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VmStats {
+    /// From `hw.pagesize`.
+    pub page_size: u64,
+    /// Physical memory in bytes, from `hw.physmem`.
+    pub physmem: u64,
+    /// Pages managed by the VM system, from `v_page_count`.
+    pub page_count: u64,
+    pub free: u64,
+    pub active: u64,
+    pub inactive: u64,
+    /// Dirty pages waiting to be written out, 0 before FreeBSD 11.1.
+    pub laundry: u64,
+    pub wired: u64,
+    /// Always 0 since FreeBSD 12, which dropped the cache queue.
+    pub cache: u64,
+}
+
+impl VmStats {
+    /// Read all counters from the running system.
+    pub fn collect() -> Result<Self, SysctlError> {
+        Ok(VmStats {
+            page_size: integer("hw.pagesize")?,
+            physmem: integer("hw.physmem")?,
+            page_count: integer("vm.stats.vm.v_page_count")?,
+            free: integer("vm.stats.vm.v_free_count")?,
+            active: integer("vm.stats.vm.v_active_count")?,
+            inactive: integer("vm.stats.vm.v_inactive_count")?,
+            laundry: counter_or_zero("vm.stats.vm.v_laundry_count")?,
+            wired: integer("vm.stats.vm.v_wire_count")?,
+            cache: counter_or_zero("vm.stats.vm.v_cache_count")?,
+        })
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.free * self.page_size
+    }
+
+    pub fn active_bytes(&self) -> u64 {
+        self.active * self.page_size
+    }
+
+    pub fn inactive_bytes(&self) -> u64 {
+        self.inactive * self.page_size
+    }
+
+    pub fn laundry_bytes(&self) -> u64 {
+        self.laundry * self.page_size
+    }
+
+    pub fn wired_bytes(&self) -> u64 {
+        self.wired * self.page_size
+    }
+
+    pub fn cache_bytes(&self) -> u64 {
+        self.cache * self.page_size
+    }
+}
+
+/// Return the memory usage by page queue, see `VmStats`.
+pub fn vm_stats() -> Result<VmStats, SysctlError> {
+    VmStats::collect()
+}
+
+/// A swap device, from `vm.swap_info`.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+///
+/// fn main() {
+///     println!("Device          1K-blocks     Used    Avail Capacity");
+///     for d in sysctl::swap_info().expect("could not read vm.swap_info") {
+///         println!(
+///             "{:<15} {:>9} {:>8} {:>8} {:>7.0}%",
+///             d.name,
+///             d.total >> 10,
+///             d.used >> 10,
+///             d.free() >> 10,
+///             100.0 * d.used as f64 / d.total as f64
+///         );
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapDevice {
+    /// The device as swapinfo(8) shows it, e.g. `/dev/ada0p3`.
+    pub name: String,
+    pub dev: u64,
+    /// `SW_*` flags.
+    pub flags: i32,
+    /// Size in bytes.
+    pub total: u64,
+    /// Bytes in use.
+    pub used: u64,
+}
+
+impl SwapDevice {
+    /// Bytes available.
+    pub fn free(&self) -> u64 {
+        self.total.saturating_sub(self.used)
+    }
+}
+
+/// Return the swap devices in use, in the order they were added.
+pub fn swap_info() -> Result<Vec<SwapDevice>, SysctlError> {
+    let page_size = integer("hw.pagesize")?;
+    let mut oid = name2oid("vm.swap_info")?;
+    let mut devices = vec![];
+
+    // The devices can not be walked, try indexes until there is none
+    for index in 0.. {
+        oid.push(index);
+        let buf = match fetch_oid(&oid, 0) {
+            Ok(buf) => buf,
+            Err(SysctlError::IoError(ref e)) if e.raw_os_error() == Some(libc::ENOENT) => break,
+            Err(e) => return Err(e),
+        };
+        oid.pop();
+
+        let mut device = decode_xswdev(&buf, page_size)?;
+        device.name = match device.dev {
+            NODEV => "[NFS swap]".to_owned(),
+            dev => format!("/dev/{}", devname(dev)?),
+        };
+        devices.push(device);
+    }
+
+    Ok(devices)
+}
+
+// Decode a struct xswdev, without the name of the device. Sizes are in
+// pages.
+fn decode_xswdev(buf: &[u8], page_size: u64) -> Result<SwapDevice, SysctlError> {
+    let short = |size| SysctlError::ShortRead {
+        read: buf.len(),
+        reported: size,
+    };
+    if buf.len() < 4 {
+        return Err(short(4));
+    }
+    let (dev, flags, nblks, used) = match NativeEndian::read_u32(&buf[XSW_VERSION..]) {
+        1 if buf.len() >= XSWDEV_V1_SIZE => {
+            let dev = match NativeEndian::read_u32(&buf[XSW_V1_DEV..]) {
+                u32::MAX => NODEV,
+                dev => u64::from(dev),
+            };
+            (dev, XSW_V1_FLAGS, XSW_V1_NBLKS, XSW_V1_USED)
+        }
+        2 if buf.len() >= XSWDEV_SIZE => {
+            let dev = NativeEndian::read_u64(&buf[XSW_DEV..]);
+            (dev, XSW_FLAGS, XSW_NBLKS, XSW_USED)
+        }
+        1 => return Err(short(XSWDEV_V1_SIZE)),
+        2 => return Err(short(XSWDEV_SIZE)),
+        v => {
+            return Err(SysctlError::InvalidValue(format!(
+                "unknown xswdev version {}",
+                v
+            )))
+        }
+    };
+
+    // The page counts are ints but never negative
+    let bytes = |offset| {
+        let pages = NativeEndian::read_u32(&buf[offset..]);
+        u64::from(pages)
+            .checked_mul(page_size)
+            .ok_or_else(|| SysctlError::InvalidValue(format!("{} pages overflow u64", pages)))
+    };
+
+    Ok(SwapDevice {
+        name: String::new(),
+        dev,
+        flags: NativeEndian::read_i32(&buf[flags..]),
+        total: bytes(nblks)?,
+        used: bytes(used)?,
+    })
+}
+
+// The name of a device, like devname(3): kern.devname takes the dev_t as
+// the new value and returns the name. Falls back to the number if there
+// is no such device, but a name that is not UTF-8 fails in strict mode.
+fn devname(dev: u64) -> Result<String, SysctlError> {
+    let lookup = || -> Result<String, SysctlError> {
+        let mut oid = name2oid("kern.devname")?;
+        let mut buf = vec![0u8; SPECNAMELEN + 1];
+        let mut len = buf.len();
+        let ret = unsafe {
+            raw(
+                &mut oid,
+                buf.as_mut_ptr() as *mut c_void,
+                &mut len,
+                &dev.to_ne_bytes(),
+            )
+        };
+        if ret < 0 {
+            return Err(SysctlError::IoError(io::Error::last_os_error()));
+        }
+        let end = buf[..len].iter().position(|&b| b == 0).unwrap_or(len);
+        decode_utf8(&buf[..end])
+    };
+    match lookup() {
+        Ok(ref name) if !name.is_empty() => Ok(name.clone()),
+        Err(e @ SysctlError::Utf8Error(_)) => Err(e),
+        _ => Ok(format!("#C:{:#x}", dev)),
+    }
+}
+
+// The counters are u_int, hw.physmem an unsigned long
+fn integer(name: &str) -> Result<u64, SysctlError> {
+    value(name)?.as_uint().ok_or(SysctlError::ExtractionError)
+}
+
+// A counter that only some kernel versions have, 0 where it does not
+// exist.
+fn counter_or_zero(name: &str) -> Result<u64, SysctlError> {
+    match integer(name) {
+        Err(SysctlError::IoError(ref e)) if e.raw_os_error() == Some(libc::ENOENT) => Ok(0),
+        r => r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vm_stats_bytes() {
+        let vm = VmStats {
+            page_size: 4096,
+            physmem: 1 << 30,
+            page_count: 250_000,
+            free: 1000,
+            active: 2000,
+            inactive: 3000,
+            laundry: 4,
+            wired: 5000,
+            cache: 0,
+        };
+        assert_eq!(vm.free_bytes(), 4_096_000);
+        assert_eq!(vm.active_bytes(), 8_192_000);
+        assert_eq!(vm.inactive_bytes(), 12_288_000);
+        assert_eq!(vm.laundry_bytes(), 16_384);
+        assert_eq!(vm.wired_bytes(), 20_480_000);
+        assert_eq!(vm.cache_bytes(), 0);
+    }
+
+    #[test]
+    fn vm_decode_xswdev() {
+        let mut buf = vec![0; 32];
+        NativeEndian::write_u32(&mut buf[XSW_VERSION..], 2);
+        NativeEndian::write_u64(&mut buf[XSW_DEV..], 0x5a);
+        NativeEndian::write_i32(&mut buf[XSW_FLAGS..], 1);
+        NativeEndian::write_i32(&mut buf[XSW_NBLKS..], 524_288);
+        NativeEndian::write_i32(&mut buf[XSW_USED..], 1024);
+        let d = decode_xswdev(&buf, 4096).unwrap();
+        assert_eq!(d.dev, 0x5a);
+        assert_eq!(d.flags, 1);
+        assert_eq!(d.total, 2 << 30);
+        assert_eq!(d.used, 4 << 20);
+        assert_eq!(d.free(), (2 << 30) - (4 << 20));
+
+        let mut v1 = vec![0; XSWDEV_V1_SIZE];
+        NativeEndian::write_u32(&mut v1[XSW_VERSION..], 1);
+        NativeEndian::write_u32(&mut v1[XSW_V1_DEV..], u32::MAX);
+        NativeEndian::write_i32(&mut v1[XSW_V1_NBLKS..], 10);
+        let d = decode_xswdev(&v1, 4096).unwrap();
+        assert_eq!(d.dev, NODEV);
+        assert_eq!(d.total, 40960);
+        assert!(decode_xswdev(&v1, u64::MAX).is_err());
+
+        assert!(decode_xswdev(&buf[..XSWDEV_SIZE - 1], 4096).is_err());
+        NativeEndian::write_u32(&mut buf[XSW_VERSION..], 3);
+        assert!(decode_xswdev(&buf, 4096).is_err());
+    }
+
+    #[test]
+    fn vm_swap_info() {
+        for d in swap_info().unwrap() {
+            assert!(d.used <= d.total);
+            assert!(!d.name.is_empty());
+        }
+    }
+
+    #[test]
+    fn vm_stats_live() {
+        let vm = vm_stats().unwrap();
+        assert!(vm.page_size >= 4096);
+        assert!(vm.physmem > 0);
+        // The counters are read one by one while pages move between the
+        // queues, so only each of them is bounded by the page count
+        for &count in &[vm.free, vm.active, vm.inactive, vm.laundry, vm.wired] {
+            assert!(count <= vm.page_count);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! A tree walk reusing its buffers.
+//!
+//! `CtlIter` hands out an owned `Ctl` for every OID and callers then
+//! allocate again for its name and format. For scans of the full tree
+//! `Walker` instead reuses its internal buffers and lends out an entry
+//! borrowing from them, which is only valid until the next call to
+//! `next()`. As a lending iterator it can not implement `Iterator`, so it
+//! is driven with `while let`.
+//!
+//! On the BSDs and macOS the walk does not allocate per OID once the
+//! buffers are large enough. On Linux every step still lists directories
+//! of `/proc/sys` and builds the name and format, only the entries handed
+//! out share the buffers.
+//!
+//! # Example
+//! ```
+//! extern crate sysctl;
+//! use sysctl::walk::Walker;
+//!
+//! fn main() {
+//!     let mut walker = Walker::root();
+//!     while let Some(entry) = walker.next() {
+//!         match entry {
+//!             Ok(e) => println!("{} ({:?})", e.name, e.ctl_type),
+//!             Err(e) => println!("error: {}", e),
+//!         }
+//!     }
+//! }
+//! ```
+
+#[cfg(not(target_os = "linux"))]
+use std::io;
+use std::mem;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use byteorder::{ByteOrder, NativeEndian};
+#[cfg(not(target_os = "linux"))]
+use libc::c_void;
+use libc::{c_int, c_uint};
+
+#[cfg(not(target_os = "linux"))]
+use super::meta::raw;
+#[cfg(target_os = "linux")]
+use super::{next_oid, oid2name, oidfmt};
+use super::{Ctl, CtlInfo, CtlType, SysctlError, CTLTYPE, CTL_MAXNAME, FIRST_NODE};
+#[cfg(not(target_os = "linux"))]
+use super::{CTL_SYSCTL, CTL_SYSCTL_NAME, CTL_SYSCTL_NEXT, CTL_SYSCTL_OIDFMT};
+
+// Initial size of the name and format buffers, grown when too small
+const BUFSIZE: usize = 256;
+
+/// The metadata of a sysctl, borrowed from a `Walker`.
+#[derive(Debug, PartialEq)]
+pub struct WalkEntry<'a> {
+    pub oid: &'a [c_int],
+    pub name: &'a str,
+    pub ctl_type: CtlType,
+    /// Raw `CTLFLAG_*` bits.
+    pub flags: c_uint,
+    pub fmt: &'a str,
+}
+
+impl<'a> WalkEntry<'a> {
+    /// An owned handle for the sysctl, to keep beyond the next step of
+    /// the walk.
+    pub fn ctl(&self) -> Ctl {
+        // The walk already fetched the metadata, hand it on
+        Ctl {
+            oid: self.oid.to_vec(),
+            info: Some(CtlInfo {
+                ctl_type: self.ctl_type,
+                fmt: self.fmt.to_owned(),
+                flags: self.flags,
+            }),
+        }
+    }
+}
+
+/// Walks the sysctl tree reusing its buffers, see the module
+/// documentation.
+pub struct Walker {
+    // Only OIDs starting with base are visited
+    base: Vec<c_int>,
+    oid: Vec<c_int>,
+    // Meta query buffers, /proc/sys is not walked with meta queries
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    query: Vec<c_int>,
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    next: Vec<u8>,
+    name: Vec<u8>,
+    name_len: usize,
+    fmt: Vec<u8>,
+    fmt_len: usize,
+    done: bool,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Walker {
+    /// Walk the complete sysctl tree.
+    pub fn root() -> Self {
+        Walker::start(vec![], FIRST_NODE.to_vec())
+    }
+
+    /// Walk all sysctls below the given node.
+    pub fn below(node: &Ctl) -> Self {
+        Walker::start(node.oid.clone(), node.oid.clone())
+    }
+
+    fn start(base: Vec<c_int>, oid: Vec<c_int>) -> Self {
+        Walker {
+            base,
+            oid,
+            query: Vec::with_capacity(CTL_MAXNAME as usize + 2),
+            next: vec![0; CTL_MAXNAME as usize * mem::size_of::<c_int>()],
+            name: vec![0; BUFSIZE],
+            name_len: 0,
+            fmt: vec![0; BUFSIZE],
+            fmt_len: 0,
+            done: false,
+            cancel: None,
+        }
+    }
+
+    /// End the walk early once `cancel` is set, see `CtlIter::cancel_on`.
+    pub fn cancel_on(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Advance to the next sysctl. Returns `None` at the end of the walk;
+    /// the walk also ends after an error.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<WalkEntry<'_>, SysctlError>> {
+        if self.done {
+            return None;
+        }
+        if let Some(ref cancel) = self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                self.done = true;
+                return None;
+            }
+        }
+        match self.advance() {
+            Ok(true) => {}
+            Ok(false) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+
+        let name = match str::from_utf8(trim_nul(&self.name[..self.name_len])) {
+            Ok(s) => s,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(SysctlError::Utf8Error(e)));
+            }
+        };
+        if self.fmt_len < mem::size_of::<u32>() {
+            self.done = true;
+            return Some(Err(SysctlError::ExtractionError));
+        }
+        // The kind is followed by the format string
+        let kind = NativeEndian::read_u32(&self.fmt);
+        let fmt = match str::from_utf8(trim_nul(&self.fmt[mem::size_of::<u32>()..self.fmt_len])) {
+            Ok(s) => s,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(SysctlError::Utf8Error(e)));
+            }
+        };
+
+        Some(Ok(WalkEntry {
+            oid: &self.oid,
+            name,
+            ctl_type: CtlType::from(kind & CTLTYPE),
+            flags: kind,
+            fmt,
+        }))
+    }
+
+    // Move to the next OID and fetch its name and format. Returns false
+    // at the end of the walk.
+    #[cfg(not(target_os = "linux"))]
+    fn advance(&mut self) -> Result<bool, SysctlError> {
+        let len = match query(CTL_SYSCTL_NEXT, &self.oid, &mut self.query, &mut self.next) {
+            Ok(len) => len,
+            Err(SysctlError::IoError(ref e)) if e.raw_os_error() == Some(libc::ENOENT) => {
+                return Ok(false)
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.oid.clear();
+        self.oid.extend(
+            self.next[..len]
+                .chunks_exact(mem::size_of::<c_int>())
+                .map(NativeEndian::read_i32),
+        );
+        if !self.oid.starts_with(&self.base) {
+            return Ok(false);
+        }
+
+        self.name_len = query(CTL_SYSCTL_NAME, &self.oid, &mut self.query, &mut self.name)?;
+        self.fmt_len = query(CTL_SYSCTL_OIDFMT, &self.oid, &mut self.query, &mut self.fmt)?;
+        Ok(true)
+    }
+
+    // Move to the next OID of /proc/sys and fill the buffers the way the
+    // meta queries do elsewhere.
+    #[cfg(target_os = "linux")]
+    fn advance(&mut self) -> Result<bool, SysctlError> {
+        let oid = match next_oid(&self.oid)? {
+            Some(oid) => oid,
+            None => return Ok(false),
+        };
+        if !oid.starts_with(&self.base) {
+            return Ok(false);
+        }
+        let name = oid2name(&oid)?;
+        let info = oidfmt(&oid)?;
+        self.oid = oid;
+
+        self.name.clear();
+        self.name.extend_from_slice(name.as_bytes());
+        self.name_len = self.name.len();
+        self.fmt.clear();
+        self.fmt.extend_from_slice(&info.flags.to_ne_bytes());
+        self.fmt.extend_from_slice(info.fmt.as_bytes());
+        self.fmt_len = self.fmt.len();
+        Ok(true)
+    }
+}
+
+// Run the meta query cmd for oid into buf, growing buf as needed. Returns
+// the length of the reply.
+#[cfg(not(target_os = "linux"))]
+fn query(
+    cmd: c_int,
+    oid: &[c_int],
+    qoid: &mut Vec<c_int>,
+    buf: &mut Vec<u8>,
+) -> Result<usize, SysctlError> {
+    qoid.clear();
+    qoid.extend(&[CTL_SYSCTL, cmd]);
+    qoid.extend(oid);
+
+    loop {
+        let mut len = buf.len();
+        let ret = unsafe { raw(qoid, buf.as_mut_ptr() as *mut c_void, &mut len, &[]) };
+        if ret == 0 {
+            return Ok(len);
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ENOMEM) {
+            return Err(SysctlError::IoError(e));
+        }
+        let size = buf.len() * 2;
+        buf.resize(size, 0);
+    }
+}
+
+fn trim_nul(buf: &[u8]) -> &[u8] {
+    match buf.iter().position(|&b| b == 0) {
+        Some(end) => &buf[..end],
+        None => buf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "linux"))]
+    const NODE: &str = "kern";
+    #[cfg(target_os = "linux")]
+    const NODE: &str = "kernel";
+
+    #[test]
+    fn walk_below() {
+        let kern = Ctl::new(NODE).unwrap();
+        let expected: Vec<String> = kern
+            .clone()
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|c| c.name().unwrap())
+            .collect();
+
+        let mut walker = Walker::below(&kern);
+        let mut names = vec![];
+        while let Some(entry) = walker.next() {
+            let entry = entry.unwrap();
+            assert_eq!(entry.ctl().name().unwrap(), entry.name);
+            names.push(entry.name.to_owned());
+        }
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn walk_cancel() {
+        let cancel = Arc::new(AtomicBool::new(true));
+        assert!(Walker::root().cancel_on(cancel).next().is_none());
+    }
+
+    #[test]
+    fn walk_trim_nul() {
+        assert_eq!(trim_nul(b"kern\0\0"), b"kern");
+        assert_eq!(trim_nul(b"kern"), b"kern");
+        assert_eq!(trim_nul(b""), b"");
+    }
+}
@@ -0,0 +1,1364 @@
+//! Watching the values of sysctls over time.
+//!
+//! A `CtlWatcher` polls sysctls on a background thread and reports their
+//! changes over a channel or to a callback, along with the failures to
+//! read them. It is built from the pieces below, which can be used on
+//! their own as well.
+//!
+//! A `History` keeps the last values read of every sysctl in a ring
+//! buffer, along with the time they were read, e.g. to draw a sparkline
+//! or a graph without an external time series database. `aggregate()`
+//! computes their minimum, maximum, mean and percentiles, e.g. to publish
+//! smoothed values instead of the last one read.
+//!
+//! A `Schedule` polls groups of sysctls at different intervals from one
+//! thread. A random jitter can be added to every interval, so that many
+//! pollers started together do not all read at the same instant.
+//!
+//! A `Debounce` turns the values read of a sysctl into the changes worth
+//! reporting: values equal to the one last reported are dropped, and
+//! values that flap back and forth can be held back until a new value
+//! held for a while, so that slow consumers are not flooded.
+//!
+//! Sysctls can start failing to be read, e.g. because the device went
+//! away. A `Backoff` tells when to read such a sysctl again, waiting twice
+//! as long after every failure up to a maximum, and turns failures and
+//! recoveries into `WatchStatus` events to report.
+//!
+//! For a quick capture without any metrics infrastructure, a `CsvWriter`
+//! writes changes or samples as timestamped CSV or TSV rows.
+//!
+//! # Example
+//! ```no_run
+//! extern crate sysctl;
+//! use std::io;
+//! use std::time::Duration;
+//! use sysctl::watch::{CsvWriter, CtlWatcher};
+//!
+//! fn main() {
+//!     let ctl = sysctl::Ctl::new("vm.stats.vm.v_free_count").expect("could not get control");
+//!     let handle = CtlWatcher::new(Duration::from_secs(1))
+//!         .watch_threshold(ctl.clone(), 1000)
+//!         .history(60)
+//!         .spawn_with(CsvWriter::csv(io::stdout()).into_callback())
+//!         .expect("could not read value");
+//!     std::thread::sleep(Duration::from_secs(60));
+//!     if let Some(a) = handle.aggregate(&ctl, Duration::from_secs(30)) {
+//!         println!("min {} max {} p90 {}", a.min, a.max, a.percentile(90.0));
+//!     }
+//! }
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::export::Transform;
+#[cfg(target_os = "linux")]
+use super::linux;
+use super::{numeric, Ctl, CtlValue, SysctlError};
+
+/// A value of a sysctl and the time it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub time: SystemTime,
+    pub value: CtlValue,
+}
+
+/// A sysctl that failed to be read or recovered, see `Backoff`.
+#[derive(Debug)]
+pub enum WatchStatus {
+    /// Reading the sysctl failed, it is tried again after `retry_in`.
+    Failing {
+        ctl: Ctl,
+        error: SysctlError,
+        retry_in: Duration,
+    },
+    /// The sysctl could be read again after failing.
+    Recovered { ctl: Ctl },
+}
+
+/// A change of a watched sysctl, see `CtlWatcher`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtlChange {
+    pub ctl: Ctl,
+    /// The value last reported, or read when watching started.
+    pub old: CtlValue,
+    pub new: CtlValue,
+}
+
+/// The minimum, maximum, mean and percentiles of the numeric values of
+/// some samples, see `aggregate()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    // Sorted, for percentile()
+    values: Vec<f64>,
+}
+
+impl Aggregate {
+    /// The smallest value that at least `p` percent of the values are
+    /// less than or equal to. `p` is clamped to 0-100.
+    pub fn percentile(&self, p: f64) -> f64 {
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.values.len() as f64).ceil() as usize;
+        self.values[rank.max(1) - 1]
+    }
+}
+
+/// Aggregate the numeric values of the samples, `None` if there are none.
+/// Other values are ignored.
+pub fn aggregate<'a, I>(samples: I) -> Option<Aggregate>
+where
+    I: IntoIterator<Item = &'a Sample>,
+{
+    let mut values: Vec<f64> = samples
+        .into_iter()
+        .filter_map(|s| numeric(&s.value))
+        .map(|v| v as f64)
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).expect("integers are never NaN"));
+
+    Some(Aggregate {
+        count: values.len(),
+        min: values[0],
+        max: values[values.len() - 1],
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        values,
+    })
+}
+
+/// The last values read of some sysctls, see the module documentation.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::thread;
+/// use std::time::Duration;
+/// use sysctl::watch::History;
+///
+/// fn main() {
+///     let ctl = sysctl::Ctl::new("vm.stats.vm.v_free_count").expect("could not get control");
+///     let mut history = History::new(60);
+///     for _ in 0..60 {
+///         history.sample(&ctl).expect("could not read value");
+///         thread::sleep(Duration::from_secs(1));
+///     }
+///     for sample in history.samples(&ctl) {
+///         println!("{:?} {}", sample.time, sample.value);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct History {
+    len: usize,
+    // In the order the sysctls were first added, oldest sample first
+    samples: Vec<(Ctl, VecDeque<Sample>)>,
+}
+
+impl History {
+    /// Keep the last `len` values of every sysctl.
+    pub fn new(len: usize) -> Self {
+        History {
+            len,
+            samples: vec![],
+        }
+    }
+
+    /// Read the sysctl and add its value.
+    pub fn sample(&mut self, ctl: &Ctl) -> Result<(), SysctlError> {
+        let value = ctl.value()?;
+        self.record(ctl, value);
+        Ok(())
+    }
+
+    /// Add a value of the sysctl read just now.
+    pub fn record(&mut self, ctl: &Ctl, value: CtlValue) {
+        self.push(
+            ctl,
+            Sample {
+                time: SystemTime::now(),
+                value,
+            },
+        );
+    }
+
+    /// Add a sample of the sysctl, dropping its oldest one if it has `len`
+    /// samples already.
+    pub fn push(&mut self, ctl: &Ctl, sample: Sample) {
+        if self.len == 0 {
+            return;
+        }
+        let i = match self.samples.iter().position(|(c, _)| c == ctl) {
+            Some(i) => i,
+            None => {
+                self.samples
+                    .push((ctl.clone(), VecDeque::with_capacity(self.len)));
+                self.samples.len() - 1
+            }
+        };
+        let samples = &mut self.samples[i].1;
+        if samples.len() == self.len {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// The samples of the sysctl, oldest first. Empty if none were added.
+    pub fn samples(&self, ctl: &Ctl) -> Vec<Sample> {
+        self.samples
+            .iter()
+            .find(|&(c, _)| c == ctl)
+            .map_or_else(Vec::new, |(_, samples)| samples.iter().cloned().collect())
+    }
+
+    /// Aggregate the samples of the sysctl read within the last `window`.
+    /// `None` if there are no numeric values.
+    pub fn aggregate(&self, ctl: &Ctl, window: Duration) -> Option<Aggregate> {
+        let samples = self.samples(ctl);
+        match SystemTime::now().checked_sub(window) {
+            Some(since) => aggregate(samples.iter().filter(|s| s.time >= since)),
+            None => aggregate(&samples),
+        }
+    }
+
+    /// The sysctls with samples, in the order they were first added.
+    pub fn ctls(&self) -> Vec<Ctl> {
+        self.samples.iter().map(|(ctl, _)| ctl.clone()).collect()
+    }
+}
+
+/// When to poll each of some groups of sysctls, at the interval of the
+/// group plus a random jitter.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::thread;
+/// use std::time::{Duration, Instant};
+/// use sysctl::watch::Schedule;
+///
+/// fn main() {
+///     let groups = vec![
+///         vec![sysctl::Ctl::new("vm.stats.vm.v_free_count").expect("could not get control")],
+///         vec![sysctl::Ctl::new("kern.maxproc").expect("could not get control")],
+///     ];
+///     let mut schedule = Schedule::new(Duration::from_millis(100));
+///     schedule.add(Duration::from_secs(1));
+///     schedule.add(Duration::from_secs(60));
+///     while let Some(next) = schedule.next_due() {
+///         thread::sleep(next.saturating_duration_since(Instant::now()));
+///         for group in schedule.due(Instant::now()) {
+///             for ctl in &groups[group] {
+///                 println!("{:?}", ctl.value());
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    jitter: Jitter,
+    // The interval of every group and when it is due next
+    groups: Vec<(Duration, Instant)>,
+}
+
+impl Schedule {
+    /// A schedule waiting up to `jitter` longer than the interval before
+    /// every poll, chosen at random each time.
+    pub fn new(jitter: Duration) -> Self {
+        Schedule {
+            jitter: Jitter::new(jitter),
+            groups: vec![],
+        }
+    }
+
+    /// Add a group polled every `interval`, first one interval from now.
+    /// Returns the index of the group.
+    pub fn add(&mut self, interval: Duration) -> usize {
+        let due = Instant::now() + interval + self.jitter.next();
+        self.groups.push((interval, due));
+        self.groups.len() - 1
+    }
+
+    /// When the next group is due, `None` if there are no groups.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.groups.iter().map(|&(_, due)| due).min()
+    }
+
+    /// The indices of the groups due at `now`, which are then due again
+    /// one interval later.
+    pub fn due(&mut self, now: Instant) -> Vec<usize> {
+        let mut groups = vec![];
+        for i in 0..self.groups.len() {
+            let (interval, due) = self.groups[i];
+            if due <= now {
+                self.groups[i].1 = now + interval + self.jitter.next();
+                groups.push(i);
+            }
+        }
+        groups
+    }
+
+    /// Poll the group `delay` after `now` next, instead of one interval
+    /// later, e.g. to retry reading it later after it failed.
+    pub fn delay(&mut self, group: usize, now: Instant, delay: Duration) {
+        self.groups[group].1 = now + delay + self.jitter.next();
+    }
+}
+
+/// Reports the changes of the values read of a sysctl, see the module
+/// documentation.
+///
+/// # Example
+/// ```
+/// extern crate sysctl;
+/// use std::time::{Duration, Instant};
+/// use sysctl::watch::Debounce;
+/// use sysctl::CtlValue;
+///
+/// fn main() {
+///     let mut debounce = Debounce::new(Duration::from_secs(5));
+///     let start = Instant::now();
+///     assert_eq!(debounce.update(CtlValue::Int(0), start), None);
+///     // A new value is reported once it held for the window
+///     assert_eq!(debounce.update(CtlValue::Int(1), start), None);
+///     assert_eq!(
+///         debounce.update(CtlValue::Int(1), start + Duration::from_secs(5)),
+///         Some((CtlValue::Int(0), CtlValue::Int(1)))
+///     );
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debounce {
+    window: Duration,
+    // The value last reported
+    last: Option<CtlValue>,
+    // The changed value not yet reported and when it was first read
+    pending: Option<(CtlValue, Instant)>,
+}
+
+impl Debounce {
+    /// Report a changed value once it was read again `window` after it
+    /// was first read. With a window of zero, every change is reported
+    /// when it is read.
+    pub fn new(window: Duration) -> Self {
+        Debounce {
+            window,
+            last: None,
+            pending: None,
+        }
+    }
+
+    /// The value last reported, or first read.
+    pub fn last(&self) -> Option<&CtlValue> {
+        self.last.as_ref()
+    }
+
+    /// Take a value read at `now`. Returns the value last reported and
+    /// the new one if the value changed and held for the window, `None`
+    /// otherwise. The first value read is the one changes are measured
+    /// from, and is not reported.
+    pub fn update(&mut self, value: CtlValue, now: Instant) -> Option<(CtlValue, CtlValue)> {
+        let last = match self.last {
+            Some(ref last) => last,
+            None => {
+                self.last = Some(value);
+                return None;
+            }
+        };
+        if *last == value {
+            self.pending = None;
+            return None;
+        }
+        if self.window > Duration::from_secs(0) {
+            match self.pending {
+                Some((ref pending, since)) if *pending == value => {
+                    if now.duration_since(since) < self.window {
+                        return None;
+                    }
+                }
+                _ => {
+                    self.pending = Some((value, now));
+                    return None;
+                }
+            }
+        }
+        self.pending = None;
+        let old = self.last.replace(value.clone())?;
+        Some((old, value))
+    }
+}
+
+/// When to read a sysctl again after it failed, see the module
+/// documentation. Keeps the number of failures in a row of one sysctl.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::thread;
+/// use std::time::Duration;
+/// use sysctl::watch::Backoff;
+///
+/// fn main() {
+///     let ctl = sysctl::Ctl::new("dev.cpu.0.temperature").expect("could not get control");
+///     let interval = Duration::from_secs(1);
+///     let mut backoff = Backoff::new(Duration::from_secs(60));
+///     loop {
+///         let delay = match ctl.value() {
+///             Ok(value) => {
+///                 if let Some(status) = backoff.succeeded(&ctl) {
+///                     println!("{:?}", status);
+///                 }
+///                 println!("{}", value);
+///                 interval
+///             }
+///             Err(error) => {
+///                 let status = backoff.failed(&ctl, interval, error);
+///                 println!("{:?}", status);
+///                 backoff.delay(interval)
+///             }
+///         };
+///         thread::sleep(delay);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    /// Wait twice as long after every failure in a row, but no longer
+    /// than `max`. A `max` no longer than the interval of the sysctl
+    /// retries it at its interval.
+    pub fn new(max: Duration) -> Self {
+        Backoff { max, failures: 0 }
+    }
+
+    /// The number of failures in a row.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// The delay before reading the sysctl again, for one read every
+    /// `interval`. Never shorter than the interval.
+    pub fn delay(&self, interval: Duration) -> Duration {
+        if self.failures == 0 {
+            return interval;
+        }
+        let factor = 1u32.checked_shl(self.failures).unwrap_or(u32::MAX);
+        interval
+            .checked_mul(factor)
+            .map_or(self.max, |delay| delay.min(self.max))
+            .max(interval)
+    }
+
+    /// Count a failed read of the sysctl, read every `interval`. Returns
+    /// the event to report, with the new delay.
+    pub fn failed(&mut self, ctl: &Ctl, interval: Duration, error: SysctlError) -> WatchStatus {
+        self.failures = self.failures.saturating_add(1);
+        WatchStatus::Failing {
+            ctl: ctl.clone(),
+            error,
+            retry_in: self.delay(interval),
+        }
+    }
+
+    /// Count a successful read of the sysctl. Returns the event to report
+    /// if it failed before.
+    pub fn succeeded(&mut self, ctl: &Ctl) -> Option<WatchStatus> {
+        if self.failures == 0 {
+            return None;
+        }
+        self.failures = 0;
+        Some(WatchStatus::Recovered { ctl: ctl.clone() })
+    }
+}
+
+// A sysctl to watch and how.
+#[derive(Debug, Clone)]
+struct Watched {
+    ctl: Ctl,
+    threshold: u64,
+    interval: Duration,
+}
+
+/// Polls sysctls on a background thread and reports their changes, see
+/// the module documentation.
+///
+/// Every sysctl is polled on a `Schedule`, at the interval it was added
+/// with plus the jitter. The values read are fed through a `Debounce`
+/// and recorded in a `History`, and sysctls failing to be read are
+/// retried after a `Backoff`.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::time::Duration;
+/// use sysctl::watch::CtlWatcher;
+///
+/// fn main() {
+///     let ctl = sysctl::Ctl::new("kern.openfiles").expect("could not get control");
+///     let (handle, changes) = CtlWatcher::new(Duration::from_secs(1))
+///         .watch(ctl)
+///         .jitter(Duration::from_millis(100))
+///         .spawn()
+///         .expect("could not read value");
+///     for change in changes.iter().take(10) {
+///         println!("{:?}: {} -> {}", change.ctl.name(), change.old, change.new);
+///     }
+///     handle.stop();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CtlWatcher {
+    interval: Duration,
+    jitter: Duration,
+    debounce: Duration,
+    backoff: Duration,
+    status: Option<Sender<WatchStatus>>,
+    history: usize,
+    watched: Vec<Watched>,
+}
+
+impl CtlWatcher {
+    /// A watcher polling the sysctls added by `watch()` every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        CtlWatcher {
+            interval,
+            jitter: Duration::from_secs(0),
+            debounce: Duration::from_secs(0),
+            backoff: Duration::from_secs(0),
+            status: None,
+            history: 0,
+            watched: vec![],
+        }
+    }
+
+    /// Report every change of the sysctl.
+    pub fn watch(self, ctl: Ctl) -> Self {
+        self.watch_threshold(ctl, 0)
+    }
+
+    /// Report a change of a numeric sysctl only once it differs by more
+    /// than `threshold` from the value last reported. Other values are
+    /// reported on every change.
+    pub fn watch_threshold(mut self, ctl: Ctl, threshold: u64) -> Self {
+        let interval = self.interval;
+        self.watched.push(Watched {
+            ctl,
+            threshold,
+            interval,
+        });
+        self
+    }
+
+    /// Poll the sysctls every `interval` instead of the interval of the
+    /// watcher, e.g. to poll a few slowly changing sysctls less often.
+    pub fn watch_group<I>(mut self, ctls: I, interval: Duration) -> Self
+    where
+        I: IntoIterator<Item = Ctl>,
+    {
+        self.watched.extend(ctls.into_iter().map(|ctl| Watched {
+            ctl,
+            threshold: 0,
+            interval,
+        }));
+        self
+    }
+
+    /// Wait up to `jitter` longer than the interval before every poll,
+    /// see `Schedule`.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Report a change only once the new value held for `window`, see
+    /// `Debounce`.
+    pub fn debounce(mut self, window: Duration) -> Self {
+        self.debounce = window;
+        self
+    }
+
+    /// Retry sysctls failing to be read after up to `max`, see `Backoff`.
+    /// By default they are retried at their interval.
+    pub fn backoff(mut self, max: Duration) -> Self {
+        self.backoff = max;
+        self
+    }
+
+    /// Send a `WatchStatus` whenever a sysctl fails to be read or
+    /// recovers. Failures are not reported otherwise.
+    pub fn status_events(mut self, status: Sender<WatchStatus>) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Keep the last `len` values read of every sysctl, see
+    /// `WatchHandle::history()`.
+    pub fn history(mut self, len: usize) -> Self {
+        self.history = len;
+        self
+    }
+
+    /// Start polling, sending every change over the returned channel.
+    /// Polling stops once the receiver is dropped, or the handle is
+    /// stopped.
+    ///
+    /// Fails if a sysctl can not be read, the changes are measured from
+    /// the values read now.
+    pub fn spawn(self) -> Result<(WatchHandle, WatchReceiver), SysctlError> {
+        let (changes, rx) = mpsc::channel();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let receiver = WatchReceiver {
+            rx,
+            dropped: dropped.clone(),
+        };
+        let handle = self.spawn_while(
+            move |change| changes.send(change).is_ok(),
+            move || !dropped.load(Ordering::Relaxed),
+        )?;
+        Ok((handle, receiver))
+    }
+
+    /// Start polling, calling `callback` with every change. Polling stops
+    /// once the callback returns `false`, or the handle is stopped.
+    ///
+    /// Fails if a sysctl can not be read, the changes are measured from
+    /// the values read now.
+    pub fn spawn_with<F>(self, callback: F) -> Result<WatchHandle, SysctlError>
+    where
+        F: FnMut(CtlChange) -> bool + Send + 'static,
+    {
+        self.spawn_while(callback, || true)
+    }
+
+    // Like spawn_with(), but polling also stops once `alive` returns
+    // false, which is checked before every poll, so that the thread
+    // notices a consumer gone even while the values do not change.
+    pub(crate) fn spawn_while<F, A>(self, callback: F, alive: A) -> Result<WatchHandle, SysctlError>
+    where
+        F: FnMut(CtlChange) -> bool + Send + 'static,
+        A: Fn() -> bool + Send + 'static,
+    {
+        let mut readers: Vec<ReadFn> = vec![];
+        for watched in &self.watched {
+            let mut reader = Reader::new(&watched.ctl)?;
+            readers.push(Box::new(move || reader.value()));
+        }
+        self.spawn_reading(readers, callback, alive)
+    }
+
+    // Poll by calling readers[i] for the i-th watched sysctl, so that
+    // tests can make them fail.
+    fn spawn_reading<F, A>(
+        self,
+        mut readers: Vec<ReadFn>,
+        mut callback: F,
+        alive: A,
+    ) -> Result<WatchHandle, SysctlError>
+    where
+        F: FnMut(CtlChange) -> bool + Send + 'static,
+        A: Fn() -> bool + Send + 'static,
+    {
+        let history = Arc::new(Mutex::new(History::new(self.history)));
+        let mut debounces = vec![];
+        let mut schedule = Schedule::new(self.jitter);
+        for (watched, reader) in self.watched.iter().zip(&mut readers) {
+            let value = reader()?;
+            history
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .record(&watched.ctl, value.clone());
+            let mut debounce = Debounce::new(self.debounce);
+            debounce.update(value, Instant::now());
+            debounces.push(debounce);
+            schedule.add(watched.interval);
+        }
+        let mut backoffs = vec![Backoff::new(self.backoff); self.watched.len()];
+
+        let samples = history.clone();
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            let timeout = match schedule.next_due() {
+                Some(next) => next.saturating_duration_since(Instant::now()),
+                // Nothing to poll, wait to be stopped
+                None => {
+                    let _ = stopped.recv();
+                    return;
+                }
+            };
+            match stopped.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) if alive() => (),
+                _ => return,
+            }
+            let now = Instant::now();
+            for i in schedule.due(now) {
+                let watched = &self.watched[i];
+                let value = match readers[i]() {
+                    Ok(value) => value,
+                    Err(error) => {
+                        let status = backoffs[i].failed(&watched.ctl, watched.interval, error);
+                        schedule.delay(i, now, backoffs[i].delay(watched.interval));
+                        self.report(status);
+                        continue;
+                    }
+                };
+                if let Some(status) = backoffs[i].succeeded(&watched.ctl) {
+                    self.report(status);
+                }
+                samples
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .record(&watched.ctl, value.clone());
+
+                // A value within the threshold counts as unchanged
+                let value = match debounces[i].last() {
+                    Some(last) if !changed(last, &value, watched.threshold) => last.clone(),
+                    _ => value,
+                };
+                if let Some((old, new)) = debounces[i].update(value, now) {
+                    let change = CtlChange {
+                        ctl: watched.ctl.clone(),
+                        old,
+                        new,
+                    };
+                    if !callback(change) {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(WatchHandle {
+            history,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn report(&self, status: WatchStatus) {
+        if let Some(ref events) = self.status {
+            let _ = events.send(status);
+        }
+    }
+}
+
+/// Whether `new` differs from `old` by more than `threshold`. Values that
+/// are not both numeric only need to differ.
+fn changed(old: &CtlValue, new: &CtlValue, threshold: u64) -> bool {
+    match (numeric(old), numeric(new)) {
+        (Some(old), Some(new)) => old.abs_diff(new) > u128::from(threshold),
+        _ => old != new,
+    }
+}
+
+/// A running `CtlWatcher`. Dropping the handle stops it as well.
+#[derive(Debug)]
+pub struct WatchHandle {
+    history: Arc<Mutex<History>>,
+    stop: Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Stop polling and wait for the thread to finish.
+    pub fn stop(self) {}
+
+    /// The last values read of the sysctl, oldest first, see
+    /// `CtlWatcher::history()`.
+    pub fn history(&self, ctl: &Ctl) -> Vec<Sample> {
+        self.history
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .samples(ctl)
+    }
+
+    /// Aggregate the values of the sysctl read within the last `window`,
+    /// see `History::aggregate()`.
+    pub fn aggregate(&self, ctl: &Ctl, window: Duration) -> Option<Aggregate> {
+        self.history
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .aggregate(ctl, window)
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The changes sent by a `CtlWatcher`, returned by `CtlWatcher::spawn()`.
+/// Dereferences to the `Receiver` of the channel.
+///
+/// Dropping the receiver stops polling before the next poll.
+#[derive(Debug)]
+pub struct WatchReceiver {
+    rx: Receiver<CtlChange>,
+    dropped: Arc<AtomicBool>,
+}
+
+impl Deref for WatchReceiver {
+    type Target = Receiver<CtlChange>;
+
+    fn deref(&self) -> &Receiver<CtlChange> {
+        &self.rx
+    }
+}
+
+impl Drop for WatchReceiver {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Relaxed);
+    }
+}
+
+// Reads the value of a watched sysctl.
+type ReadFn = Box<dyn FnMut() -> Result<CtlValue, SysctlError> + Send>;
+
+// Reads a watched sysctl. On Linux the file in /proc/sys is kept open and
+// read again, instead of opened for every poll.
+#[cfg(target_os = "linux")]
+struct Reader(linux::ProcFile);
+
+#[cfg(target_os = "linux")]
+impl Reader {
+    fn new(ctl: &Ctl) -> Result<Self, SysctlError> {
+        linux::ProcFile::open(&ctl.name()?).map(Reader)
+    }
+
+    fn value(&mut self) -> Result<CtlValue, SysctlError> {
+        self.0.value()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Reader(Ctl);
+
+#[cfg(not(target_os = "linux"))]
+impl Reader {
+    fn new(ctl: &Ctl) -> Result<Self, SysctlError> {
+        Ok(Reader(ctl.clone()))
+    }
+
+    fn value(&mut self) -> Result<CtlValue, SysctlError> {
+        self.0.value()
+    }
+}
+
+/// Writes changes or samples as rows of comma or tab separated values,
+/// with a header: the time in seconds since the epoch, the name of the
+/// sysctl, the old value and the new value. Samples have no old value.
+///
+/// CSV fields are quoted where needed, in TSV tabs, newlines and
+/// backslashes are escaped with a backslash. Values of sysctls with a
+/// `Transform` are written as `Transform::format()` renders them.
+///
+/// # Example
+/// ```no_run
+/// extern crate sysctl;
+/// use std::fs::File;
+/// use std::thread;
+/// use std::time::{Duration, Instant};
+/// use sysctl::watch::{CsvWriter, Debounce};
+///
+/// fn main() {
+///     let ctl = sysctl::Ctl::new("kern.openfiles").expect("could not get control");
+///     let out = File::create("openfiles.csv").expect("could not create file");
+///     let mut csv = CsvWriter::csv(out);
+///     let mut changes = Debounce::new(Duration::from_secs(0));
+///     for _ in 0..60 {
+///         let value = ctl.value().expect("could not read value");
+///         if let Some((old, new)) = changes.update(value, Instant::now()) {
+///             csv.write_change(&ctl, &old, &new).expect("could not write row");
+///         }
+///         thread::sleep(Duration::from_secs(1));
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CsvWriter<W: Write> {
+    out: W,
+    delimiter: char,
+    header: bool,
+    transforms: BTreeMap<String, Transform>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Write comma separated values.
+    pub fn csv(out: W) -> Self {
+        CsvWriter {
+            out,
+            delimiter: ',',
+            header: false,
+            transforms: BTreeMap::new(),
+        }
+    }
+
+    /// Write tab separated values.
+    pub fn tsv(out: W) -> Self {
+        CsvWriter {
+            out,
+            delimiter: '\t',
+            header: false,
+            transforms: BTreeMap::new(),
+        }
+    }
+
+    /// Write the values of the sysctl converted by `transform`.
+    pub fn transform(mut self, sysctl: &str, transform: Transform) -> Self {
+        self.transforms.insert(sysctl.to_owned(), transform);
+        self
+    }
+
+    /// Write a row for a change of the sysctl, timestamped now.
+    pub fn write_change(&mut self, ctl: &Ctl, old: &CtlValue, new: &CtlValue) -> io::Result<()> {
+        self.write_row(SystemTime::now(), ctl, Some(old), new)
+    }
+
+    /// Write a row for a sample, e.g. from `History::samples()`.
+    pub fn write_sample(&mut self, ctl: &Ctl, sample: &Sample) -> io::Result<()> {
+        self.write_row(sample.time, ctl, None, &sample.value)
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// A callback for `CtlWatcher::spawn_with()` writing every change.
+    /// Watching stops once a row can not be written.
+    pub fn into_callback(mut self) -> impl FnMut(CtlChange) -> bool {
+        move |change| {
+            self.write_change(&change.ctl, &change.old, &change.new)
+                .is_ok()
+        }
+    }
+
+    fn write_row(
+        &mut self,
+        time: SystemTime,
+        ctl: &Ctl,
+        old: Option<&CtlValue>,
+        new: &CtlValue,
+    ) -> io::Result<()> {
+        let d = self.delimiter;
+        if !self.header {
+            writeln!(self.out, "time{}name{}old{}new", d, d, d)?;
+            self.header = true;
+        }
+        // Times before the epoch are written as negative seconds
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(t) => t.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        };
+        // Name the sysctl by its OID if it vanished
+        let name = ctl.name().unwrap_or_else(|_| format!("{:?}", ctl.oid));
+        let old = old.map_or_else(String::new, |old| self.render(&name, old));
+        let new = self.render(&name, new);
+        writeln!(
+            self.out,
+            "{:.3}{}{}{}{}{}{}",
+            secs,
+            d,
+            self.field(&name),
+            d,
+            self.field(&old),
+            d,
+            self.field(&new)
+        )?;
+        // Rows are read while they are written, e.g. with tail -f
+        self.out.flush()
+    }
+
+    fn render(&self, name: &str, value: &CtlValue) -> String {
+        match self.transforms.get(name) {
+            Some(transform) => transform.format(value),
+            None => value.to_string(),
+        }
+    }
+
+    fn field(&self, s: &str) -> String {
+        if self.delimiter == '\t' {
+            s.replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+        } else if s.contains(&[',', '"', '\n', '\r'][..]) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_owned()
+        }
+    }
+}
+
+// Random delays of up to max, from a xorshift generator. Spreading polls
+// needs no better randomness.
+#[derive(Debug, Clone)]
+struct Jitter {
+    max: u64,
+    state: u64,
+}
+
+impl Jitter {
+    fn new(max: Duration) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        Jitter {
+            max: max.as_nanos().min(u128::from(u64::MAX)) as u64,
+            // The state must not be 0
+            state: u64::from(seed) | 1,
+        }
+    }
+
+    fn next(&mut self) -> Duration {
+        if self.max == 0 {
+            return Duration::from_secs(0);
+        }
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        Duration::from_nanos(self.state % self.max.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::{MockBackend, SysctlBackend};
+
+    #[cfg(target_os = "linux")]
+    const NUMERIC: &str = "kernel.pid_max";
+    #[cfg(not(target_os = "linux"))]
+    const NUMERIC: &str = "kern.maxproc";
+
+    #[test]
+    fn watch_history() {
+        let a = Ctl::from_oid(vec![1, 1]);
+        let b = Ctl::from_oid(vec![1, 2]);
+        let mut history = History::new(3);
+        for i in 0..5 {
+            history.record(&a, CtlValue::Int(i));
+        }
+        history.record(&b, CtlValue::Int(10));
+
+        let values: Vec<_> = history.samples(&a).into_iter().map(|s| s.value).collect();
+        assert_eq!(
+            values,
+            vec![CtlValue::Int(2), CtlValue::Int(3), CtlValue::Int(4)]
+        );
+        assert_eq!(history.samples(&b).len(), 1);
+        assert!(history.samples(&Ctl::from_oid(vec![1, 3])).is_empty());
+        assert_eq!(history.ctls(), vec![a.clone(), b]);
+
+        let mut none = History::new(0);
+        none.record(&a, CtlValue::Int(1));
+        assert!(none.samples(&a).is_empty());
+    }
+
+    #[test]
+    fn watch_aggregate() {
+        let samples: Vec<Sample> = [4, 1, 3, 2]
+            .iter()
+            .map(|&v| Sample {
+                time: SystemTime::now(),
+                value: CtlValue::Int(v),
+            })
+            .chain(Some(Sample {
+                time: SystemTime::now(),
+                value: CtlValue::String("x".into()),
+            }))
+            .collect();
+        let a = aggregate(&samples).unwrap();
+        assert_eq!((a.count, a.min, a.max, a.mean), (4, 1.0, 4.0, 2.5));
+        assert_eq!(a.percentile(50.0), 2.0);
+        assert_eq!(a.percentile(75.0), 3.0);
+        assert_eq!(a.percentile(0.0), 1.0);
+        assert_eq!(a.percentile(150.0), 4.0);
+        assert!(aggregate(&samples[4..]).is_none());
+
+        // Only the samples within the window count
+        let ctl = Ctl::from_oid(vec![1, 1]);
+        let mut history = History::new(10);
+        history.push(
+            &ctl,
+            Sample {
+                time: SystemTime::now() - Duration::from_secs(3600),
+                value: CtlValue::Int(100),
+            },
+        );
+        history.record(&ctl, CtlValue::Int(1));
+        let a = history.aggregate(&ctl, Duration::from_secs(60)).unwrap();
+        assert_eq!((a.count, a.max), (1, 1.0));
+        assert_eq!(
+            history
+                .aggregate(&ctl, Duration::from_secs(7200))
+                .unwrap()
+                .count,
+            2
+        );
+    }
+
+    #[test]
+    fn watch_jitter() {
+        let mut none = Jitter::new(Duration::from_secs(0));
+        assert_eq!(none.next(), Duration::from_secs(0));
+        let mut jitter = Jitter::new(Duration::from_millis(5));
+        let delays: Vec<Duration> = (0..100).map(|_| jitter.next()).collect();
+        assert!(delays.iter().all(|&d| d <= Duration::from_millis(5)));
+        assert!(delays.iter().any(|&d| d != delays[0]));
+    }
+
+    #[test]
+    fn watch_schedule() {
+        let mut schedule = Schedule::new(Duration::from_secs(0));
+        assert!(schedule.next_due().is_none());
+        let start = Instant::now();
+        let fast = schedule.add(Duration::from_secs(1));
+        let slow = schedule.add(Duration::from_secs(60));
+        assert!(schedule.next_due().unwrap() < start + Duration::from_secs(2));
+
+        assert!(schedule.due(start).is_empty());
+        let now = start + Duration::from_secs(2);
+        assert_eq!(schedule.due(now), vec![fast]);
+        assert_eq!(schedule.next_due(), Some(now + Duration::from_secs(1)));
+        assert_eq!(
+            schedule.due(start + Duration::from_secs(61)),
+            vec![fast, slow]
+        );
+
+        schedule.delay(fast, now, Duration::from_secs(10));
+        assert_eq!(schedule.groups[fast].1, now + Duration::from_secs(10));
+    }
+
+    #[test]
+    fn watch_debounce() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut changes = Debounce::new(Duration::from_secs(0));
+        assert_eq!(changes.update(CtlValue::Int(0), at(0)), None);
+        assert_eq!(changes.update(CtlValue::Int(0), at(1)), None);
+        assert_eq!(
+            changes.update(CtlValue::Int(1), at(2)),
+            Some((CtlValue::Int(0), CtlValue::Int(1)))
+        );
+        assert_eq!(changes.last(), Some(&CtlValue::Int(1)));
+
+        let mut debounce = Debounce::new(Duration::from_secs(5));
+        assert_eq!(debounce.update(CtlValue::Int(0), at(0)), None);
+        // Flapping back resets the window
+        assert_eq!(debounce.update(CtlValue::Int(1), at(1)), None);
+        assert_eq!(debounce.update(CtlValue::Int(0), at(2)), None);
+        assert_eq!(debounce.update(CtlValue::Int(1), at(3)), None);
+        assert_eq!(debounce.update(CtlValue::Int(2), at(4)), None);
+        assert_eq!(debounce.update(CtlValue::Int(2), at(8)), None);
+        assert_eq!(
+            debounce.update(CtlValue::Int(2), at(9)),
+            Some((CtlValue::Int(0), CtlValue::Int(2)))
+        );
+        assert_eq!(debounce.update(CtlValue::Int(2), at(20)), None);
+    }
+
+    #[test]
+    fn watch_csv_writer() {
+        // Named by its OID, as it does not exist
+        let ctl = Ctl::from_oid(vec![0x7fff_ffff]);
+        let mut csv = CsvWriter::csv(vec![]);
+        csv.write_change(&ctl, &CtlValue::String("a,\"b\"".into()), &CtlValue::Int(1))
+            .unwrap();
+        let sample = Sample {
+            time: UNIX_EPOCH + Duration::from_millis(1500),
+            value: CtlValue::String("x\ty".into()),
+        };
+        csv.write_sample(&ctl, &sample).unwrap();
+        let csv = String::from_utf8(csv.into_inner()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "time,name,old,new");
+        assert!(rows[1].ends_with(",[2147483647],\"a,\"\"b\"\"\",1"));
+        assert_eq!(rows[2], "1.500,[2147483647],,x\ty");
+
+        let mut tsv = CsvWriter::tsv(vec![]);
+        tsv.write_sample(&ctl, &sample).unwrap();
+        let tsv = String::from_utf8(tsv.into_inner()).unwrap();
+        assert_eq!(tsv, "time\tname\told\tnew\n1.500\t[2147483647]\t\tx\\ty\n");
+
+        // Transforms apply to the name the rows carry
+        let mut csv = CsvWriter::csv(vec![]).transform(
+            "[2147483647]",
+            Transform::new().scale(0.5).unit("B").label(0, "none"),
+        );
+        csv.write_change(&ctl, &CtlValue::Int(0), &CtlValue::Int(3))
+            .unwrap();
+        let csv = String::from_utf8(csv.into_inner()).unwrap();
+        assert!(csv.ends_with(",[2147483647],none,1.5 B\n"));
+    }
+
+    #[test]
+    fn watch_backoff() {
+        // A sysctl that fails to be read four times, then recovers
+        let ctl = Ctl::from_oid(vec![1, 1]);
+        let reads = (0..6).map(|i| {
+            if (1..5).contains(&i) {
+                Err(SysctlError::NoReadAccess)
+            } else {
+                Ok(CtlValue::Int(i))
+            }
+        });
+        let interval = Duration::from_secs(1);
+        let mut backoff = Backoff::new(Duration::from_secs(10));
+
+        let mut delays = vec![];
+        let mut events = vec![];
+        for read in reads {
+            let delay = match read {
+                Ok(_) => {
+                    events.extend(backoff.succeeded(&ctl));
+                    interval
+                }
+                Err(error) => {
+                    events.push(backoff.failed(&ctl, interval, error));
+                    backoff.delay(interval)
+                }
+            };
+            delays.push(delay.as_secs());
+        }
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 1]);
+        assert_eq!(backoff.failures(), 0);
+
+        assert_eq!(events.len(), 5);
+        for (event, &retry) in events.iter().zip(&[2, 4, 8, 10]) {
+            match *event {
+                WatchStatus::Failing {
+                    ref ctl,
+                    error: SysctlError::NoReadAccess,
+                    retry_in,
+                } => {
+                    assert_eq!(ctl.oid, vec![1, 1]);
+                    assert_eq!(retry_in, Duration::from_secs(retry));
+                }
+                ref e => panic!("unexpected {:?}", e),
+            }
+        }
+        match events[4] {
+            WatchStatus::Recovered { ref ctl } => assert_eq!(ctl.oid, vec![1, 1]),
+            ref e => panic!("unexpected {:?}", e),
+        }
+
+        // Without room to back off, failed reads are retried at the interval
+        let mut none = Backoff::new(Duration::from_secs(0));
+        none.failed(&ctl, interval, SysctlError::NoReadAccess);
+        none.failed(&ctl, interval, SysctlError::NoReadAccess);
+        assert_eq!(none.delay(interval), interval);
+    }
+
+    #[test]
+    fn watch_backoff_status() {
+        // A sysctl that goes away after being read once, then comes back
+        let mock = Arc::new(Mutex::new(MockBackend::new()));
+        mock.lock().unwrap().insert("test.value", CtlValue::Int(1));
+        let backend = mock.clone();
+        let reader: ReadFn = Box::new(move || backend.lock().unwrap().value("test.value"));
+
+        let ctl = Ctl::from_oid(vec![1, 1]);
+        let (status, statuses) = mpsc::channel();
+        let handle = CtlWatcher::new(Duration::from_millis(10))
+            .watch(ctl.clone())
+            .backoff(Duration::from_millis(40))
+            .status_events(status)
+            .spawn_reading(vec![reader], |_| true, || true)
+            .expect("could not spawn watcher");
+        mock.lock().unwrap().remove("test.value");
+
+        // Every failure is reported, waiting longer up to the maximum
+        let mut delays = vec![];
+        while delays.len() < 4 {
+            match statuses.recv_timeout(Duration::from_secs(10)).unwrap() {
+                WatchStatus::Failing {
+                    ctl: ref c,
+                    retry_in,
+                    ..
+                } if *c == ctl => delays.push(retry_in.as_millis()),
+                e => panic!("unexpected {:?}", e),
+            }
+        }
+        assert_eq!(delays, vec![20, 40, 40, 40]);
+
+        mock.lock().unwrap().insert("test.value", CtlValue::Int(2));
+        // Failures may still be reported until it is read again
+        let recovered = loop {
+            let status = statuses.recv_timeout(Duration::from_secs(10)).unwrap();
+            if let WatchStatus::Recovered { ctl } = status {
+                break ctl;
+            }
+        };
+        assert_eq!(recovered, ctl);
+        handle.stop();
+    }
+
+    #[test]
+    fn watch_changed() {
+        assert!(!changed(&CtlValue::Int(10), &CtlValue::Int(15), 5));
+        assert!(changed(&CtlValue::Int(10), &CtlValue::Int(16), 5));
+        assert!(changed(&CtlValue::Uint(10), &CtlValue::Uint(4), 5));
+        assert!(changed(&CtlValue::Int(1), &CtlValue::Int(2), 0));
+        assert!(!changed(&CtlValue::Int(1), &CtlValue::Int(1), 0));
+        // Strings only need to differ
+        let a = CtlValue::String("a".to_owned());
+        let b = CtlValue::String("b".to_owned());
+        assert!(changed(&a, &b, 100));
+        assert!(!changed(&a, &a.clone(), 100));
+    }
+
+    #[test]
+    fn watch_spawn() {
+        let ctl = Ctl::new(NUMERIC).expect("could not get control");
+        let (handle, changes) = CtlWatcher::new(Duration::from_millis(10))
+            .watch(ctl.clone())
+            .history(5)
+            .spawn()
+            .expect("could not spawn watcher");
+        // The value read when starting is kept
+        assert!(!handle.history(&ctl).is_empty());
+        handle.stop();
+        // The sender is gone once the thread finished
+        assert!(changes.recv().is_err());
+    }
+
+    #[test]
+    fn watch_receiver_dropped() {
+        let ctl = Ctl::new(NUMERIC).expect("could not get control");
+        let (handle, changes) = CtlWatcher::new(Duration::from_millis(10))
+            .watch(ctl)
+            .spawn()
+            .expect("could not spawn watcher");
+        // The value does not change, the thread stops before polling
+        // again
+        drop(changes);
+        let thread = handle.thread.as_ref().unwrap();
+        for _ in 0..100 {
+            if thread.is_finished() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("watcher still polling");
+    }
+}